@@ -33,6 +33,34 @@ contract PriceFeed is AggregatorV2V3Interface, Ownable {
     }
 
     function updatePrice(uint256 _price) external onlyOwner {
+        _updatePrice(_price);
+    }
+
+    // Updates the price with the fallback value submitted when the oracle origin fails
+    function updatePriceFallback(uint256 _price) external onlyOwner {
+        _updatePrice(_price);
+
+        emit FallbackPriceUsed(_price, currentRoundId);
+    }
+
+    // Updates the price with the owner signature of (price, timestamp, feed address),
+    // which is kept on chain for the verification of the reported prices
+    function updatePriceSigned(
+        uint256 _price,
+        uint256 _timestamp,
+        uint8 v,
+        bytes32 r,
+        bytes32 s
+    ) external onlyOwner {
+        bytes32 digest = keccak256(abi.encode(_price, _timestamp, address(this)));
+        require(ecrecover(digest, v, r, s) == owner(), "invalid price signature");
+
+        _updatePrice(_price);
+
+        emit SignedPriceSubmitted(_price, _timestamp, currentRoundId, v, r, s);
+    }
+
+    function _updatePrice(uint256 _price) internal {
         currentRoundId += 1;
         rounds[currentRoundId] = RoundData({
             answer: _price,
@@ -130,4 +158,15 @@ contract PriceFeed is AggregatorV2V3Interface, Ownable {
         uint256 indexed roundId,
         uint256 updatedAt
     );
+
+    event FallbackPriceUsed(uint256 answer, uint256 indexed roundId);
+
+    event SignedPriceSubmitted(
+        uint256 answer,
+        uint256 timestamp,
+        uint256 indexed roundId,
+        uint8 v,
+        bytes32 r,
+        bytes32 s
+    );
 }
@@ -0,0 +1,6 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+interface IBatchPriceFeed {
+  function batchUpdatePrice(address[] calldata contracts, int256[] calldata prices) external;
+}
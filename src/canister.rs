@@ -1,5 +1,6 @@
 use std::cell::RefCell;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::future::Future;
 use std::rc::Rc;
 use std::str::FromStr;
 use std::time::Duration;
@@ -7,10 +8,8 @@ use std::time::Duration;
 use candid::{CandidType, Principal};
 use did::{H160, H256, U256};
 use eth_signer::sign_strategy::TransactionSigner;
-use ethers_core::abi::ethabi;
 use ethers_core::types::Signature;
-use futures::TryFutureExt;
-use ic_canister::{generate_idl, init, query, update, Canister, Idl, PreUpdate};
+use ic_canister::{generate_idl, init, post_upgrade, query, update, Canister, Idl, PreUpdate};
 use ic_exports::ic_cdk;
 use ic_exports::ic_cdk::api::management_canister::http_request::{
     HttpResponse as MHttpResponse, TransformArgs,
@@ -23,13 +22,35 @@ use serde::{Deserialize, Serialize};
 use serde_bytes::ByteBuf;
 use serde_json::Value;
 
+use crate::constants::{
+    BALANCE_MEASUREMENT_CYCLES, BLOCK_MAX_RESPONSE_BYTES, DEFAULT_HTTP_MAX_RESPONSE_BYTES,
+    ESTIMATED_JSON_RPC_PAYLOAD_BYTES, EVM_CALL_MAX_RESPONSE_BYTES, JSON_RPC_MAX_RESPONSE_BYTES,
+    MAX_DESCRIPTION_BYTES, MAX_LABEL_BYTES, MAX_ORACLES_PER_USER, MAX_ORACLE_TAGS,
+    MAX_RECEIPT_CHECK_RETRIES, MAX_RESPONSE_BYTES, MAX_SIGNATURE_PROOF_TTL_SECS,
+    MAX_STABLE_MEMORY_PAGES, MAX_TAG_CHARS, MAX_TRIGGER_DEPTH, MIN_RESPONSE_BYTES,
+    ORACLE_SNAPSHOT_SCHEMA_VERSION, SECONDS_PER_DAY, THROTTLED_UPDATE_RETRY_SECS,
+};
 use crate::context::{get_base_context, Context, ContextImpl};
-use crate::error::{Error, Result};
-use crate::http::{self, transform, HttpRequest, HttpResponse};
+use crate::eip712::{self, Eip712DomainConfig, SignedOracleData};
+use crate::error::{Error, OracleStage, PipelineError, PipelineResult, Result, WithStage};
+use crate::events::{self, OracleEvent, OracleEventType};
+use crate::http::{
+    self, transform, transform_login, CookieConfig, HmacSigningConfig, HttpAuth, HttpRequest,
+    HttpResponse, DEFAULT_PRICE_DECIMALS,
+};
 use crate::log::LoggerConfigService;
-use crate::provider::{self, get_transaction, Provider, UPDATE_PRICE};
-use crate::state::oracle_storage::OracleMetadata;
-use crate::state::{Settings, State, UpdateOracleMetadata};
+use crate::memory::{self, ORACLE_STORAGE_MEMORY_ID, SETTINGS_MEMORY_ID};
+use crate::parser::{self, AggregateOp, PriceParseMode};
+use crate::provider::{self, get_transaction, Provider};
+use crate::state::oracle_storage::{
+    ConfirmedBlock, DestinationResult, OracleMetadata, OracleSkipReason, OracleStatus,
+    OracleStorage, ResponseSchema, SimulationResult, StorableOracleMetadata, Transform,
+    TxReceiptStatus, TxResult, TxStatus,
+};
+use crate::state::{
+    CookieCache, DeadLetterEntry, OracleSignerConfig, RateLimit, ResponseCache, SerializedFunction,
+    Settings, State, UpdateOracleMetadata,
+};
 
 /// Type alias for the shared mutable context implementation we use in the canister
 type SharedContext = Rc<RefCell<ContextImpl>>;
@@ -90,6 +111,187 @@ impl Oracular {
         self.with_state_mut(|state| state.reset(settings));
     }
 
+    /// Starts the timers of the active oracles again, since timers do not
+    /// survive canister upgrades, drops the cached nonces and fills the user index
+    /// when upgrading from a release without it
+    #[post_upgrade]
+    pub fn post_upgrade(&mut self) {
+        self.with_state(|state| {
+            state.nonce_cache().clear();
+            state.oracle_storage().init_user_index();
+        });
+
+        for error in self.start_active_oracle_timers().1 {
+            log::error!("failed to restore oracle timer: {error:?}");
+        }
+    }
+
+    /// Starts the timers of the active oracles, staggered over the max startup
+    /// jitter, and returns the number of started timers along with the errors
+    fn start_active_oracle_timers(&mut self) -> (u64, Vec<String>) {
+        let oracles = self.with_state(|state| state.oracle_storage().get_oracles());
+        let now = ic::time();
+        let active_oracles = oracles
+            .into_iter()
+            .flat_map(|(user_address, user_oracles)| {
+                user_oracles
+                    .into_iter()
+                    .filter(|(_, metadata)| metadata.status.has_running_timer())
+                    .map(move |(contract_address, metadata)| {
+                        (
+                            user_address.clone(),
+                            contract_address,
+                            metadata.timer_interval,
+                            pending_delay_secs(&metadata.status, metadata.first_tick_at, now),
+                        )
+                    })
+            })
+            .collect::<Vec<_>>();
+
+        // Stagger the timers, so that the oracles are not all updated at once
+        let max_jitter_secs = self.with_state(|state| state.max_startup_jitter_secs());
+        let count = active_oracles.len();
+        let mut started = 0;
+        let mut errors = vec![];
+
+        for (index, (user_address, contract_address, timer_interval, pending_delay)) in
+            active_oracles.into_iter().enumerate()
+        {
+            let timer_id = Self::init_price_timer(
+                get_base_context(&self.context.0),
+                user_address.clone(),
+                contract_address.clone(),
+                timer_interval,
+                pending_delay.or_else(|| startup_delay(index, count, max_jitter_secs)),
+            );
+
+            match self.with_state_mut(|state| {
+                state.mut_oracle_storage().update_oracle_metadata(
+                    user_address.clone(),
+                    contract_address.clone(),
+                    Some(timer_id),
+                    UpdateOracleMetadata::default(),
+                )
+            }) {
+                Ok(()) => started += 1,
+                Err(e) => {
+                    ic_exports::ic_cdk_timers::clear_timer(timer_id);
+                    errors.push(format!(
+                        "oracle {contract_address} of user {user_address}: {e}"
+                    ));
+                }
+            }
+        }
+
+        (started, errors)
+    }
+
+    /// Sets the maximum delay used to stagger the oracle timers restored after an upgrade
+    #[update]
+    pub fn set_max_startup_jitter(&mut self, max_jitter_secs: u64) -> Result<()> {
+        self.check_owner(ic::caller())?;
+
+        self.with_state_mut(|state| state.set_max_startup_jitter_secs(max_jitter_secs));
+
+        Ok(())
+    }
+
+    /// Limits the number of oracles each user can create in an hour; `None`
+    /// removes the limit
+    #[update]
+    pub fn set_oracle_creation_rate_limit(&mut self, limit: Option<RateLimit>) -> Result<()> {
+        self.check_owner(ic::caller())?;
+
+        self.with_state_mut(|state| state.set_oracle_creation_rate_limit(limit));
+
+        Ok(())
+    }
+
+    /// Returns the oracle creation rate limit, if any
+    #[query]
+    pub fn get_oracle_creation_rate_limit(&self) -> Option<RateLimit> {
+        self.with_state(|state| state.oracle_creation_rate_limit())
+    }
+
+    /// Disables the check of the destination provider chain id on oracle creation,
+    /// e.g. in testing environments
+    #[update]
+    pub fn set_skip_chain_id_validation(&mut self, skip: bool) -> Result<()> {
+        self.check_owner(ic::caller())?;
+
+        self.with_state_mut(|state| state.set_skip_chain_id_validation(skip));
+
+        Ok(())
+    }
+
+    /// Returns whether the check of the destination provider chain id is disabled
+    #[query]
+    pub fn get_skip_chain_id_validation(&self) -> bool {
+        self.with_state(|state| state.skip_chain_id_validation())
+    }
+
+    /// Limits the number of oracle updates run at once by the timers, protecting
+    /// the HTTP outcall quota and the cycle budget of the canister
+    #[update]
+    pub fn set_max_concurrent_updates(&mut self, limit: u32) -> Result<()> {
+        self.check_owner(ic::caller())?;
+
+        if limit == 0 {
+            return Err(Error::Internal(
+                "max concurrent updates must be greater than 0".to_string(),
+            ));
+        }
+
+        self.with_state_mut(|state| state.set_max_concurrent_updates(limit));
+
+        Ok(())
+    }
+
+    /// Returns the maximum number of oracle updates run at once by the timers
+    #[query]
+    pub fn get_max_concurrent_updates(&self) -> u32 {
+        self.with_state(|state| state.max_concurrent_updates())
+    }
+
+    /// Sets the threshold ECDSA key and derivation suffix used by the oracle signers
+    ///
+    /// Changing the config changes the addresses sending the update transactions
+    /// of all the oracles.
+    #[update]
+    pub fn set_signer_config(&mut self, config: OracleSignerConfig) -> Result<()> {
+        self.check_owner(ic::caller())?;
+
+        self.with_state_mut(|state| state.set_signer_config(config));
+
+        Ok(())
+    }
+
+    /// Returns the threshold ECDSA key and derivation suffix used by the oracle signers
+    #[query]
+    pub fn get_signer_config(&self) -> OracleSignerConfig {
+        self.with_state(|state| state.signer_config())
+    }
+
+    /// Sets the components prepended to the derivation path of every oracle signer,
+    /// isolating the keys of this deployment from the other ones
+    ///
+    /// Changing the prefix changes the addresses sending the update transactions
+    /// of all the oracles.
+    #[update]
+    pub fn set_derivation_prefix(&mut self, prefix: Vec<Vec<u8>>) -> Result<()> {
+        self.check_owner(ic::caller())?;
+
+        self.with_state_mut(|state| state.set_derivation_prefix(prefix));
+
+        Ok(())
+    }
+
+    /// Returns the components prepended to the derivation path of every oracle signer
+    #[query]
+    pub fn get_derivation_prefix(&self) -> Vec<Vec<u8>> {
+        self.with_state(|state| state.derivation_prefix())
+    }
+
     /// Returns the owner of the canister
     #[query]
     pub fn owner(&self) -> Principal {
@@ -107,564 +309,7142 @@ impl Oracular {
         Ok(())
     }
 
-    /// Updates the runtime configuration of the logger with a new filter in the same form as the `RUST_LOG`
-    /// environment variable.
-    /// Example of valid filters:
-    /// - info
-    /// - debug,crate1::mod1=error,crate1::mod2,crate2=debug
+    /// Adds an admin that can manage the canister
     #[update]
-    pub fn set_logger_filter(&mut self, filter: String) -> Result<()> {
+    pub fn add_admin(&mut self, admin: Principal) -> Result<()> {
+        check_anonymous_principal(admin)?;
         self.check_owner(ic::caller())?;
-        self.logger_config_service().set_logger_filter(&filter)?;
-
-        debug!("updated logger filter to {filter}");
 
-        Ok(())
+        self.with_state_mut(|state| state.add_admin(admin))
     }
 
-    /// Gets the logs
-    /// - `count` is the number of logs to return
+    /// Removes an admin of the canister
     #[update]
-    pub fn ic_logs(&self, count: usize) -> Result<Vec<String>> {
+    pub fn remove_admin(&mut self, admin: Principal) -> Result<()> {
         self.check_owner(ic::caller())?;
 
-        Ok(ic_log::take_memory_records(count))
+        self.with_state_mut(|state| state.remove_admin(admin))
     }
 
-    /// Get all the oracles created
+    /// Returns the admins of the canister
     #[query]
-    pub fn get_all_oracles(&self) -> Vec<(H160, BTreeMap<H160, OracleMetadata>)> {
-        self.with_state(|state| state.oracle_storage().get_oracles())
+    pub fn list_admins(&self) -> Vec<Principal> {
+        self.with_state(|state| state.admins())
     }
 
-    /// Returns the list of oracles for the given user
-    #[query]
-    pub fn get_user_oracles(&self, user_address: H160) -> Result<Vec<(H160, OracleMetadata)>> {
-        let oracles =
-            self.with_state(|state| state.oracle_storage().get_user_oracles(user_address))?;
+    /// Restricts the URLs of HTTP origins to the given prefixes
+    ///
+    /// The scheme and host of the URLs are matched case-insensitively.
+    #[update]
+    pub fn set_url_allowlist(&mut self, prefixes: Vec<String>) -> Result<()> {
+        self.check_owner(ic::caller())?;
 
-        Ok(oracles)
+        self.with_state_mut(|state| state.set_url_allowlist(Some(prefixes)));
+        Ok(())
     }
 
-    /// Returns the address of the sender of the transaction using
-    /// the management canister
+    /// Registers the provider of a chain, used by the oracle destinations
+    /// with a `chain_id`
     #[update]
-    pub async fn get_address(&self, address: H160) -> Result<H160> {
-        let signer = {
-            self.context
-                .0
-                .borrow()
-                .get_state()
-                .signer
-                .get_oracle_signer(address)
+    pub fn register_provider(&mut self, chain_id: u64, endpoints: Vec<String>) -> Result<()> {
+        self.check_owner(ic::caller())?;
+
+        let provider = Provider {
+            chain_id,
+            endpoints,
         };
+        validate_provider_endpoints(&provider)?;
 
-        Ok(signer.get_address().await?)
+        self.with_state(|state| state.provider_registry().insert(provider));
+        Ok(())
     }
 
-    /// Returns the metadata of the given oracle
+    /// Sets the API key of the providers of a chain, replacing the `{API_KEY}`
+    /// placeholder of their endpoints
     ///
-    /// # Arguments
-    /// * `contract_address` - The address of the contract that will be fetched
-    /// * `user_address` - The address of the user that created the oracle
-    #[query]
-    pub fn get_oracle_metadata(
-        &self,
-        user_address: H160,
-        contract_address: H160,
-    ) -> Result<OracleMetadata> {
-        let metadata = self.with_state(|state| {
-            state
-                .oracle_storage()
-                .get_oracle_by_address(user_address, contract_address)
-        })?;
+    /// The key is never returned by the canister.
+    #[update]
+    pub fn set_provider_api_key(&mut self, chain_id: u64, api_key: String) -> Result<()> {
+        self.check_owner(ic::caller())?;
 
-        Ok(metadata)
+        if api_key.is_empty() {
+            return Err(Error::Internal("api key cannot be empty".to_string()));
+        }
+
+        self.with_state(|state| state.provider_credentials().insert(chain_id, api_key));
+
+        Ok(())
     }
 
-    /// Recovers the public key from the given message and signature
-    /// and adds the signer to the list of signers
+    /// Deletes the API key of the providers of a chain
+    #[update]
+    pub fn delete_provider_api_key(&mut self, chain_id: u64) -> Result<()> {
+        self.check_owner(ic::caller())?;
+
+        self.with_state(|state| state.provider_credentials().remove(chain_id))
+    }
+
+    /// Registers a function from its JSON ABI, callable by the custom update
+    /// functions of the oracles as `FunctionRef::Registered(name)`
     ///
-    /// This is used for the users to sign the transactions using the threshold
-    /// ECDSA
-    pub fn recover_pubkey(message: String, signature: String) -> Result<H160> {
-        let signature = Signature::from_str(&signature).map_err(|e| {
-            Error::Internal(format!("failed to parse signature: {:?}", e.to_string()))
-        })?;
+    /// The function previously registered under the name is replaced.
+    #[update]
+    pub fn register_function(&mut self, name: String, abi_json: String) -> Result<()> {
+        self.check_owner(ic::caller())?;
 
-        let address = signature.recover(message).map_err(|e| {
-            Error::Internal(format!("failed to recover public key: {:?}", e.to_string()))
-        })?;
+        if name.is_empty() {
+            return Err(Error::Internal("function name cannot be empty".to_string()));
+        }
 
-        Ok(address.into())
+        let function = SerializedFunction::parse(abi_json)?;
+        self.with_state(|state| state.function_registry().insert(name, function));
+
+        Ok(())
     }
 
+    /// Returns the names of the registered functions
     #[query]
-    fn http_request(&self, req: HttpRequest) -> HttpResponse {
-        if req.method.as_ref() != "POST" {
-            return HttpResponse::error(400, "Method not allowed".to_string());
-        }
+    pub fn list_registered_functions(&self) -> Vec<String> {
+        self.with_state(|state| state.function_registry().list())
+    }
 
-        HttpResponse {
-            status_code: 204,
-            headers: HashMap::new(),
-            body: ByteBuf::new(),
-            upgrade: Some(true),
-        }
+    /// Returns the JSON ABI of the function registered under the name
+    #[query]
+    pub fn get_registered_function(&self, name: String) -> Result<String> {
+        self.with_state(|state| state.function_registry().get(&name))
+            .map(|function| function.abi_json().to_string())
     }
 
+    /// Updates the endpoints of a registered provider and of all the oracle
+    /// destinations bound to its chain
     #[update]
-    pub async fn http_request_update(&self, req: HttpRequest) -> HttpResponse {
-        log::debug!("start http_request_update: {:?}", req);
+    pub fn update_provider(&mut self, chain_id: u64, endpoints: Vec<String>) -> Result<()> {
+        self.check_owner_or_admin(ic::caller())?;
 
-        let body = serde_json::from_slice::<Value>(&req.body)
-            .map_err(|e| Error::Http(format!("serde_json err: {}", e)))
-            .and_then(|body| {
-                let message = body
-                    .get("message")
-                    .and_then(|v| v.as_str())
-                    .ok_or_else(|| Error::Http("message is missing".to_string()))?;
-                let signature = body
-                    .get("signature")
-                    .and_then(|v| v.as_str())
-                    .ok_or_else(|| Error::Http("signature is missing".to_string()))?;
-                Ok((message.to_string(), signature.to_string()))
-            });
+        self.with_state(|state| state.provider_registry().get(chain_id))?;
 
-        match body {
-            Ok((message, signature)) => {
-                let address =
-                    Self::recover_pubkey(message, signature).expect("failed to recover public key");
+        let provider = Provider {
+            chain_id,
+            endpoints,
+        };
+        validate_provider_endpoints(&provider)?;
 
-                let signer = self
-                    .context
-                    .0
-                    .borrow()
-                    .get_state()
-                    .signer
-                    .get_oracle_signer(address);
+        let updated = self.with_state(|state| {
+            state.provider_registry().insert(provider.clone());
+            state.oracle_storage().update_registered_provider(&provider)
+        });
 
-                let address = signer
-                    .get_address()
-                    .await
-                    .map_err(|e| Error::Internal(format!("failed to get address: {e}")));
+        log::info!("updated the provider of chain {chain_id} for {updated} oracles");
 
-                match address {
-                    Ok(address) => HttpResponse::new(
-                        200,
-                        HashMap::from([("content-type".into(), "text/plain".into())]),
-                        ByteBuf::from(address.0.as_bytes()),
-                        None,
-                    ),
-                    Err(e) => {
-                        log::error!("failed to get address: {:?}", e.to_string());
-                        HttpResponse::error(400, e.to_string())
-                    }
-                }
+        Ok(())
+    }
+
+    /// Replaces the endpoints of the providers of the given chain in the oracles
+    /// of all the users and returns the number of updated oracles
+    ///
+    /// The provider registered for the chain, if any, is replaced as well. The
+    /// timers failing to restart are logged without stopping the other restarts.
+    ///
+    /// # Arguments
+    /// * `chain_id` - The chain of the providers to update
+    /// * `endpoints` - The new JSON-RPC endpoints of the providers
+    /// * `update_origins` - Whether the providers of the EVM origins are updated
+    /// * `update_destinations` - Whether the providers of the destinations are updated
+    /// * `restart_timers` - Whether the timers of the updated oracles are started again
+    #[update]
+    pub fn update_provider_for_chain(
+        &mut self,
+        chain_id: u64,
+        endpoints: Vec<String>,
+        update_origins: bool,
+        update_destinations: bool,
+        restart_timers: bool,
+    ) -> Result<u64> {
+        self.check_owner_or_admin(ic::caller())?;
+
+        let provider = Provider {
+            chain_id,
+            endpoints,
+        };
+        validate_provider_endpoints(&provider)?;
+
+        let updated = self.with_state(|state| {
+            if state.provider_registry().get(chain_id).is_ok() {
+                state.provider_registry().insert(provider.clone());
             }
-            Err(e) => {
-                log::error!("failed to recover public key: {:?}", e.to_string());
-                HttpResponse::error(400, e.to_string())
+
+            state.oracle_storage().update_chain_provider(
+                &provider,
+                update_origins,
+                update_destinations,
+            )
+        });
+
+        if restart_timers {
+            let errors = updated
+                .iter()
+                .filter_map(|(user_address, contract_address)| {
+                    self.restart_oracle_timer(user_address.clone(), contract_address.clone())
+                        .err()
+                        .map(|e| format!("oracle {contract_address} of user {user_address}: {e}"))
+                })
+                .collect::<Vec<_>>();
+            for error in errors {
+                log::error!("failed to restart oracle timer: {error:?}");
             }
         }
+
+        log::info!(
+            "updated the provider of chain {chain_id} for {} oracles",
+            updated.len()
+        );
+
+        Ok(updated.len() as u64)
     }
 
-    /// Requirements for Http outcalls, used to ignore small differences in the data obtained
-    /// by different nodes of the IC subnet to reach a consensus, more info:
-    /// https://internetcomputer.org/docs/current/developer-docs/integrations/http_requests/http_requests-how-it-works#transformation-function
+    /// Returns the provider registered for the chain
     #[query]
-    fn transform(&self, raw: TransformArgs) -> MHttpResponse {
-        transform(raw)
+    pub fn get_provider(&self, chain_id: u64) -> Result<Provider> {
+        self.with_state(|state| state.provider_registry().get(chain_id))
     }
 
-    /// Updates the metadata of the given oracle
+    /// Checks that the provider is reachable by fetching its latest block number
     ///
-    /// # Arguments
-    /// * `contract_address` - The address of the contract that will be updated
-    /// * `metadata` - The metadata that will be used to update the oracle
+    /// The cycles consumed by the check are measured from the canister balance.
+    #[update]
+    pub async fn check_provider_health(&self, provider: Provider) -> Result<ProviderHealthReport> {
+        provider.validate()?;
+
+        let balance_before = ic_cdk::api::canister_balance128();
+        let mut report = check_provider(&provider).await;
+        report.cycles_consumed = Some(consumed_cycles(
+            balance_before,
+            ic_cdk::api::canister_balance128(),
+        ));
+
+        Ok(report)
+    }
+
+    /// Checks the providers of the registry in parallel
     ///
-    /// # Errors
-    /// * If the caller is not the owner
-    /// * If the metadata is None
-    /// * If the oracle is not found
+    /// The cycles consumed by each check are not measured, as the checks run
+    /// concurrently.
+    #[update]
+    pub async fn check_all_registered_providers(&self) -> Vec<(u64, ProviderHealthReport)> {
+        let providers = self.with_state(|state| state.provider_registry().list());
+
+        let reports = futures::future::join_all(providers.iter().map(check_provider)).await;
+
+        providers
+            .into_iter()
+            .map(|provider| provider.chain_id)
+            .zip(reports)
+            .collect()
+    }
+
+    /// Removes the URL allowlist, allowing any URL for HTTP origins
+    #[update]
+    pub fn clear_url_allowlist(&mut self) -> Result<()> {
+        self.check_owner(ic::caller())?;
+
+        self.with_state_mut(|state| state.set_url_allowlist(None));
+        Ok(())
+    }
+
+    /// Returns the URL prefixes allowed for HTTP origins
+    #[query]
+    pub fn get_url_allowlist(&self) -> Option<Vec<String>> {
+        self.with_state(|state| state.url_allowlist())
+    }
+
+    /// Updates the runtime configuration of the logger with a new filter in the same form as the `RUST_LOG`
+    /// environment variable.
+    /// Example of valid filters:
+    /// - info
+    /// - debug,crate1::mod1=error,crate1::mod2,crate2=debug
+    #[update]
+    pub fn set_logger_filter(&mut self, filter: String) -> Result<()> {
+        self.check_owner_or_admin(ic::caller())?;
+        self.logger_config_service().set_logger_filter(&filter)?;
+
+        debug!("updated logger filter to {filter}");
+
+        Ok(())
+    }
+
+    /// Gets the logs
+    /// - `count` is the number of logs to return
+    #[update]
+    pub fn ic_logs(&self, count: usize) -> Result<Vec<String>> {
+        self.check_owner_or_admin(ic::caller())?;
+
+        Ok(ic_log::take_memory_records(count))
+    }
+
+    /// Get all the oracles created
+    #[query]
+    pub fn get_all_oracles(&self) -> Result<Vec<(H160, BTreeMap<H160, OracleMetadata>)>> {
+        self.check_owner_or_admin(ic::caller())?;
+
+        Ok(self.with_state(|state| state.oracle_storage().get_oracles()))
+    }
+
+    /// Returns the addresses of the users with at least one oracle
+    #[query]
+    pub fn get_all_users(&self) -> Result<Vec<H160>> {
+        self.check_owner(ic::caller())?;
+
+        Ok(self.with_state(|state| state.oracle_storage().get_users()))
+    }
+
+    /// Returns the `limit` most recent oracle updates which failed after all their
+    /// retries, most recent first
+    #[query]
+    pub fn get_dead_letter_queue(&self, limit: usize) -> Result<Vec<(u64, DeadLetterEntry)>> {
+        self.check_owner_or_admin(ic::caller())?;
+
+        Ok(self.with_state(|state| state.dead_letter_queue().get_entries(limit)))
+    }
+
+    /// Drops the failed oracle updates of the dead letter queue and returns their number
+    #[update]
+    pub fn clear_dead_letter_queue(&mut self) -> Result<u64> {
+        self.check_owner(ic::caller())?;
+
+        Ok(self.with_state_mut(|state| state.dead_letter_queue().drain()))
+    }
+
+    /// Returns the number of oracles registered by the given user
+    #[query]
+    pub fn get_user_oracle_count(&self, user_address: H160) -> u64 {
+        self.with_state(|state| state.oracle_storage().get_user_oracle_count(user_address))
+    }
+
+    /// Returns the cycles balance of the canister
+    #[query]
+    pub fn get_canister_cycle_balance(&self) -> Result<u128> {
+        self.check_owner(ic::caller())?;
+
+        Ok(ic_cdk::api::canister_balance128())
+    }
+
+    /// Returns the resources of the canister along with its number of oracles and users
+    #[query]
+    pub fn get_canister_stats(&self) -> Result<CanisterStats> {
+        self.check_owner(ic::caller())?;
+
+        let (oracle_count, user_count) = self.with_state(|state| {
+            let oracle_storage = state.oracle_storage();
+            (
+                oracle_storage.get_oracle_count(),
+                oracle_storage.get_users().len() as u64,
+            )
+        });
+
+        Ok(CanisterStats {
+            cycle_balance: ic_cdk::api::canister_balance128(),
+            stable_memory_pages: ic_cdk::api::stable::stable64_size(),
+            heap_memory_bytes: heap_memory_bytes(),
+            oracle_count,
+            user_count,
+        })
+    }
+
+    /// Returns the resources of the canister for the monitoring probes, which
+    /// cannot authenticate
     ///
-    /// # Note
-    /// When we update the metadata, we also update the timer that will be used
-    /// to update the price of the oracle
+    /// The oracles are counted without being converted.
+    #[query]
+    pub fn get_canister_health(&self) -> CanisterHealth {
+        let (statuses, oracle_count, user_count) = self.with_state(|state| {
+            let oracle_storage = state.oracle_storage();
+            (
+                oracle_storage.get_oracle_statuses(),
+                oracle_storage.get_oracle_count(),
+                oracle_storage.get_users().len() as u64,
+            )
+        });
+
+        CanisterHealth {
+            stable_memory_pages: ic_cdk::api::stable::stable64_size(),
+            active_timer_count: statuses
+                .iter()
+                .filter(|status| status.has_running_timer())
+                .count() as u64,
+            oracle_count,
+            user_count,
+            cycle_balance: ic_cdk::api::canister_balance128(),
+            ic_time_ns: ic_cdk::api::time(),
+        }
+    }
+
+    /// Returns the stable memory pages allocated to the oracle storage and the settings
+    #[query]
+    pub fn get_memory_usage(&self) -> Result<MemoryUsageReport> {
+        self.check_owner(ic::caller())?;
+
+        Ok(MemoryUsageReport {
+            oracle_storage_pages: memory::memory_pages(ORACLE_STORAGE_MEMORY_ID),
+            settings_pages: memory::memory_pages(SETTINGS_MEMORY_ID),
+            total_pages: ic_cdk::api::stable::stable64_size(),
+            max_pages: MAX_STABLE_MEMORY_PAGES,
+        })
+    }
+
+    /// Returns the number of users in the oracle storage, without loading their oracles
+    #[query]
+    pub fn get_oracle_storage_entry_count(&self) -> u64 {
+        self.with_state(|state| state.oracle_storage().get_storage_entry_count())
+    }
+
+    /// Sets the stable memory size in pages above which a warning is logged on
+    /// oracle creation; `None` disables the warning
     #[update]
-    pub async fn update_oracle_metadata(
-        &self,
-        user_address: H160,
-        contract_address: H160,
-        metadata: UpdateOracleMetadata,
-    ) -> Result<()> {
-        // If all the values are None, then return an error
-        if metadata.is_none() {
-            return Err(Error::Internal(
-                "At least one of the metadata fields must be set".to_string(),
-            ));
+    pub fn set_stable_memory_alert_pages(&mut self, pages: Option<u64>) -> Result<()> {
+        self.check_owner(ic::caller())?;
+
+        self.with_state_mut(|state| state.set_stable_memory_alert_pages(pages));
+
+        Ok(())
+    }
+
+    /// Returns the stable memory alert threshold in pages, if any
+    #[query]
+    pub fn get_stable_memory_alert_pages(&self) -> Option<u64> {
+        self.with_state(|state| state.stable_memory_alert_pages())
+    }
+
+    /// Returns a snapshot of the oracles and canister resources
+    #[query]
+    pub fn get_health_summary(&self) -> OracleHealthSummary {
+        let oracles = self.with_state(|state| state.oracle_storage().get_oracles());
+
+        let mut summary = OracleHealthSummary {
+            total_users: oracles.len() as u64,
+            cycles_balance: ic_cdk::api::canister_balance128(),
+            stable_memory_bytes: ic_cdk::api::stable::stable64_size() * 65536,
+            ..Default::default()
+        };
+
+        for metadata in oracles
+            .iter()
+            .flat_map(|(_, user_oracles)| user_oracles.values())
+        {
+            summary.total_oracles += 1;
+            match metadata.status {
+                OracleStatus::Active => summary.active_oracles += 1,
+                OracleStatus::Paused => summary.paused_oracles += 1,
+                OracleStatus::Error => summary.errored_oracles += 1,
+                OracleStatus::CircuitOpen { .. } => summary.circuit_open_oracles += 1,
+                OracleStatus::Pending => summary.pending_oracles += 1,
+            }
         }
 
-        let old_md = self.with_state(|state| {
-            state
-                .oracle_storage()
-                .get_oracle_by_address(user_address.0.into(), contract_address.clone())
-        })?;
+        summary
+    }
 
-        // Check the old owner of the oracle
-        if old_md.owner != user_address {
-            return Err(Error::Internal(
-                "caller is not the owner of the oracle".to_string(),
-            ));
+    /// Returns the statuses of all the oracles along with their activity in the
+    /// last 24 hours
+    #[query]
+    pub fn get_dashboard_stats(&self) -> DashboardStats {
+        let oracles = self.with_state(|state| state.oracle_storage().get_oracles());
+
+        let now = ic::time();
+        let mut stats = DashboardStats::default();
+        for metadata in oracles
+            .iter()
+            .flat_map(|(_, user_oracles)| user_oracles.values())
+        {
+            stats.record(metadata, now);
         }
 
-        let timer_id = self.with_state(|state| {
-            state
-                .oracle_storage()
-                .get_timer_id_by_address(user_address.0.into(), contract_address.clone())
-        })?;
+        stats
+    }
 
-        ic_exports::ic_cdk_timers::clear_timer(timer_id);
+    /// Returns the number of oracles, of active oracles and of errored oracles
+    ///
+    /// The statuses are read without converting the oracles, making it cheaper
+    /// than `get_dashboard_stats`.
+    #[query]
+    pub fn get_quick_stats(&self) -> (u64, u64, u64) {
+        let statuses = self.with_state(|state| state.oracle_storage().get_oracle_statuses());
 
-        let timer_id = Self::init_price_timer(
-            get_base_context(&self.context.0),
-            user_address.0.into(),
-            metadata.timestamp.unwrap_or(old_md.timer_interval),
-            metadata.origin.clone().unwrap_or(old_md.origin),
-            metadata.evm.clone().unwrap_or(old_md.evm),
+        let count = |status: OracleStatus| statuses.iter().filter(|s| **s == status).count() as u64;
+
+        (
+            statuses.len() as u64,
+            count(OracleStatus::Active),
+            count(OracleStatus::Error),
         )
-        .await?;
+    }
 
-        self.with_state_mut(|state| {
-            state.mut_oracle_storage().update_oracle_metadata(
-                user_address,
-                contract_address,
-                Some(timer_id),
-                metadata,
-            )
-        })?;
+    /// Returns the trigger edges between the oracles of all the users
+    #[query]
+    pub fn get_oracle_dependency_graph(&self) -> Vec<DependencyEdge> {
+        let oracles = self.with_state(|state| state.oracle_storage().get_oracles());
 
-        Ok(())
+        dependency_edges(&oracles)
     }
 
-    #[update]
-    pub fn delete_oracle(&mut self, user_address: H160, contract_address: H160) -> Result<()> {
-        // Get the owner
-        let owner = self.with_state(|state| {
+    /// Returns the contract addresses of all the oracles ordered so that every
+    /// oracle comes before the oracles it triggers
+    ///
+    /// Fails with `CircularDependency` when the triggers form a cycle.
+    #[query]
+    pub fn validate_dependency_graph(&self) -> Result<Vec<H160>> {
+        let oracles = self.with_state(|state| state.oracle_storage().get_oracles());
+
+        let nodes = oracles.iter().flat_map(|(user_address, user_oracles)| {
+            user_oracles
+                .keys()
+                .map(move |contract_address| (user_address.clone(), contract_address.clone()))
+        });
+        let order = dependency_order(nodes, &dependency_edges(&oracles))?;
+
+        Ok(order
+            .into_iter()
+            .map(|(_, contract_address)| contract_address)
+            .collect())
+    }
+
+    /// Returns the list of oracles for the given user
+    #[query]
+    pub fn get_user_oracles(&self, user_address: H160) -> Result<Vec<(H160, OracleMetadata)>> {
+        let oracles =
+            self.with_state(|state| state.oracle_storage().get_user_oracles(user_address))?;
+
+        Ok(oracles)
+    }
+
+    /// Returns the oracles of the given user with the given origin type
+    #[query]
+    pub fn get_user_oracles_by_type(
+        &self,
+        user_address: H160,
+        origin_type: OriginType,
+    ) -> Result<Vec<(H160, OracleMetadata)>> {
+        self.with_state(|state| {
             state
                 .oracle_storage()
-                .get_oracle_owner(user_address.0.into(), contract_address.clone())
-        })?;
-
-        if owner != user_address {
-            return Err(Error::Internal(
-                "caller is not the owner of the oracle".to_string(),
-            ));
-        }
+                .get_user_oracles_by_type(user_address, origin_type)
+        })
+    }
 
-        let timer_id = self.with_state(|state| {
+    /// Returns the oracles of the given user with the given tag
+    #[query]
+    pub fn get_user_oracles_by_tag(
+        &self,
+        user_address: H160,
+        tag: String,
+    ) -> Result<Vec<(H160, OracleMetadata)>> {
+        self.with_state(|state| {
             state
                 .oracle_storage()
-                .get_timer_id_by_address(user_address.0.into(), contract_address.clone())
-        })?;
+                .get_user_oracles_by_tag(user_address, &tag)
+        })
+    }
 
-        ic_exports::ic_cdk_timers::clear_timer(timer_id);
+    /// Returns the `(user_address, contract_address, metadata)` of the oracles
+    /// of all the users with the given tag
+    #[query]
+    pub fn get_all_oracles_by_tag(&self, tag: String) -> Result<Vec<(H160, H160, OracleMetadata)>> {
+        self.check_owner_or_admin(ic::caller())?;
+
+        Ok(self.with_state(|state| state.oracle_storage().get_oracles_by_tag(&tag)))
+    }
+
+    /// Adds a tag to the given oracle
+    #[update]
+    pub fn tag_oracle(
+        &mut self,
+        user_address: H160,
+        contract_address: H160,
+        tag: String,
+    ) -> Result<()> {
+        self.check_oracle_owner(&user_address, &contract_address)?;
+        check_tags(std::slice::from_ref(&tag))?;
 
         self.with_state_mut(|state| {
             state
                 .mut_oracle_storage()
-                .remove_oracle_by_address(user_address, contract_address)
-        })?;
-
-        Ok(())
+                .add_oracle_tag(user_address, contract_address, tag)
+        })
     }
 
-    /// Creates an oracle that will fetch the data from the given URL
-    /// and will update the price of the given contract
-    /// every `timestamp` seconds
-    ///
-    /// # Arguments
-    /// * `origin` - The origin of the data that will be used to update the price
-    /// * `timestamp` - The interval in seconds that will be used to update the price
-    /// * `destination` - The destination of the data that will be used to update the price
-    ///
+    /// Removes a tag from the given oracle
     #[update]
-    pub async fn create_oracle(
+    pub fn untag_oracle(
         &mut self,
         user_address: H160,
-        origin: Origin,
-        timestamp: u64,
-        destination: EvmDestination,
+        contract_address: H160,
+        tag: String,
     ) -> Result<()> {
-        log::debug!("creating new oracle: {:?}", origin);
+        self.check_oracle_owner(&user_address, &contract_address)?;
 
-        // Start the timer
-        let timer_id = Self::init_price_timer(
-            get_base_context(&self.context.0),
-            user_address.0.into(),
-            timestamp,
-            origin.clone(),
-            destination.clone(),
-        )
-        .await?;
+        self.with_state_mut(|state| {
+            state
+                .mut_oracle_storage()
+                .remove_oracle_tag(user_address, contract_address, &tag)
+        })
+    }
+
+    /// Returns the oracles of the given user whose label contains `label`, ignoring case
+    #[query]
+    pub fn find_oracles_by_label(
+        &self,
+        user_address: H160,
+        label: String,
+    ) -> Result<Vec<(H160, OracleMetadata)>> {
+        self.with_state(|state| {
+            state
+                .oracle_storage()
+                .find_user_oracles_by_label(user_address, &label)
+        })
+    }
+
+    /// Get all the oracles with the given origin type
+    #[query]
+    pub fn get_all_oracles_by_type(
+        &self,
+        origin_type: OriginType,
+    ) -> Result<Vec<(H160, BTreeMap<H160, OracleMetadata>)>> {
+        self.check_owner_or_admin(ic::caller())?;
+
+        Ok(self.with_state(|state| state.oracle_storage().get_oracles_by_type(origin_type)))
+    }
+
+    /// Returns the `(user_address, contract_address)` pairs of the expired oracles
+    #[query]
+    pub fn get_expired_oracles(&self) -> Result<Vec<(H160, H160)>> {
+        self.check_owner_or_admin(ic::caller())?;
+
+        Ok(self.with_state(|state| state.oracle_storage().get_expired_oracles(ic::time())))
+    }
+
+    /// Returns the address of the sender of the transaction using
+    /// the management canister
+    #[update]
+    pub async fn get_address(&self, address: H160) -> Result<H160> {
+        let signer = {
+            self.context
+                .0
+                .borrow()
+                .get_state()
+                .signer
+                .get_oracle_signer(address)
+        };
+
+        Ok(signer.get_address().await?)
+    }
+
+    /// Rotates the signing key of the oracle and returns the new address sending
+    /// its update transactions
+    ///
+    /// The new address must be funded and authorized by the contract of the oracle
+    /// before the next update. Only the owner or an admin of the canister can rotate
+    /// the signer.
+    #[update]
+    pub async fn rotate_oracle_signer(
+        &self,
+        user_address: H160,
+        contract_address: H160,
+    ) -> Result<H160> {
+        self.check_owner_or_admin(ic::caller())?;
+        self.check_oracle_owner(&user_address, &contract_address)?;
+
+        let metadata = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_by_address(user_address.clone(), contract_address.clone())
+        })?;
+        let rotation_count = metadata
+            .rotation_count
+            .checked_add(1)
+            .ok_or_else(|| Error::Internal("signer rotation limit reached".to_string()))?;
+
+        let signer = self.with_state(|state| {
+            state.signer().get_rotated_oracle_signer(
+                user_address.clone(),
+                rotation_count,
+                &metadata.derivation_suffix,
+            )
+        });
+        let address = signer.get_address().await?;
+
+        // Another rotation may have completed while the address was derived
+        let current_rotation_count = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_by_address(user_address.clone(), contract_address.clone())
+                .map(|metadata| metadata.rotation_count)
+        })?;
+        if current_rotation_count != metadata.rotation_count {
+            return Err(Error::Internal(
+                "the signer of the oracle was rotated concurrently".to_string(),
+            ));
+        }
+
+        self.with_state_mut(|state| {
+            state.mut_oracle_storage().set_rotation_count(
+                user_address.clone(),
+                contract_address.clone(),
+                rotation_count,
+            )
+        })?;
+
+        // Start a new update schedule with the new signer
+        self.restart_oracle_timer(user_address.clone(), contract_address.clone())?;
+
+        log::info!(
+            "rotated signer of oracle {contract_address} of user {user_address} to {address}"
+        );
+        self.emit_event(user_address, contract_address, OracleEventType::Updated);
+
+        Ok(address)
+    }
+
+    /// Returns the metadata of the given oracle
+    ///
+    /// # Arguments
+    /// * `contract_address` - The address of the contract that will be fetched
+    /// * `user_address` - The address of the user that created the oracle
+    #[query]
+    pub fn get_oracle_metadata(
+        &self,
+        user_address: H160,
+        contract_address: H160,
+    ) -> Result<OracleMetadata> {
+        let metadata = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_by_address(user_address, contract_address)
+        })?;
+
+        Ok(metadata)
+    }
+
+    /// Estimates the cycles an oracle would consume in 24 hours
+    ///
+    /// The estimation does not make any HTTP call; it is computed from the
+    /// length of the URLs used by the oracle.
+    ///
+    /// # Arguments
+    /// * `origin` - The origin of the data that will be used to update the price
+    /// * `destination` - The destination of the data that will be used to update the price
+    /// * `interval_secs` - The interval in seconds that will be used to update the price
+    #[query]
+    pub fn estimate_oracle_daily_cost(
+        &self,
+        origin: Origin,
+        destination: EvmDestination,
+        interval_secs: u64,
+    ) -> u128 {
+        let updates_per_day = (SECONDS_PER_DAY / interval_secs.max(1)) as u128;
+
+        estimate_update_cost(&origin, &destination) * updates_per_day
+    }
+
+    /// Returns the latest round of the given oracle, mirroring Chainlink's
+    /// `AggregatorV3Interface::latestRoundData`
+    ///
+    /// # Arguments
+    /// * `user_address` - The address of the user that created the oracle
+    /// * `contract_address` - The address of the contract updated by the oracle
+    #[query]
+    pub fn latest_round_data(
+        &self,
+        user_address: H160,
+        contract_address: H160,
+    ) -> Result<RoundData> {
+        let metadata = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_by_address(user_address, contract_address)
+        })?;
+
+        let answer = metadata.last_price.ok_or(Error::PriceNotAvailable)?;
+        let updated_at = metadata.last_updated_at / 1_000_000_000;
+
+        Ok(RoundData {
+            round_id: metadata.round_id,
+            answer,
+            started_at: updated_at,
+            updated_at,
+            answered_in_round: metadata.round_id,
+        })
+    }
+
+    /// Returns the last price pushed by the given oracle, mirroring Chainlink's
+    /// `AggregatorInterface::latestAnswer`
+    #[query]
+    pub fn latest_answer(&self, user_address: H160, contract_address: H160) -> Result<U256> {
+        Ok(self
+            .latest_round_data(user_address, contract_address)?
+            .answer)
+    }
+
+    /// Signs the last price of the given oracle following EIP-712 with the oracle key
+    ///
+    /// The domain is the one of the oracle: its name and version are fixed on
+    /// creation, the chain and the verifying contract are the ones of its destination.
+    /// The signed data is stored in the oracle metadata as its last attestation.
+    ///
+    /// # Arguments
+    /// * `user_address` - The address of the user that created the oracle
+    /// * `contract_address` - The contract updated by the oracle
+    #[update]
+    pub async fn sign_oracle_data_eip712(
+        &self,
+        user_address: H160,
+        contract_address: H160,
+    ) -> Result<SignedOracleData> {
+        self.check_owner_or_admin(ic::caller())?;
+
+        let metadata = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_by_address(user_address.clone(), contract_address.clone())
+        })?;
+
+        let price = metadata.last_price.ok_or(Error::PriceNotAvailable)?;
+        let timestamp = metadata.last_updated_at / 1_000_000_000;
+
+        let domain = metadata
+            .eip712_domain
+            .domain(metadata.evm.provider.chain_id, contract_address.clone());
+        let digest = eip712::oracle_data_digest(&domain, &contract_address, &price, timestamp);
+
+        let signer = self.with_state(|state| {
+            state.signer().get_rotated_oracle_signer(
+                user_address.clone(),
+                metadata.rotation_count,
+                &metadata.derivation_suffix,
+            )
+        });
+        let signature = signer.sign_digest(digest).await?;
+
+        let signed_data = SignedOracleData {
+            price,
+            timestamp,
+            signature,
+        };
+
+        self.with_state_mut(|state| {
+            state.mut_oracle_storage().set_last_attestation(
+                user_address,
+                contract_address,
+                signed_data.clone(),
+            )
+        })?;
+
+        Ok(signed_data)
+    }
+
+    /// Recovers the public key from the given message and signature
+    /// and adds the signer to the list of signers
+    ///
+    /// This is used for the users to sign the transactions using the threshold
+    /// ECDSA
+    pub fn recover_pubkey(message: String, signature: String) -> Result<H160> {
+        let signature = Signature::from_str(&signature).map_err(|e| {
+            Error::Internal(format!("failed to parse signature: {:?}", e.to_string()))
+        })?;
+
+        let address = signature.recover(message).map_err(|e| {
+            Error::Internal(format!("failed to recover public key: {:?}", e.to_string()))
+        })?;
+
+        Ok(address.into())
+    }
+
+    #[query]
+    fn http_request(&self, req: HttpRequest) -> HttpResponse {
+        if req.method.as_ref() != "POST" {
+            return HttpResponse::error(400, "Method not allowed".to_string());
+        }
+
+        HttpResponse {
+            status_code: 204,
+            headers: HashMap::new(),
+            body: ByteBuf::new(),
+            upgrade: Some(true),
+        }
+    }
+
+    #[update]
+    pub async fn http_request_update(&self, req: HttpRequest) -> HttpResponse {
+        log::debug!("start http_request_update: {:?}", req);
+
+        let body = serde_json::from_slice::<Value>(&req.body)
+            .map_err(|e| Error::Http(format!("serde_json err: {}", e)))
+            .and_then(|body| {
+                let message = body
+                    .get("message")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| Error::Http("message is missing".to_string()))?;
+                let signature = body
+                    .get("signature")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| Error::Http("signature is missing".to_string()))?;
+                Ok((message.to_string(), signature.to_string()))
+            });
+
+        match body {
+            Ok((message, signature)) => {
+                let address =
+                    Self::recover_pubkey(message, signature).expect("failed to recover public key");
+
+                let signer = self
+                    .context
+                    .0
+                    .borrow()
+                    .get_state()
+                    .signer
+                    .get_oracle_signer(address);
+
+                let address = signer
+                    .get_address()
+                    .await
+                    .map_err(|e| Error::Internal(format!("failed to get address: {e}")));
+
+                match address {
+                    Ok(address) => HttpResponse::new(
+                        200,
+                        HashMap::from([("content-type".into(), "text/plain".into())]),
+                        ByteBuf::from(address.0.as_bytes()),
+                        None,
+                    ),
+                    Err(e) => {
+                        log::error!("failed to get address: {:?}", e.to_string());
+                        HttpResponse::error(400, e.to_string())
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("failed to recover public key: {:?}", e.to_string());
+                HttpResponse::error(400, e.to_string())
+            }
+        }
+    }
+
+    /// Requirements for Http outcalls, used to ignore small differences in the data obtained
+    /// by different nodes of the IC subnet to reach a consensus, more info:
+    /// https://internetcomputer.org/docs/current/developer-docs/integrations/http_requests/http_requests-how-it-works#transformation-function
+    #[query]
+    fn transform(&self, raw: TransformArgs) -> MHttpResponse {
+        transform(raw)
+    }
+
+    /// Transform of the login outcalls of the HTTP origins, keeping only their session cookie
+    #[query]
+    fn transform_login(&self, raw: TransformArgs) -> MHttpResponse {
+        transform_login(raw)
+    }
+
+    /// Updates the metadata of the given oracle
+    ///
+    /// # Arguments
+    /// * `contract_address` - The address of the contract that will be updated
+    /// * `metadata` - The metadata that will be used to update the oracle
+    ///
+    /// # Errors
+    /// * If the caller is not the owner
+    /// * If the metadata is None
+    /// * If the oracle is not found
+    ///
+    /// # Note
+    /// When we update the metadata of a running oracle, we also update the timer
+    /// that will be used to update the price of the oracle. The oracles paused, by
+    /// their user or by `pause_all_oracles`, stay paused.
+    #[update]
+    pub async fn update_oracle_metadata(
+        &self,
+        user_address: H160,
+        contract_address: H160,
+        mut metadata: UpdateOracleMetadata,
+    ) -> Result<()> {
+        // If all the values are None, then return an error
+        if metadata.is_none() {
+            return Err(Error::Internal(
+                "At least one of the metadata fields must be set".to_string(),
+            ));
+        }
+
+        let old_md = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_by_address(user_address.0.into(), contract_address.clone())
+        })?;
+
+        // Check the old owner of the oracle
+        if old_md.owner != user_address {
+            return Err(Error::Internal(
+                "caller is not the owner of the oracle".to_string(),
+            ));
+        }
+
+        if let Some(origin) = &metadata.origin {
+            self.check_origin_allowed(origin)?;
+            warn_plaintext_auth(origin);
+        }
+        if let Some(evm) = &mut metadata.evm {
+            self.resolve_destination(evm)?;
+        }
+        if let Some(triggers) = &metadata.triggers {
+            self.check_triggers(&user_address, &contract_address, triggers)?;
+        }
+        check_label(metadata.label.as_deref(), metadata.description.as_deref())?;
+        if let Some(tags) = &metadata.tags {
+            check_tags(tags)?;
+        }
+        if let Some(transforms) = &metadata.transforms {
+            transforms.iter().try_for_each(Transform::validate)?;
+        }
+        if let Some(outlier_config) = &metadata.outlier_config {
+            outlier_config.validate()?;
+        }
+        if let Some(custom_update_fn) = &metadata.custom_update_fn {
+            custom_update_fn.validate()?;
+        }
+        if metadata.tx_deadline_secs == Some(0) {
+            return Err(Error::Internal(
+                "transaction deadline must be greater than 0".to_string(),
+            ));
+        }
+        if metadata.max_gas_price_gwei == Some(0) {
+            return Err(Error::Internal(
+                "max gas price must be greater than 0".to_string(),
+            ));
+        }
+        if metadata.confirmation_blocks == Some(0) {
+            return Err(Error::Internal(
+                "confirmation blocks must be greater than 0".to_string(),
+            ));
+        }
+        if metadata
+            .price_bounds
+            .as_ref()
+            .is_some_and(|bounds| bounds.min_price > bounds.max_price)
+        {
+            return Err(Error::Internal(
+                "min price must not be greater than max price".to_string(),
+            ));
+        }
+        if metadata
+            .circuit_breaker
+            .as_ref()
+            .is_some_and(|circuit_breaker| circuit_breaker.failure_threshold == 0)
+        {
+            return Err(Error::Internal(
+                "circuit breaker failure threshold must be greater than 0".to_string(),
+            ));
+        }
+        if metadata.max_executions == Some(0) {
+            return Err(Error::Internal(
+                "max executions must be greater than 0".to_string(),
+            ));
+        }
+        let origin = metadata.origin.as_ref().unwrap_or(&old_md.origin);
+        validate_providers(origin, metadata.evm.as_ref().unwrap_or(&old_md.evm))?;
+        validate_origin(origin)?;
+        if metadata.decimals.is_some() && !matches!(origin, Origin::Http(_)) {
+            return Err(Error::Internal(
+                "decimals can only be set on HTTP origins".to_string(),
+            ));
+        }
+        if let Some(max_response_bytes) = metadata.max_response_bytes {
+            if !matches!(origin, Origin::Http(_)) {
+                return Err(Error::Internal(
+                    "max response bytes can only be set on HTTP origins".to_string(),
+                ));
+            }
+            check_max_response_bytes(max_response_bytes)?;
+        }
+        if let Some(expires_at) = metadata.expires_at {
+            check_expiry(expires_at)?;
+        }
+
+        // The timer of a stopped oracle, paused or failed, is left stopped, so that
+        // an edit does not resume it
+        let timer_id = if old_md.status.has_running_timer() {
+            let timer_id = self.with_state(|state| {
+                state
+                    .oracle_storage()
+                    .get_timer_id_by_address(user_address.0.into(), contract_address.clone())
+            })?;
+            ic_exports::ic_cdk_timers::clear_timer(timer_id);
+
+            Some(Self::init_price_timer(
+                get_base_context(&self.context.0),
+                user_address.clone(),
+                contract_address.clone(),
+                metadata.timestamp.unwrap_or(old_md.timer_interval),
+                pending_delay_secs(&old_md.status, old_md.first_tick_at, ic::time()),
+            ))
+        } else {
+            None
+        };
+
+        self.with_state_mut(|state| {
+            state.mut_oracle_storage().update_oracle_metadata(
+                user_address.clone(),
+                contract_address.clone(),
+                timer_id,
+                metadata,
+            )
+        })?;
+
+        self.emit_event(user_address, contract_address, OracleEventType::Updated);
+
+        Ok(())
+    }
+
+    /// Submits the prices of several contracts with a single `batchUpdatePrice`
+    /// transaction sent to the batch contract
+    ///
+    /// # Arguments
+    /// * `user_address` - The address of the user whose signer sends the transaction
+    /// * `batch_contract` - The contract implementing `IBatchPriceFeed`
+    /// * `updates` - The `(contract, price)` pairs to submit
+    /// * `provider` - The provider of the chain of the contracts
+    #[update]
+    pub async fn submit_prices_batch(
+        &self,
+        user_address: H160,
+        batch_contract: H160,
+        updates: Vec<(H160, U256)>,
+        provider: Provider,
+    ) -> Result<H256> {
+        self.check_owner_or_admin(ic::caller())?;
+        provider.validate()?;
+        if updates.is_empty() {
+            return Err(Error::Internal(
+                "at least one price update is required".to_string(),
+            ));
+        }
+
+        let data = provider::encode_batch_update_price(&updates)?;
+
+        let context = get_base_context(&self.context.0);
+        let transaction = get_transaction(
+            user_address,
+            0,
+            &[],
+            provider.clone(),
+            Some(batch_contract),
+            U256::zero(),
+            data,
+            None,
+            None,
+            &context,
+        )
+        .await?;
+
+        let tx_hash = provider::send_raw_transaction(&transaction, &provider, &context).await?;
+
+        log::info!(
+            "submitted {} prices to batch contract {batch_contract}: {:?}",
+            updates.len(),
+            tx_hash
+        );
+
+        Ok(tx_hash)
+    }
+
+    /// Runs an immediate update of the oracle, outside of its timer schedule
+    ///
+    /// The update is recorded like a timer-driven one and also runs on an oracle
+    /// paused by its user. It is rejected while the oracles are paused by
+    /// `pause_all_oracles`, once the oracle has expired, while its circuit is open
+    /// and when the max concurrent updates are running. Its timer and max executions
+    /// are left untouched.
+    #[update]
+    pub async fn trigger_oracle_update(
+        &self,
+        user_address: H160,
+        contract_address: H160,
+    ) -> Result<()> {
+        self.check_owner_or_admin(ic::caller())?;
+
+        let metadata = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_by_address(user_address.clone(), contract_address.clone())
+        })?;
+        if metadata.owner != user_address {
+            return Err(Error::Internal(
+                "user is not the owner of the oracle".to_string(),
+            ));
+        }
+
+        if metadata.status_before_emergency.is_some() {
+            return Err(Error::Internal(
+                "the oracle is paused by an emergency".to_string(),
+            ));
+        }
+        if metadata
+            .expires_at
+            .is_some_and(|expires_at| ic::time() >= expires_at)
+        {
+            return Err(Error::Internal("the oracle has expired".to_string()));
+        }
+        if let OracleStatus::CircuitOpen { resets_at, .. } = metadata.status {
+            if ic::time() < resets_at {
+                return Err(Error::Internal(
+                    "the circuit of the oracle is open".to_string(),
+                ));
+            }
+        }
+
+        let context = get_base_context(&self.context.0);
+        let limit = {
+            let context = context.borrow();
+            let state = context.get_state();
+            let limit = state.max_concurrent_updates();
+            (!state.active_updates().try_acquire(limit)).then_some(limit)
+        };
+        if let Some(limit) = limit {
+            return Err(Error::ConcurrentUpdatesExceeded(limit));
+        }
+        let guard = UpdateGuard::new(
+            context.clone(),
+            user_address.clone(),
+            contract_address.clone(),
+        );
+
+        let result = Self::execute_update(context, user_address, contract_address, metadata).await;
+
+        guard.finish();
+
+        result
+    }
+
+    #[update]
+    pub fn delete_oracle(&mut self, user_address: H160, contract_address: H160) -> Result<()> {
+        self.remove_oracle(user_address, contract_address)
+    }
+
+    /// Deletes the given oracles of the user
+    ///
+    /// A failed deletion does not abort the remaining ones; the result of each
+    /// deletion is returned along with the contract address.
+    #[update]
+    pub fn delete_oracles_batch(
+        &mut self,
+        user_address: H160,
+        contract_addresses: Vec<H160>,
+    ) -> Result<Vec<(H160, Result<()>)>> {
+        let results = contract_addresses
+            .into_iter()
+            .map(|contract_address| {
+                let result = self.remove_oracle(user_address.clone(), contract_address.clone());
+                (contract_address, result)
+            })
+            .collect();
+
+        Ok(results)
+    }
+
+    /// Deletes all the oracles of the user and returns the number of deleted oracles
+    ///
+    /// An oracle failing to be deleted is logged without stopping the deletion of
+    /// the other ones.
+    #[update]
+    pub fn delete_all_user_oracles(&mut self, user_address: H160) -> Result<u32> {
+        let oracles = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_user_oracles(user_address.clone())
+        })?;
+
+        let mut deleted = 0;
+        for (contract_address, _) in oracles {
+            match self.remove_oracle(user_address.clone(), contract_address.clone()) {
+                Ok(()) => deleted += 1,
+                Err(e) => log::error!(
+                    "failed to delete oracle {contract_address} of user {user_address}: {e}"
+                ),
+            }
+        }
+
+        Ok(deleted)
+    }
+
+    /// Returns the cycles consumed by the given oracle and its cycle budget
+    #[query]
+    pub fn get_oracle_cycle_stats(
+        &self,
+        user_address: H160,
+        contract_address: H160,
+    ) -> Result<OracleCycleStats> {
+        let metadata = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_by_address(user_address, contract_address)
+        })?;
+
+        Ok(OracleCycleStats {
+            consumed: metadata.cycles_consumed,
+            budget: metadata.cycle_budget,
+            remaining_budget: metadata
+                .cycle_budget
+                .map(|budget| budget.saturating_sub(metadata.cycles_consumed)),
+        })
+    }
+
+    /// Returns the cycles consumed by the updates of all the oracles
+    #[query]
+    pub fn get_total_cycles_consumed(&self) -> Result<u128> {
+        self.check_owner(ic::caller())?;
+
+        Ok(self.with_state(|state| state.oracle_storage().get_total_cycles_consumed()))
+    }
+
+    /// Returns the cycles consumed by the updates of the oracles of the given user
+    #[query]
+    pub fn get_user_cycles_consumed(&self, user_address: H160) -> Result<u128> {
+        self.check_owner(ic::caller())?;
+
+        Ok(self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_user_cycles_consumed(user_address)
+        }))
+    }
+
+    /// Returns the update statistics of the given oracle
+    #[query]
+    pub fn get_oracle_stats(
+        &self,
+        user_address: H160,
+        contract_address: H160,
+    ) -> Result<OracleStats> {
+        let metadata = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_by_address(user_address, contract_address)
+        })?;
+
+        Ok(OracleStats::from(&metadata))
+    }
+
+    /// Returns the cycles consumed by the recent updates of the given oracle
+    #[query]
+    pub fn get_oracle_cost_stats(
+        &self,
+        user_address: H160,
+        contract_address: H160,
+    ) -> Result<CostStats> {
+        let metadata = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_by_address(user_address, contract_address)
+        })?;
+
+        Ok(CostStats::from(&metadata))
+    }
+
+    /// Returns the cost statistics of the oracles of all the users, as
+    /// `(user_address, contract_address, stats)`
+    #[query]
+    pub fn get_all_oracle_cost_stats(&self) -> Result<Vec<(H160, H160, CostStats)>> {
+        self.check_owner(ic::caller())?;
+
+        let oracles = self.with_state(|state| state.oracle_storage().get_oracles());
+
+        Ok(oracles
+            .iter()
+            .flat_map(|(user_address, user_oracles)| {
+                user_oracles.iter().map(|(contract_address, metadata)| {
+                    (
+                        user_address.clone(),
+                        contract_address.clone(),
+                        CostStats::from(metadata),
+                    )
+                })
+            })
+            .collect())
+    }
+
+    /// Returns the reason of the last update of the given oracle skipped before its
+    /// price was submitted
+    #[query]
+    pub fn get_oracle_skip_reason(
+        &self,
+        user_address: H160,
+        contract_address: H160,
+    ) -> Result<Option<OracleSkipReason>> {
+        let metadata = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_by_address(user_address, contract_address)
+        })?;
+
+        Ok(metadata.last_skip_reason)
+    }
+
+    /// Returns the `limit` most recent lifecycle events of the given oracle, most recent first
+    #[query]
+    pub fn get_oracle_events(
+        &self,
+        user_address: H160,
+        contract_address: H160,
+        limit: usize,
+    ) -> Result<Vec<OracleEvent>> {
+        self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_events(user_address, contract_address, limit)
+        })
+    }
+
+    /// Returns the update statistics of all the oracles of the given user
+    #[query]
+    pub fn get_all_oracle_stats(&self, user_address: H160) -> Result<Vec<(H160, OracleStats)>> {
+        let oracles =
+            self.with_state(|state| state.oracle_storage().get_user_oracles(user_address))?;
+
+        Ok(oracles
+            .iter()
+            .map(|(contract_address, metadata)| {
+                (contract_address.clone(), OracleStats::from(metadata))
+            })
+            .collect())
+    }
+
+    /// Returns the result of the last simulated update transaction of the given oracle
+    #[query]
+    pub fn get_simulation_result(
+        &self,
+        user_address: H160,
+        contract_address: H160,
+    ) -> Result<Option<SimulationResult>> {
+        let metadata = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_by_address(user_address, contract_address)
+        })?;
+
+        Ok(metadata.last_simulated_result)
+    }
+
+    /// Returns the result of the update transaction sent to each destination of the
+    /// given oracle by its last update, keyed by contract address
+    #[query]
+    pub fn get_last_broadcast_results(
+        &self,
+        user_address: H160,
+        contract_address: H160,
+    ) -> Result<Vec<(H160, TxResult)>> {
+        let metadata = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_by_address(user_address, contract_address)
+        })?;
+
+        Ok(metadata
+            .last_broadcast_results
+            .into_iter()
+            .map(|destination| (destination.contract, destination.result))
+            .collect())
+    }
+
+    /// Resets the cycles consumed by the given oracle
+    ///
+    /// An oracle paused after exhausting its budget is not resumed.
+    #[update]
+    pub fn reset_oracle_cycle_counter(
+        &mut self,
+        user_address: H160,
+        contract_address: H160,
+    ) -> Result<()> {
+        let owner = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_owner(user_address.clone(), contract_address.clone())
+        })?;
+
+        if owner != user_address {
+            return Err(Error::Internal(
+                "caller is not the owner of the oracle".to_string(),
+            ));
+        }
+
+        self.with_state_mut(|state| {
+            state
+                .mut_oracle_storage()
+                .reset_cycles_consumed(user_address, contract_address)
+        })
+    }
+
+    /// Accepts the cycles attached to the call as a deposit to the cycle budget of
+    /// the given oracle and returns its remaining budget
+    ///
+    /// An oracle paused by its cycle budget is resumed when the remaining budget
+    /// exceeds its `resume_threshold_cycles`.
+    #[update]
+    pub fn deposit_cycles_for_oracle(
+        &mut self,
+        user_address: H160,
+        contract_address: H160,
+    ) -> Result<u128> {
+        let metadata = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_by_address(user_address.clone(), contract_address.clone())
+        })?;
+
+        if metadata.cycle_budget.is_none() {
+            return Err(Error::Internal(
+                "the oracle has no cycle budget".to_string(),
+            ));
+        }
+
+        let available = ic_cdk::api::call::msg_cycles_available128();
+        if available == 0 {
+            return Err(Error::Internal(
+                "no cycles attached to the call".to_string(),
+            ));
+        }
+        let deposit = ic_cdk::api::call::msg_cycles_accept128(available);
+
+        let remaining_budget = self.with_state_mut(|state| {
+            state.mut_oracle_storage().deposit_cycles(
+                user_address.clone(),
+                contract_address.clone(),
+                deposit,
+            )
+        })?;
+
+        log::debug!(
+            "deposited {deposit} cycles for oracle {contract_address} of user {user_address}"
+        );
+
+        let exhausted = metadata
+            .cycle_budget
+            .is_some_and(|budget| metadata.cycles_consumed >= budget);
+        if metadata.status == OracleStatus::Paused
+            && metadata.status_before_emergency.is_none()
+            && exhausted
+            && remaining_budget > metadata.resume_threshold_cycles.unwrap_or_default()
+        {
+            let timer_id = Self::init_price_timer(
+                get_base_context(&self.context.0),
+                user_address.clone(),
+                contract_address.clone(),
+                metadata.timer_interval,
+                None,
+            );
+
+            self.with_state_mut(|state| {
+                state.mut_oracle_storage().set_oracle_status(
+                    user_address.clone(),
+                    contract_address.clone(),
+                    OracleStatus::Active,
+                    Some(timer_id),
+                )
+            })?;
+
+            self.emit_event(user_address, contract_address, OracleEventType::Resumed);
+        }
+
+        Ok(remaining_budget)
+    }
+
+    /// Returns the last nonce used by the signature proofs of the address
+    #[query]
+    pub fn get_last_proof_nonce(&self, address: H160) -> Option<u64> {
+        self.with_state(|state| state.proof_nonces().last(&address))
+    }
+
+    /// Returns the message that `from` has to sign to transfer the oracle to `to`
+    #[query]
+    pub fn get_transfer_message(
+        &self,
+        from: H160,
+        to: H160,
+        contract_address: H160,
+        nonce: u64,
+        expires_at: u64,
+    ) -> String {
+        transfer_message(ic::id(), &from, &to, &contract_address, nonce, expires_at)
+    }
+
+    /// Transfers the oracle to another user address
+    ///
+    /// The `proof` must be signed by the `from` address over the message returned
+    /// by `get_transfer_message`, with a nonce greater than the last one used by
+    /// `from`, returned by `get_last_proof_nonce`. The triggers of the other oracles
+    /// are moved to `to`, and the oracle timer is started again, so that the updates
+    /// are signed with the key of `to`.
+    #[update]
+    pub fn transfer_oracle(
+        &mut self,
+        from: H160,
+        to: H160,
+        contract_address: H160,
+        proof: SignatureProof,
+    ) -> Result<()> {
+        if from == to {
+            return Err(Error::Internal(
+                "cannot transfer the oracle to the same address".to_string(),
+            ));
+        }
+
+        let now = ic::time();
+        proof.check_expiry(now)?;
+        proof.verify(
+            transfer_message(
+                ic::id(),
+                &from,
+                &to,
+                &contract_address,
+                proof.nonce,
+                proof.expires_at,
+            ),
+            &from,
+        )?;
+
+        self.check_oracle_limit(&to, 1)?;
+
+        let nonce_used =
+            !self.with_state(|state| state.proof_nonces().try_use(from.clone(), proof.nonce));
+        if nonce_used {
+            return Err(Error::Internal(
+                "the nonce of the proof must be greater than the last used one".to_string(),
+            ));
+        }
+
+        let metadata = self.with_state_mut(|state| {
+            state
+                .mut_oracle_storage()
+                .transfer_oracle(from, to.clone(), contract_address.clone())
+        })?;
+
+        if metadata.status.has_running_timer() {
+            ic_exports::ic_cdk_timers::clear_timer(metadata.timer_id);
+
+            let timer_id = Self::init_price_timer(
+                get_base_context(&self.context.0),
+                to.clone(),
+                contract_address.clone(),
+                metadata.timer_interval,
+                pending_delay_secs(&metadata.status, metadata.first_tick_at, ic::time()),
+            );
+
+            self.with_state_mut(|state| {
+                state.mut_oracle_storage().set_oracle_status(
+                    to,
+                    contract_address,
+                    metadata.status.clone(),
+                    Some(timer_id),
+                )
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Stops the timers of all the active oracles and returns the number of paused oracles
+    ///
+    /// Meant for incidents, the paused oracles are started again by `resume_all_oracles`.
+    #[update]
+    pub fn pause_all_oracles(&mut self) -> Result<u64> {
+        self.check_owner(ic::caller())?;
+
+        let paused = self.with_state_mut(|state| state.mut_oracle_storage().pause_active_oracles());
+
+        for (user_address, contract_address, timer_id) in &paused {
+            ic_exports::ic_cdk_timers::clear_timer(*timer_id);
+
+            self.emit_event(
+                user_address.clone(),
+                contract_address.clone(),
+                OracleEventType::Paused,
+            );
+        }
+
+        log::warn!("paused {} oracles", paused.len());
+
+        Ok(paused.len() as u64)
+    }
+
+    /// Starts the timers of the oracles paused by `pause_all_oracles` again and
+    /// returns the number of resumed oracles
+    ///
+    /// The oracles get back the status they had before being paused.
+    #[update]
+    pub fn resume_all_oracles(&mut self) -> Result<u64> {
+        self.check_owner(ic::caller())?;
+
+        let paused = self.with_state(|state| state.oracle_storage().get_emergency_paused_oracles());
+        let now = ic::time();
+
+        let timers = paused
+            .into_iter()
+            .map(|(user_address, contract_address, metadata)| {
+                let initial_delay = metadata
+                    .status_before_emergency
+                    .and_then(|status| pending_delay_secs(&status, metadata.first_tick_at, now));
+                let timer_id = Self::init_price_timer(
+                    get_base_context(&self.context.0),
+                    user_address.clone(),
+                    contract_address.clone(),
+                    metadata.timer_interval,
+                    initial_delay,
+                );
+
+                (user_address, contract_address, timer_id)
+            })
+            .collect::<Vec<_>>();
+        let count = timers.len() as u64;
+
+        self.with_state_mut(|state| {
+            state
+                .mut_oracle_storage()
+                .resume_emergency_paused_oracles(timers.clone())
+        });
+
+        for (user_address, contract_address, _) in timers {
+            self.emit_event(user_address, contract_address, OracleEventType::Resumed);
+        }
+
+        log::warn!("resumed {count} oracles");
+
+        Ok(count)
+    }
+
+    /// Drops the oldest prices of the history of the given oracle beyond
+    /// `keep_entries` and returns the number of removed prices
+    #[update]
+    pub fn prune_oracle_price_history(
+        &mut self,
+        user_address: H160,
+        contract_address: H160,
+        keep_entries: u32,
+    ) -> Result<u32> {
+        self.check_owner(ic::caller())?;
+
+        let removed = self.with_state_mut(|state| {
+            state.mut_oracle_storage().prune_price_history(
+                user_address,
+                contract_address,
+                keep_entries as usize,
+            )
+        })?;
+
+        Ok(removed as u32)
+    }
+
+    /// Drops the oldest prices of the histories of all the oracles beyond
+    /// `keep_entries` and returns the number of removed prices
+    #[update]
+    pub fn prune_all_price_histories(&mut self, keep_entries: u32) -> Result<u64> {
+        self.check_owner(ic::caller())?;
+
+        let removed = self.with_state_mut(|state| {
+            state
+                .mut_oracle_storage()
+                .prune_all_price_histories(keep_entries as usize)
+        });
+
+        log::info!("pruned {removed} prices from the oracle histories");
+
+        Ok(removed)
+    }
+
+    /// Resets the error counters of the given oracle
+    ///
+    /// If the oracle reached its error threshold, its timer is started again.
+    #[update]
+    pub async fn reset_oracle_errors(
+        &self,
+        user_address: H160,
+        contract_address: H160,
+    ) -> Result<()> {
+        let metadata = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_by_address(user_address.clone(), contract_address.clone())
+        })?;
+
+        if metadata.owner != user_address {
+            return Err(Error::Internal(
+                "caller is not the owner of the oracle".to_string(),
+            ));
+        }
+
+        self.with_state_mut(|state| {
+            state
+                .mut_oracle_storage()
+                .reset_oracle_errors(user_address.clone(), contract_address.clone())
+        })?;
+
+        if metadata.status == OracleStatus::Error {
+            let timer_id = self.with_state(|state| {
+                state
+                    .oracle_storage()
+                    .get_timer_id_by_address(user_address.clone(), contract_address.clone())
+            })?;
+
+            ic_exports::ic_cdk_timers::clear_timer(timer_id);
+
+            let timer_id = Self::init_price_timer(
+                get_base_context(&self.context.0),
+                user_address.clone(),
+                contract_address.clone(),
+                metadata.timer_interval,
+                None,
+            );
+
+            self.with_state_mut(|state| {
+                state.mut_oracle_storage().set_oracle_status(
+                    user_address.clone(),
+                    contract_address.clone(),
+                    OracleStatus::Active,
+                    Some(timer_id),
+                )
+            })?;
+
+            self.emit_event(user_address, contract_address, OracleEventType::Resumed);
+        }
+
+        Ok(())
+    }
+
+    /// Creates an oracle that will fetch the data from the given URL
+    /// and will update the price of the given contract
+    /// every `timestamp` seconds
+    ///
+    /// # Arguments
+    /// * `origin` - The origin of the data that will be used to update the price
+    /// * `timestamp` - The interval in seconds that will be used to update the price
+    /// * `destination` - The destination of the data that will be used to update the price
+    /// * `initial_delay_secs` - Seconds before the first timer tick, the oracle
+    ///   being `Pending` until then
+    /// * `update_immediately` - Whether the first update runs right away, its
+    ///   errors not failing the creation
+    ///
+    #[update]
+    pub async fn create_oracle(
+        &mut self,
+        user_address: H160,
+        origin: Origin,
+        timestamp: u64,
+        destination: EvmDestination,
+        initial_delay_secs: Option<u64>,
+        update_immediately: Option<bool>,
+    ) -> Result<()> {
+        self.check_owner_or_admin(ic::caller())?;
+        self.check_oracle_limit(&user_address, 1)?;
+        self.check_creation_rate_limit(&user_address, 1)?;
+
+        let contract_address = destination.contract.clone();
+        self.register_oracle(
+            user_address.clone(),
+            origin,
+            timestamp,
+            destination,
+            None,
+            initial_delay_secs,
+        )
+        .await?;
+
+        if update_immediately.unwrap_or_default() {
+            self.update_new_oracle(user_address, contract_address).await;
+        }
+
+        Ok(())
+    }
+
+    /// Creates an oracle with the settings of the source oracle, updating another contract
+    ///
+    /// The runtime state of the source oracle, such as its prices and error
+    /// counters, is not copied.
+    ///
+    /// # Arguments
+    /// * `user_address` - The address of the user that owns the source oracle
+    /// * `source_contract` - The contract updated by the source oracle
+    /// * `new_destination` - The destination of the new oracle
+    #[update]
+    pub async fn clone_oracle(
+        &mut self,
+        user_address: H160,
+        source_contract: H160,
+        mut new_destination: EvmDestination,
+    ) -> Result<()> {
+        self.check_owner_or_admin(ic::caller())?;
+        self.check_oracle_limit(&user_address, 1)?;
+        self.check_creation_rate_limit(&user_address, 1)?;
+
+        let source = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_by_address(user_address.clone(), source_contract)
+        })?;
+
+        self.resolve_destination(&mut new_destination)?;
+        self.check_provider_chain_id(&new_destination.provider)
+            .await?;
+
+        let metadata = new_oracle_from_config(user_address, source, new_destination);
+
+        self.save_new_oracle(metadata, None)
+    }
+
+    /// Exports the configuration of all the oracles along with the canister settings
+    #[query]
+    pub fn export_all_oracle_configs(&self) -> Result<OracleExport> {
+        self.check_owner(ic::caller())?;
+
+        let oracles = self.with_state(|state| state.oracle_storage().get_oracles());
+
+        Ok(OracleExport {
+            oracles: oracles
+                .into_iter()
+                .map(|(user_address, oracles)| (user_address, oracles.into_iter().collect()))
+                .collect(),
+            settings: Settings::read(|s| s.clone()),
+        })
+    }
+
+    /// Creates the oracles of an export, skipping the existing ones
+    ///
+    /// The oracles are created with fresh timers and statistics; the settings of
+    /// the export are not imported. The chain ids of the providers are not checked
+    /// again: the exported oracles were checked on creation, and an import of many
+    /// oracles would not fit the outcalls in a single call.
+    #[update]
+    pub fn import_oracle_configs(&mut self, export: OracleExport) -> Result<ImportSummary> {
+        self.check_owner(ic::caller())?;
+
+        let mut summary = ImportSummary {
+            created: 0,
+            skipped_existing: 0,
+            failed: vec![],
+        };
+
+        for (user_address, oracles) in export.oracles {
+            for (contract_address, config) in oracles {
+                let triggers = config.triggers.clone();
+                let destination = config.evm.clone();
+                let mut metadata =
+                    new_oracle_from_config(user_address.clone(), config, destination);
+                metadata.triggers = triggers;
+
+                match self.save_new_oracle(metadata, None) {
+                    Ok(()) => summary.created += 1,
+                    Err(Error::OracleAlreadyExists) => summary.skipped_existing += 1,
+                    Err(e) => summary.failed.push(format!(
+                        "oracle {contract_address} of user {user_address}: {e}"
+                    )),
+                }
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Returns a snapshot of all the oracles, with their statistics, and of the
+    /// canister settings, to be restored with `restore_oracle_state`
+    #[query]
+    pub fn snapshot_oracle_state(&self) -> Result<OracleStateSnapshot> {
+        self.check_owner(ic::caller())?;
+
+        let state = OracleState {
+            oracles: self.with_state(|state| state.oracle_storage().get_storable_oracles()),
+            settings: Settings::read(|s| s.clone()),
+        };
+
+        Ok(OracleStateSnapshot::new(&state))
+    }
+
+    /// Replaces all the oracles and the canister settings with the ones of the snapshot
+    /// and starts the timers of the active oracles
+    ///
+    /// The snapshot is decoded before the current state is cleared, so an invalid
+    /// snapshot leaves the state untouched. The owner of the canister is kept.
+    #[update]
+    pub fn restore_oracle_state(
+        &mut self,
+        snapshot: OracleStateSnapshot,
+    ) -> Result<RestoreSummary> {
+        self.check_owner(ic::caller())?;
+
+        let OracleState {
+            oracles,
+            mut settings,
+        } = snapshot.decode()?;
+
+        let current_oracles = self.with_state(|state| state.oracle_storage().get_oracles());
+        for metadata in current_oracles
+            .into_iter()
+            .flat_map(|(_, oracles)| oracles.into_values())
+            .filter(|metadata| metadata.status.has_running_timer())
+        {
+            ic_exports::ic_cdk_timers::clear_timer(metadata.timer_id);
+        }
+
+        settings.owner = self.with_state(|state| state.owner());
+        Settings::update(|current| *current = settings);
+
+        let oracles_restored = oracles.len() as u64;
+        self.with_state(|state| state.oracle_storage().replace_oracles(oracles));
+
+        let (timers_started, errors) = self.start_active_oracle_timers();
+
+        log::warn!("restored {oracles_restored} oracles from a snapshot");
+
+        Ok(RestoreSummary {
+            oracles_restored,
+            timers_started,
+            errors,
+        })
+    }
+
+    /// Creates an oracle that stops updating the price at `expires_at`
+    ///
+    /// # Arguments
+    /// * `origin` - The origin of the data that will be used to update the price
+    /// * `interval` - The interval in seconds that will be used to update the price
+    /// * `destination` - The destination of the data that will be used to update the price
+    /// * `expires_at` - The IC time in nanoseconds after which the oracle is paused
+    #[update]
+    pub async fn create_oracle_with_expiry(
+        &mut self,
+        user_address: H160,
+        origin: Origin,
+        interval: u64,
+        destination: EvmDestination,
+        expires_at: u64,
+    ) -> Result<()> {
+        self.check_owner_or_admin(ic::caller())?;
+        self.check_oracle_limit(&user_address, 1)?;
+        self.check_creation_rate_limit(&user_address, 1)?;
+        check_expiry(expires_at)?;
+
+        self.register_oracle(
+            user_address,
+            origin,
+            interval,
+            destination,
+            Some(expires_at),
+            None,
+        )
+        .await
+    }
+
+    /// Creates an oracle whose signer derivation path ends with `suffix`, so that it
+    /// sends its update transactions from its own address
+    ///
+    /// # Arguments
+    /// * `origin` - The origin of the data that will be used to update the price
+    /// * `interval` - The interval in seconds that will be used to update the price
+    /// * `destination` - The destination of the data that will be used to update the price
+    /// * `suffix` - The components appended to the derivation path of the signer
+    #[update]
+    pub async fn create_oracle_with_derivation(
+        &mut self,
+        user_address: H160,
+        origin: Origin,
+        interval: u64,
+        destination: EvmDestination,
+        suffix: Vec<Vec<u8>>,
+    ) -> Result<()> {
+        self.check_owner_or_admin(ic::caller())?;
+        self.check_oracle_limit(&user_address, 1)?;
+        self.check_creation_rate_limit(&user_address, 1)?;
+
+        let mut destination = destination;
+        self.resolve_destination(&mut destination)?;
+        self.check_provider_chain_id(&destination.provider).await?;
+
+        let mut metadata = StorableOracleMetadata::new(
+            user_address,
+            origin,
+            interval,
+            TimerId::default(),
+            destination,
+        );
+        metadata.derivation_suffix = suffix;
+
+        self.save_new_oracle(metadata, None)
+    }
+
+    /// Creates an oracle from the given parameters, along with its label and description
+    #[update]
+    pub async fn create_oracle_with_params(&mut self, params: CreateOracleParams) -> Result<()> {
+        self.check_owner_or_admin(ic::caller())?;
+        self.check_oracle_limit(&params.user_address, 1)?;
+        self.check_creation_rate_limit(&params.user_address, 1)?;
+        if let Some(expires_at) = params.expires_at {
+            check_expiry(expires_at)?;
+        }
+
+        let mut destination = params.destination;
+        self.resolve_destination(&mut destination)?;
+        self.check_provider_chain_id(&destination.provider).await?;
+
+        let mut metadata = StorableOracleMetadata::new(
+            params.user_address,
+            params.origin,
+            params.interval,
+            TimerId::default(),
+            destination,
+        );
+        metadata.expires_at = params.expires_at;
+        metadata.label = params.label;
+        metadata.description = params.description;
+        if let Some(eip712_domain) = params.eip712_domain {
+            metadata.eip712_domain = eip712_domain;
+        }
+
+        let user_address = metadata.owner.clone();
+        let contract_address = metadata.evm.contract.clone();
+        self.save_new_oracle(metadata, params.initial_delay_secs)?;
+
+        if params.update_immediately {
+            self.update_new_oracle(user_address, contract_address).await;
+        }
+
+        Ok(())
+    }
+
+    /// Creates multiple oracles in a single call
+    ///
+    /// The whole batch is counted against the per-user oracle limit and creation
+    /// rate limit before any oracle is created. Failures are recorded per request and do not abort the
+    /// batch; the result preserves the order of the requests. A repeated request for
+    /// the same oracle fails with `OracleAlreadyExists`.
+    ///
+    /// # Arguments
+    /// * `requests` - The oracles to create
+    #[update]
+    pub async fn create_oracles_batch(
+        &mut self,
+        requests: Vec<BatchOracleRequest>,
+    ) -> Result<Vec<(H160, Result<()>)>> {
+        self.check_owner_or_admin(ic::caller())?;
+
+        // Only the first request for an oracle is created, the repeated ones are rejected
+        let mut requested = BTreeSet::new();
+        let requests = requests
+            .into_iter()
+            .map(|request| {
+                let first = requested.insert((
+                    request.user_address.clone(),
+                    request.destination.contract.clone(),
+                ));
+                (request, first)
+            })
+            .collect::<Vec<_>>();
+
+        let mut new_oracles_per_user = BTreeMap::<H160, u64>::new();
+        for (request, _) in requests.iter().filter(|(_, first)| *first) {
+            *new_oracles_per_user
+                .entry(request.user_address.clone())
+                .or_default() += 1;
+        }
+
+        for (user_address, new_oracles) in &new_oracles_per_user {
+            self.check_oracle_limit(user_address, *new_oracles)?;
+            self.check_creation_rate_limit(user_address, *new_oracles)?;
+        }
+
+        // The oracles are reserved before the first `await`, so that the calls
+        // interleaved with the batch count them against the limits
+        let mut reservation =
+            CreationReservation::new(get_base_context(&self.context.0), new_oracles_per_user);
+
+        let mut results = Vec::with_capacity(requests.len());
+        for (request, first) in requests {
+            let contract_address = request.destination.contract.clone();
+            if !first {
+                results.push((contract_address, Err(Error::OracleAlreadyExists)));
+                continue;
+            }
+
+            let result = self
+                .register_oracle(
+                    request.user_address.clone(),
+                    request.origin,
+                    request.interval,
+                    request.destination,
+                    None,
+                    None,
+                )
+                .await;
+            reservation.release(&request.user_address);
+
+            match &result {
+                Ok(()) if request.update_immediately => {
+                    self.update_new_oracle(request.user_address, contract_address.clone())
+                        .await;
+                }
+                Ok(()) => {}
+                Err(e) => log::error!("failed to create oracle {contract_address}: {e}"),
+            }
+
+            results.push((contract_address, result));
+        }
+
+        Ok(results)
+    }
+
+    /// Stops the timer of the oracle and removes it from the storage
+    fn remove_oracle(&mut self, user_address: H160, contract_address: H160) -> Result<()> {
+        // Get the owner
+        let owner = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_owner(user_address.0.into(), contract_address.clone())
+        })?;
+
+        if owner != user_address {
+            return Err(Error::Internal(
+                "caller is not the owner of the oracle".to_string(),
+            ));
+        }
+
+        let timer_id = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_timer_id_by_address(user_address.0.into(), contract_address.clone())
+        })?;
+
+        ic_exports::ic_cdk_timers::clear_timer(timer_id);
+
+        self.with_state_mut(|state| {
+            state
+                .mut_oracle_storage()
+                .remove_oracle_by_address(user_address.clone(), contract_address.clone())
+        })?;
+
+        self.emit_event(user_address, contract_address, OracleEventType::Deleted);
+
+        Ok(())
+    }
+
+    /// Emits an event of the oracle at the current IC time
+    fn emit_event(&self, user_address: H160, contract_address: H160, event_type: OracleEventType) {
+        let event = OracleEvent::new(user_address, contract_address, event_type, None);
+
+        self.with_state(|state| events::emit(state.oracle_storage(), event));
+    }
+
+    /// Starts the timer of the oracle again if it is running
+    fn restart_oracle_timer(&self, user_address: H160, contract_address: H160) -> Result<()> {
+        let metadata = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_by_address(user_address.clone(), contract_address.clone())
+        })?;
+        if !metadata.status.has_running_timer() {
+            return Ok(());
+        }
+
+        ic_exports::ic_cdk_timers::clear_timer(metadata.timer_id);
+
+        let timer_id = Self::init_price_timer(
+            get_base_context(&self.context.0),
+            user_address.clone(),
+            contract_address.clone(),
+            metadata.timer_interval,
+            pending_delay_secs(&metadata.status, metadata.first_tick_at, ic::time()),
+        );
+
+        self.with_state_mut(|state| {
+            state.mut_oracle_storage().set_oracle_status(
+                user_address,
+                contract_address,
+                metadata.status,
+                Some(timer_id),
+            )
+        })
+    }
+
+    /// Starts the price timer of a new oracle and saves its metadata
+    async fn register_oracle(
+        &self,
+        user_address: H160,
+        origin: Origin,
+        timestamp: u64,
+        destination: EvmDestination,
+        expires_at: Option<u64>,
+        initial_delay_secs: Option<u64>,
+    ) -> Result<()> {
+        let mut destination = destination;
+        self.resolve_destination(&mut destination)?;
+        self.check_provider_chain_id(&destination.provider).await?;
+
+        let mut metadata = StorableOracleMetadata::new(
+            user_address,
+            origin,
+            timestamp,
+            TimerId::default(),
+            destination,
+        );
+        metadata.expires_at = expires_at;
+
+        self.save_new_oracle(metadata, initial_delay_secs)
+    }
+
+    /// Runs the first update of a new oracle without waiting for its timer
+    ///
+    /// The errors are only logged, they are recorded in the oracle metadata and the
+    /// timer retries on schedule.
+    async fn update_new_oracle(&self, user_address: H160, contract_address: H160) {
+        let metadata = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_by_address(user_address.clone(), contract_address.clone())
+        });
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                log::debug!("failed to get oracle {contract_address} of user {user_address}: {e}");
+                return;
+            }
+        };
+
+        if let Err(e) = Self::execute_update(
+            get_base_context(&self.context.0),
+            user_address.clone(),
+            contract_address.clone(),
+            metadata,
+        )
+        .await
+        {
+            log::warn!(
+                "immediate update of oracle {contract_address} of user {user_address} failed: {e}"
+            );
+        }
+    }
+
+    /// Checks that the provider is connected to its declared chain with `eth_chainId`
+    ///
+    /// The check is skipped for local providers and when disabled in the settings.
+    async fn check_provider_chain_id(&self, provider: &Provider) -> Result<()> {
+        let skip = self.with_state(|state| state.skip_chain_id_validation());
+        if skip
+            || provider
+                .endpoints
+                .iter()
+                .all(|endpoint| is_local_endpoint(endpoint))
+        {
+            return Ok(());
+        }
+
+        let chain_id = provider
+            .call_jsonrpc(
+                "eth_chainId",
+                Value::Null,
+                Some(JSON_RPC_MAX_RESPONSE_BYTES),
+            )
+            .await?;
+        let actual = serde_json::from_value::<U256>(chain_id)?.0.low_u64();
+
+        if actual != provider.chain_id {
+            return Err(Error::ChainIdMismatch {
+                declared: provider.chain_id,
+                actual,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Validates a new oracle, starts its price timer and saves its metadata
+    fn save_new_oracle(
+        &self,
+        mut metadata: StorableOracleMetadata,
+        initial_delay_secs: Option<u64>,
+    ) -> Result<()> {
+        log::debug!("creating new oracle: {:?}", metadata.origin);
+
+        self.resolve_destination(&mut metadata.evm)?;
+        check_label(metadata.label.as_deref(), metadata.description.as_deref())?;
+        check_tags(&metadata.tags)?;
+        metadata
+            .transforms
+            .iter()
+            .try_for_each(Transform::validate)?;
+        if let Some(outlier_config) = &metadata.outlier_config {
+            outlier_config.validate()?;
+        }
+        if let Some(custom_update_fn) = &metadata.custom_update_fn {
+            custom_update_fn.validate()?;
+        }
+        self.check_origin_allowed(&metadata.origin)?;
+        warn_plaintext_auth(&metadata.origin);
+        validate_origin(&metadata.origin)?;
+        validate_providers(&metadata.origin, &metadata.evm)?;
+
+        let exists = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_by_address(metadata.owner.clone(), metadata.evm.contract.clone())
+                .is_ok()
+        });
+        if exists {
+            return Err(Error::OracleAlreadyExists);
+        }
+
+        // The oracle is pending until the first tick of its timer
+        if let Some(initial_delay_secs) = initial_delay_secs {
+            metadata.status = OracleStatus::Pending;
+            metadata.first_tick_at =
+                Some(ic::time().saturating_add(initial_delay_secs.saturating_mul(1_000_000_000)));
+        }
+
+        // Start the timer
+        metadata.timer_id = Self::init_price_timer(
+            get_base_context(&self.context.0),
+            metadata.owner.clone(),
+            metadata.evm.contract.clone(),
+            metadata.timer_interval,
+            initial_delay_secs,
+        );
+
+        let (user_address, contract_address) =
+            (metadata.owner.clone(), metadata.evm.contract.clone());
+
+        // Save the metadata
+        self.with_state_mut(|state| {
+            state.mut_oracle_storage().insert_oracle(metadata);
+            state
+                .user_creation_timestamps()
+                .record(user_address.clone(), ic::time());
+        });
+
+        self.emit_event(user_address, contract_address, OracleEventType::Created);
+        self.check_stable_memory_alert();
+
+        log::debug!("oracle created successfully ");
+
+        Ok(())
+    }
+
+    /// Replaces the provider of a destination bound to a chain with the registered one
+    fn resolve_destination(&self, destination: &mut EvmDestination) -> Result<()> {
+        if let Some(chain_id) = destination.chain_id {
+            destination.provider =
+                self.with_state(|state| state.provider_registry().get(chain_id))?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the triggers of an oracle target oracles of the same user and
+    /// do not create a circular dependency
+    fn check_triggers(
+        &self,
+        user_address: &H160,
+        contract_address: &H160,
+        triggers: &[(H160, H160)],
+    ) -> Result<()> {
+        if triggers
+            .iter()
+            .any(|(trigger_user, _)| trigger_user != user_address)
+        {
+            return Err(Error::Internal(
+                "triggers can only target oracles of the same user".to_string(),
+            ));
+        }
+
+        self.with_state(|state| {
+            let oracle_storage = state.oracle_storage();
+
+            check_circular_dependency(
+                &(user_address.clone(), contract_address.clone()),
+                triggers,
+                |(user_address, contract_address)| {
+                    oracle_storage
+                        .get_oracle_by_address(user_address.clone(), contract_address.clone())
+                        .map(|metadata| metadata.triggers)
+                        .unwrap_or_default()
+                },
+            )
+        })
+    }
+
+    /// Checks that the URLs of the HTTP origins use HTTPS and match the URL allowlist, if any
+    fn check_origin_allowed(&self, origin: &Origin) -> Result<()> {
+        let allowlist = self.with_state(|state| state.url_allowlist());
+
+        for source in origin_sources(origin) {
+            let Origin::Http(HttpOrigin {
+                url,
+                session_cookie,
+                ..
+            }) = source
+            else {
+                continue;
+            };
+
+            let login_url = session_cookie.as_ref().map(|config| &config.login_url);
+            for url in std::iter::once(url).chain(login_url) {
+                http::validate_https_url(url)?;
+
+                if let Some(allowlist) = &allowlist {
+                    if !http::is_url_allowed(url, allowlist) {
+                        return Err(Error::UrlNotAllowed(url.clone()));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the user can register `new_oracles` more oracles
+    fn check_oracle_limit(&self, user_address: &H160, new_oracles: u64) -> Result<()> {
+        let oracle_count = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_user_oracle_count(user_address.clone())
+                + state.pending_creations().count(user_address)
+        });
+
+        if oracle_count + new_oracles > MAX_ORACLES_PER_USER {
+            return Err(Error::OracleLimitExceeded(MAX_ORACLES_PER_USER));
+        }
+
+        Ok(())
+    }
+
+    /// Logs a warning if the stable memory grew above the alert threshold, if any
+    fn check_stable_memory_alert(&self) {
+        let Some(alert_pages) = self.with_state(|state| state.stable_memory_alert_pages()) else {
+            return;
+        };
+
+        let pages = ic_cdk::api::stable::stable64_size();
+        if pages >= alert_pages {
+            log::warn!(
+                "stable memory size of {pages} pages crossed the alert threshold of {alert_pages} pages"
+            );
+        }
+    }
+
+    /// Checks that the user can create `new_oracles` more oracles without exceeding
+    /// the oracle creation rate limit, if any
+    fn check_creation_rate_limit(&self, user_address: &H160, new_oracles: u64) -> Result<()> {
+        let Some(limit) = self.with_state(|state| state.oracle_creation_rate_limit()) else {
+            return Ok(());
+        };
+
+        let recent_creations = self.with_state(|state| {
+            state
+                .user_creation_timestamps()
+                .count_recent(user_address, ic::time())
+                + state.pending_creations().count(user_address)
+        });
+
+        if recent_creations + new_oracles > u64::from(limit.max_per_hour) {
+            return Err(Error::RateLimitExceeded(limit.max_per_hour));
+        }
+
+        Ok(())
+    }
+
+    /// Initializes the timer that will be used to update the price
+    ///
+    /// With an initial delay, a one-shot timer starts the interval timer after
+    /// the delay and stores its id in the oracle metadata.
+    pub fn init_price_timer(
+        context: Rc<RefCell<dyn Context>>,
+        user_address: H160,
+        contract_address: H160,
+        timestamp: u64,
+        initial_delay_secs: Option<u64>,
+    ) -> TimerId {
+        let Some(initial_delay_secs) = initial_delay_secs else {
+            return Self::init_interval_timer(context, user_address, contract_address, timestamp);
+        };
+
+        ic_exports::ic_cdk_timers::set_timer(Duration::from_secs(initial_delay_secs), move || {
+            let timer_id = Self::init_interval_timer(
+                context.clone(),
+                user_address.clone(),
+                contract_address.clone(),
+                timestamp,
+            );
+
+            if let Err(e) = context
+                .borrow()
+                .get_state()
+                .oracle_storage()
+                .set_oracle_timer_id(user_address, contract_address, timer_id)
+            {
+                log::debug!("failed to store oracle timer: {:?}", e.to_string());
+                ic_exports::ic_cdk_timers::clear_timer(timer_id);
+            }
+        })
+    }
+
+    fn init_interval_timer(
+        context: Rc<RefCell<dyn Context>>,
+        user_address: H160,
+        contract_address: H160,
+        timestamp: u64,
+    ) -> TimerId {
+        ic_exports::ic_cdk_timers::set_timer_interval(Duration::from_secs(timestamp), move || {
+            ic_cdk::spawn(Self::update_oracle(
+                context.clone(),
+                user_address.clone(),
+                contract_address.clone(),
+            ));
+        })
+    }
+
+    /// Runs a single update of the given oracle using its current metadata
+    ///
+    /// An expired oracle is paused instead of being updated. An oracle reaching its
+    /// max executions is updated a last time and paused. When the max concurrent
+    /// updates are running, the update is counted as failed and attempted again
+    /// after `THROTTLED_UPDATE_RETRY_SECS` seconds, with a single retry pending per
+    /// oracle. An oracle that is not running is not updated.
+    async fn update_oracle(
+        context: Rc<RefCell<dyn Context>>,
+        user_address: H160,
+        contract_address: H160,
+    ) {
+        let metadata = context
+            .borrow()
+            .get_state()
+            .oracle_storage()
+            .get_oracle_by_address(user_address.clone(), contract_address.clone());
+
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                log::debug!("failed to get oracle {contract_address} of user {user_address}: {e}");
+                return;
+            }
+        };
+
+        // A throttled retry may run after the oracle was paused
+        if !metadata.status.has_running_timer() {
+            log::debug!(
+                "oracle {contract_address} of user {user_address} is not running, skipping the update"
+            );
+            return;
+        }
+
+        if metadata
+            .expires_at
+            .is_some_and(|expires_at| ic::time() >= expires_at)
+        {
+            log::info!(
+                "oracle {contract_address} of user {user_address} expired, stopping its timer"
+            );
+            Self::pause_oracle(&context, &user_address, &contract_address, "oracle expired");
+
+            return;
+        }
+
+        if let OracleStatus::CircuitOpen { resets_at, .. } = metadata.status {
+            if ic::time() < resets_at {
+                log::debug!(
+                    "circuit of oracle {contract_address} of user {user_address} is open, skipping the update"
+                );
+                return;
+            }
+
+            log::info!(
+                "circuit of oracle {contract_address} of user {user_address} is half-open, attempting an update"
+            );
+        }
+
+        let acquired = {
+            let context = context.borrow();
+            let state = context.get_state();
+            state
+                .active_updates()
+                .try_acquire(state.max_concurrent_updates())
+        };
+        if !acquired {
+            Self::throttle_update(context, user_address, contract_address);
+            return;
+        }
+        let guard = UpdateGuard::new(
+            context.clone(),
+            user_address.clone(),
+            contract_address.clone(),
+        );
+
+        let execution = context
+            .borrow()
+            .get_state()
+            .oracle_storage()
+            .record_execution(user_address.clone(), contract_address.clone());
+        match execution {
+            Ok(Some(true)) => {
+                log::info!(
+                    "oracle {contract_address} of user {user_address} reached its max executions, stopping its timer"
+                );
+                Self::pause_oracle(
+                    &context,
+                    &user_address,
+                    &contract_address,
+                    "max_executions reached",
+                );
+            }
+            Ok(Some(false)) => {}
+            Ok(None) => {
+                Self::pause_oracle(
+                    &context,
+                    &user_address,
+                    &contract_address,
+                    "max_executions reached",
+                );
+                guard.finish();
+                return;
+            }
+            Err(e) => log::debug!("failed to record oracle execution: {:?}", e.to_string()),
+        }
+
+        Self::check_reorg(&context, &user_address, &contract_address, &metadata).await;
+
+        // The errors are recorded in the oracle metadata
+        let _ =
+            Self::execute_update(context.clone(), user_address, contract_address, metadata).await;
+
+        guard.finish();
+    }
+
+    /// Checks that the block of the last confirmed update transaction of the oracle
+    /// is still in the canonical chain, until it reaches the confirmation blocks of
+    /// the oracle
+    ///
+    /// A transaction reorged out of the chain is recorded as an update error.
+    async fn check_reorg(
+        context: &Rc<RefCell<dyn Context>>,
+        user_address: &H160,
+        contract_address: &H160,
+        metadata: &OracleMetadata,
+    ) {
+        if metadata.confirmation_blocks <= 1 {
+            return;
+        }
+        let Some(TxStatus {
+            hash,
+            status: TxReceiptStatus::Success,
+            confirmed_block: Some(block),
+            ..
+        }) = &metadata.last_tx_status
+        else {
+            return;
+        };
+        if block.finalized {
+            return;
+        }
+
+        let check = metadata
+            .evm
+            .provider
+            .call_jsonrpc_batch(
+                vec![
+                    ("eth_getBlockByNumber", serde_json::json!(["latest", false])),
+                    (
+                        "eth_getBlockByNumber",
+                        serde_json::json!([block.number, false]),
+                    ),
+                ],
+                Some(BLOCK_MAX_RESPONSE_BYTES),
+            )
+            .await
+            .and_then(|blocks| {
+                <[Value; 2]>::try_from(blocks)
+                    .map_err(|_| Error::Internal("unexpected batch response length".to_string()))
+            })
+            .and_then(|[latest, canonical]| {
+                check_confirmed_block(block, &latest, &canonical, metadata.confirmation_blocks)
+            });
+
+        let reorged = match check {
+            Ok(BlockCheck::Pending) => return,
+            Ok(BlockCheck::Finalized) => false,
+            Ok(BlockCheck::Reorged) => true,
+            Err(e) => {
+                log::debug!(
+                    "failed to check the block of transaction {hash}: {:?}",
+                    e.to_string()
+                );
+                return;
+            }
+        };
+
+        let updated = context
+            .borrow()
+            .get_state()
+            .oracle_storage()
+            .update_last_tx_status(
+                user_address.clone(),
+                contract_address.clone(),
+                hash,
+                |tx_status| {
+                    if reorged {
+                        tx_status.status = TxReceiptStatus::Reorged;
+                    } else if let Some(block) = &mut tx_status.confirmed_block {
+                        block.finalized = true;
+                    }
+                },
+            );
+
+        match updated {
+            Ok(true) if reorged => {
+                let error = PipelineError::new(
+                    OracleStage::ReceiptVerification,
+                    Error::TransactionReorged(hash.clone()),
+                );
+                log::warn!("update of oracle {contract_address} of user {user_address}: {error}");
+
+                Self::handle_update_error(
+                    context,
+                    user_address.clone(),
+                    contract_address.clone(),
+                    &error,
+                );
+            }
+            Ok(_) => {}
+            Err(e) => log::debug!("failed to record transaction status: {:?}", e.to_string()),
+        }
+    }
+
+    /// Counts an update skipped because of the concurrent updates limit as failed
+    /// and schedules it again after `THROTTLED_UPDATE_RETRY_SECS` seconds
+    fn throttle_update(
+        context: Rc<RefCell<dyn Context>>,
+        user_address: H160,
+        contract_address: H160,
+    ) {
+        let limit = context.borrow().get_state().max_concurrent_updates();
+        log::warn!(
+            "{limit} concurrent updates running, delaying the update of oracle {contract_address} of user {user_address}"
+        );
+
+        if let Err(e) = context
+            .borrow()
+            .get_state()
+            .oracle_storage()
+            .record_skipped_update(
+                user_address.clone(),
+                contract_address.clone(),
+                // the throttled update stops before fetching its price
+                PipelineError::new(
+                    OracleStage::DataFetch,
+                    Error::ConcurrentUpdatesExceeded(limit),
+                ),
+                OracleSkipReason::ConcurrentUpdatesExceeded(limit),
+            )
+        {
+            log::debug!("failed to record throttled update: {:?}", e.to_string());
+        }
+
+        let schedule = context
+            .borrow()
+            .get_state()
+            .active_updates()
+            .try_schedule_retry(user_address.clone(), contract_address.clone());
+        if !schedule {
+            log::debug!(
+                "a retry of oracle {contract_address} of user {user_address} is already pending"
+            );
+            return;
+        }
+
+        ic_exports::ic_cdk_timers::set_timer(
+            Duration::from_secs(THROTTLED_UPDATE_RETRY_SECS),
+            move || {
+                context
+                    .borrow()
+                    .get_state()
+                    .active_updates()
+                    .finish_retry(&user_address, &contract_address);
+                ic_cdk::spawn(Self::update_oracle(context, user_address, contract_address));
+            },
+        );
+    }
+
+    /// Runs an update of the oracle with the given metadata and records its outcome,
+    /// like the price, the consumed cycles and the error counters
+    async fn execute_update(
+        context: Rc<RefCell<dyn Context>>,
+        user_address: H160,
+        contract_address: H160,
+        metadata: OracleMetadata,
+    ) -> Result<()> {
+        if let Err(e) = context
+            .borrow()
+            .get_state()
+            .oracle_storage()
+            .record_update_attempt(user_address.clone(), contract_address.clone(), ic::time())
+        {
+            log::debug!(
+                "failed to record oracle update attempt: {:?}",
+                e.to_string()
+            );
+        }
+
+        let provider = metadata.evm.provider.clone();
+        let balance_before = ic_cdk::api::canister_balance128();
+
+        let result = retry_on_http_error(
+            metadata.max_retries,
+            metadata.retry_base_delay_secs,
+            || Self::send_transaction(&metadata, user_address.clone(), context.clone()),
+            wait_for,
+        )
+        .await;
+
+        let consumed_cycles = consumed_cycles(balance_before, ic_cdk::api::canister_balance128());
+        Self::record_consumed_cycles(&context, &user_address, &contract_address, consumed_cycles);
+
+        let result = match result {
+            Ok(UpdateOutcome::Simulated(simulation)) => {
+                if let Err(e) = context
+                    .borrow()
+                    .get_state()
+                    .oracle_storage()
+                    .set_simulation_result(user_address, contract_address, simulation)
+                {
+                    log::debug!("failed to record simulation result: {:?}", e.to_string());
+                }
+
+                return Ok(());
+            }
+            Ok(UpdateOutcome::Submitted {
+                price,
+                tx_hash,
+                transaction,
+                using_fallback,
+            }) => {
+                if let Err(e) = context
+                    .borrow()
+                    .get_state()
+                    .oracle_storage()
+                    .record_submission(
+                        user_address.clone(),
+                        contract_address.clone(),
+                        price.clone(),
+                        using_fallback,
+                    )
+                {
+                    log::debug!("failed to record oracle submission: {:?}", e.to_string());
+                }
+
+                Ok((price, tx_hash, transaction))
+            }
+            Ok(UpdateOutcome::Suppressed(price)) => {
+                log::debug!(
+                    "price {price:?} of oracle {contract_address} of user {user_address} unchanged, skipping the update"
+                );
+
+                return Ok(());
+            }
+            Err(e) => {
+                Self::record_dead_letter(
+                    &context,
+                    &user_address,
+                    &contract_address,
+                    &e,
+                    metadata.last_submitted_price.clone(),
+                );
+                Err(e)
+            }
+        };
+
+        let submitted = result.is_ok();
+        let replacement = match (&result, metadata.tx_deadline_secs) {
+            (Ok((_, _, transaction)), Some(deadline_secs)) => Some((
+                TxReplacement {
+                    user_address: user_address.clone(),
+                    contract_address: contract_address.clone(),
+                    provider: provider.clone(),
+                    rotation_count: metadata.rotation_count,
+                    derivation_suffix: metadata.derivation_suffix.clone(),
+                    transaction: transaction.clone(),
+                },
+                deadline_secs,
+            )),
+            _ => None,
+        };
+        let (result, receipt_check) = match result {
+            Ok((price, tx_hash, transaction)) if metadata.verify_receipt => (
+                Ok(price.clone()),
+                Some(ReceiptCheck {
+                    user_address: user_address.clone(),
+                    contract_address: contract_address.clone(),
+                    provider,
+                    tx_hash,
+                    replaced: None,
+                    price,
+                    call: provider::transaction_call(&transaction),
+                    delay_secs: metadata.confirmation_delay_secs,
+                    retries: 0,
+                    waited_secs: 0,
+                    deadline: ic::time().saturating_add(
+                        Duration::from_secs(metadata.max_wait_secs).as_nanos() as u64,
+                    ),
+                }),
+            ),
+            result => (result.map(|(price, _, _)| price), None),
+        };
+
+        // The success of a verified update is recorded once its receipt is found, so
+        // that reverted transactions count as consecutive errors
+        if receipt_check.is_none() {
+            Self::handle_update_result(
+                &context,
+                user_address.clone(),
+                contract_address.clone(),
+                &result,
+            );
+        }
+
+        if submitted {
+            Self::trigger_downstream_oracles(&context, metadata.triggers);
+        }
+
+        if let Some((replacement, deadline_secs)) = replacement {
+            Self::schedule_tx_replacement(context.clone(), replacement, deadline_secs);
+        }
+
+        if let Some(check) = receipt_check {
+            Self::watch_transaction_receipt(context, check);
+        }
+
+        result.map(|_| ()).map_err(Error::from)
+    }
+
+    fn schedule_tx_replacement(
+        context: Rc<RefCell<dyn Context>>,
+        replacement: TxReplacement,
+        deadline_secs: u64,
+    ) {
+        ic_exports::ic_cdk_timers::set_timer(Duration::from_secs(deadline_secs), move || {
+            ic_cdk::spawn(Self::replace_stuck_transaction(context, replacement));
+        });
+    }
+
+    /// Replaces an update transaction without receipt at its deadline by one with the
+    /// same nonce and a higher gas price
+    ///
+    /// The replacement is recorded as the last transaction of the oracle along with the
+    /// hash it replaced, so that a pending receipt check follows it.
+    async fn replace_stuck_transaction(
+        context: Rc<RefCell<dyn Context>>,
+        replacement: TxReplacement,
+    ) {
+        let original: H256 = replacement.transaction.hash.into();
+
+        let receipt = replacement
+            .provider
+            .call_jsonrpc(
+                "eth_getTransactionReceipt",
+                serde_json::json!([original]),
+                Some(JSON_RPC_MAX_RESPONSE_BYTES),
+            )
+            .await;
+        match receipt {
+            Ok(Value::Null) => {}
+            Ok(_) => return,
+            Err(e) => {
+                log::debug!(
+                    "failed to get receipt of transaction {}: {:?}",
+                    original,
+                    e.to_string()
+                );
+                return;
+            }
+        }
+
+        let result = async {
+            let transaction = provider::get_replacement_transaction(
+                &replacement.transaction,
+                replacement.user_address.clone(),
+                replacement.rotation_count,
+                &replacement.derivation_suffix,
+                &context,
+            )
+            .await?;
+
+            provider::send_replacement_transaction(&transaction, &replacement.provider).await
+        }
+        .await;
+
+        let replacement_hash = match result {
+            Ok(replacement_hash) => replacement_hash,
+            Err(e) => {
+                log::warn!(
+                    "failed to replace transaction {} of oracle {}: {}",
+                    original,
+                    replacement.contract_address,
+                    e
+                );
+                return;
+            }
+        };
+
+        log::warn!(
+            "update of oracle {} stuck: {}",
+            replacement.contract_address,
+            Error::TransactionReplaced {
+                original: original.clone(),
+                replacement: replacement_hash.clone(),
+            }
+        );
+
+        let tx_status = TxStatus {
+            hash: replacement_hash,
+            status: TxReceiptStatus::Pending,
+            replaced: Some(original),
+            confirmed_block: None,
+        };
+
+        if let Err(e) = context
+            .borrow()
+            .get_state()
+            .oracle_storage()
+            .set_last_tx_status(
+                replacement.user_address,
+                replacement.contract_address,
+                tx_status,
+            )
+        {
+            log::debug!("failed to record transaction status: {:?}", e.to_string());
+        }
+    }
+
+    /// Schedules an immediate update of the active downstream oracles
+    fn trigger_downstream_oracles(context: &Rc<RefCell<dyn Context>>, triggers: Vec<(H160, H160)>) {
+        for (user_address, contract_address) in triggers {
+            let is_active = context
+                .borrow()
+                .get_state()
+                .oracle_storage()
+                .get_oracle_by_address(user_address.clone(), contract_address.clone())
+                .is_ok_and(|metadata| metadata.status == OracleStatus::Active);
+            if !is_active {
+                log::debug!(
+                    "skipping trigger of inactive oracle {contract_address} of user {user_address}"
+                );
+                continue;
+            }
+
+            let context = context.clone();
+            ic_exports::ic_cdk_timers::set_timer(Duration::ZERO, move || {
+                ic_cdk::spawn(Self::update_oracle(context, user_address, contract_address));
+            });
+        }
+    }
+
+    /// Appends an update which failed after all its retries to the dead letter queue
+    fn record_dead_letter(
+        context: &Rc<RefCell<dyn Context>>,
+        user_address: &H160,
+        contract_address: &H160,
+        error: &PipelineError,
+        last_submitted_price: Option<U256>,
+    ) {
+        let price_attempted = match &error.inner {
+            Error::PriceOutOfBounds { price, .. }
+            | Error::PriceOutlier(price)
+            | Error::PriceChangeTooLarge { price, .. } => Some(price.clone()),
+            _ => None,
+        };
+
+        context
+            .borrow()
+            .get_state()
+            .dead_letter_queue()
+            .push(DeadLetterEntry {
+                timestamp: ic::time(),
+                user_address: user_address.clone(),
+                contract_address: contract_address.clone(),
+                error: error.to_string(),
+                price_attempted,
+                last_submitted_price,
+            });
+    }
+
+    /// Adds the cycles consumed by an update to the oracle and pauses it when its
+    /// cycle budget is exhausted
+    fn record_consumed_cycles(
+        context: &Rc<RefCell<dyn Context>>,
+        user_address: &H160,
+        contract_address: &H160,
+        cycles: u128,
+    ) {
+        let exhausted = context
+            .borrow()
+            .get_state()
+            .oracle_storage()
+            .add_consumed_cycles(user_address.clone(), contract_address.clone(), cycles);
+
+        match exhausted {
+            Ok(true) => {
+                log::warn!(
+                    "oracle {} of user {} exhausted its cycle budget, pausing it",
+                    contract_address,
+                    user_address
+                );
+                Self::pause_oracle(
+                    context,
+                    user_address,
+                    contract_address,
+                    "cycle budget exhausted",
+                );
+            }
+            Ok(false) => {}
+            Err(e) => log::debug!("failed to record consumed cycles: {:?}", e.to_string()),
+        }
+    }
+
+    /// Stops the timer of the oracle and pauses it, emitting a `Paused` event with
+    /// the reason
+    fn pause_oracle(
+        context: &Rc<RefCell<dyn Context>>,
+        user_address: &H160,
+        contract_address: &H160,
+        reason: &str,
+    ) {
+        let context = context.borrow();
+        let state = context.get_state();
+        let oracle_storage = state.oracle_storage();
+
+        if let Ok(timer_id) =
+            oracle_storage.get_timer_id_by_address(user_address.clone(), contract_address.clone())
+        {
+            ic_exports::ic_cdk_timers::clear_timer(timer_id);
+        }
+
+        if let Err(e) = oracle_storage.set_oracle_status(
+            user_address.clone(),
+            contract_address.clone(),
+            OracleStatus::Paused,
+            None,
+        ) {
+            log::debug!("failed to pause oracle: {:?}", e.to_string());
+        }
+
+        events::emit(
+            oracle_storage,
+            OracleEvent::new(
+                user_address.clone(),
+                contract_address.clone(),
+                OracleEventType::Paused,
+                Some(reason.to_string()),
+            ),
+        );
+    }
+
+    /// Marks the transaction as pending and schedules the check of its receipt
+    fn watch_transaction_receipt(context: Rc<RefCell<dyn Context>>, check: ReceiptCheck) {
+        let tx_status = TxStatus {
+            hash: check.tx_hash.clone(),
+            status: TxReceiptStatus::Pending,
+            replaced: None,
+            confirmed_block: None,
+        };
+
+        if let Err(e) = context
+            .borrow()
+            .get_state()
+            .oracle_storage()
+            .set_last_tx_status(
+                check.user_address.clone(),
+                check.contract_address.clone(),
+                tx_status,
+            )
+        {
+            log::debug!("failed to record transaction status: {:?}", e.to_string());
+        }
+
+        Self::schedule_receipt_check(context, check);
+    }
+
+    fn schedule_receipt_check(context: Rc<RefCell<dyn Context>>, mut check: ReceiptCheck) {
+        let delay_secs = backoff_delay_secs(check.delay_secs, check.retries);
+        check.waited_secs = check.waited_secs.saturating_add(delay_secs);
+
+        ic_exports::ic_cdk_timers::set_timer(Duration::from_secs(delay_secs), move || {
+            ic_cdk::spawn(Self::check_transaction_receipt(context, check));
+        });
+    }
+
+    /// Checks the receipt of an update transaction and records its status
+    ///
+    /// A missing receipt is checked again with an exponential backoff, at most
+    /// `MAX_RECEIPT_CHECK_RETRIES` times and until the deadline of the check, after
+    /// which it is recorded as an update error. A reverted transaction is replayed to
+    /// get its revert reason and recorded as an update error, while a successful one
+    /// records the success of the update.
+    async fn check_transaction_receipt(context: Rc<RefCell<dyn Context>>, mut check: ReceiptCheck) {
+        let params = serde_json::json!([check.tx_hash]);
+
+        let receipt = check
+            .provider
+            .call_jsonrpc(
+                "eth_getTransactionReceipt",
+                params,
+                Some(JSON_RPC_MAX_RESPONSE_BYTES),
+            )
+            .await;
+
+        let status = match &receipt {
+            Ok(Value::Null) => None,
+            Ok(receipt) if receipt.get("status").and_then(Value::as_str) == Some("0x0") => {
+                Some(TxReceiptStatus::Failed)
+            }
+            Ok(_) => Some(TxReceiptStatus::Success),
+            Err(e) => {
+                log::debug!(
+                    "failed to get receipt of transaction {}: {:?}",
+                    check.tx_hash,
+                    e.to_string()
+                );
+                None
+            }
+        };
+
+        if status.is_none() {
+            Self::follow_replacement(&context, &mut check);
+        }
+
+        let confirmed_block = match (&status, &receipt) {
+            (Some(TxReceiptStatus::Success), Ok(receipt)) => receipt_block(receipt),
+            _ => None,
+        };
+
+        let status = match status {
+            Some(status) => status,
+            None if check.retries < MAX_RECEIPT_CHECK_RETRIES && ic::time() < check.deadline => {
+                check.retries += 1;
+                Self::schedule_receipt_check(context, check);
+                return;
+            }
+            None => TxReceiptStatus::Timeout,
+        };
+
+        let result = match status {
+            TxReceiptStatus::Failed => {
+                let block = receipt
+                    .ok()
+                    .and_then(|receipt| receipt.get("blockNumber").cloned())
+                    .and_then(|block| serde_json::from_value(block).ok())
+                    .map_or(Value::from("latest"), parent_block);
+                let reason = provider::get_revert_reason(&check.provider, &check.call, block).await;
+
+                log::warn!(
+                    "update transaction {} of oracle {} reverted: {}",
+                    check.tx_hash,
+                    check.contract_address,
+                    reason
+                );
+
+                Err(Error::TransactionReverted(reason))
+            }
+            TxReceiptStatus::Timeout => Err(Error::ReceiptTimeout(check.waited_secs)),
+            _ => Ok(check.price),
+        }
+        .at_stage(OracleStage::ReceiptVerification);
+
+        let tx_status = TxStatus {
+            hash: check.tx_hash,
+            status,
+            replaced: check.replaced,
+            confirmed_block,
+        };
+
+        if let Err(e) = context
+            .borrow()
+            .get_state()
+            .oracle_storage()
+            .set_last_tx_status(
+                check.user_address.clone(),
+                check.contract_address.clone(),
+                tx_status,
+            )
+        {
+            log::debug!("failed to record transaction status: {:?}", e.to_string());
+        }
+
+        Self::handle_update_result(
+            &context,
+            check.user_address,
+            check.contract_address,
+            &result,
+        );
+    }
+
+    /// Makes the receipt check follow the replacement of its transaction, if any
+    fn follow_replacement(context: &Rc<RefCell<dyn Context>>, check: &mut ReceiptCheck) {
+        let replacement = context
+            .borrow()
+            .get_state()
+            .oracle_storage()
+            .get_oracle_by_address(check.user_address.clone(), check.contract_address.clone())
+            .ok()
+            .and_then(|metadata| metadata.last_tx_status)
+            .filter(|tx_status| tx_status.replaced.as_ref() == Some(&check.tx_hash));
+
+        if let Some(replacement) = replacement {
+            log::debug!(
+                "transaction {} was replaced by {}, checking its receipt",
+                check.tx_hash,
+                replacement.hash
+            );
+            check.replaced = Some(std::mem::replace(&mut check.tx_hash, replacement.hash));
+        }
+    }
+
+    /// Records the outcome of an oracle update in the oracle metadata
+    fn handle_update_result(
+        context: &Rc<RefCell<dyn Context>>,
+        user_address: H160,
+        contract_address: H160,
+        result: &PipelineResult<U256>,
+    ) {
+        match result {
+            Ok(price) => {
+                let context = context.borrow();
+                let state = context.get_state();
+                let oracle_storage = state.oracle_storage();
+
+                if let Err(e) = oracle_storage.record_update_success(
+                    user_address.clone(),
+                    contract_address.clone(),
+                    price.clone(),
+                    ic::time(),
+                ) {
+                    log::debug!("failed to record oracle update: {:?}", e.to_string());
+                }
+
+                events::emit(
+                    oracle_storage,
+                    OracleEvent::new(
+                        user_address,
+                        contract_address,
+                        OracleEventType::UpdateSuccess {
+                            price: price.clone(),
+                        },
+                        None,
+                    ),
+                );
+            }
+            Err(e) => Self::handle_update_error(context, user_address, contract_address, e),
+        }
+    }
+
+    /// Records a failed oracle update in the oracle metadata
+    ///
+    /// When the oracle reaches its error threshold, its timer is cleared.
+    fn handle_update_error(
+        context: &Rc<RefCell<dyn Context>>,
+        user_address: H160,
+        contract_address: H160,
+        error: &PipelineError,
+    ) {
+        log::error!("failed to update oracle: {:?}", error.to_string());
+
+        let context = context.borrow();
+        let state = context.get_state();
+        let oracle_storage = state.oracle_storage();
+
+        // A rejected outlier or price change, a gas price above the maximum, an
+        // unfunded signer or a duplicate transaction is not a failure of the origin
+        let status = match OracleSkipReason::from_error(&error.inner) {
+            Some(reason) => oracle_storage.record_skipped_update(
+                user_address.clone(),
+                contract_address.clone(),
+                error.clone(),
+                reason,
+            ),
+            None => oracle_storage.record_update_failure(
+                user_address.clone(),
+                contract_address.clone(),
+                error.clone(),
+                ic::time(),
+            ),
+        };
+
+        events::emit(
+            oracle_storage,
+            OracleEvent::new(
+                user_address.clone(),
+                contract_address.clone(),
+                OracleEventType::UpdateFailed {
+                    error: error.to_string(),
+                },
+                None,
+            ),
+        );
+
+        match status {
+            Ok(OracleStatus::Error) => {
+                log::error!(
+                    "oracle {} of user {} reached its error threshold, stopping its timer",
+                    contract_address,
+                    user_address
+                );
+
+                if let Ok(timer_id) = oracle_storage
+                    .get_timer_id_by_address(user_address.clone(), contract_address.clone())
+                {
+                    ic_exports::ic_cdk_timers::clear_timer(timer_id);
+                }
+
+                events::emit(
+                    oracle_storage,
+                    OracleEvent::new(
+                        user_address,
+                        contract_address,
+                        OracleEventType::Paused,
+                        Some("error threshold reached".to_string()),
+                    ),
+                );
+            }
+            Ok(OracleStatus::CircuitOpen { resets_at, .. }) => log::warn!(
+                "circuit of oracle {} of user {} is open, skipping its updates until {}",
+                contract_address,
+                user_address,
+                resets_at
+            ),
+            Ok(_) => {}
+            Err(e) => log::debug!("failed to record oracle error: {:?}", e.to_string()),
+        }
+    }
+
+    /// Fetches the price from an EVM or HTTP origin, checking the HTTP responses
+    /// with the validators
+    async fn fetch_price(
+        origin: Origin,
+        response_cache: ResponseCache,
+        cookie_cache: CookieCache,
+        validators: Vec<ResponseSchema>,
+    ) -> Result<U256> {
+        match origin {
+            Origin::Evm(EvmOrigin {
+                ref provider,
+                ref target_address,
+                ref method,
+                ref return_types,
+                return_value_index,
+            }) => {
+                let data = provider::function_selector(method, &[]).encode_input(&[])?;
+
+                let data_hex = did::Bytes::from(data).to_hex_str();
+                let params = serde_json::json!([{
+                    "to": target_address,
+                    "data": data_hex,
+                }]);
+
+                let res = provider
+                    .call_jsonrpc("eth_call", params, Some(80000))
+                    .await?;
+
+                let return_data = serde_json::from_value::<String>(res)?;
+                let return_data = hex::decode(return_data.trim_start_matches("0x"))
+                    .map_err(|e| Error::AbiDecodeError(e.to_string()))?;
+
+                provider::decode_return_value(&return_data, return_types, return_value_index)
+            }
+            Origin::Http(http_origin) => {
+                http::get_price(&http_origin, &response_cache, &cookie_cache, &validators).await
+            }
+            Origin::Combined(_) => Err(Error::Internal(
+                "combined origins cannot be nested".to_string(),
+            )),
+        }
+    }
+
+    /// Sends a transaction to the EVM and returns the submitted price with the transaction hash
+    ///
+    /// When the origin fails, the fallback price of the oracle is submitted with
+    /// `updatePriceFallback`. Oracles including a signed price submit it with
+    /// `updatePriceSigned` along with their signature of the price. In simulation mode, the transaction is simulated with
+    /// `eth_call` instead. Oracles suppressing duplicates send nothing when the
+    /// price did not change since the last submitted one.
+    ///
+    /// The price is sent to the extra destinations of the oracle at the same time as
+    /// its main destination, and the result of each transaction is recorded. Only
+    /// the main destination is simulated, and a retried update sends the price to
+    /// every destination again.
+    async fn send_transaction(
+        metadata: &OracleMetadata,
+        user_address: H160,
+        context: Rc<RefCell<dyn Context>>,
+    ) -> PipelineResult<UpdateOutcome> {
+        let evm_destination = metadata.evm.clone();
+
+        log::debug!(
+            "Updating oracle price: user_address :{} origin: {:?} evm_destination: {:?} ",
+            user_address,
+            metadata.origin,
+            evm_destination
+        );
+
+        let (response_cache, cookie_cache) = {
+            let context = context.borrow();
+            let state = context.get_state();
+            (state.response_cache().clone(), state.cookie_cache().clone())
+        };
+        let (response, source) = fetch_origin_price(
+            metadata.origin.clone(),
+            metadata.fallback_price.clone(),
+            |origin| {
+                Self::fetch_price(
+                    origin,
+                    response_cache.clone(),
+                    cookie_cache.clone(),
+                    metadata.response_validators.clone(),
+                )
+            },
+        )
+        .await
+        .map_err(|e| PipelineError::new(fetch_error_stage(&e), e))?;
+
+        // The fallback price is submitted as is
+        let response = match source {
+            PriceSource::FallbackPrice => response,
+            PriceSource::Origin | PriceSource::CombinedFallback => apply_transforms(
+                response,
+                &metadata.transforms,
+                origin_decimals(&metadata.origin),
+            )
+            .at_stage(OracleStage::DataParse)?,
+        };
+
+        if is_duplicate_price(metadata, &response, source) {
+            return Ok(UpdateOutcome::Suppressed(response));
+        }
+
+        if let Some(price_bounds) = &metadata.price_bounds {
+            if let Err(e) = price_bounds.check(&response) {
+                log::warn!(
+                    "not submitting the price of oracle {}: {}",
+                    evm_destination.contract,
+                    e
+                );
+                return Err(PipelineError::new(OracleStage::DataParse, e));
+            }
+        }
+
+        // The fallback price is set by the user and is not compared to the history
+        if let Some(outlier_config) = metadata
+            .outlier_config
+            .as_ref()
+            .filter(|_| source != PriceSource::FallbackPrice)
+        {
+            if let Err(e) = outlier_config.check(&response, &metadata.price_history) {
+                log::warn!(
+                    "not submitting the price of oracle {}: {}",
+                    evm_destination.contract,
+                    e
+                );
+                return Err(PipelineError::new(OracleStage::DataParse, e));
+            }
+        }
+
+        if let Some(max_change_rate_bps) = metadata
+            .max_change_rate_bps
+            .filter(|_| source != PriceSource::FallbackPrice)
+        {
+            if let Err(e) = check_price_change(
+                &response,
+                metadata.last_submitted_price.as_ref(),
+                max_change_rate_bps,
+            ) {
+                log::warn!(
+                    "not submitting the price of oracle {}: {}",
+                    evm_destination.contract,
+                    e
+                );
+                return Err(PipelineError::new(OracleStage::DataParse, e));
+            }
+        }
+
+        if metadata.simulation_mode {
+            let data = Self::encode_update_data(
+                metadata,
+                source,
+                &response,
+                &evm_destination.contract,
+                user_address.clone(),
+                &context,
+            )
+            .await
+            .at_stage(OracleStage::TransactionSigning)?;
+            let provider = evm_destination.provider;
+
+            let transaction = get_transaction(
+                user_address,
+                metadata.rotation_count,
+                &metadata.derivation_suffix,
+                provider.clone(),
+                Some(evm_destination.contract.0.into()),
+                evm_destination.value_wei.clone().unwrap_or_default(),
+                data,
+                metadata.max_gas_price_gwei,
+                None,
+                &context,
+            )
+            .await?;
+
+            let return_data = provider::simulate_transaction(&transaction, &provider)
+                .await
+                .at_stage(OracleStage::TransactionSubmission)?;
+
+            log::info!(
+                "simulated update of oracle {} with price {:?}: {}",
+                evm_destination.contract,
+                response,
+                return_data
+            );
+
+            return Ok(UpdateOutcome::Simulated(SimulationResult {
+                price: response,
+                return_data,
+                estimated_gas: transaction.gas.into(),
+                simulated_at: ic::time(),
+            }));
+        }
+
+        let destinations = evm_destination.destinations();
+        let send = |destination, primary| {
+            Self::send_to_destination(
+                metadata,
+                source,
+                &response,
+                destination,
+                primary,
+                user_address.clone(),
+                &context,
+            )
+        };
+        let (result, extra_results) = futures::future::join(
+            send(&destinations[0], true),
+            futures::future::join_all(
+                destinations[1..]
+                    .iter()
+                    .map(|destination| send(destination, false)),
+            ),
+        )
+        .await;
+
+        let broadcast_results = destinations
+            .iter()
+            .zip(std::iter::once(&result).chain(&extra_results))
+            .map(|(destination, result)| DestinationResult {
+                contract: destination.contract.clone(),
+                chain_id: destination.provider.chain_id,
+                result: match result {
+                    Ok((tx_hash, _)) => TxResult::Sent(tx_hash.clone()),
+                    Err(e) => {
+                        log::warn!(
+                            "failed to send the price of oracle {} to contract {}: {}",
+                            evm_destination.contract,
+                            destination.contract,
+                            e
+                        );
+                        TxResult::Failed(e.to_string())
+                    }
+                },
+            })
+            .collect();
+        if let Err(e) = context
+            .borrow()
+            .get_state()
+            .oracle_storage()
+            .set_broadcast_results(
+                user_address,
+                evm_destination.contract.clone(),
+                broadcast_results,
+            )
+        {
+            log::debug!("failed to record broadcast results: {:?}", e.to_string());
+        }
+
+        // The outcome of the update is the one of its main destination
+        let (tx_hash, transaction) = result?;
+
+        log::debug!("transaction hash: {:?}", tx_hash);
+
+        Ok(UpdateOutcome::Submitted {
+            price: response,
+            tx_hash,
+            transaction,
+            using_fallback: source != PriceSource::Origin,
+        })
+    }
+
+    /// Encodes the call updating the price of the contract
+    async fn encode_update_data(
+        metadata: &OracleMetadata,
+        source: PriceSource,
+        price: &U256,
+        contract: &H160,
+        user_address: H160,
+        context: &Rc<RefCell<dyn Context>>,
+    ) -> Result<Vec<u8>> {
+        let data = match source {
+            PriceSource::FallbackPrice => provider::encode_update_price_fallback(price)?,
+            PriceSource::Origin | PriceSource::CombinedFallback
+                if metadata.include_signed_price =>
+            {
+                let timestamp = ic::time() / 1_000_000_000;
+                let digest = provider::signed_price_digest(price, timestamp, contract);
+
+                let signer = {
+                    let context = context.borrow();
+                    let signer = context.get_state().signer().get_rotated_oracle_signer(
+                        user_address,
+                        metadata.rotation_count,
+                        &metadata.derivation_suffix,
+                    );
+
+                    signer
+                };
+                let signature = signer.sign_digest(digest).await?;
+                let signature = Signature {
+                    r: signature.r.into(),
+                    s: signature.s.into(),
+                    v: ethers_core::types::U64::from(signature.v).as_u64(),
+                };
+
+                provider::encode_update_price_signed(price, timestamp, &signature)?
+            }
+            PriceSource::Origin | PriceSource::CombinedFallback => {
+                match &metadata.custom_update_fn {
+                    Some(custom_update_fn) => custom_update_fn.encode(price)?,
+                    None => {
+                        let round_id = metadata
+                            .include_round_id
+                            .then_some(metadata.round_id.saturating_add(1));
+                        provider::encode_update_price(price, round_id)?
+                    }
+                }
+            }
+        };
+
+        Ok(data)
+    }
+
+    /// Sends the transaction updating the price of one of the destinations of the
+    /// oracle and returns its hash along with the signed transaction
+    ///
+    /// The transaction to the primary destination is skipped when it is the last
+    /// one submitted by the oracle.
+    async fn send_to_destination(
+        metadata: &OracleMetadata,
+        source: PriceSource,
+        price: &U256,
+        destination: &SingleEvmDestination,
+        primary: bool,
+        user_address: H160,
+        context: &Rc<RefCell<dyn Context>>,
+    ) -> PipelineResult<(H256, ethers_core::types::Transaction)> {
+        let data = Self::encode_update_data(
+            metadata,
+            source,
+            price,
+            &destination.contract,
+            user_address.clone(),
+            context,
+        )
+        .await
+        .at_stage(OracleStage::TransactionSigning)?;
+
+        let transaction = get_transaction(
+            user_address.clone(),
+            metadata.rotation_count,
+            &metadata.derivation_suffix,
+            destination.provider.clone(),
+            Some(destination.contract.0.into()),
+            metadata.evm.value_wei.clone().unwrap_or_default(),
+            data,
+            metadata.max_gas_price_gwei,
+            metadata.min_eth_balance_check.as_ref(),
+            context,
+        )
+        .await?;
+
+        let submit =
+            || provider::send_raw_transaction(&transaction, &destination.provider, context);
+        let tx_hash = if primary {
+            let oracle_storage = context.borrow().get_state().oracle_storage().clone();
+            submit_once(
+                &oracle_storage,
+                &user_address,
+                &metadata.evm.contract,
+                transaction.hash.into(),
+                submit,
+            )
+            .await
+        } else {
+            submit().await
+        }
+        .at_stage(OracleStage::TransactionSubmission)?;
+
+        Ok((tx_hash, transaction))
+    }
+
+    /// Checks that the oracle exists and is owned by the given user
+    fn check_oracle_owner(&self, user_address: &H160, contract_address: &H160) -> Result<()> {
+        let owner = self.with_state(|state| {
+            state
+                .oracle_storage()
+                .get_oracle_owner(user_address.clone(), contract_address.clone())
+        })?;
+
+        if &owner != user_address {
+            return Err(Error::Internal(
+                "caller is not the owner of the oracle".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn check_owner(&self, caller: Principal) -> Result<()> {
+        let owner = self.with_state(|state| state.owner());
+        if caller != owner {
+            return Err(Error::Internal("caller is not the owner".to_string()));
+        }
+
+        Ok(())
+    }
+
+    fn check_owner_or_admin(&self, caller: Principal) -> Result<()> {
+        let allowed = self.with_state(|state| caller == state.owner() || state.is_admin(caller));
+        if !allowed {
+            return Err(Error::Internal(
+                "caller is not the owner or an admin".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns candid IDL.
+    /// This should be the last fn to see previous endpoints in macro.
+    pub fn idl() -> Idl {
+        generate_idl!()
+    }
+}
+
+/// Configuration of all the oracles and settings of the canister
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize)]
+pub struct OracleExport {
+    /// The oracles of each user, keyed by contract address
+    pub oracles: Vec<(H160, Vec<(H160, OracleMetadata)>)>,
+    pub settings: Settings,
+}
+
+/// Outcome of an oracle import
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub created: u64,
+    /// Number of oracles that already existed
+    pub skipped_existing: u64,
+    /// Errors of the oracles that could not be created
+    pub failed: Vec<String>,
+}
+
+/// Oracles and settings of the canister encoded with bincode
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OracleStateSnapshot {
+    /// Version of the layout of `data`, `ORACLE_SNAPSHOT_SCHEMA_VERSION` for the
+    /// snapshots taken by this canister version
+    pub schema_version: u32,
+    pub data: Vec<u8>,
+}
+
+impl OracleStateSnapshot {
+    fn new(state: &OracleState) -> Self {
+        Self {
+            schema_version: ORACLE_SNAPSHOT_SCHEMA_VERSION,
+            data: did::codec::bincode_encode(state),
+        }
+    }
+
+    /// Decodes the snapshot, rejecting the ones taken with another layout
+    fn decode(&self) -> Result<OracleState> {
+        if self.schema_version != ORACLE_SNAPSHOT_SCHEMA_VERSION {
+            return Err(Error::UnsupportedSnapshotVersion {
+                version: self.schema_version,
+                supported: ORACLE_SNAPSHOT_SCHEMA_VERSION,
+            });
+        }
+
+        bincode::deserialize(&self.data)
+            .map_err(|e| Error::Internal(format!("invalid oracle state snapshot: {e}")))
+    }
+}
+
+/// Content of an oracle state snapshot
+#[derive(Debug, Serialize, Deserialize)]
+struct OracleState {
+    oracles: Vec<StorableOracleMetadata>,
+    settings: Settings,
+}
+
+/// Outcome of an oracle state restore
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RestoreSummary {
+    pub oracles_restored: u64,
+    pub timers_started: u64,
+    /// Errors of the oracles whose timer could not be started
+    pub errors: Vec<String>,
+}
+
+/// Builds a new oracle with the configuration of `source`, updating `destination`
+///
+/// The statistics and the triggers of `source` are not copied.
+fn new_oracle_from_config(
+    user_address: H160,
+    source: OracleMetadata,
+    destination: EvmDestination,
+) -> StorableOracleMetadata {
+    let mut metadata = StorableOracleMetadata::new(
+        user_address,
+        source.origin,
+        source.timer_interval,
+        TimerId::default(),
+        destination,
+    );
+    metadata.error_threshold = source.error_threshold;
+    metadata.expires_at = source.expires_at;
+    metadata.verify_receipt = source.verify_receipt;
+    metadata.confirmation_delay_secs = source.confirmation_delay_secs;
+    metadata.confirmation_blocks = source.confirmation_blocks;
+    metadata.max_wait_secs = source.max_wait_secs;
+    metadata.cycle_budget = source.cycle_budget;
+    metadata.resume_threshold_cycles = source.resume_threshold_cycles;
+    metadata.price_bounds = source.price_bounds;
+    metadata.outlier_config = source.outlier_config;
+    metadata.max_change_rate_bps = source.max_change_rate_bps;
+    metadata.response_validators = source.response_validators;
+    metadata.simulation_mode = source.simulation_mode;
+    metadata.max_gas_price_gwei = source.max_gas_price_gwei;
+    metadata.min_eth_balance_check = source.min_eth_balance_check;
+    metadata.suppress_duplicates = source.suppress_duplicates;
+    metadata.include_round_id = source.include_round_id;
+    metadata.include_signed_price = source.include_signed_price;
+    metadata.custom_update_fn = source.custom_update_fn;
+    metadata.circuit_breaker = source.circuit_breaker;
+    metadata.label = source.label;
+    metadata.description = source.description;
+    metadata.tags = source.tags;
+    metadata.fallback_price = source.fallback_price;
+    metadata.transforms = source.transforms;
+    metadata.max_executions = source.max_executions;
+    metadata.max_retries = source.max_retries;
+    metadata.retry_base_delay_secs = source.retry_base_delay_secs;
+    metadata.tx_deadline_secs = source.tx_deadline_secs;
+    metadata.max_history_entries = source.max_history_entries;
+    metadata.derivation_suffix = source.derivation_suffix;
+    metadata.eip712_domain = source.eip712_domain;
+
+    metadata
+}
+
+/// Oracles reserved by a batch creation, released as the oracles are created
+///
+/// The oracles left when the guard is dropped, by a trap of the batch, are
+/// released as well.
+struct CreationReservation {
+    context: Rc<RefCell<dyn Context>>,
+    remaining: BTreeMap<H160, u64>,
+}
+
+impl CreationReservation {
+    fn new(context: Rc<RefCell<dyn Context>>, oracles_per_user: BTreeMap<H160, u64>) -> Self {
+        {
+            let context = context.borrow();
+            let state = context.get_state();
+            for (user_address, count) in &oracles_per_user {
+                state
+                    .pending_creations()
+                    .reserve(user_address.clone(), *count);
+            }
+        }
+
+        Self {
+            context,
+            remaining: oracles_per_user,
+        }
+    }
+
+    /// Releases one oracle of the user, once it is created or failed
+    fn release(&mut self, user_address: &H160) {
+        let Some(remaining) = self.remaining.get_mut(user_address) else {
+            return;
+        };
+        *remaining = remaining.saturating_sub(1);
+
+        self.context
+            .borrow()
+            .get_state()
+            .pending_creations()
+            .release(user_address, 1);
+    }
+}
+
+impl Drop for CreationReservation {
+    fn drop(&mut self) {
+        let Ok(context) = self.context.try_borrow() else {
+            return;
+        };
+
+        let state = context.get_state();
+        for (user_address, count) in &self.remaining {
+            state.pending_creations().release(user_address, *count);
+        }
+    }
+}
+
+/// Slot of an oracle update in the active updates, released when the update ends
+///
+/// On the IC, a panic traps the update and the changes made since its last `await`
+/// are rolled back, but the future is dropped in the cleanup of the call. A guard
+/// dropped before `finish` is called counts the update as panicked and failed, so
+/// that its slot is not leaked and the other oracles keep being updated.
+struct UpdateGuard {
+    context: Rc<RefCell<dyn Context>>,
+    user_address: H160,
+    contract_address: H160,
+    finished: bool,
+}
+
+impl UpdateGuard {
+    fn new(context: Rc<RefCell<dyn Context>>, user_address: H160, contract_address: H160) -> Self {
+        Self {
+            context,
+            user_address,
+            contract_address,
+            finished: false,
+        }
+    }
+
+    /// Ends the update, releasing its slot
+    fn finish(mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for UpdateGuard {
+    fn drop(&mut self) {
+        // The context may still be borrowed mutably by the panicking update
+        if self.context.try_borrow().is_err() {
+            log::error!(
+                "update of oracle {} of user {} ended with the context borrowed",
+                self.contract_address,
+                self.user_address
+            );
+            return;
+        }
+
+        self.context.borrow().get_state().active_updates().release();
+        if self.finished {
+            return;
+        }
+
+        log::error!(
+            "update of oracle {} of user {} panicked",
+            self.contract_address,
+            self.user_address
+        );
+        if let Err(e) = self
+            .context
+            .borrow()
+            .get_state()
+            .oracle_storage()
+            .record_update_panic(self.user_address.clone(), self.contract_address.clone())
+        {
+            log::debug!("failed to record oracle panic: {:?}", e.to_string());
+        }
+
+        // The stage of the panic is unknown, it is recorded at the first stage
+        let error = PipelineError::new(
+            OracleStage::DataFetch,
+            Error::Internal("the update panicked".to_string()),
+        );
+        Oracular::handle_update_error(
+            &self.context,
+            self.user_address.clone(),
+            self.contract_address.clone(),
+            &error,
+        );
+    }
+}
+
+/// Outcome of a successful oracle update
+enum UpdateOutcome {
+    /// The price was pushed to the EVM
+    Submitted {
+        price: U256,
+        tx_hash: H256,
+        /// The signed transaction, replayed to get its revert reason
+        transaction: ethers_core::types::Transaction,
+        /// Whether the price was fetched from the fallback of a combined origin or
+        /// is the fallback price of the oracle
+        using_fallback: bool,
+    },
+    /// The update transaction was simulated
+    Simulated(SimulationResult),
+    /// The fetched price is the last submitted one and no transaction was sent
+    Suppressed(U256),
+}
+
+/// Source of the price submitted by an oracle update
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PriceSource {
+    /// The price was fetched from the origin
+    Origin,
+    /// The price was fetched from the fallback of a combined origin
+    CombinedFallback,
+    /// The origin failed and the fallback price of the oracle is used
+    FallbackPrice,
+}
+
+/// Pending check of the receipt of an update transaction
+struct ReceiptCheck {
+    user_address: H160,
+    contract_address: H160,
+    /// Provider the transaction was sent to
+    provider: Provider,
+    tx_hash: H256,
+    /// Hash of the stuck transaction replaced by `tx_hash`
+    replaced: Option<H256>,
+    /// Price submitted by the transaction
+    price: U256,
+    /// `eth_call` parameters replaying the transaction
+    call: Value,
+    /// Seconds to wait before the first check, doubled on each retry
+    delay_secs: u64,
+    /// Number of times the missing receipt was checked again
+    retries: u32,
+    /// Seconds waited for the receipt so far
+    waited_secs: u64,
+    /// IC time in nanoseconds after which a missing receipt times out
+    deadline: u64,
+}
+
+/// Replacement of an update transaction scheduled at its deadline
+struct TxReplacement {
+    user_address: H160,
+    contract_address: H160,
+    /// Provider the transaction was sent to
+    provider: Provider,
+    /// Rotation of the oracle signer that signed the transaction
+    rotation_count: u32,
+    /// Derivation suffix of the oracle signer that signed the transaction
+    derivation_suffix: Vec<Vec<u8>>,
+    /// The signed transaction, replaced if it has no receipt at the deadline
+    transaction: ethers_core::types::Transaction,
+}
+
+/// This is the origin of the data that will be used to update the price
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Origin {
+    /// EVM origin
+    Evm(EvmOrigin),
+    /// HTTP origin
+    Http(HttpOrigin),
+    /// Primary origin with a fallback
+    Combined(CombinedOrigin),
+}
+
+impl Origin {
+    /// Returns the providers of the EVM origins, including the ones of a combined origin
+    pub fn evm_providers_mut(&mut self) -> Vec<&mut Provider> {
+        match self {
+            Origin::Evm(EvmOrigin { provider, .. }) => vec![provider],
+            Origin::Http(_) => vec![],
+            Origin::Combined(CombinedOrigin {
+                primary, fallback, ..
+            }) => {
+                let mut providers = primary.evm_providers_mut();
+                providers.extend(fallback.evm_providers_mut());
+                providers
+            }
+        }
+    }
+}
+
+/// The kind of an [`Origin`], without its data
+#[derive(Debug, Clone, Copy, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub enum OriginType {
+    /// EVM origin
+    Evm,
+    /// HTTP origin
+    Http,
+    /// Combined origin
+    Combined,
+}
+
+impl From<&Origin> for OriginType {
+    fn from(origin: &Origin) -> Self {
+        match origin {
+            Origin::Evm(_) => OriginType::Evm,
+            Origin::Http(_) => OriginType::Http,
+            Origin::Combined(_) => OriginType::Combined,
+        }
+    }
+}
+
+/// Origin fetching the price from `primary`, or from `fallback` when `primary`
+/// fails with an HTTP error
+///
+/// Neither `primary` nor `fallback` can be combined origins.
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CombinedOrigin {
+    pub primary: Box<Origin>,
+    pub fallback: Box<Origin>,
+    /// Whether the fallback is used when the primary origin fails
+    pub use_fallback_on_error: bool,
+}
+
+/// EVM origin data
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EvmOrigin {
+    /// The EVM provider that will be used to fetch the data
+    pub provider: Provider,
+    /// The address of the contract that will be called
+    pub target_address: H160,
+    /// The method that will be called on the contract
+    pub method: String,
+    /// ABI types of the values returned by the method, a single `uint256` when empty
+    pub return_types: Vec<String>,
+    /// Index of the returned value used as the price
+    pub return_value_index: usize,
+}
+
+/// HTTP origin data that will be used to fetch the data from the given URL
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HttpOrigin {
+    /// The URL that will be used to fetch the data
+    pub url: String,
+    /// The JSON path that will be used to extract the data
+    pub json_path: String,
+    /// JSON paths whose values are collected in an array, reduced to a price by
+    /// `json_aggregate`; only used when `json_path` is empty
+    pub json_paths: Vec<String>,
+    /// JSON paths of error envelopes, the response is rejected when any of them
+    /// resolves, e.g. `error`
+    pub forbidden_paths: Vec<String>,
+    /// JSON paths which must resolve in the response, whatever their value
+    pub required_paths: Vec<String>,
+    /// Number of decimals of the price, `DEFAULT_PRICE_DECIMALS` when `None`
+    pub decimals: Option<u8>,
+    /// Max response bytes of the request, `DEFAULT_HTTP_MAX_RESPONSE_BYTES` when `None`
+    pub max_response_bytes: Option<u64>,
+    /// How the price is read from the JSON value, `PriceParseMode::Auto` when `None`
+    pub parse_mode: Option<PriceParseMode>,
+    /// Authentication header sent with the request, stored in plaintext
+    pub auth: Option<HttpAuth>,
+    /// Whether the JSON path can contain `[?key=value]` filters and `[index]` indexes
+    pub enable_filter_expressions: bool,
+    /// Whether the response cache is bypassed and the price always fetched
+    pub skip_cache: bool,
+    /// Reduction of the array matched by the JSON path, or of the values of the JSON
+    /// paths, to a single price
+    pub json_aggregate: Option<AggregateOp>,
+    /// Login flow opening the session the price is fetched with
+    pub session_cookie: Option<CookieConfig>,
+    /// HTTP status codes of the successful responses, `DEFAULT_VALID_STATUS_CODES`
+    /// for new origins
+    pub valid_status_codes: Vec<u16>,
+    /// HMAC signature of the request, sent in a header
+    pub signing_config: Option<HmacSigningConfig>,
+    /// Milliseconds after which the request is recorded as timed out, instead of
+    /// waiting for the deadline of the HTTP outcall
+    pub timeout_ms: Option<u64>,
+}
+
+/// This is the destination of the data that will be used to update the price
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EvmDestination {
+    /// The address of the contract that will be called
+    pub contract: H160,
+    /// The EVM provider that will be used to fetch the data
+    pub provider: Provider,
+    /// Chain of a provider registered in the canister; when set, the registered
+    /// provider replaces `provider` and follows its updates
+    pub chain_id: Option<u64>,
+    /// Contracts on other chains receiving the same price at each update
+    pub extra_destinations: Vec<SingleEvmDestination>,
+    /// Native token amount in wei attached to every update transaction
+    pub value_wei: Option<U256>,
+}
+
+impl EvmDestination {
+    /// Checks that the contract addresses are not zero, that the provider
+    /// endpoints are valid HTTPS URLs and that every destination is on its own chain
+    ///
+    /// The transactions to all the destinations are sent at once, so two destinations
+    /// on the same chain would get the same nonce.
+    pub fn validate(&self) -> Result<()> {
+        let destinations = self.destinations();
+        for destination in &destinations {
+            destination.validate()?;
+        }
+
+        let mut chain_ids = destinations
+            .iter()
+            .map(|destination| destination.provider.chain_id)
+            .collect::<Vec<_>>();
+        chain_ids.sort_unstable();
+        if let Some(chain_id) = chain_ids.windows(2).find(|w| w[0] == w[1]).map(|w| w[0]) {
+            return Err(Error::InvalidDestination(format!(
+                "more than one destination on chain {chain_id}"
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the main destination followed by the extra destinations
+    pub fn destinations(&self) -> Vec<SingleEvmDestination> {
+        let main = SingleEvmDestination {
+            contract: self.contract.clone(),
+            provider: self.provider.clone(),
+        };
+
+        std::iter::once(main)
+            .chain(self.extra_destinations.iter().cloned())
+            .collect()
+    }
+}
+
+/// Contract updated by an oracle along with its main destination
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SingleEvmDestination {
+    /// The address of the contract that will be called
+    pub contract: H160,
+    /// The EVM provider the transaction is sent to
+    pub provider: Provider,
+}
+
+impl SingleEvmDestination {
+    /// Checks that the contract address is not zero and that the provider
+    /// endpoints are valid HTTPS URLs
+    pub fn validate(&self) -> Result<()> {
+        if self.contract.0.is_zero() {
+            return Err(Error::InvalidDestination(
+                "contract address cannot be zero".to_string(),
+            ));
+        }
+
+        validate_provider_endpoints(&self.provider)
+    }
+}
+
+/// Round data of an oracle in the form of Chainlink's `latestRoundData`
+///
+/// The answer is never negative since the oracles only push unsigned prices.
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RoundData {
+    /// Number of successful updates of the oracle
+    pub round_id: u128,
+    /// The last price pushed by the oracle
+    pub answer: U256,
+    /// Timestamp in seconds at which the round started
+    pub started_at: u64,
+    /// Timestamp in seconds at which the round was updated
+    pub updated_at: u64,
+    /// Round in which the answer was computed
+    pub answered_in_round: u128,
+}
+
+/// Resources of the canister returned by `get_canister_stats`
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CanisterStats {
+    pub cycle_balance: u128,
+    /// Size of the stable memory in 64 KiB pages
+    pub stable_memory_pages: u64,
+    /// Size of the heap memory in bytes
+    pub heap_memory_bytes: u64,
+    pub oracle_count: u64,
+    /// Number of users with at least one oracle
+    pub user_count: u64,
+}
+
+/// Resources of the canister returned by `get_canister_health`
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CanisterHealth {
+    /// Size of the stable memory in 64 KiB pages
+    pub stable_memory_pages: u64,
+    /// Number of oracles whose update timer is running
+    pub active_timer_count: u64,
+    pub oracle_count: u64,
+    /// Number of users with at least one oracle
+    pub user_count: u64,
+    pub cycle_balance: u128,
+    /// IC time in nanoseconds, to detect the clock skew of the probes
+    pub ic_time_ns: u64,
+}
+
+/// Stable memory usage returned by `get_memory_usage`, in 64 KiB pages
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MemoryUsageReport {
+    pub oracle_storage_pages: u64,
+    pub settings_pages: u64,
+    /// Size of the whole stable memory, including the other memories and the
+    /// memory manager header
+    pub total_pages: u64,
+    /// Maximum size of the stable memory of a canister
+    pub max_pages: u64,
+}
+
+/// Result of a provider health check
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProviderHealthReport {
+    /// Whether the provider returned its latest block number
+    pub is_reachable: bool,
+    pub block_number: Option<U256>,
+    /// Duration in milliseconds of the `eth_blockNumber` call
+    pub latency_ms: Option<u64>,
+    /// Cycles consumed by the check, when measured
+    pub cycles_consumed: Option<u128>,
+    pub error: Option<String>,
+}
+
+impl ProviderHealthReport {
+    /// Builds the report of a check that took `elapsed_nanos` nanoseconds
+    fn new(block_number: Result<U256>, elapsed_nanos: u64) -> Self {
+        match block_number {
+            Ok(block_number) => Self {
+                is_reachable: true,
+                block_number: Some(block_number),
+                latency_ms: Some(Duration::from_nanos(elapsed_nanos).as_millis() as u64),
+                cycles_consumed: None,
+                error: None,
+            },
+            Err(e) => Self {
+                is_reachable: false,
+                block_number: None,
+                latency_ms: None,
+                cycles_consumed: None,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+/// Snapshot of the oracles and canister resources returned by `get_health_summary`
+#[derive(Debug, Clone, Default, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OracleHealthSummary {
+    /// Number of users with at least one oracle
+    pub total_users: u64,
+    pub total_oracles: u64,
+    pub active_oracles: u64,
+    pub paused_oracles: u64,
+    /// Number of oracles that reached their error threshold
+    pub errored_oracles: u64,
+    /// Number of oracles whose circuit breaker is open
+    pub circuit_open_oracles: u64,
+    /// Number of oracles waiting for their first timer tick
+    pub pending_oracles: u64,
+    /// Cycles balance of the canister
+    pub cycles_balance: u128,
+    /// Size of the canister stable memory in bytes
+    pub stable_memory_bytes: u64,
+}
+
+/// Statistics of all the oracles returned by `get_dashboard_stats`
+///
+/// The 24 hour counters only see the last update of each oracle, so they count
+/// oracles rather than individual updates.
+#[derive(Debug, Clone, Default, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DashboardStats {
+    pub total_oracles: u64,
+    pub active_oracles: u64,
+    pub paused_oracles: u64,
+    /// Number of oracles that reached their error threshold
+    pub errored_oracles: u64,
+    /// Number of oracles whose last update was attempted in the last 24 hours
+    pub total_updates_24h: u64,
+    /// Number of oracles whose last update, attempted in the last 24 hours, did
+    /// not succeed
+    pub failed_updates_24h: u64,
+    /// Cycles consumed in the last 24 hours, estimated from the average cost of
+    /// the updates and the timer interval of the oracles
+    pub estimated_cycles_consumed_24h: u128,
+    /// IC time in nanoseconds of the least recent successful update of an
+    /// oracle, 0 when no oracle was updated
+    pub oldest_update_timestamp: u64,
+    /// IC time in nanoseconds of the most recent successful update
+    pub newest_update_timestamp: u64,
+}
+
+impl DashboardStats {
+    /// Adds the oracle to the statistics at `now`
+    fn record(&mut self, metadata: &OracleMetadata, now: u64) {
+        self.total_oracles += 1;
+        match metadata.status {
+            OracleStatus::Active => self.active_oracles += 1,
+            OracleStatus::Paused => self.paused_oracles += 1,
+            OracleStatus::Error => self.errored_oracles += 1,
+            OracleStatus::CircuitOpen { .. } | OracleStatus::Pending => {}
+        }
+
+        let day_start = now.saturating_sub(SECONDS_PER_DAY * 1_000_000_000);
+        if metadata.last_attempt_at > day_start {
+            self.total_updates_24h += 1;
+            if metadata.last_updated_at < metadata.last_attempt_at {
+                self.failed_updates_24h += 1;
+            }
+
+            let updates_per_day = SECONDS_PER_DAY / metadata.timer_interval.max(1);
+            let daily_updates = metadata.total_updates.min(updates_per_day) as u128;
+            let average_cost = metadata.cycles_consumed / (metadata.total_updates.max(1) as u128);
+            self.estimated_cycles_consumed_24h = self
+                .estimated_cycles_consumed_24h
+                .saturating_add(average_cost * daily_updates);
+        }
+
+        if metadata.last_updated_at > 0 {
+            if self.oldest_update_timestamp == 0 {
+                self.oldest_update_timestamp = metadata.last_updated_at;
+            }
+            self.oldest_update_timestamp =
+                self.oldest_update_timestamp.min(metadata.last_updated_at);
+            self.newest_update_timestamp =
+                self.newest_update_timestamp.max(metadata.last_updated_at);
+        }
+    }
+}
+
+/// Trigger of an oracle, updated after each successful update of the `from` oracle
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DependencyEdge {
+    pub from_user: H160,
+    pub from_contract: H160,
+    pub to_user: H160,
+    pub to_contract: H160,
+}
+
+/// Cycles consumed by an oracle
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OracleCycleStats {
+    /// Estimated cycles consumed by the oracle updates
+    pub consumed: u128,
+    /// Cycles after which the oracle is paused
+    pub budget: Option<u128>,
+    /// Cycles left before the oracle is paused
+    pub remaining_budget: Option<u128>,
+}
+
+/// Update statistics of an oracle
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OracleStats {
+    /// Number of attempted updates
+    pub total_updates: u64,
+    /// Number of failed updates
+    pub failed_updates: u64,
+    /// Number of failed updates since the last successful one
+    pub consecutive_errors: u32,
+    /// Message of the last error of the oracle
+    pub last_error: Option<String>,
+    /// IC time in nanoseconds of the last successful update
+    pub last_success_at: u64,
+    /// IC time in nanoseconds of the last attempted update
+    pub last_attempt_at: u64,
+}
+
+impl From<&OracleMetadata> for OracleStats {
+    fn from(metadata: &OracleMetadata) -> Self {
+        Self {
+            total_updates: metadata.total_updates,
+            failed_updates: metadata.total_errors,
+            consecutive_errors: metadata.consecutive_errors,
+            last_error: metadata.last_error.as_ref().map(ToString::to_string),
+            last_success_at: metadata.last_updated_at,
+            last_attempt_at: metadata.last_attempt_at,
+        }
+    }
+}
+
+/// Cycles consumed by the updates of an oracle
+///
+/// The per update statistics cover the last `MAX_CYCLES_PER_CALL_HISTORY` updates
+/// and are 0 before the first update.
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CostStats {
+    pub avg_cycles_per_call: u64,
+    pub min_cycles_per_call: u64,
+    pub max_cycles_per_call: u64,
+    /// Cycles consumed by all the updates, less the cycles deposited for the oracle
+    pub total_cycles_consumed: u128,
+}
+
+impl From<&OracleMetadata> for CostStats {
+    fn from(metadata: &OracleMetadata) -> Self {
+        let history = &metadata.cycles_per_call_history;
+        let total = history.iter().map(|cycles| *cycles as u128).sum::<u128>();
+
+        Self {
+            avg_cycles_per_call: total
+                .checked_div(history.len() as u128)
+                .map_or(0, |avg| avg as u64),
+            min_cycles_per_call: history.iter().min().copied().unwrap_or_default(),
+            max_cycles_per_call: history.iter().max().copied().unwrap_or_default(),
+            total_cycles_consumed: metadata.cycles_consumed,
+        }
+    }
+}
+
+/// A signature of a message built by the canister, proving the control of an EVM address
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SignatureProof {
+    /// Nonce of the proof, greater than the last nonce used by the signer
+    pub nonce: u64,
+    /// IC time in nanoseconds after which the proof is rejected
+    pub expires_at: u64,
+    /// The hex encoded signature of the message
+    pub signature: String,
+}
+
+impl SignatureProof {
+    /// Checks that the proof has not expired and does not expire after
+    /// `MAX_SIGNATURE_PROOF_TTL_SECS`
+    pub fn check_expiry(&self, now: u64) -> Result<()> {
+        if self.expires_at <= now {
+            return Err(Error::Internal("the proof has expired".to_string()));
+        }
+
+        if self.expires_at - now > MAX_SIGNATURE_PROOF_TTL_SECS * 1_000_000_000 {
+            return Err(Error::Internal(format!(
+                "the proof must expire within {MAX_SIGNATURE_PROOF_TTL_SECS} seconds"
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that `message` was signed by `address`
+    pub fn verify(&self, message: String, address: &H160) -> Result<()> {
+        let signer = Oracular::recover_pubkey(message, self.signature.clone())?;
+        if &signer != address {
+            return Err(Error::Internal(
+                "signature does not match the address".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the message signed by `from` to transfer the oracle of `contract_address` to `to`
+fn transfer_message(
+    canister_id: Principal,
+    from: &H160,
+    to: &H160,
+    contract_address: &H160,
+    nonce: u64,
+    expires_at: u64,
+) -> String {
+    format!(
+        "transfer:{canister_id}:{}:{}:{}:{nonce}:{expires_at}",
+        from.to_hex_str(),
+        to.to_hex_str(),
+        contract_address.to_hex_str()
+    )
+}
+
+/// A single oracle creation request used by `create_oracles_batch`
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BatchOracleRequest {
+    /// The address of the user that creates the oracle
+    pub user_address: H160,
+    /// The origin of the data that will be used to update the price
+    pub origin: Origin,
+    /// The interval in seconds that will be used to update the price
+    pub interval: u64,
+    /// The destination of the data that will be used to update the price
+    pub destination: EvmDestination,
+    /// Whether the first update runs right away instead of after the interval
+    pub update_immediately: bool,
+}
+
+/// Parameters of an oracle created by `create_oracle_with_params`
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CreateOracleParams {
+    /// The address of the user that creates the oracle
+    pub user_address: H160,
+    /// The origin of the data that will be used to update the price
+    pub origin: Origin,
+    /// The interval in seconds that will be used to update the price
+    pub interval: u64,
+    /// The destination of the data that will be used to update the price
+    pub destination: EvmDestination,
+    /// Delay in seconds before the first update, the interval when `None`
+    pub initial_delay_secs: Option<u64>,
+    /// The IC time in nanoseconds after which the oracle is paused
+    pub expires_at: Option<u64>,
+    /// Short human readable name of the oracle, at most `MAX_LABEL_BYTES` long
+    pub label: Option<String>,
+    /// Description of the oracle, at most `MAX_DESCRIPTION_BYTES` long
+    pub description: Option<String>,
+    /// Whether the first update runs right away instead of after the interval
+    pub update_immediately: bool,
+    /// Name and version of the EIP-712 domain of the attestations; `None` uses
+    /// `DEFAULT_EIP712_DOMAIN_NAME` and `DEFAULT_EIP712_DOMAIN_VERSION`
+    pub eip712_domain: Option<Eip712DomainConfig>,
+}
+
+/// Returns the size of the canister heap memory in bytes
+fn heap_memory_bytes() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        core::arch::wasm32::memory_size(0) as u64 * 65536
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        0
+    }
+}
+
+/// Returns the cycles consumed between two balance measurements, excluding the
+/// cost of the measurements themselves
+fn consumed_cycles(balance_before: u128, balance_after: u128) -> u128 {
+    balance_before
+        .saturating_sub(balance_after)
+        .saturating_sub(BALANCE_MEASUREMENT_CYCLES)
+}
+
+/// Returns whether the host of the endpoint is the local machine
+fn is_local_endpoint(endpoint: &str) -> bool {
+    url::Url::parse(endpoint)
+        .is_ok_and(|url| matches!(url.host_str(), Some("localhost" | "127.0.0.1")))
+}
+
+/// Returns the seconds to wait before a retry, doubling the initial delay on each retry
+fn backoff_delay_secs(delay_secs: u64, retries: u32) -> u64 {
+    delay_secs.saturating_mul(2u64.saturating_pow(retries))
+}
+
+/// Returns the block preceding the given one, on whose state a transaction of the
+/// block is replayed
+fn parent_block(block_number: U256) -> Value {
+    serde_json::json!(U256(block_number.0.saturating_sub(1u64.into())))
+}
+
+/// Returns the block including the transaction of the receipt
+fn receipt_block(receipt: &Value) -> Option<ConfirmedBlock> {
+    Some(ConfirmedBlock {
+        number: serde_json::from_value(receipt.get("blockNumber")?.clone()).ok()?,
+        hash: serde_json::from_value(receipt.get("blockHash")?.clone()).ok()?,
+        finalized: false,
+    })
+}
+
+/// Result of the check of the block of a confirmed transaction against the
+/// canonical chain
+#[derive(Debug, PartialEq, Eq)]
+enum BlockCheck {
+    /// The block is canonical, but not enough blocks were built on top of it
+    Pending,
+    /// The block is canonical and reached the confirmation blocks
+    Finalized,
+    /// The block was orphaned by a chain reorganization
+    Reorged,
+}
+
+/// Checks the confirmed block against the `latest` block of the chain and the
+/// `canonical` block at its number
+///
+/// The confirmed block counts as one of its confirmation blocks. A provider
+/// behind the confirmed block leaves the check pending.
+fn check_confirmed_block(
+    block: &ConfirmedBlock,
+    latest: &Value,
+    canonical: &Value,
+    confirmation_blocks: u8,
+) -> Result<BlockCheck> {
+    let latest_number: U256 =
+        serde_json::from_value(latest.get("number").cloned().unwrap_or_default())?;
+    if latest_number.0 < block.number.0 {
+        return Ok(BlockCheck::Pending);
+    }
+
+    let canonical_hash = match canonical.get("hash") {
+        Some(hash) => Some(serde_json::from_value::<H256>(hash.clone())?),
+        None => None,
+    };
+    if canonical_hash.as_ref() != Some(&block.hash) {
+        return Ok(BlockCheck::Reorged);
+    }
+
+    let confirmations = latest_number.0 - block.number.0 + 1u64;
+    if confirmations >= ethers_core::types::U256::from(confirmation_blocks) {
+        Ok(BlockCheck::Finalized)
+    } else {
+        Ok(BlockCheck::Pending)
+    }
+}
+
+/// Fetches the latest block number of the provider, measuring the duration of the call
+async fn check_provider(provider: &Provider) -> ProviderHealthReport {
+    let started_at = ic::time();
+
+    let block_number = provider
+        .call_jsonrpc(
+            "eth_blockNumber",
+            serde_json::Value::Null,
+            Some(JSON_RPC_MAX_RESPONSE_BYTES),
+        )
+        .await
+        .and_then(|block_number| Ok(serde_json::from_value::<U256>(block_number)?));
+
+    ProviderHealthReport::new(block_number, ic::time().saturating_sub(started_at))
+}
+
+/// Estimates the cycles of the HTTP outcalls made by a single oracle update
+fn estimate_update_cost(origin: &Origin, destination: &EvmDestination) -> u128 {
+    let origin_cost = estimate_origin_cost(origin);
+
+    let transactions_cost = destination
+        .destinations()
+        .iter()
+        .map(|destination| {
+            let hostname = destination.provider.primary_endpoint();
+            // nonce and gas price are fetched with a single batch request
+            let batch_cost = http::get_request_costs(
+                hostname,
+                2 * ESTIMATED_JSON_RPC_PAYLOAD_BYTES,
+                JSON_RPC_MAX_RESPONSE_BYTES,
+            );
+            let gas_estimation_cost = http::get_request_costs(
+                hostname,
+                ESTIMATED_JSON_RPC_PAYLOAD_BYTES,
+                JSON_RPC_MAX_RESPONSE_BYTES,
+            );
+            let send_cost = http::get_request_costs(
+                hostname,
+                ESTIMATED_JSON_RPC_PAYLOAD_BYTES,
+                EVM_CALL_MAX_RESPONSE_BYTES,
+            );
+
+            batch_cost + gas_estimation_cost + send_cost
+        })
+        .sum::<u128>();
+
+    origin_cost + transactions_cost
+}
+
+/// Estimates the cycles of the HTTP outcall fetching the price from the origin
+///
+/// The fallback of a combined origin is only called when its primary fails,
+/// so only the primary is counted.
+fn estimate_origin_cost(origin: &Origin) -> u128 {
+    match origin {
+        Origin::Evm(EvmOrigin { provider, .. }) => http::get_request_costs(
+            provider.primary_endpoint(),
+            ESTIMATED_JSON_RPC_PAYLOAD_BYTES,
+            EVM_CALL_MAX_RESPONSE_BYTES,
+        ),
+        Origin::Http(HttpOrigin {
+            url,
+            max_response_bytes,
+            ..
+        }) => http::get_request_costs(
+            url,
+            0,
+            max_response_bytes.unwrap_or(DEFAULT_HTTP_MAX_RESPONSE_BYTES),
+        ),
+        Origin::Combined(CombinedOrigin { primary, .. }) => estimate_origin_cost(primary),
+    }
+}
+
+/// Fetches the price of a combined origin with `fetch`, trying the fallback when
+/// the primary fails with an HTTP error and `use_fallback_on_error` is set
+///
+/// Returns the price and whether the fallback was used.
+async fn fetch_combined_price<F, Fut>(combined: CombinedOrigin, fetch: F) -> Result<(U256, bool)>
+where
+    F: Fn(Origin) -> Fut,
+    Fut: Future<Output = Result<U256>>,
+{
+    match fetch(*combined.primary).await {
+        Err(Error::Http(e)) if combined.use_fallback_on_error => {
+            log::warn!("primary origin failed: {e}, using the fallback origin");
+
+            Ok((fetch(*combined.fallback).await?, true))
+        }
+        result => Ok((result?, false)),
+    }
+}
+
+/// Fetches the price of the origin with `fetch`, using `fallback_price` when the
+/// origin fails with an HTTP or a parse error
+///
+/// Returns the price and its source.
+async fn fetch_origin_price<F, Fut>(
+    origin: Origin,
+    fallback_price: Option<U256>,
+    fetch: F,
+) -> Result<(U256, PriceSource)>
+where
+    F: Fn(Origin) -> Fut,
+    Fut: Future<Output = Result<U256>>,
+{
+    let result = match origin {
+        Origin::Combined(combined) => {
+            fetch_combined_price(combined, &fetch)
+                .await
+                .map(|(price, using_fallback)| {
+                    let source = if using_fallback {
+                        PriceSource::CombinedFallback
+                    } else {
+                        PriceSource::Origin
+                    };
+                    (price, source)
+                })
+        }
+        origin => fetch(origin)
+            .await
+            .map(|price| (price, PriceSource::Origin)),
+    };
+
+    match (result, fallback_price) {
+        (Err(e @ (Error::Http(_) | Error::ParseError(_))), Some(fallback_price)) => {
+            log::warn!("origin failed: {e}, submitting the fallback price");
+
+            Ok((fallback_price, PriceSource::FallbackPrice))
+        }
+        (result, _) => result,
+    }
+}
+
+/// Runs `operation` until it succeeds or fails with an error other than an HTTP one,
+/// retrying it at most `max_retries` times
+///
+/// Before each retry, `wait` is called with `base_delay_secs * 2^attempt` seconds.
+async fn retry_on_http_error<T, E, F, Fut, W, WFut>(
+    max_retries: u8,
+    base_delay_secs: u64,
+    mut operation: F,
+    mut wait: W,
+) -> std::result::Result<T, E>
+where
+    E: std::borrow::Borrow<Error>,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = std::result::Result<T, E>>,
+    W: FnMut(Duration) -> WFut,
+    WFut: Future<Output = ()>,
+{
+    let mut attempt = 0;
+    loop {
+        match operation().await {
+            Err(e) if attempt < max_retries => {
+                let error: &Error = e.borrow();
+                let Error::Http(message) = error else {
+                    return Err(e);
+                };
+                let delay_secs = backoff_delay_secs(base_delay_secs, attempt.into());
+                attempt += 1;
+                log::warn!("attempt {attempt} failed: {message}, retrying in {delay_secs} seconds");
+                wait(Duration::from_secs(delay_secs)).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Waits for the duration with a one-shot timer
+pub(crate) async fn wait_for(duration: Duration) {
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    ic_exports::ic_cdk_timers::set_timer(duration, move || {
+        let _ = sender.send(());
+    });
+
+    let _ = receiver.await;
+}
+
+/// Applies the transforms in order to a price with `decimals` decimals
+fn apply_transforms(price: U256, transforms: &[Transform], decimals: u8) -> Result<U256> {
+    transforms
+        .iter()
+        .try_fold(price, |price, transform| transform.apply(price, decimals))
+}
+
+/// Returns whether the price fetched from the origin is the last submitted one of
+/// an oracle suppressing the duplicate prices
+///
+/// A price submitted after a fallback is sent again, so that the contract leaves
+/// its fallback state.
+fn is_duplicate_price(metadata: &OracleMetadata, price: &U256, source: PriceSource) -> bool {
+    metadata.suppress_duplicates
+        && source == PriceSource::Origin
+        && !metadata.using_fallback
+        && metadata.last_submitted_price.as_ref() == Some(price)
+}
+
+/// Checks that the price moved by at most `max_bps` basis points from the last
+/// submitted price of an oracle
+///
+/// The first price of an oracle and the prices following a zero price are not checked.
+fn check_price_change(price: &U256, last_price: Option<&U256>, max_bps: u32) -> Result<()> {
+    let Some(last_price) = last_price.filter(|last_price| !last_price.0.is_zero()) else {
+        return Ok(());
+    };
+
+    let change = if price.0 > last_price.0 {
+        price.0 - last_price.0
+    } else {
+        last_price.0 - price.0
+    };
+    let change_bps = change.saturating_mul(10_000u64.into()) / last_price.0;
+    let change_bps = change_bps.min(u32::MAX.into()).low_u32();
+
+    if change_bps > max_bps {
+        return Err(Error::PriceChangeTooLarge {
+            price: price.clone(),
+            change_bps,
+            max_bps,
+        });
+    }
+
+    Ok(())
+}
+
+/// Submits the transaction of an oracle with `send`, unless its hash is the last
+/// one submitted by the oracle
+///
+/// The hash is stored before the transaction is submitted, so that an update of
+/// the oracle running at the same time detects the duplicate, and the previous
+/// hash is restored when the submission fails.
+async fn submit_once<F, Fut>(
+    oracle_storage: &OracleStorage,
+    user_address: &H160,
+    contract_address: &H160,
+    tx_hash: H256,
+    send: F,
+) -> Result<H256>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<H256>>,
+{
+    let previous = oracle_storage.swap_last_tx_hash(
+        user_address.clone(),
+        contract_address.clone(),
+        Some(tx_hash.clone()),
+    )?;
+    if previous.as_ref() == Some(&tx_hash) {
+        log::warn!(
+            "transaction {tx_hash} of oracle {contract_address} of user {user_address} already submitted, skipping it"
+        );
+        return Err(Error::DuplicateTransaction(tx_hash));
+    }
+
+    let result = send().await;
+    if result.is_err() {
+        if let Err(e) = oracle_storage.swap_last_tx_hash(
+            user_address.clone(),
+            contract_address.clone(),
+            previous,
+        ) {
+            log::debug!(
+                "failed to restore the last transaction hash: {:?}",
+                e.to_string()
+            );
+        }
+    }
+
+    result
+}
+
+/// Returns the stage of an error fetching the price of an origin
+///
+/// The errors reading the price from the origin response belong to the
+/// `DataParse` stage, the others to the `DataFetch` stage.
+fn fetch_error_stage(error: &Error) -> OracleStage {
+    match error {
+        Error::ParseError(_)
+        | Error::SchemaValidationFailed(_)
+        | Error::ResponseForbiddenField { .. }
+        | Error::AbiDecodeError(_) => OracleStage::DataParse,
+        _ => OracleStage::DataFetch,
+    }
+}
+
+/// Returns the number of decimals of the prices fetched from the origin
+///
+/// Only HTTP origins scale their prices, the default decimals are assumed for the
+/// other origins.
+fn origin_decimals(origin: &Origin) -> u8 {
+    match origin {
+        Origin::Http(HttpOrigin { decimals, .. }) => decimals.unwrap_or(DEFAULT_PRICE_DECIMALS),
+        Origin::Combined(CombinedOrigin { primary, .. }) => origin_decimals(primary),
+        Origin::Evm(_) => DEFAULT_PRICE_DECIMALS,
+    }
+}
+
+/// Checks that the providers of the origin have endpoints and that the destination is valid
+fn validate_providers(origin: &Origin, destination: &EvmDestination) -> Result<()> {
+    for origin in origin_sources(origin) {
+        if let Origin::Evm(EvmOrigin { provider, .. }) = origin {
+            provider.validate()?;
+        }
+    }
+
+    destination.validate()
+}
+
+/// Reminds the operators that the authentication headers and login bodies of the
+/// origin are stored in plaintext in stable memory
+fn warn_plaintext_auth(origin: &Origin) {
+    let has_auth = origin_sources(origin).into_iter().any(|source| {
+        matches!(source, Origin::Http(HttpOrigin { auth, session_cookie, .. })
+            if auth.is_some() || session_cookie.is_some())
+    });
+    if has_auth {
+        log::warn!("HTTP origin credentials are stored in plaintext in stable memory");
+    }
+}
+
+/// Returns the origin, or the primary and fallback of a combined origin
+fn origin_sources(origin: &Origin) -> Vec<&Origin> {
+    match origin {
+        Origin::Combined(CombinedOrigin {
+            primary, fallback, ..
+        }) => vec![primary, fallback],
+        origin => vec![origin],
+    }
+}
+
+/// Checks that the provider has at least one endpoint and that its endpoints
+/// are valid HTTPS URLs
+fn validate_provider_endpoints(provider: &Provider) -> Result<()> {
+    provider
+        .validate()
+        .map_err(|e| Error::InvalidDestination(e.to_string()))?;
+
+    for endpoint in &provider.endpoints {
+        let url = url::Url::parse(endpoint)
+            .map_err(|e| Error::InvalidDestination(format!("invalid endpoint {endpoint}: {e}")))?;
+
+        if url.scheme() != "https" {
+            return Err(Error::InvalidDestination(format!(
+                "endpoint {endpoint} is not an HTTPS URL"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that `oracle` cannot be reached from its `triggers`, walking the
+/// dependency graph up to `MAX_TRIGGER_DEPTH` levels
+fn check_circular_dependency(
+    oracle: &(H160, H160),
+    triggers: &[(H160, H160)],
+    get_triggers: impl Fn(&(H160, H160)) -> Vec<(H160, H160)>,
+) -> Result<()> {
+    let mut visited = BTreeSet::new();
+    let mut level = triggers.to_vec();
+
+    for _ in 0..MAX_TRIGGER_DEPTH {
+        if level.contains(oracle) {
+            return Err(Error::CircularDependency);
+        }
+
+        level = level
+            .into_iter()
+            .filter(|trigger| visited.insert(trigger.clone()))
+            .flat_map(|trigger| get_triggers(&trigger))
+            .collect();
+    }
+
+    Ok(())
+}
+
+/// Returns an edge from every oracle to each of its triggers
+fn dependency_edges(oracles: &[(H160, BTreeMap<H160, OracleMetadata>)]) -> Vec<DependencyEdge> {
+    oracles
+        .iter()
+        .flat_map(|(user_address, user_oracles)| {
+            user_oracles
+                .iter()
+                .flat_map(move |(contract_address, metadata)| {
+                    metadata
+                        .triggers
+                        .iter()
+                        .map(move |(to_user, to_contract)| DependencyEdge {
+                            from_user: user_address.clone(),
+                            from_contract: contract_address.clone(),
+                            to_user: to_user.clone(),
+                            to_contract: to_contract.clone(),
+                        })
+                })
+        })
+        .collect()
+}
+
+/// Returns the `(user_address, contract_address)` of the oracles in topological
+/// order with Kahn's algorithm, the oracles without dependencies ordered by address
+///
+/// The targets of the edges missing from `nodes` are added to the graph.
+fn dependency_order(
+    nodes: impl IntoIterator<Item = (H160, H160)>,
+    edges: &[DependencyEdge],
+) -> Result<Vec<(H160, H160)>> {
+    let mut in_degrees = nodes
+        .into_iter()
+        .map(|node| (node, 0))
+        .collect::<BTreeMap<_, usize>>();
+    let mut successors = BTreeMap::<_, Vec<_>>::new();
+    for edge in edges {
+        let from = (edge.from_user.clone(), edge.from_contract.clone());
+        let to = (edge.to_user.clone(), edge.to_contract.clone());
+        in_degrees.entry(from.clone()).or_default();
+        *in_degrees.entry(to.clone()).or_default() += 1;
+        successors.entry(from).or_default().push(to);
+    }
+
+    let mut ready = in_degrees
+        .iter()
+        .filter(|(_, in_degree)| **in_degree == 0)
+        .map(|(node, _)| node.clone())
+        .collect::<BTreeSet<_>>();
+    let mut order = Vec::with_capacity(in_degrees.len());
+
+    while let Some(node) = ready.pop_first() {
+        for successor in successors.get(&node).into_iter().flatten() {
+            let in_degree = in_degrees
+                .get_mut(successor)
+                .expect("every edge target is a node");
+            *in_degree -= 1;
+            if *in_degree == 0 {
+                ready.insert(successor.clone());
+            }
+        }
+        order.push(node);
+    }
+
+    // The nodes left with dependencies are part of a cycle
+    if order.len() < in_degrees.len() {
+        return Err(Error::CircularDependency);
+    }
+
+    Ok(order)
+}
+
+/// Checks that the max response bytes of the HTTP origins are within the allowed range
+/// and that combined origins are not nested
+fn validate_origin(origin: &Origin) -> Result<()> {
+    for source in origin_sources(origin) {
+        match source {
+            Origin::Http(HttpOrigin {
+                json_path,
+                json_paths,
+                forbidden_paths,
+                required_paths,
+                max_response_bytes,
+                enable_filter_expressions,
+                session_cookie,
+                valid_status_codes,
+                signing_config,
+                timeout_ms,
+                ..
+            }) => {
+                if let Some(max_response_bytes) = max_response_bytes {
+                    check_max_response_bytes(*max_response_bytes)?;
+                }
+                http::check_valid_status_codes(valid_status_codes)?;
+                if *enable_filter_expressions {
+                    for path in std::iter::once(json_path)
+                        .chain(json_paths)
+                        .chain(forbidden_paths)
+                        .chain(required_paths)
+                    {
+                        parser::check_filtered_path(path)?;
+                    }
+                }
+                if let Some(session_cookie) = session_cookie {
+                    session_cookie.validate()?;
+                }
+                if let Some(signing_config) = signing_config {
+                    signing_config.validate()?;
+                }
+                if *timeout_ms == Some(0) {
+                    return Err(Error::Internal(
+                        "the timeout must be greater than 0".to_string(),
+                    ));
+                }
+            }
+            Origin::Evm(EvmOrigin {
+                return_types,
+                return_value_index,
+                ..
+            }) => provider::check_return_types(return_types, *return_value_index)?,
+            Origin::Combined(_) => {
+                return Err(Error::Internal(
+                    "combined origins cannot be nested".to_string(),
+                ))
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn check_max_response_bytes(max_response_bytes: u64) -> Result<()> {
+    if !(MIN_RESPONSE_BYTES..=MAX_RESPONSE_BYTES).contains(&max_response_bytes) {
+        return Err(Error::Internal(format!(
+            "max response bytes must be between {MIN_RESPONSE_BYTES} and {MAX_RESPONSE_BYTES}"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Returns the seconds left before the first timer tick of a pending oracle, the
+/// initial delay of its restarted timer
+fn pending_delay_secs(status: &OracleStatus, first_tick_at: Option<u64>, now: u64) -> Option<u64> {
+    if *status != OracleStatus::Pending {
+        return None;
+    }
+
+    let remaining = first_tick_at.unwrap_or_default().saturating_sub(now);
+    Some(remaining.div_ceil(1_000_000_000))
+}
+
+/// Returns the startup delay of the oracle at `index` among `count` restored oracles,
+/// spreading them evenly over `max_jitter_secs`
+fn startup_delay(index: usize, count: usize, max_jitter_secs: u64) -> Option<u64> {
+    if max_jitter_secs == 0 || count == 0 {
+        return None;
+    }
+
+    Some(max_jitter_secs * index as u64 / count as u64)
+}
+
+/// Checks the length of the label and the description of an oracle
+fn check_label(label: Option<&str>, description: Option<&str>) -> Result<()> {
+    if label.is_some_and(|label| label.len() > MAX_LABEL_BYTES) {
+        return Err(Error::Internal(format!(
+            "label must be at most {MAX_LABEL_BYTES} bytes"
+        )));
+    }
+    if description.is_some_and(|description| description.len() > MAX_DESCRIPTION_BYTES) {
+        return Err(Error::Internal(format!(
+            "description must be at most {MAX_DESCRIPTION_BYTES} bytes"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Checks the number of tags of an oracle, their length and that they are unique
+fn check_tags(tags: &[String]) -> Result<()> {
+    if tags.len() > MAX_ORACLE_TAGS {
+        return Err(Error::Internal(format!(
+            "an oracle can have at most {MAX_ORACLE_TAGS} tags"
+        )));
+    }
+
+    for (index, tag) in tags.iter().enumerate() {
+        if tag.is_empty() || tag.chars().count() > MAX_TAG_CHARS {
+            return Err(Error::Internal(format!(
+                "tags must have between 1 and {MAX_TAG_CHARS} characters"
+            )));
+        }
+        if tags[..index].contains(tag) {
+            return Err(Error::Internal(format!("duplicate tag {tag}")));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the expiry time of an oracle is in the future
+fn check_expiry(expires_at: u64) -> Result<()> {
+    if expires_at <= ic::time() {
+        return Err(Error::Internal(
+            "expiry time must be in the future".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// inspect function to check whether the provided principal is anonymous
+fn check_anonymous_principal(principal: Principal) -> Result<()> {
+    if principal == Principal::anonymous() {
+        return Err(Error::Internal("Principal is anonymous".to_string()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use candid::Principal;
+    use futures::executor::block_on;
+    use ic_canister::{canister_call, Canister};
+    use ic_exports::ic_kit::mock_principals::{alice, bob};
+    use ic_exports::ic_kit::MockContext;
+
+    use super::*;
+    use crate::canister::Oracular;
+    use crate::constants::DEFAULT_MAX_CONCURRENT_UPDATES;
+    use crate::parser::ParseError;
+
+    pub fn oracular_principal_mock() -> Principal {
+        const MOCK_PRINCIPAL: &str = "sgymv-uiaaa-aaaaa-aaaia-cai";
+        Principal::from_text(MOCK_PRINCIPAL).expect("valid principal")
+    }
+
+    async fn init_canister<'a>() -> (Oracular, &'a mut MockContext) {
+        let ctx = MockContext::new().inject();
+
+        let mut canister = Oracular::from_principal(oracular_principal_mock());
+
+        canister_call!(
+            canister.init(InitData {
+                owner: Principal::management_canister(),
+                log_settings: None,
+            }),
+            ()
+        )
+        .await
+        .unwrap();
+
+        (canister, ctx)
+    }
+
+    #[tokio::test]
+    async fn test_set_owner_anonymous() {
+        let (mut canister, ctx) = init_canister().await;
+
+        let res = canister_call!(canister.set_owner(Principal::anonymous()), ())
+            .await
+            .unwrap();
+
+        assert!(res.is_err());
+
+        ctx.update_id(Principal::management_canister());
+
+        let res = canister_call!(canister.set_owner(alice()), ())
+            .await
+            .unwrap();
+
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_admin_management() {
+        let (mut canister, ctx) = init_canister().await;
+
+        // Only the owner can add admins
+        ctx.update_caller(alice());
+        let res = canister_call!(canister.add_admin(bob()), Result<()>)
+            .await
+            .unwrap();
+        assert!(res.is_err());
+
+        ctx.update_caller(Principal::management_canister());
+        canister_call!(canister.add_admin(bob()), Result<()>)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let admins = canister_call!(canister.list_admins(), Vec<Principal>)
+            .await
+            .unwrap();
+        assert_eq!(admins, vec![bob()]);
+
+        // Admins can access non-destructive operations but cannot manage admins
+        ctx.update_caller(bob());
+        let res = canister_call!(
+            canister.get_all_oracles(),
+            Result<Vec<(H160, BTreeMap<H160, OracleMetadata>)>>
+        )
+        .await
+        .unwrap();
+        assert!(res.is_ok());
+
+        let res = canister_call!(canister.add_admin(alice()), Result<()>)
+            .await
+            .unwrap();
+        assert!(res.is_err());
+
+        ctx.update_caller(Principal::management_canister());
+        canister_call!(canister.remove_admin(bob()), Result<()>)
+            .await
+            .unwrap()
+            .unwrap();
+
+        ctx.update_caller(bob());
+        let res = canister_call!(
+            canister.get_all_oracles(),
+            Result<Vec<(H160, BTreeMap<H160, OracleMetadata>)>>
+        )
+        .await
+        .unwrap();
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_set_max_concurrent_updates() {
+        let (mut canister, ctx) = init_canister().await;
+
+        let limit = canister_call!(canister.get_max_concurrent_updates(), u32)
+            .await
+            .unwrap();
+        assert_eq!(limit, DEFAULT_MAX_CONCURRENT_UPDATES);
+
+        // Only the owner can change the limit
+        ctx.update_caller(alice());
+        let res = canister_call!(canister.set_max_concurrent_updates(20), Result<()>)
+            .await
+            .unwrap();
+        assert!(res.is_err());
+
+        ctx.update_caller(Principal::management_canister());
+        let res = canister_call!(canister.set_max_concurrent_updates(0), Result<()>)
+            .await
+            .unwrap();
+        assert!(res.is_err());
+
+        canister_call!(canister.set_max_concurrent_updates(20), Result<()>)
+            .await
+            .unwrap()
+            .unwrap();
+        let limit = canister_call!(canister.get_max_concurrent_updates(), u32)
+            .await
+            .unwrap();
+        assert_eq!(limit, 20);
+    }
+
+    #[tokio::test]
+    async fn test_dead_letter_queue() {
+        let (mut canister, ctx) = init_canister().await;
+
+        let entry = DeadLetterEntry {
+            timestamp: 100,
+            user_address: H160::from_slice(&[1; 20]),
+            contract_address: H160::from_slice(&[2; 20]),
+            error: Error::PriceNotAvailable.to_string(),
+            price_attempted: None,
+            last_submitted_price: None,
+        };
+        canister.with_state(|state| state.dead_letter_queue().push(entry.clone()));
+
+        // Only the owner and the admins can read the queue
+        ctx.update_caller(alice());
+        let res = canister_call!(
+            canister.get_dead_letter_queue(10),
+            Result<Vec<(u64, DeadLetterEntry)>>
+        )
+        .await
+        .unwrap();
+        assert!(res.is_err());
+
+        let res = canister_call!(canister.clear_dead_letter_queue(), Result<u64>)
+            .await
+            .unwrap();
+        assert!(res.is_err());
+
+        ctx.update_caller(Principal::management_canister());
+        let entries = canister_call!(
+            canister.get_dead_letter_queue(10),
+            Result<Vec<(u64, DeadLetterEntry)>>
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(entries, vec![(0, entry)]);
+
+        let cleared = canister_call!(canister.clear_dead_letter_queue(), Result<u64>)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(cleared, 1);
+    }
+
+    #[tokio::test]
+    async fn test_update_guard_isolates_panics() {
+        let (canister, _ctx) = init_canister().await;
+        let context = get_base_context(&canister.context.0);
+
+        let user_address = H160::from_slice(&[1; 20]);
+        let origin = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
+        });
+        let panicking = H160::from_slice(&[2; 20]);
+        let sibling = H160::from_slice(&[3; 20]);
+        for contract in [&panicking, &sibling] {
+            let destination = EvmDestination {
+                contract: contract.clone(),
+                provider: Provider {
+                    chain_id: 1,
+                    endpoints: vec![String::from("https://example.com")],
+                },
+                chain_id: None,
+                extra_destinations: vec![],
+                value_wei: None,
+            };
+            canister.with_state(|state| {
+                state.oracle_storage().add_oracle(
+                    user_address.clone(),
+                    origin.clone(),
+                    60,
+                    TimerId::default(),
+                    destination,
+                )
+            });
+        }
+
+        let try_acquire = || {
+            canister.with_state(|state| {
+                state
+                    .active_updates()
+                    .try_acquire(DEFAULT_MAX_CONCURRENT_UPDATES)
+            })
+        };
+        let get_oracle = |contract: &H160| {
+            canister
+                .with_state(|state| {
+                    state
+                        .oracle_storage()
+                        .get_oracle_by_address(user_address.clone(), contract.clone())
+                })
+                .unwrap()
+        };
+
+        // An update panicking on a malformed ABI encoding
+        assert!(try_acquire());
+        let guard = UpdateGuard::new(context.clone(), user_address.clone(), panicking.clone());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+            let _guard = guard;
+            ethers_core::abi::decode(&[ethers_core::abi::ParamType::Int(256)], &[1, 2, 3]).unwrap()
+        }));
+        assert!(result.is_err());
+
+        let metadata = get_oracle(&panicking);
+        assert_eq!(metadata.panic_count, 1);
+        assert_eq!(metadata.total_errors, 1);
+        assert_eq!(
+            metadata.last_error.map(|error| error.inner),
+            Some(Error::Internal("the update panicked".to_string()))
+        );
+        assert_eq!(
+            canister.with_state(|state| state.active_updates().count()),
+            0
+        );
+
+        // The sibling oracle is still updated on the next tick
+        assert!(try_acquire());
+        UpdateGuard::new(context, user_address.clone(), sibling.clone()).finish();
+
+        let metadata = get_oracle(&sibling);
+        assert_eq!(metadata.panic_count, 0);
+        assert_eq!(metadata.total_errors, 0);
+        assert_eq!(
+            canister.with_state(|state| state.active_updates().count()),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_submit_once() {
+        let (canister, _ctx) = init_canister().await;
+
+        let user_address = H160::from_slice(&[1; 20]);
+        let contract_address = H160::from_slice(&[2; 20]);
+        let origin = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
+        });
+        let destination = EvmDestination {
+            contract: contract_address.clone(),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+        let oracle_storage = canister.with_state(|state| {
+            let oracle_storage = state.oracle_storage();
+            oracle_storage.add_oracle(
+                user_address.clone(),
+                origin,
+                60,
+                TimerId::default(),
+                destination,
+            );
+            oracle_storage.clone()
+        });
+
+        let calls = std::cell::Cell::new(0);
+        let submit = |tx_hash: H256, result: Result<()>| {
+            block_on(submit_once(
+                &oracle_storage,
+                &user_address,
+                &contract_address,
+                tx_hash.clone(),
+                || async {
+                    calls.set(calls.get() + 1);
+                    result.map(|_| tx_hash)
+                },
+            ))
+        };
+        let tx_hash = H256::from_slice(&[5; 32]);
+        let other_tx_hash = H256::from_slice(&[6; 32]);
+
+        // The same transaction is submitted once
+        assert_eq!(submit(tx_hash.clone(), Ok(())), Ok(tx_hash.clone()));
+        assert_eq!(
+            submit(tx_hash.clone(), Ok(())),
+            Err(Error::DuplicateTransaction(tx_hash.clone()))
+        );
+        assert_eq!(calls.get(), 1);
+
+        // A failed submission keeps the last submitted hash
+        let error = Error::JsonRpcError(String::from("nonce too low"));
+        assert_eq!(
+            submit(other_tx_hash.clone(), Err(error.clone())),
+            Err(error)
+        );
+        assert_eq!(
+            submit(tx_hash.clone(), Ok(())),
+            Err(Error::DuplicateTransaction(tx_hash.clone()))
+        );
+        assert_eq!(
+            submit(other_tx_hash.clone(), Ok(())),
+            Ok(other_tx_hash.clone())
+        );
+        assert_eq!(calls.get(), 3);
+
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address, contract_address)
+            .unwrap();
+        assert_eq!(metadata.last_tx_hash, Some(other_tx_hash));
+    }
+
+    #[tokio::test]
+    async fn test_estimate_oracle_daily_cost() {
+        let (canister, _ctx) = init_canister().await;
+
+        let origin = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
+        });
+
+        let destination = EvmDestination {
+            contract: H160::from_slice(&[2; 20]),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+
+        let update_cost = estimate_update_cost(&origin, &destination);
+        assert!(update_cost > http::get_request_costs("https://example.com", 0, 8000));
+
+        let daily_cost = canister_call!(
+            canister.estimate_oracle_daily_cost(origin.clone(), destination.clone(), 60),
+            u128
+        )
+        .await
+        .unwrap();
+        assert_eq!(daily_cost, update_cost * 1440);
+
+        // A longer URL costs more
+        let long_origin = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com/a/very/long/path/to/the/price"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
+        });
+        let long_daily_cost = canister_call!(
+            canister.estimate_oracle_daily_cost(long_origin, destination, 60),
+            u128
+        )
+        .await
+        .unwrap();
+        assert!(long_daily_cost > daily_cost);
+    }
+
+    #[test]
+    fn test_recover_pub_key_with_correct_payload() {
+        let message = "Testing".to_string();
+        let signature =
+        "0x4bce59ed739b43e739f304cb790cacde57b800aa712dde352cc8aa4f4727979d3849a8c52f59c34083f5060b4f1630ad7d34902a68ae216431332f27b830953b1b".to_string();
+
+        let expected_address =
+            H160::from_hex_str("0xE757Bd3f57C51D2068742d0CEA6f49D38d567310").unwrap();
+
+        let address = Oracular::recover_pubkey(message, signature).unwrap();
+
+        assert_eq!(address, expected_address);
+    }
+
+    #[test]
+    fn test_recover_pub_key_with_incorrect_payload() {
+        let message = "Testing 123".to_string();
+        let signature =
+        "0x4bce59ed739b43e739f304cb790cacde57b800aa712dde352cc8aa4f4727979d3849a8c52f59c34083f5060b4f1630ad7d34902a68ae216431332f27b830953b1b".to_string();
+
+        let expected_address =
+            H160::from_hex_str("0xE757Bd3f57C51D2068742d0CEA6f49D38d567310").unwrap();
+
+        let address = Oracular::recover_pubkey(message, signature).unwrap();
+
+        assert_ne!(address, expected_address);
+    }
+
+    #[test]
+    fn test_verify_signature_proof() {
+        let proof = SignatureProof {
+            nonce: 1,
+            expires_at: 2_000_000_000,
+            signature: "0x4bce59ed739b43e739f304cb790cacde57b800aa712dde352cc8aa4f4727979d3849a8c52f59c34083f5060b4f1630ad7d34902a68ae216431332f27b830953b1b".to_string(),
+        };
+
+        let signer = H160::from_hex_str("0xE757Bd3f57C51D2068742d0CEA6f49D38d567310").unwrap();
+
+        assert!(proof.verify("Testing".to_string(), &signer).is_ok());
+        assert!(proof
+            .verify("Testing".to_string(), &H160::from_slice(&[1; 20]))
+            .is_err());
+        assert!(proof.verify("Other".to_string(), &signer).is_err());
+
+        assert!(proof.check_expiry(1_000_000_000).is_ok());
+        assert!(proof.check_expiry(2_000_000_000).is_err());
+        assert!(SignatureProof {
+            expires_at: (MAX_SIGNATURE_PROOF_TTL_SECS + 1) * 1_000_000_000,
+            ..proof
+        }
+        .check_expiry(0)
+        .is_err());
+    }
+
+    #[test]
+    fn test_transfer_message() {
+        let message = transfer_message(
+            Principal::management_canister(),
+            &H160::from_slice(&[1; 20]),
+            &H160::from_slice(&[2; 20]),
+            &H160::from_slice(&[3; 20]),
+            7,
+            100,
+        );
+
+        assert_eq!(
+            message,
+            format!(
+                "transfer:aaaaa-aa:0x{}:0x{}:0x{}:7:100",
+                "01".repeat(20),
+                "02".repeat(20),
+                "03".repeat(20)
+            )
+        );
+    }
+
+    #[test]
+    fn test_validate_evm_destination() {
+        let mut destination = EvmDestination {
+            contract: H160::from_slice(&[1; 20]),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+        assert!(destination.validate().is_ok());
+
+        destination.provider.endpoints = vec![String::from("http://example.com")];
+        assert!(matches!(
+            destination.validate(),
+            Err(Error::InvalidDestination(_))
+        ));
+
+        destination.provider.endpoints = vec![String::from("not a url")];
+        assert!(matches!(
+            destination.validate(),
+            Err(Error::InvalidDestination(_))
+        ));
+
+        destination.provider.endpoints = vec![String::from("https://example.com")];
+        destination.contract = H160::from_slice(&[0; 20]);
+        assert_eq!(
+            destination.validate(),
+            Err(Error::InvalidDestination(
+                "contract address cannot be zero".to_string()
+            ))
+        );
+
+        destination.contract = H160::from_slice(&[1; 20]);
+        let extra_destination = |chain_id| SingleEvmDestination {
+            contract: H160::from_slice(&[2; 20]),
+            provider: Provider {
+                chain_id,
+                endpoints: vec![String::from("https://other.example.com")],
+            },
+        };
+        destination.extra_destinations = vec![extra_destination(137), extra_destination(8453)];
+        assert!(destination.validate().is_ok());
+        assert_eq!(
+            destination
+                .destinations()
+                .iter()
+                .map(|destination| destination.provider.chain_id)
+                .collect::<Vec<_>>(),
+            vec![1, 137, 8453]
+        );
+
+        destination.extra_destinations = vec![extra_destination(137), extra_destination(1)];
+        assert_eq!(
+            destination.validate(),
+            Err(Error::InvalidDestination(
+                "more than one destination on chain 1".to_string()
+            ))
+        );
+
+        let mut invalid_destination = extra_destination(137);
+        invalid_destination.provider.endpoints = vec![String::from("http://example.com")];
+        destination.extra_destinations = vec![invalid_destination];
+        assert!(matches!(
+            destination.validate(),
+            Err(Error::InvalidDestination(_))
+        ));
+    }
+
+    #[test]
+    fn test_oracle_state_snapshot_round_trip() {
+        use crate::provider::{AbiToken, CustomUpdateFn, FunctionRef};
+        use crate::state::oracle_storage::{CircuitBreakerConfig, OutlierConfig, PriceBounds};
+
+        let user_address = H160::from_slice(&[1; 20]);
+        let provider = |chain_id| Provider {
+            chain_id,
+            endpoints: vec![String::from("https://example.com")],
+        };
+        let origin = Origin::Combined(CombinedOrigin {
+            primary: Box::new(Origin::Http(HttpOrigin {
+                url: String::from("https://example.com/price"),
+                json_path: String::from("data.prices"),
+                json_paths: vec![],
+                forbidden_paths: vec![],
+                required_paths: vec![],
+                decimals: Some(6),
+                max_response_bytes: Some(4_096),
+                parse_mode: Some(PriceParseMode::String),
+                auth: Some(HttpAuth::ApiKey {
+                    header_name: String::from("X-API-KEY"),
+                    key: String::from("secret"),
+                }),
+                enable_filter_expressions: true,
+                skip_cache: true,
+                json_aggregate: Some(AggregateOp::Median),
+                session_cookie: Some(CookieConfig {
+                    login_url: String::from("https://example.com/login"),
+                    login_body: String::from("{}"),
+                    cookie_header_name: String::from("session"),
+                    cookie_ttl_secs: 600,
+                }),
+                valid_status_codes: vec![200, 202],
+                signing_config: Some(HmacSigningConfig {
+                    secret_key_id: 7,
+                    algorithm: crate::http::HmacAlgorithm::Sha256,
+                    header_name: String::from("X-Signature"),
+                }),
+                timeout_ms: None,
+            })),
+            fallback: Box::new(Origin::Evm(EvmOrigin {
+                provider: provider(1),
+                target_address: H160::from_slice(&[3; 20]),
+                method: String::from("latestRoundData"),
+                return_types: vec![String::from("uint80"), String::from("int256")],
+                return_value_index: 1,
+            })),
+            use_fallback_on_error: true,
+        });
+        let destination = EvmDestination {
+            contract: H160::from_slice(&[2; 20]),
+            provider: provider(1),
+            chain_id: Some(1),
+            extra_destinations: vec![SingleEvmDestination {
+                contract: H160::from_slice(&[4; 20]),
+                provider: provider(137),
+            }],
+            value_wei: None,
+        };
+
+        let mut metadata = StorableOracleMetadata::new(
+            user_address.clone(),
+            origin,
+            60,
+            TimerId::default(),
+            destination,
+        );
+        metadata.status = OracleStatus::CircuitOpen {
+            since: 10,
+            resets_at: 20,
+        };
+        metadata.expires_at = Some(1_000);
+        metadata.first_tick_at = Some(500);
+        metadata.last_price = Some(U256::from(42u64));
+        metadata.last_submitted_price = Some(U256::from(42u64));
+        metadata.last_tx_status = Some(TxStatus {
+            hash: H256::from_slice(&[5; 32]),
+            status: TxReceiptStatus::Success,
+            replaced: Some(H256::from_slice(&[6; 32])),
+            confirmed_block: Some(ConfirmedBlock {
+                number: U256::from(100u64),
+                hash: H256::from_slice(&[7; 32]),
+                finalized: false,
+            }),
+        });
+        metadata.cycle_budget = Some(1_000_000);
+        metadata.resume_threshold_cycles = Some(10_000);
+        metadata.cycles_per_call_history = [1_000, 2_000].into();
+        metadata.last_attestation = Some(SignedOracleData {
+            price: U256::from(42u64),
+            timestamp: 30,
+            signature: Signature {
+                r: 1u64.into(),
+                s: 2u64.into(),
+                v: 27,
+            }
+            .into(),
+        });
+        metadata.last_error = Some(PipelineError::new(
+            OracleStage::DataFetch,
+            Error::Http(String::from("origin is down")),
+        ));
+        metadata.panic_count = 1;
+        metadata.last_tx_hash = Some(H256::from_slice(&[5; 32]));
+        metadata.last_skip_reason = Some(OracleSkipReason::GasPriceTooHigh {
+            current_gwei: 200,
+            max_gwei: 100,
+        });
+        metadata.triggers = vec![(user_address.clone(), H160::from_slice(&[8; 20]))];
+        metadata.price_bounds = Some(PriceBounds {
+            min_price: U256::from(1u64),
+            max_price: U256::from(1_000u64),
+        });
+        metadata.max_gas_price_gwei = Some(100);
+        metadata.min_eth_balance_check = Some(U256::from(1_000_000u64));
+        metadata.evm.value_wei = Some(U256::from(1_000u64));
+        metadata.last_simulated_result = Some(SimulationResult {
+            price: U256::from(42u64),
+            return_data: String::from("0x"),
+            estimated_gas: U256::from(21_000u64),
+            simulated_at: 40,
+        });
+        metadata.last_broadcast_results = vec![DestinationResult {
+            contract: H160::from_slice(&[4; 20]),
+            chain_id: 137,
+            result: TxResult::Failed(String::from("nonce too low")),
+        }];
+        metadata.custom_update_fn = Some(CustomUpdateFn {
+            function: FunctionRef::Registered(String::from("setPrice")),
+            price_param_index: 1,
+            extra_params: vec![AbiToken::FixedBytes32(vec![9; 32])],
+        });
+        metadata.circuit_breaker = Some(CircuitBreakerConfig {
+            failure_threshold: 3,
+            reset_after_secs: 60,
+        });
+        metadata.label = Some(String::from("BTC/USD"));
+        metadata.description = Some(String::from("Median BTC price"));
+        metadata.tags = vec![String::from("crypto")];
+        metadata.max_executions = Some(10);
+        metadata.fallback_price = Some(U256::from(40u64));
+        metadata.transforms = vec![
+            Transform::Multiply(U256::from(10u64)),
+            Transform::Clamp {
+                min: U256::from(1u64),
+                max: U256::from(1_000u64),
+            },
+        ];
+        metadata.tx_deadline_secs = Some(300);
+        metadata.outlier_config = Some(OutlierConfig {
+            min_history: 4,
+            iqr_multiplier: 1.5,
+        });
+        metadata.max_change_rate_bps = Some(500);
+        metadata.price_history = [U256::from(41u64), U256::from(42u64)].into();
+        metadata.max_history_entries = Some(16);
+        metadata.derivation_suffix = vec![b"btc-usd".to_vec()];
+        metadata.eip712_domain = Eip712DomainConfig {
+            name: String::from("PriceFeed"),
+            version: String::from("2"),
+        };
+        metadata.response_validators = vec![ResponseSchema::RequireStatus(String::from("ok"))];
+        metadata.events = [OracleEvent {
+            timestamp: 50,
+            user_address: user_address.clone(),
+            contract_address: H160::from_slice(&[2; 20]),
+            event_type: OracleEventType::Created,
+            details: Some(String::from("created")),
+        }]
+        .into();
+
+        let mut settings = Settings::new(Principal::management_canister());
+        settings.admins = Some(vec![Principal::anonymous()]);
+        settings.url_allowlist = Some(vec![String::from("https://example.com")]);
+        settings.oracle_creation_rate_limit = Some(RateLimit { max_per_hour: 5 });
+        settings.signer_config = Some(OracleSignerConfig::default());
+        settings.skip_chain_id_validation = Some(true);
+        settings.max_concurrent_updates = Some(3);
+        settings.derivation_prefix = Some(vec![b"staging".to_vec()]);
+        settings.stable_memory_alert_pages = Some(1_000);
+
+        let state = OracleState {
+            oracles: vec![metadata],
+            settings,
+        };
+        let snapshot = OracleStateSnapshot::new(&state);
+        assert_eq!(snapshot.schema_version, ORACLE_SNAPSHOT_SCHEMA_VERSION);
+
+        let decoded = snapshot.decode().unwrap();
+        assert_eq!(format!("{decoded:?}"), format!("{state:?}"));
+
+        let incompatible = OracleStateSnapshot {
+            schema_version: ORACLE_SNAPSHOT_SCHEMA_VERSION + 1,
+            ..snapshot.clone()
+        };
+        assert_eq!(
+            incompatible.decode().unwrap_err(),
+            Error::UnsupportedSnapshotVersion {
+                version: ORACLE_SNAPSHOT_SCHEMA_VERSION + 1,
+                supported: ORACLE_SNAPSHOT_SCHEMA_VERSION,
+            }
+        );
+
+        let corrupted = OracleStateSnapshot {
+            data: snapshot.data[..snapshot.data.len() / 2].to_vec(),
+            ..snapshot
+        };
+        assert!(corrupted.decode().is_err());
+    }
+
+    #[test]
+    fn test_pending_delay_secs() {
+        let now = 10_000_000_000;
+
+        assert_eq!(
+            pending_delay_secs(&OracleStatus::Pending, Some(now + 2_500_000_000), now),
+            Some(3)
+        );
+        // The first tick is overdue
+        assert_eq!(
+            pending_delay_secs(&OracleStatus::Pending, Some(now - 1), now),
+            Some(0)
+        );
+        assert_eq!(
+            pending_delay_secs(&OracleStatus::Active, Some(now + 2_500_000_000), now),
+            None
+        );
+    }
+
+    #[test]
+    fn test_startup_delay_staggers_timers() {
+        let delays = (0..10)
+            .map(|index| startup_delay(index, 10, 60).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(delays, vec![0, 6, 12, 18, 24, 30, 36, 42, 48, 54]);
+        assert!(delays.iter().all(|delay| *delay < 60));
+
+        // No jitter starts every timer right away
+        assert_eq!(startup_delay(3, 10, 0), None);
+    }
+
+    #[test]
+    fn test_validate_origin_max_response_bytes() {
+        let origin = |max_response_bytes| {
+            Origin::Http(HttpOrigin {
+                url: String::from("https://example.com"),
+                json_path: String::from("data"),
+                json_paths: vec![],
+                forbidden_paths: vec![],
+                required_paths: vec![],
+                decimals: None,
+                max_response_bytes,
+                parse_mode: None,
+                auth: None,
+                enable_filter_expressions: false,
+                skip_cache: false,
+                json_aggregate: None,
+                session_cookie: None,
+                valid_status_codes: vec![200],
+                signing_config: None,
+                timeout_ms: None,
+            })
+        };
+
+        assert!(validate_origin(&origin(None)).is_ok());
+        assert!(validate_origin(&origin(Some(MIN_RESPONSE_BYTES))).is_ok());
+        assert!(validate_origin(&origin(Some(MAX_RESPONSE_BYTES))).is_ok());
+        assert!(validate_origin(&origin(Some(MIN_RESPONSE_BYTES - 1))).is_err());
+        assert!(validate_origin(&origin(Some(MAX_RESPONSE_BYTES + 1))).is_err());
+
+        // The response size cap is reflected in the estimated cost
+        let destination = EvmDestination {
+            contract: H160::from_slice(&[1; 20]),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+        assert!(
+            estimate_update_cost(&origin(Some(MAX_RESPONSE_BYTES)), &destination)
+                > estimate_update_cost(&origin(None), &destination)
+        );
+    }
+
+    #[test]
+    fn test_check_circular_dependency() {
+        let oracle = |n: u8| (H160::from_slice(&[1; 20]), H160::from_slice(&[n; 20]));
+        // 2 -> 3 -> 4
+        let graph = BTreeMap::from([(oracle(2), vec![oracle(3)]), (oracle(3), vec![oracle(4)])]);
+        let get_triggers = |trigger: &(H160, H160)| graph.get(trigger).cloned().unwrap_or_default();
+
+        assert!(check_circular_dependency(&oracle(1), &[oracle(2)], get_triggers).is_ok());
+        assert_eq!(
+            check_circular_dependency(&oracle(1), &[oracle(1)], get_triggers),
+            Err(Error::CircularDependency)
+        );
+        assert_eq!(
+            check_circular_dependency(&oracle(4), &[oracle(2)], get_triggers),
+            Err(Error::CircularDependency)
+        );
+
+        // A chain longer than the max depth is not walked to the end
+        let chain = (10..10 + MAX_TRIGGER_DEPTH as u8)
+            .map(|n| (oracle(n), vec![oracle(n + 1)]))
+            .collect::<BTreeMap<_, _>>();
+        let get_triggers = |trigger: &(H160, H160)| chain.get(trigger).cloned().unwrap_or_default();
+        let last = oracle(10 + MAX_TRIGGER_DEPTH as u8);
+        assert!(check_circular_dependency(&last, &[oracle(10)], get_triggers).is_ok());
+        assert_eq!(
+            check_circular_dependency(&last, &[oracle(11)], get_triggers),
+            Err(Error::CircularDependency)
+        );
+    }
+
+    #[test]
+    fn test_dependency_order() {
+        let oracle = |n: u8| (H160::from_slice(&[1; 20]), H160::from_slice(&[n; 20]));
+        let edge = |from: u8, to: u8| DependencyEdge {
+            from_user: oracle(from).0,
+            from_contract: oracle(from).1,
+            to_user: oracle(to).0,
+            to_contract: oracle(to).1,
+        };
+
+        // 4 -> 3 -> 2, 4 -> 2 and 1 without triggers
+        let edges = [edge(4, 3), edge(3, 2), edge(4, 2)];
+        assert_eq!(
+            dependency_order((1..=4).map(oracle), &edges),
+            Ok(vec![oracle(1), oracle(4), oracle(3), oracle(2)])
+        );
+        assert_eq!(
+            dependency_order([], &edges),
+            Ok(vec![oracle(4), oracle(3), oracle(2)])
+        );
+        assert_eq!(dependency_order([], &[]), Ok(vec![]));
+
+        // 2 -> 4 closes the cycle 4 -> 3 -> 2 -> 4
+        let edges = [edge(4, 3), edge(3, 2), edge(2, 4)];
+        assert_eq!(
+            dependency_order((1..=4).map(oracle), &edges),
+            Err(Error::CircularDependency)
+        );
+        assert_eq!(
+            dependency_order([], &[edge(1, 1)]),
+            Err(Error::CircularDependency)
+        );
+    }
+
+    #[test]
+    fn test_fetch_combined_price() {
+        let http_origin = |url: &str| {
+            Box::new(Origin::Http(HttpOrigin {
+                url: url.to_string(),
+                json_path: String::from("data"),
+                json_paths: vec![],
+                forbidden_paths: vec![],
+                required_paths: vec![],
+                decimals: None,
+                max_response_bytes: None,
+                parse_mode: None,
+                auth: None,
+                enable_filter_expressions: false,
+                skip_cache: false,
+                json_aggregate: None,
+                session_cookie: None,
+                valid_status_codes: vec![200],
+                signing_config: None,
+                timeout_ms: None,
+            }))
+        };
+        let combined = |use_fallback_on_error| CombinedOrigin {
+            primary: http_origin("https://primary.example.com"),
+            fallback: http_origin("https://fallback.example.com"),
+            use_fallback_on_error,
+        };
+        let fetch = |origin: Origin| async move {
+            match origin {
+                Origin::Http(HttpOrigin { url, .. }) if url == "https://fallback.example.com" => {
+                    Ok(U256::from(42u64))
+                }
+                _ => Err(Error::Http("primary is down".to_string())),
+            }
+        };
+
+        // The fallback price is submitted when the primary fails
+        assert_eq!(
+            block_on(fetch_combined_price(combined(true), fetch)),
+            Ok((U256::from(42u64), true))
+        );
+        assert_eq!(
+            block_on(fetch_combined_price(combined(false), fetch)),
+            Err(Error::Http("primary is down".to_string()))
+        );
 
-        // Save the metadata
-        self.with_state_mut(|state| {
-            state.mut_oracle_storage().add_oracle(
-                user_address,
-                origin,
-                timestamp,
-                timer_id,
-                destination,
-            )
+        // Errors other than HTTP errors do not use the fallback
+        let fetch = |_| async { Err(Error::PriceNotAvailable) };
+        assert_eq!(
+            block_on(fetch_combined_price(combined(true), fetch)),
+            Err(Error::PriceNotAvailable)
+        );
+
+        let fetch = |_| async { Ok(U256::from(7u64)) };
+        assert_eq!(
+            block_on(fetch_combined_price(combined(true), fetch)),
+            Ok((U256::from(7u64), false))
+        );
+    }
+
+    #[test]
+    fn test_fetch_origin_price_with_fallback_price() {
+        let origin = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
         });
+        let fallback_price = Some(U256::from(100u64));
 
-        log::debug!("oracle created successfully ");
+        // The fallback price is submitted when the origin fails
+        let fetch = |_| async { Err(Error::Http("origin is down".to_string())) };
+        assert_eq!(
+            block_on(fetch_origin_price(
+                origin.clone(),
+                fallback_price.clone(),
+                fetch
+            )),
+            Ok((U256::from(100u64), PriceSource::FallbackPrice))
+        );
+        assert_eq!(
+            block_on(fetch_origin_price(origin.clone(), None, fetch)),
+            Err(Error::Http("origin is down".to_string()))
+        );
 
-        Ok(())
-    }
+        let fetch = |_| async {
+            Err(Error::ParseError(ParseError::InvalidPrice(
+                "not a number".to_string(),
+            )))
+        };
+        assert_eq!(
+            block_on(fetch_origin_price(
+                origin.clone(),
+                fallback_price.clone(),
+                fetch
+            )),
+            Ok((U256::from(100u64), PriceSource::FallbackPrice))
+        );
 
-    /// Initializes the timer that will be used to update the price
-    pub async fn init_price_timer(
-        context: Rc<RefCell<dyn Context>>,
-        user_address: H160,
-        timestamp: u64,
-        origin: Origin,
-        evm: EvmDestination,
-    ) -> Result<TimerId> {
-        let timer_id = ic_exports::ic_cdk_timers::set_timer_interval(
-            Duration::from_secs(timestamp),
-            move || {
-                let future = Self::send_transaction(
-                    origin.clone(),
-                    user_address.0.into(),
-                    evm.clone(),
-                    context.clone(),
-                )
-                .unwrap_or_else(|e| {
-                    log::error!("failed to send transaction: {:?}", e.to_string());
-                });
+        // Other errors are not replaced by the fallback price
+        let fetch = |_| async { Err(Error::PriceNotAvailable) };
+        assert_eq!(
+            block_on(fetch_origin_price(
+                origin.clone(),
+                fallback_price.clone(),
+                fetch
+            )),
+            Err(Error::PriceNotAvailable)
+        );
 
-                ic_cdk::spawn(future);
-            },
+        let fetch = |_| async { Ok(U256::from(7u64)) };
+        assert_eq!(
+            block_on(fetch_origin_price(origin, fallback_price, fetch)),
+            Ok((U256::from(7u64), PriceSource::Origin))
         );
+    }
+
+    #[test]
+    fn test_validate_combined_origin() {
+        let http_origin = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
+        });
+        let combined = Origin::Combined(CombinedOrigin {
+            primary: Box::new(http_origin.clone()),
+            fallback: Box::new(http_origin),
+            use_fallback_on_error: true,
+        });
+        assert!(validate_origin(&combined).is_ok());
 
-        Ok(timer_id)
+        let nested = Origin::Combined(CombinedOrigin {
+            primary: Box::new(combined.clone()),
+            fallback: Box::new(combined),
+            use_fallback_on_error: true,
+        });
+        assert!(validate_origin(&nested).is_err());
     }
 
-    /// Sends a transaction to the EVM
-    async fn send_transaction(
-        origin: Origin,
-        user_address: H160,
-        evm_destination: EvmDestination,
-        context: Rc<RefCell<dyn Context>>,
-    ) -> Result<()> {
-        log::debug!(
-            "Updating oracle price: user_address :{} origin: {:?} evm_destination: {:?} ",
-            user_address,
-            origin,
-            evm_destination
+    #[test]
+    fn test_consumed_cycles() {
+        assert_eq!(
+            consumed_cycles(1_000_000, 400_000),
+            600_000 - BALANCE_MEASUREMENT_CYCLES
         );
+        // Cycles received during the update do not underflow
+        assert_eq!(consumed_cycles(1_000_000, 2_000_000), 0);
+        assert_eq!(consumed_cycles(1_000_000, 1_000_000 - 10), 0);
+    }
 
-        let response = match origin {
-            Origin::Evm(EvmOrigin {
-                ref provider,
-                ref target_address,
-                ref method,
-            }) => {
-                let data = provider::function_selector(method, &[]).encode_input(&[])?;
+    #[test]
+    fn test_is_local_endpoint() {
+        assert!(is_local_endpoint("http://localhost:8545"));
+        assert!(is_local_endpoint("https://127.0.0.1:8545"));
+        assert!(!is_local_endpoint("https://mainnet.example.com"));
+        assert!(!is_local_endpoint("https://mainnet.example.com/localhost"));
+        assert!(!is_local_endpoint("https://127.0.0.1.example.com"));
+    }
 
-                let data_hex = did::Bytes::from(data).to_hex_str();
-                let params = serde_json::json!([{
-                    "to": target_address,
-                    "data": data_hex,
-                }]);
+    #[test]
+    fn test_retry_on_http_error() {
+        // Runs an operation failing `failures` times and returns the result, the
+        // number of attempts and the waited delays
+        let run = |max_retries: u8, failures: u32, error: fn(String) -> Error| {
+            let attempts = std::cell::Cell::new(0u32);
+            let delays = RefCell::new(vec![]);
 
-                let res =
-                    http::call_jsonrpc(&provider.hostname, "eth_call", params, Some(80000)).await?;
+            let result = block_on(retry_on_http_error(
+                max_retries,
+                5,
+                || {
+                    attempts.set(attempts.get() + 1);
+                    let attempt = attempts.get();
+                    async move {
+                        if attempt <= failures {
+                            Err(error(format!("attempt {attempt} failed")))
+                        } else {
+                            Ok(attempt)
+                        }
+                    }
+                },
+                |delay| {
+                    delays.borrow_mut().push(delay);
+                    async {}
+                },
+            ));
 
-                serde_json::from_value::<U256>(res)?
-            }
-            Origin::Http(HttpOrigin {
-                ref url,
-                ref json_path,
-            }) => http::get_price(url, json_path).await?,
+            (result, attempts.get(), delays.into_inner())
         };
 
-        let (hostname, chain_id) = (
-            evm_destination.provider.hostname,
-            evm_destination.provider.chain_id,
+        // The operation eventually succeeds after two HTTP failures
+        assert_eq!(
+            run(3, 2, Error::Http),
+            (
+                Ok(3),
+                3,
+                vec![Duration::from_secs(5), Duration::from_secs(10)]
+            )
         );
 
-        let data = UPDATE_PRICE.encode_input(&[ethabi::Token::Int(response.into())])?;
+        // No retry by default
+        assert_eq!(
+            run(0, 2, Error::Http),
+            (Err(Error::Http("attempt 1 failed".to_string())), 1, vec![])
+        );
 
-        let provider = Provider {
-            chain_id,
-            hostname: hostname.to_owned(),
-        };
+        // The last error is returned once the retries are exhausted
+        assert_eq!(
+            run(3, 5, Error::Http),
+            (
+                Err(Error::Http("attempt 4 failed".to_string())),
+                4,
+                vec![
+                    Duration::from_secs(5),
+                    Duration::from_secs(10),
+                    Duration::from_secs(20)
+                ]
+            )
+        );
 
-        let transaction = get_transaction(
-            user_address,
-            provider,
-            Some(evm_destination.contract.0.into()),
-            U256::zero(),
-            data,
-            &context,
-        )
-        .await?;
+        // Other errors are not retried
+        assert_eq!(
+            run(3, 2, Error::Internal),
+            (
+                Err(Error::Internal("attempt 1 failed".to_string())),
+                1,
+                vec![]
+            )
+        );
+    }
 
-        let params = serde_json::json!([format!("0x{}", hex::encode(transaction.rlp()))]);
+    #[test]
+    fn test_backoff_delay_secs() {
+        assert_eq!(backoff_delay_secs(10, 0), 10);
+        assert_eq!(backoff_delay_secs(10, 1), 20);
+        assert_eq!(backoff_delay_secs(10, 3), 80);
+        assert_eq!(backoff_delay_secs(u64::MAX, 2), u64::MAX);
+    }
 
-        let tx_hash =
-            http::call_jsonrpc(&hostname, "eth_sendRawTransaction", params, Some(80000)).await?;
+    #[test]
+    fn test_parent_block() {
+        assert_eq!(
+            parent_block(U256::from(0x10u64)),
+            serde_json::json!(U256::from(0xfu64))
+        );
+        assert_eq!(parent_block(U256::zero()), serde_json::json!(U256::zero()));
+    }
 
-        let tx_hash = serde_json::from_value::<H256>(tx_hash)?;
+    #[test]
+    fn test_check_confirmed_block() {
+        let hash = H256::from_slice(&[1; 32]);
+        let receipt = serde_json::json!({
+            "status": "0x1",
+            "blockNumber": U256::from(0x10u64),
+            "blockHash": hash,
+        });
+        let block = receipt_block(&receipt).unwrap();
+        assert_eq!(
+            block,
+            ConfirmedBlock {
+                number: U256::from(0x10u64),
+                hash: hash.clone(),
+                finalized: false,
+            }
+        );
+        assert_eq!(receipt_block(&serde_json::json!({ "status": "0x1" })), None);
 
-        log::debug!("transaction hash: {:?}", tx_hash);
+        let latest = |number: u64| serde_json::json!({ "number": U256::from(number) });
+        let canonical = serde_json::json!({ "number": U256::from(0x10u64), "hash": hash });
 
-        Ok(())
+        assert_eq!(
+            check_confirmed_block(&block, &latest(0x11), &canonical, 3),
+            Ok(BlockCheck::Pending)
+        );
+        assert_eq!(
+            check_confirmed_block(&block, &latest(0x12), &canonical, 3),
+            Ok(BlockCheck::Finalized)
+        );
+        // A provider behind the confirmed block does not report a reorg
+        assert_eq!(
+            check_confirmed_block(&block, &latest(0xf), &Value::Null, 3),
+            Ok(BlockCheck::Pending)
+        );
+
+        let orphaned = serde_json::json!({
+            "number": U256::from(0x10u64),
+            "hash": H256::from_slice(&[2; 32]),
+        });
+        assert_eq!(
+            check_confirmed_block(&block, &latest(0x11), &orphaned, 3),
+            Ok(BlockCheck::Reorged)
+        );
+        assert_eq!(
+            check_confirmed_block(&block, &latest(0x11), &Value::Null, 3),
+            Ok(BlockCheck::Reorged)
+        );
     }
 
-    fn check_owner(&self, caller: Principal) -> Result<()> {
-        let owner = self.with_state(|state| state.owner());
-        if caller != owner {
-            return Err(Error::Internal("caller is not the owner".to_string()));
-        }
+    #[test]
+    fn test_apply_transforms() {
+        let price = U256::from(2_500_000_000u64);
 
-        Ok(())
-    }
+        // MMBtu to therms, then a spread and a ceiling
+        let transforms = vec![
+            Transform::Divide(U256::from(10u64)),
+            Transform::Add(-1_000),
+            Transform::Multiply(U256::from(3u64)),
+            Transform::Subtract(2_000),
+            Transform::Clamp {
+                min: U256::zero(),
+                max: U256::from(700_000_000u64),
+            },
+        ];
+        assert_eq!(
+            apply_transforms(price.clone(), &transforms[..4], 8),
+            Ok(U256::from(749_995_000u64))
+        );
+        assert_eq!(
+            apply_transforms(price.clone(), &transforms, 8),
+            Ok(U256::from(700_000_000u64))
+        );
+        assert_eq!(apply_transforms(price.clone(), &[], 8), Ok(price));
 
-    /// Returns candid IDL.
-    /// This should be the last fn to see previous endpoints in macro.
-    pub fn idl() -> Idl {
-        generate_idl!()
+        // 4 with 2 decimals inverts to 0.25
+        assert_eq!(
+            apply_transforms(U256::from(400u64), &[Transform::Reciprocal], 2),
+            Ok(U256::from(25u64))
+        );
+
+        assert!(matches!(
+            apply_transforms(U256::from(1u64), &[Transform::Divide(U256::zero())], 8),
+            Err(Error::InvalidTransform(_))
+        ));
+        assert!(matches!(
+            apply_transforms(U256::from(1u64), &[Transform::Subtract(2)], 8),
+            Err(Error::InvalidTransform(_))
+        ));
+        assert!(matches!(
+            apply_transforms(U256::zero(), &[Transform::Reciprocal], 8),
+            Err(Error::InvalidTransform(_))
+        ));
+        assert!(matches!(
+            apply_transforms(
+                U256(ethers_core::types::U256::MAX),
+                &[Transform::Multiply(U256::from(2u64))],
+                8
+            ),
+            Err(Error::InvalidTransform(_))
+        ));
     }
-}
 
-/// This is the origin of the data that will be used to update the price
-#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
-pub enum Origin {
-    /// EVM origin
-    Evm(EvmOrigin),
-    /// HTTP origin
-    Http(HttpOrigin),
-}
+    #[test]
+    fn test_is_duplicate_price() {
+        let origin = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
+        });
+        let destination = EvmDestination {
+            contract: H160::from_slice(&[2; 20]),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+        let mut metadata: OracleMetadata = StorableOracleMetadata::new(
+            H160::from_slice(&[1; 20]),
+            origin,
+            100,
+            TimerId::default(),
+            destination,
+        )
+        .into();
+        metadata.suppress_duplicates = true;
 
-/// EVM origin data
-#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
-pub struct EvmOrigin {
-    /// The EVM provider that will be used to fetch the data
-    pub provider: Provider,
-    /// The address of the contract that will be called
-    pub target_address: H160,
-    /// The method that will be called on the contract
-    pub method: String,
-}
+        // Three identical prices send a single transaction
+        let mut transactions = 0;
+        for _ in 0..3 {
+            let price = U256::from(100u64);
+            if !is_duplicate_price(&metadata, &price, PriceSource::Origin) {
+                transactions += 1;
+                metadata.last_submitted_price = Some(price);
+            }
+        }
+        assert_eq!(transactions, 1);
 
-/// HTTP origin data that will be used to fetch the data from the given URL
-#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
-pub struct HttpOrigin {
-    /// The URL that will be used to fetch the data
-    pub url: String,
-    /// The JSON path that will be used to extract the data
-    pub json_path: String,
-}
+        assert!(!is_duplicate_price(
+            &metadata,
+            &U256::from(101u64),
+            PriceSource::Origin
+        ));
+        assert!(!is_duplicate_price(
+            &metadata,
+            &U256::from(100u64),
+            PriceSource::CombinedFallback
+        ));
 
-/// This is the destination of the data that will be used to update the price
-#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
-pub struct EvmDestination {
-    /// The address of the contract that will be called
-    pub contract: H160,
-    /// The EVM provider that will be used to fetch the data
-    pub provider: Provider,
-}
+        metadata.using_fallback = true;
+        assert!(!is_duplicate_price(
+            &metadata,
+            &U256::from(100u64),
+            PriceSource::Origin
+        ));
 
-/// inspect function to check whether the provided principal is anonymous
-fn check_anonymous_principal(principal: Principal) -> Result<()> {
-    if principal == Principal::anonymous() {
-        return Err(Error::Internal("Principal is anonymous".to_string()));
+        metadata.using_fallback = false;
+        metadata.suppress_duplicates = false;
+        assert!(!is_duplicate_price(
+            &metadata,
+            &U256::from(100u64),
+            PriceSource::Origin
+        ));
     }
 
-    Ok(())
-}
-
-#[cfg(test)]
-mod tests {
-    use candid::Principal;
-    use ic_canister::{canister_call, Canister};
-    use ic_exports::ic_kit::mock_principals::alice;
-    use ic_exports::ic_kit::MockContext;
+    #[test]
+    fn test_fetch_error_stage() {
+        assert_eq!(
+            fetch_error_stage(&Error::Http("timed out".to_string())),
+            OracleStage::DataFetch
+        );
+        assert_eq!(
+            fetch_error_stage(&Error::ParseError(parser::ParseError::KeyNotFound(
+                "price".to_string()
+            ))),
+            OracleStage::DataParse
+        );
+        assert_eq!(
+            fetch_error_stage(&Error::SchemaValidationFailed("status".to_string())),
+            OracleStage::DataParse
+        );
+        assert_eq!(
+            fetch_error_stage(&Error::AbiDecodeError("empty data".to_string())),
+            OracleStage::DataParse
+        );
+    }
 
-    use super::*;
-    use crate::canister::Oracular;
+    #[test]
+    fn test_check_price_change() {
+        let last_price = U256::from(1_000u64);
 
-    pub fn oracular_principal_mock() -> Principal {
-        const MOCK_PRINCIPAL: &str = "sgymv-uiaaa-aaaaa-aaaia-cai";
-        Principal::from_text(MOCK_PRINCIPAL).expect("valid principal")
-    }
+        // A move of 5% is accepted up to a maximum of 500 bps, in both directions
+        assert_eq!(
+            check_price_change(&U256::from(1_050u64), Some(&last_price), 500),
+            Ok(())
+        );
+        assert_eq!(
+            check_price_change(&U256::from(950u64), Some(&last_price), 500),
+            Ok(())
+        );
+        assert_eq!(
+            check_price_change(&U256::from(1_051u64), Some(&last_price), 500),
+            Err(Error::PriceChangeTooLarge {
+                price: U256::from(1_051u64),
+                change_bps: 510,
+                max_bps: 500,
+            })
+        );
+        assert_eq!(
+            check_price_change(&U256::from(0u64), Some(&last_price), 500),
+            Err(Error::PriceChangeTooLarge {
+                price: U256::from(0u64),
+                change_bps: 10_000,
+                max_bps: 500,
+            })
+        );
 
-    async fn init_canister<'a>() -> (Oracular, &'a mut MockContext) {
-        let ctx = MockContext::new().inject();
+        // The change saturates instead of overflowing
+        assert_eq!(
+            check_price_change(&U256(ethers_core::types::U256::MAX), Some(&last_price), 500),
+            Err(Error::PriceChangeTooLarge {
+                price: U256(ethers_core::types::U256::MAX),
+                change_bps: u32::MAX,
+                max_bps: 500,
+            })
+        );
 
-        let mut canister = Oracular::from_principal(oracular_principal_mock());
+        // The first price and the prices following a zero price are not checked
+        assert_eq!(check_price_change(&last_price, None, 500), Ok(()));
+        assert_eq!(
+            check_price_change(&last_price, Some(&U256::from(0u64)), 500),
+            Ok(())
+        );
+    }
 
-        canister_call!(
-            canister.init(InitData {
-                owner: Principal::management_canister(),
-                log_settings: None,
+    #[test]
+    fn test_cost_stats() {
+        let mut metadata: OracleMetadata = StorableOracleMetadata::new(
+            H160::from_slice(&[1; 20]),
+            Origin::Http(HttpOrigin {
+                url: String::from("https://example.com"),
+                json_path: String::from("data"),
+                json_paths: vec![],
+                forbidden_paths: vec![],
+                required_paths: vec![],
+                decimals: None,
+                max_response_bytes: None,
+                parse_mode: None,
+                auth: None,
+                enable_filter_expressions: false,
+                skip_cache: false,
+                json_aggregate: None,
+                session_cookie: None,
+                valid_status_codes: vec![200],
+                signing_config: None,
+                timeout_ms: None,
             }),
-            ()
+            60,
+            TimerId::default(),
+            EvmDestination {
+                contract: H160::from_slice(&[2; 20]),
+                provider: Provider {
+                    chain_id: 1,
+                    endpoints: vec![String::from("https://example.com")],
+                },
+                chain_id: None,
+                extra_destinations: vec![],
+                value_wei: None,
+            },
         )
-        .await
-        .unwrap();
+        .into();
 
-        (canister, ctx)
+        assert_eq!(
+            CostStats::from(&metadata),
+            CostStats {
+                avg_cycles_per_call: 0,
+                min_cycles_per_call: 0,
+                max_cycles_per_call: 0,
+                total_cycles_consumed: 0,
+            }
+        );
+
+        metadata.cycles_per_call_history = [1_000, 3_000, 2_000].into();
+        metadata.cycles_consumed = 10_000;
+        assert_eq!(
+            CostStats::from(&metadata),
+            CostStats {
+                avg_cycles_per_call: 2_000,
+                min_cycles_per_call: 1_000,
+                max_cycles_per_call: 3_000,
+                total_cycles_consumed: 10_000,
+            }
+        );
     }
 
-    #[tokio::test]
-    async fn test_set_owner_anonymous() {
-        let (mut canister, ctx) = init_canister().await;
+    #[test]
+    fn test_dashboard_stats() {
+        let destination = |i: u8| EvmDestination {
+            contract: H160::from_slice(&[i; 20]),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+        let oracle = |i: u8| -> OracleMetadata {
+            StorableOracleMetadata::new(
+                H160::from_slice(&[1; 20]),
+                Origin::Http(HttpOrigin {
+                    url: String::from("https://example.com"),
+                    json_path: String::from("data"),
+                    json_paths: vec![],
+                    forbidden_paths: vec![],
+                    required_paths: vec![],
+                    decimals: None,
+                    max_response_bytes: None,
+                    parse_mode: None,
+                    auth: None,
+                    enable_filter_expressions: false,
+                    skip_cache: false,
+                    json_aggregate: None,
+                    session_cookie: None,
+                    valid_status_codes: vec![200],
+                    signing_config: None,
+                    timeout_ms: None,
+                }),
+                3_600,
+                TimerId::default(),
+                destination(i),
+            )
+            .into()
+        };
+        let day = SECONDS_PER_DAY * 1_000_000_000;
+        let now = 10 * day;
 
-        let res = canister_call!(canister.set_owner(Principal::anonymous()), ())
-            .await
-            .unwrap();
+        // Updated successfully an hour ago, 24 updates a day
+        let mut updated = oracle(2);
+        updated.last_attempt_at = now - day / 24;
+        updated.last_updated_at = now - day / 24;
+        updated.total_updates = 100;
+        updated.cycles_consumed = 1_000;
 
-        assert!(res.is_err());
+        // Last update failed an hour ago
+        let mut failed = oracle(3);
+        failed.status = OracleStatus::Error;
+        failed.last_attempt_at = now - day / 24;
+        failed.last_updated_at = now - 2 * day;
+        failed.total_updates = 2;
+        failed.cycles_consumed = 30;
 
-        ctx.update_id(Principal::management_canister());
+        // Not updated in the last 24 hours
+        let mut paused = oracle(4);
+        paused.status = OracleStatus::Paused;
+        paused.last_attempt_at = now - 3 * day;
+        paused.last_updated_at = now - 3 * day;
+        paused.total_updates = 10;
+        paused.cycles_consumed = 100;
 
-        let res = canister_call!(canister.set_owner(alice()), ())
-            .await
-            .unwrap();
+        let mut stats = DashboardStats::default();
+        for metadata in [&updated, &failed, &paused, &oracle(5)] {
+            stats.record(metadata, now);
+        }
 
-        assert!(res.is_ok());
+        assert_eq!(
+            stats,
+            DashboardStats {
+                total_oracles: 4,
+                active_oracles: 2,
+                paused_oracles: 1,
+                errored_oracles: 1,
+                total_updates_24h: 2,
+                failed_updates_24h: 1,
+                estimated_cycles_consumed_24h: 10 * 24 + 15 * 2,
+                oldest_update_timestamp: now - 3 * day,
+                newest_update_timestamp: now - day / 24,
+            }
+        );
     }
 
     #[test]
-    fn test_recover_pub_key_with_correct_payload() {
-        let message = "Testing".to_string();
-        let signature =
-        "0x4bce59ed739b43e739f304cb790cacde57b800aa712dde352cc8aa4f4727979d3849a8c52f59c34083f5060b4f1630ad7d34902a68ae216431332f27b830953b1b".to_string();
-
-        let expected_address =
-            H160::from_hex_str("0xE757Bd3f57C51D2068742d0CEA6f49D38d567310").unwrap();
-
-        let address = Oracular::recover_pubkey(message, signature).unwrap();
+    fn test_provider_health_report() {
+        let report = ProviderHealthReport::new(Ok(U256::from(1_000u64)), 1_500_000_000);
+        assert!(report.is_reachable);
+        assert_eq!(report.block_number, Some(U256::from(1_000u64)));
+        assert_eq!(report.latency_ms, Some(1_500));
+        assert_eq!(report.error, None);
 
-        assert_eq!(address, expected_address);
+        let report =
+            ProviderHealthReport::new(Err(Error::Http("connection refused".to_string())), 10);
+        assert!(!report.is_reachable);
+        assert_eq!(report.block_number, None);
+        assert_eq!(report.latency_ms, None);
+        assert_eq!(
+            report.error,
+            Some("http error : connection refused".to_string())
+        );
     }
 
     #[test]
-    fn test_recover_pub_key_with_incorrect_payload() {
-        let message = "Testing 123".to_string();
-        let signature =
-        "0x4bce59ed739b43e739f304cb790cacde57b800aa712dde352cc8aa4f4727979d3849a8c52f59c34083f5060b4f1630ad7d34902a68ae216431332f27b830953b1b".to_string();
-
-        let expected_address =
-            H160::from_hex_str("0xE757Bd3f57C51D2068742d0CEA6f49D38d567310").unwrap();
+    fn test_check_label() {
+        assert!(check_label(None, None).is_ok());
+        assert!(check_label(Some("BTC/USD"), Some("Bitcoin price in USD")).is_ok());
+        assert!(check_label(Some(&"a".repeat(MAX_LABEL_BYTES)), None).is_ok());
+        assert!(check_label(Some(&"a".repeat(MAX_LABEL_BYTES + 1)), None).is_err());
+        assert!(check_label(None, Some(&"a".repeat(MAX_DESCRIPTION_BYTES + 1))).is_err());
+    }
 
-        let address = Oracular::recover_pubkey(message, signature).unwrap();
+    #[test]
+    fn test_check_tags() {
+        assert!(check_tags(&[]).is_ok());
+        assert!(check_tags(&[String::from("crypto"), String::from("forex")]).is_ok());
+        assert!(check_tags(&[String::new()]).is_err());
+        assert!(check_tags(&["a".repeat(MAX_TAG_CHARS + 1)]).is_err());
+        assert!(check_tags(&[String::from("crypto"), String::from("crypto")]).is_err());
 
-        assert_ne!(address, expected_address);
+        let tags = (0..=MAX_ORACLE_TAGS)
+            .map(|index| format!("tag-{index}"))
+            .collect::<Vec<_>>();
+        assert!(check_tags(&tags[..MAX_ORACLE_TAGS]).is_ok());
+        assert!(check_tags(&tags).is_err());
     }
 }
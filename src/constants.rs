@@ -4,3 +4,83 @@ pub const INGRESS_MESSAGE_BYTE_RECEIVED_COST: u128 = 2_000;
 pub const HTTP_OUTCALL_REQUEST_COST: u128 = 400_000_000;
 pub const HTTP_OUTCALL_BYTE_RECEIVED_COST: u128 = 100_000;
 pub const DEFAULT_NODES_IN_SUBNET: u32 = 13;
+/// Number of consecutive failed updates after which an oracle enters the `Error` state
+pub const DEFAULT_ERROR_THRESHOLD: u32 = 10;
+/// Maximum number of oracles a single user can register
+pub const MAX_ORACLES_PER_USER: u64 = 100;
+/// Maximum number of admins that can manage the canister
+pub const MAX_ADMINS: usize = 10;
+/// Max response bytes of the JSON-RPC calls used to build a transaction
+pub const JSON_RPC_MAX_RESPONSE_BYTES: u64 = 8_000;
+/// Max response bytes of the `eth_call` and `eth_sendRawTransaction` JSON-RPC calls
+pub const EVM_CALL_MAX_RESPONSE_BYTES: u64 = 80_000;
+/// Max response bytes of the `eth_getBlockByNumber` batch of the reorg check
+pub const BLOCK_MAX_RESPONSE_BYTES: u64 = 80_000;
+/// Estimated size in bytes of a JSON-RPC request body, used for cost estimations
+pub const ESTIMATED_JSON_RPC_PAYLOAD_BYTES: usize = 256;
+/// Number of seconds in a day
+pub const SECONDS_PER_DAY: u64 = 86_400;
+/// Default number of seconds to wait before checking an update transaction receipt
+pub const DEFAULT_CONFIRMATION_DELAY_SECS: u64 = 10;
+/// Default number of blocks after which a confirmed update transaction is final
+pub const DEFAULT_CONFIRMATION_BLOCKS: u8 = 1;
+/// Default number of seconds after which a missing receipt is considered a failure
+pub const DEFAULT_RECEIPT_MAX_WAIT_SECS: u64 = 120;
+/// Maximum number of times a missing receipt is checked again, doubling the delay each time
+pub const MAX_RECEIPT_CHECK_RETRIES: u32 = 3;
+/// Default number of seconds to wait before retrying a failed update, doubled on each retry
+pub const DEFAULT_RETRY_BASE_DELAY_SECS: u64 = 5;
+/// Number of seconds of the window in which the oracle creations of a user are rate limited
+pub const ORACLE_CREATION_RATE_WINDOW_SECS: u64 = 3_600;
+/// Number of seconds after which a cached nonce is fetched again from the EVM
+pub const NONCE_CACHE_TTL_SECS: u64 = 300;
+/// Maximum number of seconds between a signature proof and its expiry time
+pub const MAX_SIGNATURE_PROOF_TTL_SECS: u64 = 3_600;
+/// Percentage of the gas price of a stuck transaction paid by its replacement
+pub const REPLACEMENT_GAS_PRICE_PERCENT: u64 = 120;
+/// Default maximum number of oracle updates run at once by the timers
+pub const DEFAULT_MAX_CONCURRENT_UPDATES: u32 = 10;
+/// Number of seconds after which an update skipped because of the concurrent
+/// updates limit is attempted again
+pub const THROTTLED_UPDATE_RETRY_SECS: u64 = 5;
+/// Default maximum delay in seconds used to stagger the oracle timers restored after an upgrade
+pub const DEFAULT_MAX_STARTUP_JITTER_SECS: u64 = 60;
+/// Default max response bytes of the HTTP origin requests
+pub const DEFAULT_HTTP_MAX_RESPONSE_BYTES: u64 = 8_000;
+/// HTTP status codes of the successful responses, unless set by the HTTP origin
+pub const DEFAULT_VALID_STATUS_CODES: &[u16] = &[200];
+/// Minimum max response bytes that can be set on an HTTP origin
+pub const MIN_RESPONSE_BYTES: u64 = 512;
+/// Maximum max response bytes that can be set on an HTTP origin, the IC limit of 2 MB
+pub const MAX_RESPONSE_BYTES: u64 = 2_097_152;
+/// Number of failed updates kept in the dead letter queue, the oldest ones are dropped first
+pub const MAX_DEAD_LETTER_ENTRIES: u64 = 10_000;
+/// Version of the layout of the oracle state snapshots, to be increased whenever the
+/// layout of the stored oracle metadata or of the settings changes
+pub const ORACLE_SNAPSHOT_SCHEMA_VERSION: u32 = 18;
+/// Number of events kept for each oracle, the oldest ones are dropped first
+pub const MAX_ORACLE_EVENTS: usize = 50;
+/// Number of pushed prices kept for each oracle to detect outliers, the oldest ones are dropped first
+pub const MAX_PRICE_HISTORY: usize = 32;
+/// Number of update costs kept for each oracle, the oldest ones are dropped first
+pub const MAX_CYCLES_PER_CALL_HISTORY: usize = 100;
+/// Depth up to which the oracle triggers are walked to detect circular dependencies
+pub const MAX_TRIGGER_DEPTH: usize = 10;
+/// Maximum length in bytes of an oracle label
+pub const MAX_LABEL_BYTES: usize = 64;
+/// Maximum length in bytes of an oracle description
+pub const MAX_DESCRIPTION_BYTES: usize = 256;
+/// Maximum number of tags of an oracle
+pub const MAX_ORACLE_TAGS: usize = 10;
+/// Maximum length in characters of an oracle tag
+pub const MAX_TAG_CHARS: usize = 32;
+/// Estimated cycles burnt by the two `canister_balance128` calls measuring an oracle update
+pub const BALANCE_MEASUREMENT_CYCLES: u128 = 1_000;
+/// Maximum size of the stable memory of a canister in 64 KiB pages, 400 GiB
+pub const MAX_STABLE_MEMORY_PAGES: u64 = 6_553_600;
+/// Placeholder of the provider endpoints replaced by the API key of their chain
+pub const API_KEY_PLACEHOLDER: &str = "{API_KEY}";
+/// Name of the EIP-712 domain of the oracles created without one
+pub const DEFAULT_EIP712_DOMAIN_NAME: &str = "Oracular";
+/// Version of the EIP-712 domain of the oracles created without one
+pub const DEFAULT_EIP712_DOMAIN_VERSION: &str = "1";
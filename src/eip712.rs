@@ -0,0 +1,140 @@
+//! EIP-712 typed structured data used to attest the oracle prices off-chain
+
+use candid::CandidType;
+use did::transaction::Signature;
+use did::{H160, U256};
+use ethers_core::abi::{encode, Token};
+use ethers_core::utils::keccak256;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{DEFAULT_EIP712_DOMAIN_NAME, DEFAULT_EIP712_DOMAIN_VERSION};
+
+/// Type of the EIP-712 domain
+const EIP712_DOMAIN_TYPE: &str =
+    "EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)";
+
+/// Type of the signed oracle data
+const ORACLE_DATA_TYPE: &str = "OracleData(address oracle,int256 price,uint256 timestamp)";
+
+/// EIP-712 domain of the contract verifying the signed oracle data
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Eip712DomainData {
+    pub name: String,
+    pub version: String,
+    pub chain_id: u64,
+    pub verifying_contract: H160,
+}
+
+/// Name and version of the EIP-712 domain of an oracle, fixed on creation
+///
+/// The chain id and the verifying contract of the domain are the ones of the
+/// oracle destination.
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Eip712DomainConfig {
+    pub name: String,
+    pub version: String,
+}
+
+impl Default for Eip712DomainConfig {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_EIP712_DOMAIN_NAME.to_string(),
+            version: DEFAULT_EIP712_DOMAIN_VERSION.to_string(),
+        }
+    }
+}
+
+impl Eip712DomainConfig {
+    /// Returns the domain of the contract `verifying_contract` on `chain_id`
+    pub fn domain(&self, chain_id: u64, verifying_contract: H160) -> Eip712DomainData {
+        Eip712DomainData {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            chain_id,
+            verifying_contract,
+        }
+    }
+}
+
+impl Eip712DomainData {
+    /// Returns the domain separator, the hash of the domain struct
+    pub fn separator(&self) -> [u8; 32] {
+        keccak256(encode(&[
+            Token::FixedBytes(keccak256(EIP712_DOMAIN_TYPE).to_vec()),
+            Token::FixedBytes(keccak256(&self.name).to_vec()),
+            Token::FixedBytes(keccak256(&self.version).to_vec()),
+            Token::Uint(self.chain_id.into()),
+            Token::Address(self.verifying_contract.0),
+        ]))
+    }
+}
+
+/// Oracle price signed following EIP-712
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SignedOracleData {
+    pub price: U256,
+    /// Timestamp in seconds of the price update
+    pub timestamp: u64,
+    pub signature: Signature,
+}
+
+/// Returns the EIP-712 digest of the oracle data to sign
+///
+/// The data is encoded as `OracleData(address oracle,int256 price,uint256 timestamp)`,
+/// where `oracle` is the contract updated by the oracle.
+pub fn oracle_data_digest(
+    domain: &Eip712DomainData,
+    oracle: &H160,
+    price: &U256,
+    timestamp: u64,
+) -> [u8; 32] {
+    let struct_hash = keccak256(encode(&[
+        Token::FixedBytes(keccak256(ORACLE_DATA_TYPE).to_vec()),
+        Token::Address(oracle.0),
+        Token::Int(price.0),
+        Token::Uint(timestamp.into()),
+    ]));
+
+    let mut message = Vec::with_capacity(66);
+    message.extend_from_slice(&[0x19, 0x01]);
+    message.extend_from_slice(&domain.separator());
+    message.extend_from_slice(&struct_hash);
+
+    keccak256(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oracle_data_digest() {
+        let domain = Eip712DomainData {
+            name: String::from("Oracular"),
+            version: String::from("1"),
+            chain_id: 355113,
+            verifying_contract: H160::from_slice(&[2; 20]),
+        };
+        assert_eq!(
+            Eip712DomainConfig::default().domain(355113, H160::from_slice(&[2; 20])),
+            domain
+        );
+
+        assert_eq!(
+            hex::encode(domain.separator()),
+            "78d34812171a1677ac87c124190d17f4cbce6c3b55589ab7353361714e830611"
+        );
+
+        let digest = oracle_data_digest(
+            &domain,
+            &H160::from_slice(&[1; 20]),
+            &U256::from(123_456_780u64),
+            1_700_000_000,
+        );
+
+        assert_eq!(
+            hex::encode(digest),
+            "d3809c79665d454ea05adb0959fa5661cd748e4af0f013d1ef83750839c28175"
+        );
+    }
+}
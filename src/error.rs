@@ -1,10 +1,11 @@
 use candid::CandidType;
+use did::{H256, U256};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::parser;
 
-#[derive(Debug, CandidType, Serialize, Deserialize, Error, PartialEq, Eq)]
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, Error, PartialEq, Eq)]
 pub enum Error {
     #[error("Internal error: {0}")]
     Internal(String),
@@ -32,6 +33,94 @@ pub enum Error {
 
     #[error("user not found")]
     UserNotFound,
+
+    #[error("no provider registered for chain {0}")]
+    ProviderNotFound(u64),
+
+    #[error("oracle limit of {0} per user exceeded")]
+    OracleLimitExceeded(u64),
+
+    #[error("oracle creation rate limit of {0} per hour exceeded")]
+    RateLimitExceeded(u32),
+
+    #[error("url not allowed: {0}")]
+    UrlNotAllowed(String),
+
+    #[error("oracle price not available")]
+    PriceNotAvailable,
+
+    #[error("transaction receipt not found within {0} seconds")]
+    ReceiptTimeout(u64),
+
+    #[error("no response within {0} ms")]
+    Timeout(u64),
+
+    #[error("transaction reverted: {0}")]
+    TransactionReverted(String),
+
+    #[error("provider chain id mismatch: declared {declared}, actual {actual}")]
+    ChainIdMismatch { declared: u64, actual: u64 },
+
+    #[error("all provider endpoints failed: {0:?}")]
+    ProviderFallbackError(Vec<String>),
+
+    #[error("invalid destination: {0}")]
+    InvalidDestination(String),
+
+    #[error("abi decode error: {0}")]
+    AbiDecodeError(String),
+
+    #[error("invalid transform: {0}")]
+    InvalidTransform(String),
+
+    #[error("circular oracle dependency")]
+    CircularDependency,
+
+    #[error("price {} out of bounds [{}, {}]", .price.0, .min.0, .max.0)]
+    PriceOutOfBounds { price: U256, min: U256, max: U256 },
+
+    #[error("price {} is an outlier of the price history", .0.0)]
+    PriceOutlier(U256),
+
+    #[error("price {} changed by {change_bps} bps, above the maximum of {max_bps} bps", .price.0)]
+    PriceChangeTooLarge {
+        price: U256,
+        change_bps: u32,
+        max_bps: u32,
+    },
+
+    #[error("response schema validation failed: {0}")]
+    SchemaValidationFailed(String),
+
+    #[error("forbidden field '{path}' found in the response: {value}")]
+    ResponseForbiddenField { path: String, value: String },
+
+    #[error("limit of {0} concurrent updates reached")]
+    ConcurrentUpdatesExceeded(u32),
+
+    #[error("transaction {original} replaced by {replacement}")]
+    TransactionReplaced { original: H256, replacement: H256 },
+
+    #[error("transaction {0} reorged out of the canonical chain")]
+    TransactionReorged(H256),
+
+    #[error("transaction {0} already submitted")]
+    DuplicateTransaction(H256),
+
+    #[error("invalid update function: {0}")]
+    InvalidUpdateFunction(String),
+
+    #[error("invalid abi: {0}")]
+    InvalidAbi(String),
+
+    #[error("gas price of {current_gwei} gwei above the maximum of {max_gwei} gwei")]
+    GasPriceTooHigh { current_gwei: u64, max_gwei: u64 },
+
+    #[error("signer balance of {} wei below the minimum of {} wei", .balance.0, .min_balance.0)]
+    InsufficientBalance { balance: U256, min_balance: U256 },
+
+    #[error("snapshot schema version {version} not supported, expected {supported}")]
+    UnsupportedSnapshotVersion { version: u32, supported: u32 },
 }
 
 impl From<String> for Error {
@@ -42,6 +131,62 @@ impl From<String> for Error {
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Stage of an oracle update
+#[derive(Debug, Clone, Copy, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub enum OracleStage {
+    /// Fetching the price from the origin
+    DataFetch,
+    /// Reading the price from the origin response, then transforming and checking it
+    DataParse,
+    /// Reading the balance, nonce and gas price of the signer and estimating the gas
+    GasEstimation,
+    /// Encoding and signing the update transaction
+    TransactionSigning,
+    /// Sending, or simulating, the update transaction
+    TransactionSubmission,
+    /// Waiting for the receipt of the update transaction and its confirmations
+    ReceiptVerification,
+}
+
+/// Error of an oracle update along with the stage it occurred in
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, Error, PartialEq, Eq)]
+#[error("{stage:?} stage failed: {inner}")]
+pub struct PipelineError {
+    pub stage: OracleStage,
+    pub inner: Error,
+}
+
+impl PipelineError {
+    pub fn new(stage: OracleStage, inner: Error) -> Self {
+        Self { stage, inner }
+    }
+}
+
+impl From<PipelineError> for Error {
+    fn from(error: PipelineError) -> Self {
+        error.inner
+    }
+}
+
+impl std::borrow::Borrow<Error> for PipelineError {
+    fn borrow(&self) -> &Error {
+        &self.inner
+    }
+}
+
+pub type PipelineResult<T> = std::result::Result<T, PipelineError>;
+
+/// Tags the error of a result with the stage of the oracle update it occurred in
+pub trait WithStage<T> {
+    fn at_stage(self, stage: OracleStage) -> PipelineResult<T>;
+}
+
+impl<T> WithStage<T> for Result<T> {
+    fn at_stage(self, stage: OracleStage) -> PipelineResult<T> {
+        self.map_err(|e| PipelineError::new(stage, e))
+    }
+}
+
 impl From<ic_canister_client::CanisterClientError> for Error {
     fn from(value: ic_canister_client::CanisterClientError) -> Self {
         Self::IcClient(value.to_string())
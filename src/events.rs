@@ -0,0 +1,71 @@
+use candid::CandidType;
+use did::{H160, U256};
+use ic_exports::ic_kit::ic;
+use serde::{Deserialize, Serialize};
+
+use crate::state::oracle_storage::OracleStorage;
+
+/// An event in the lifecycle of an oracle
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OracleEvent {
+    /// IC time in nanoseconds of the event
+    pub timestamp: u64,
+    /// The address of the user that created the oracle
+    pub user_address: H160,
+    /// The address of the contract updated by the oracle
+    pub contract_address: H160,
+    pub event_type: OracleEventType,
+    /// Additional information about the event
+    pub details: Option<String>,
+}
+
+impl OracleEvent {
+    /// Creates an event happening at the current IC time
+    pub fn new(
+        user_address: H160,
+        contract_address: H160,
+        event_type: OracleEventType,
+        details: Option<String>,
+    ) -> Self {
+        Self {
+            timestamp: ic::time(),
+            user_address,
+            contract_address,
+            event_type,
+            details,
+        }
+    }
+}
+
+/// Type of an oracle event
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub enum OracleEventType {
+    Created,
+    /// The oracle metadata was updated
+    Updated,
+    Deleted,
+    /// A new price was pushed to the EVM
+    UpdateSuccess {
+        price: U256,
+    },
+    UpdateFailed {
+        error: String,
+    },
+    /// The oracle timer was stopped
+    Paused,
+    /// The oracle timer was started again
+    Resumed,
+}
+
+/// Logs the event as JSON and stores it in the events of the oracle
+///
+/// The event is only logged if the oracle does not exist, e.g. after its deletion.
+pub fn emit(oracle_storage: &OracleStorage, event: OracleEvent) {
+    if let Ok(json) = serde_json::to_string(&event) {
+        log::info!("{}", json);
+    }
+
+    if let Err(e) = oracle_storage.push_event(event) {
+        log::debug!("failed to store oracle event: {:?}", e.to_string());
+    }
+}
@@ -1,26 +1,41 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
 
 use candid::CandidType;
 use did::U256;
+use futures::future::{self, Either};
+use hmac::{Hmac, Mac};
 use ic_exports::ic_cdk::api::management_canister::http_request::{
     http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod,
     HttpResponse as MHttpResponse, TransformArgs, TransformContext,
 };
-use jsonrpc_core::Output;
-use serde::Deserialize;
+use ic_exports::ic_kit::ic;
+use jsonrpc_core::{Id, Output};
+use serde::{Deserialize, Serialize};
 use serde_bytes::ByteBuf;
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 use url::Url;
 
+use crate::canister::{wait_for, HttpOrigin};
 use crate::constants::{
-    HTTP_OUTCALL_BYTE_RECEIVED_COST, HTTP_OUTCALL_REQUEST_COST, INGRESS_MESSAGE_BYTE_RECEIVED_COST,
-    INGRESS_MESSAGE_RECEIVED_COST, INGRESS_OVERHEAD_BYTES,
+    DEFAULT_HTTP_MAX_RESPONSE_BYTES, DEFAULT_VALID_STATUS_CODES, HTTP_OUTCALL_BYTE_RECEIVED_COST,
+    HTTP_OUTCALL_REQUEST_COST, INGRESS_MESSAGE_BYTE_RECEIVED_COST, INGRESS_MESSAGE_RECEIVED_COST,
+    INGRESS_OVERHEAD_BYTES,
 };
 use crate::error::{Error, Result};
-use crate::parser::ValueParser;
+use crate::parser::{aggregate_price, coerce_to_price, ParseError, ValueParser};
+use crate::state::oracle_storage::ResponseSchema;
+use crate::state::{CookieCache, ProviderCredentials, ResponseCache};
 
+#[deprecated(note = "use `HttpOrigin::decimals` instead")]
 pub const PRICE_MULTIPLE: f64 = 100_000_000.0;
+/// Number of decimals of the prices fetched by the HTTP origins without explicit decimals
+pub const DEFAULT_PRICE_DECIMALS: u8 = 8;
+/// Header carrying the timestamp in seconds covered by the HMAC request signatures
+pub const HMAC_TIMESTAMP_HEADER: &str = "X-Timestamp";
 
 /// The important components of an HTTP request.
 #[derive(Clone, Debug, CandidType, Deserialize)]
@@ -86,14 +101,21 @@ impl HttpResponse {
     }
 }
 
+/// Sends an HTTP outcall with the default headers followed by `extra_headers`
 async fn http_outcall(
     url: &str,
     method: HttpMethod,
     body: Option<Vec<u8>>,
     cost: u128,
     max_response_bytes: Option<u64>,
+    extra_headers: Vec<HttpHeader>,
+    transform: TransformContext,
 ) -> Result<MHttpResponse> {
-    log::debug!("http_outcall url: {}, method: {:?}, ", url, method);
+    log::debug!(
+        "http_outcall url: {}, method: {:?}, ",
+        url_origin(url),
+        method
+    );
 
     let real_url = Url::parse(url).map_err(|e| Error::Http(e.to_string()))?;
 
@@ -101,7 +123,7 @@ async fn http_outcall(
         .host_str()
         .ok_or_else(|| Error::Http("empty host of url".to_string()))?;
 
-    let headers = vec![
+    let mut headers = vec![
         HttpHeader {
             name: "Host".to_string(),
             value: host.to_string(),
@@ -115,6 +137,7 @@ async fn http_outcall(
             value: "application/json".to_string(),
         },
     ];
+    headers.extend(extra_headers);
 
     let request = CanisterHttpRequestArgument {
         url: url.to_string(),
@@ -122,7 +145,7 @@ async fn http_outcall(
         method,
         headers,
         body,
-        transform: Some(TransformContext::from_name("transform".to_string(), vec![])),
+        transform: Some(transform),
     };
 
     let res = http_request(request.clone(), cost)
@@ -133,11 +156,64 @@ async fn http_outcall(
     Ok(res)
 }
 
+/// Waits for the request until `timer` completes, then fails with `Error::Timeout`
+///
+/// The pending request is dropped on timeout, its response is ignored.
+async fn with_timeout<T>(
+    request: impl Future<Output = Result<T>>,
+    timeout_ms: u64,
+    timer: impl Future<Output = ()>,
+) -> Result<T> {
+    let request = std::pin::pin!(request);
+    let timer = std::pin::pin!(timer);
+
+    match future::select(request, timer).await {
+        Either::Left((result, _)) => result,
+        Either::Right(((), _)) => Err(Error::Timeout(timeout_ms)),
+    }
+}
+
+/// Returns the context of the `transform` function
+fn default_transform() -> TransformContext {
+    TransformContext::from_name("transform".to_string(), vec![])
+}
+
+/// Drops the headers of the response, except `Cache-Control`, so that the replicas agree on it
 pub fn transform(raw: TransformArgs) -> MHttpResponse {
     MHttpResponse {
         status: raw.response.status,
+        headers: raw
+            .response
+            .headers
+            .into_iter()
+            .filter(|header| header.name.eq_ignore_ascii_case("cache-control"))
+            .collect(),
         body: raw.response.body,
-        ..Default::default()
+    }
+}
+
+/// Keeps only the `name=value` pairs of the session cookie named in the context of
+/// a login response, dropping its body and cookie attributes, so that the replicas
+/// agree on it
+pub fn transform_login(raw: TransformArgs) -> MHttpResponse {
+    let cookie_name = String::from_utf8(raw.context).unwrap_or_default();
+
+    MHttpResponse {
+        status: raw.response.status,
+        headers: raw
+            .response
+            .headers
+            .iter()
+            .filter(|header| header.name.eq_ignore_ascii_case("set-cookie"))
+            .filter_map(|header| {
+                let value = find_cookie(std::slice::from_ref(header), &cookie_name)?;
+                Some(HttpHeader {
+                    name: "set-cookie".to_string(),
+                    value: format!("{cookie_name}={value}"),
+                })
+            })
+            .collect(),
+        body: vec![],
     }
 }
 
@@ -149,7 +225,7 @@ pub async fn call_jsonrpc(
 ) -> Result<Value> {
     log::debug!(
         "calling json_rpc url: {}, method: {}, params: {}",
-        url,
+        url_origin(url),
         method,
         params
     );
@@ -163,11 +239,20 @@ pub async fn call_jsonrpc(
 
     let cost = get_request_costs(url, body.len(), max_response_bytes.unwrap_or(8000));
 
-    let res = http_outcall(url, HttpMethod::POST, Some(body), cost, max_response_bytes).await?;
+    let res = http_outcall(
+        url,
+        HttpMethod::POST,
+        Some(body),
+        cost,
+        max_response_bytes,
+        vec![],
+        default_transform(),
+    )
+    .await?;
 
-    if res.status != 200 {
+    if !is_valid_status(&res.status, DEFAULT_VALID_STATUS_CODES) {
         return Err(Error::Internal(format!(
-            "error calling jsonrpc, status: {} res: {}",
+            "error calling jsonrpc, status: {} res: {}
             res.status,
             String::from_utf8(res.body).unwrap_or_default()
         )));
@@ -176,49 +261,640 @@ pub async fn call_jsonrpc(
     let json_body = serde_json::from_slice::<Output>(&res.body)
         .map_err(|e| Error::Http(format!("serde_json err: {e}")))?;
 
-    let output = match json_body {
-        Output::Success(success) => success.result,
-        Output::Failure(failure) => {
-            return Err(Error::Http(format!(
-                "JSON-RPC error: {}",
-                failure.error.message
-            )))
+    output_result(json_body)
+}
+
+/// Calls several JSON-RPC methods with a single batch request
+///
+/// The results are returned in the order of the requests.
+pub async fn call_jsonrpc_batch(
+    url: &str,
+    requests: Vec<(&str, Value)>,
+    max_response_bytes: Option<u64>,
+) -> Result<Vec<Value>> {
+    log::debug!(
+        "calling json_rpc batch url: {}, requests: {:?}",
+        url_origin(url),
+        requests
+    );
+    let count = requests.len();
+    let body = serde_json::to_vec(&batch_request_body(requests))
+        .map_err(|e| Error::Http(format!("serde_json err: {e}")))?;
+
+    let cost = get_request_costs(url, body.len(), max_response_bytes.unwrap_or(8000));
+
+    let res = http_outcall(
+        url,
+        HttpMethod::POST,
+        Some(body),
+        cost,
+        max_response_bytes,
+        vec![],
+        default_transform(),
+    )
+    .await?;
+
+    if !is_valid_status(&res.status, DEFAULT_VALID_STATUS_CODES) {
+        return Err(Error::Internal(format!(
+            "error calling jsonrpc, status: {} res: {}
+            res.status,
+            String::from_utf8(res.body).unwrap_or_default()
+        )));
+    }
+
+    let outputs = serde_json::from_slice::<Vec<Output>>(&res.body)
+        .map_err(|e| Error::Http(format!("serde_json err: {e}")))?;
+
+    match_batch_outputs(outputs, count)
+}
+
+/// Builds the body of a JSON-RPC batch request, using the request index as id
+fn batch_request_body(requests: Vec<(&str, Value)>) -> Value {
+    requests
+        .into_iter()
+        .enumerate()
+        .map(|(id, (method, params))| {
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": method,
+                "params": params,
+            })
+        })
+        .collect()
+}
+
+/// Matches the outputs of a batch request to the requests by id
+///
+/// The outputs of a batch may be returned in any order.
+fn match_batch_outputs(outputs: Vec<Output>, count: usize) -> Result<Vec<Value>> {
+    let mut results = vec![None; count];
+
+    for output in outputs {
+        let index = match output.id() {
+            Id::Num(id) if (*id as usize) < count => *id as usize,
+            id => return Err(Error::Http(format!("unexpected JSON-RPC id: {id:?}"))),
+        };
+
+        results[index] = Some(output_result(output)?);
+    }
+
+    results
+        .into_iter()
+        .enumerate()
+        .map(|(id, result)| {
+            result.ok_or_else(|| Error::Http(format!("missing JSON-RPC response for id {id}")))
+        })
+        .collect()
+}
+
+/// Returns the result of a JSON-RPC output
+fn output_result(output: Output) -> Result<Value> {
+    match output {
+        Output::Success(success) => Ok(success.result),
+        Output::Failure(failure) => Err(Error::Http(format!(
+            "JSON-RPC error: {}",
+            failure.error.message
+        ))),
+    }
+}
+
+/// Fetches the price of the HTTP origin
+///
+/// The response is checked with the validators, in order, before the price is
+/// extracted. Unless the origin skips the cache, a valid response body is reused
+/// for the `max-age` of its `Cache-Control` header. Responses of authenticated
+/// requests are never cached.
+pub async fn get_price(
+    origin: &HttpOrigin,
+    response_cache: &ResponseCache,
+    cookie_cache: &CookieCache,
+    validators: &[ResponseSchema],
+) -> Result<U256> {
+    let HttpOrigin {
+        url,
+        json_path,
+        json_paths,
+        forbidden_paths,
+        required_paths,
+        decimals,
+        max_response_bytes,
+        parse_mode,
+        auth,
+        enable_filter_expressions,
+        skip_cache,
+        json_aggregate,
+        session_cookie,
+        valid_status_codes,
+        signing_config,
+        timeout_ms,
+    } = origin;
+    log::debug!("getting price url: {}, json_path: {}", url, json_path);
+
+    let use_cache =
+        !skip_cache && auth.is_none() && session_cookie.is_none() && signing_config.is_none();
+    let cached_body = if use_cache {
+        response_cache.get(url, ic::time())
+    } else {
+        None
+    };
+
+    let (body, expires_at) = match cached_body {
+        Some(body) => {
+            log::debug!("using cached response of url: {}", url);
+            (body, None)
+        }
+        None => {
+            let mut headers = auth.iter().map(HttpAuth::header).collect::<Vec<_>>();
+            if let Some(config) = session_cookie {
+                let cookie = login(config, cookie_cache).await?;
+                headers.push(HttpHeader {
+                    name: "Cookie".to_string(),
+                    value: format!("{}={cookie}", config.cookie_header_name),
+                });
+            }
+            if let Some(config) = signing_config {
+                let secret_key = ProviderCredentials::default()
+                    .get(config.secret_key_id)
+                    .ok_or_else(|| {
+                        Error::Http(format!(
+                            "no secret key {} to sign the request",
+                            config.secret_key_id
+                        ))
+                    })?;
+                let timestamp_secs = ic::time() / 1_000_000_000;
+                headers.extend(config.headers(secret_key.as_bytes(), timestamp_secs, url, &[]));
+            }
+
+            let max_response_bytes = max_response_bytes.unwrap_or(DEFAULT_HTTP_MAX_RESPONSE_BYTES);
+            let cost = get_request_costs(url, 0, max_response_bytes);
+            let request = http_outcall(
+                url,
+                HttpMethod::GET,
+                None,
+                cost,
+                Some(max_response_bytes),
+                headers,
+                default_transform(),
+            );
+            let res = match timeout_ms {
+                Some(timeout_ms) => {
+                    let timer = wait_for(Duration::from_millis(*timeout_ms));
+                    with_timeout(request, *timeout_ms, timer).await?
+                }
+                None => request.await?,
+            };
+
+            // A rejected session is opened again on the next update
+            if let Some(config) = session_cookie
+                .as_ref()
+                .filter(|_| res.status == 401 || res.status == 403)
+            {
+                cookie_cache.remove(&config.session_key());
+            }
+
+            if !is_valid_status(&res.status, valid_status_codes) {
+                return Err(Error::Http(format!(
+                    "error fetching price, status: {} res: {}",
+                    res.status,
+                    String::from_utf8(res.body).unwrap_or_default()
+                )));
+            }
+
+            let expires_at = cache_max_age(&res.headers)
+                .filter(|_| use_cache)
+                .map(|max_age| ic::time().saturating_add(max_age.saturating_mul(1_000_000_000)));
+
+            (res.body, expires_at)
         }
     };
 
-    Ok(output)
+    let json_body = serde_json::from_slice::<Value>(&body)
+        .map_err(|e| Error::Http(format!("serde_json err: {e}")))?;
+    check_response(&json_body, validators)?;
+
+    if let Some(expires_at) = expires_at {
+        response_cache.insert(url.clone(), body, expires_at);
+    }
+
+    check_paths(
+        &json_body,
+        forbidden_paths,
+        required_paths,
+        *enable_filter_expressions,
+    )?;
+    let price = extract_value(
+        &json_body,
+        json_path,
+        json_paths,
+        *enable_filter_expressions,
+    )?;
+
+    let decimals = decimals.unwrap_or(DEFAULT_PRICE_DECIMALS);
+    let parse_mode = parse_mode.unwrap_or_default();
+    match json_aggregate {
+        Some(op) => aggregate_price(&price, *op, decimals, parse_mode),
+        None => coerce_to_price(&price, decimals, parse_mode),
+    }
+    .map_err(Error::from)
 }
 
-pub async fn get_price(url: &str, json_path: &str) -> Result<U256> {
-    log::debug!("getting price url: {}, json_path: {}", url, json_path);
+/// Checks that none of the forbidden paths and all the required paths resolve in
+/// the response
+fn check_paths(
+    json_body: &Value,
+    forbidden_paths: &[String],
+    required_paths: &[String],
+    enable_filter_expressions: bool,
+) -> Result<()> {
+    if let Some((path, value)) = forbidden_paths.iter().find_map(|path| {
+        parse_path(json_body, path, enable_filter_expressions)
+            .ok()
+            .map(|value| (path, value))
+    }) {
+        return Err(Error::ResponseForbiddenField {
+            path: path.clone(),
+            value: value.to_string(),
+        });
+    }
+
+    if let Some(path) = required_paths
+        .iter()
+        .find(|path| parse_path(json_body, path, enable_filter_expressions).is_err())
+    {
+        return Err(Error::SchemaValidationFailed(format!(
+            "missing field '{path}'"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Returns the value of the JSON path in the response, or the array of the values
+/// of each of the JSON paths when the JSON path is empty
+fn extract_value(
+    json_body: &Value,
+    json_path: &str,
+    json_paths: &[String],
+    enable_filter_expressions: bool,
+) -> std::result::Result<Value, ParseError> {
+    let parse = |path: &str| parse_path(json_body, path, enable_filter_expressions);
+
+    if !json_path.is_empty() || json_paths.is_empty() {
+        return parse(json_path);
+    }
+
+    json_paths
+        .iter()
+        .map(|path| parse(path))
+        .collect::<std::result::Result<_, _>>()
+        .map(Value::Array)
+}
+
+/// Returns the value of the JSON path in the response
+fn parse_path(
+    json_body: &Value,
+    path: &str,
+    enable_filter_expressions: bool,
+) -> std::result::Result<Value, ParseError> {
+    if enable_filter_expressions {
+        json_body.parse_filtered(path)
+    } else {
+        json_body.parse(path)
+    }
+}
+
+/// Returns the cached session cookie of the origin, logging in again when it is
+/// missing or expired
+///
+/// Each replica sends its own login request, so the login URL must return the same
+/// cookie to all of them for the outcall to reach consensus.
+async fn login(config: &CookieConfig, cookie_cache: &CookieCache) -> Result<String> {
+    let session_key = config.session_key();
+    if let Some(cookie) = cookie_cache.get(&session_key, ic::time()) {
+        return Ok(cookie);
+    }
 
-    let cost = get_request_costs(url, 0, 8000);
-    let res = http_outcall(url, HttpMethod::GET, None, cost, Some(8000)).await?;
+    log::debug!("logging in to {}", config.login_url);
+
+    let body = config.login_body.clone().into_bytes();
+    let cost = get_request_costs(
+        &config.login_url,
+        body.len(),
+        DEFAULT_HTTP_MAX_RESPONSE_BYTES,
+    );
+    let res = http_outcall(
+        &config.login_url,
+        HttpMethod::POST,
+        Some(body),
+        cost,
+        Some(DEFAULT_HTTP_MAX_RESPONSE_BYTES),
+        vec![],
+        TransformContext::from_name(
+            "transform_login".to_string(),
+            config.cookie_header_name.clone().into_bytes(),
+        ),
+    )
+    .await?;
 
     if res.status != 200 {
         return Err(Error::Http(format!(
-            "error fetching price, status: {} res: {}",
-            res.status,
-            String::from_utf8(res.body).unwrap_or_default()
+            "error logging in to {}, status: {}",
+            config.login_url, res.status
         )));
     }
 
-    let json_body = serde_json::from_slice::<Value>(&res.body)
-        .map_err(|e| Error::Http(format!("serde_json err: {e}")))?;
+    let cookie = find_cookie(&res.headers, &config.cookie_header_name).ok_or_else(|| {
+        Error::Http(format!(
+            "login response without the '{}' cookie",
+            config.cookie_header_name
+        ))
+    })?;
+
+    let expires_at =
+        ic::time().saturating_add(config.cookie_ttl_secs.saturating_mul(1_000_000_000));
+    cookie_cache.insert(session_key, cookie.clone(), expires_at);
 
-    let price = json_body.parse(json_path)?;
+    Ok(cookie)
+}
 
-    let price_f64 = price
-        .as_str()
-        .map(|s| s.parse::<f64>())
-        .ok_or_else(|| Error::Internal(format!("price is not a f64, price: {}", price)))?
-        .map_err(|e| {
-            Error::Internal(format!("price is not a f64, price: {}, err: {}", price, e))
-        })?;
+/// Returns the value of the named cookie set by the `Set-Cookie` headers
+fn find_cookie(headers: &[HttpHeader], cookie_name: &str) -> Option<String> {
+    headers
+        .iter()
+        .filter(|header| header.name.eq_ignore_ascii_case("set-cookie"))
+        .find_map(|header| {
+            let pair = header.value.split(';').next()?;
+            let (name, value) = pair.split_once('=')?;
+            (name.trim() == cookie_name).then(|| value.trim().to_string())
+        })
+}
+
+/// Checks the response with the validators in order, failing with the first error
+fn check_response(response: &Value, validators: &[ResponseSchema]) -> Result<()> {
+    validators
+        .iter()
+        .try_for_each(|validator| validator.check(response))
+}
+
+/// Returns whether the HTTP status is one of the valid status codes
+fn is_valid_status(status: &candid::Nat, valid_status_codes: &[u16]) -> bool {
+    valid_status_codes
+        .iter()
+        .any(|code| *status == candid::Nat::from(u64::from(*code)))
+}
+
+/// Checks that the valid status codes of an HTTP origin are not empty and are
+/// HTTP status codes
+pub fn check_valid_status_codes(valid_status_codes: &[u16]) -> Result<()> {
+    if valid_status_codes.is_empty() {
+        return Err(Error::Internal(
+            "at least one valid status code is required".to_string(),
+        ));
+    }
+    if let Some(code) = valid_status_codes
+        .iter()
+        .find(|code| !(100..=599).contains(*code))
+    {
+        return Err(Error::Internal(format!("invalid HTTP status code {code}")));
+    }
 
-    let price_u64 = (price_f64 * PRICE_MULTIPLE).round() as u64;
+    Ok(())
+}
+
+/// Returns the `max-age` seconds of the `Cache-Control` header, if the response can be cached
+fn cache_max_age(headers: &[HttpHeader]) -> Option<u64> {
+    let header = headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case("cache-control"))?;
+
+    let mut max_age = None;
+    for directive in header.value.split(',').map(str::trim) {
+        if directive.eq_ignore_ascii_case("no-store") || directive.eq_ignore_ascii_case("no-cache")
+        {
+            return None;
+        }
+        if let Some((name, value)) = directive.split_once('=') {
+            if name.trim().eq_ignore_ascii_case("max-age") {
+                max_age = value.trim().trim_matches('"').parse::<u64>().ok();
+            }
+        }
+    }
+
+    max_age.filter(|max_age| *max_age > 0)
+}
+
+/// Authentication of the requests of an HTTP origin
+#[derive(Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub enum HttpAuth {
+    /// Sent as `Authorization: Bearer <token>`
+    Bearer(String),
+    /// Sent as `<header_name>: <key>`, e.g. `X-CMC_PRO_API_KEY`
+    ApiKey { header_name: String, key: String },
+}
+
+impl HttpAuth {
+    /// Returns the header carrying the credentials
+    fn header(&self) -> HttpHeader {
+        match self {
+            HttpAuth::Bearer(token) => HttpHeader {
+                name: "Authorization".to_string(),
+                value: format!("Bearer {token}"),
+            },
+            HttpAuth::ApiKey { header_name, key } => HttpHeader {
+                name: header_name.clone(),
+                value: key.clone(),
+            },
+        }
+    }
+}
 
-    Ok(U256::from(price_u64))
+/// HMAC signature of the requests of an HTTP origin
+///
+/// The signature covers the request timestamp, sent in the `HMAC_TIMESTAMP_HEADER`
+/// header, the URL and the body.
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HmacSigningConfig {
+    /// Id of the secret key in the provider credentials store
+    pub secret_key_id: u64,
+    pub algorithm: HmacAlgorithm,
+    /// Name of the header carrying the hex encoded signature
+    pub header_name: String,
+}
+
+impl HmacSigningConfig {
+    /// Checks that the signature header has a name
+    pub fn validate(&self) -> Result<()> {
+        if self.header_name.trim().is_empty() {
+            return Err(Error::Http(
+                "signature header name cannot be empty".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the timestamp and signature headers of a request sent at
+    /// `timestamp_secs`
+    fn headers(
+        &self,
+        secret_key: &[u8],
+        timestamp_secs: u64,
+        url: &str,
+        body: &[u8],
+    ) -> Vec<HttpHeader> {
+        let timestamp = timestamp_secs.to_string();
+        let message = [timestamp.as_bytes(), url.as_bytes(), body].concat();
+        let signature = match self.algorithm {
+            HmacAlgorithm::Sha256 => hmac_sha256_hex(secret_key, &message),
+        };
+
+        vec![
+            HttpHeader {
+                name: HMAC_TIMESTAMP_HEADER.to_string(),
+                value: timestamp,
+            },
+            HttpHeader {
+                name: self.header_name.clone(),
+                value: signature,
+            },
+        ]
+    }
+}
+
+/// Hash function of an HMAC request signature
+#[derive(Debug, Clone, Copy, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub enum HmacAlgorithm {
+    Sha256,
+}
+
+/// Returns the hex encoded HMAC-SHA256 of the message
+fn hmac_sha256_hex(key: &[u8], message: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Login flow of an HTTP origin serving its prices to sessions only
+#[derive(Clone, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CookieConfig {
+    /// URL the login body is posted to
+    pub login_url: String,
+    /// Body of the login request, stored in plaintext
+    pub login_body: String,
+    /// Name of the session cookie set by the login response
+    pub cookie_header_name: String,
+    /// Seconds after which the session cookie is renewed with a new login
+    pub cookie_ttl_secs: u64,
+}
+
+impl CookieConfig {
+    /// Checks that the cookie name can be sent in a `Cookie` header and that the
+    /// cookie is kept for some time
+    pub fn validate(&self) -> Result<()> {
+        let name = &self.cookie_header_name;
+        if name.is_empty() || name.contains(['=', ';', ' ']) {
+            return Err(Error::Http(format!("invalid session cookie name '{name}'")));
+        }
+        if self.cookie_ttl_secs == 0 {
+            return Err(Error::Http(
+                "session cookie ttl must be greater than 0".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the key of the session in the cookie cache
+    ///
+    /// The origins share a session only when they log in to the same URL with the
+    /// same body and read the same cookie. The body is hashed to keep the
+    /// credentials out of the cache.
+    pub fn session_key(&self) -> String {
+        format!(
+            "{} {} {}",
+            self.login_url,
+            hex::encode(Sha256::digest(self.login_body.as_bytes())),
+            self.cookie_header_name
+        )
+    }
+}
+
+/// Keeps the login credentials out of the logs
+impl std::fmt::Debug for CookieConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CookieConfig")
+            .field("login_url", &self.login_url)
+            .field("login_body", &"***")
+            .field("cookie_header_name", &self.cookie_header_name)
+            .field("cookie_ttl_secs", &self.cookie_ttl_secs)
+            .finish()
+    }
+}
+
+/// Keeps the credentials out of the logs
+impl std::fmt::Debug for HttpAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HttpAuth::Bearer(_) => f.write_str("Bearer(***)"),
+            HttpAuth::ApiKey { header_name, .. } => f
+                .debug_struct("ApiKey")
+                .field("header_name", header_name)
+                .field("key", &"***")
+                .finish(),
+        }
+    }
+}
+
+/// Returns the scheme and authority of the URL, leaving out its path and query
+/// which may hold credentials
+fn url_origin(url: &str) -> String {
+    Url::parse(url).map_or_else(
+        |_| String::from("invalid url"),
+        |url| url.origin().ascii_serialization(),
+    )
+}
+
+pub fn validate_https_url(url: &str) -> Result<()> {
+    let url = Url::parse(url).map_err(|e| Error::Http(e.to_string()))?;
+    if url.scheme() != "https" {
+        return Err(Error::Http("only HTTPS URLs are allowed".into()));
+    }
+
+    Ok(())
+}
+
+/// Checks whether the URL matches any of the allowed prefixes
+///
+/// The scheme, host, port and credentials must be equal, the path of the URL must
+/// start with the path of the prefix on a `/` boundary.
+pub fn is_url_allowed(url: &str, allowlist: &[String]) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return false;
+    };
+
+    allowlist
+        .iter()
+        .filter_map(|prefix| Url::parse(prefix).ok())
+        .any(|prefix| url_matches_prefix(&url, &prefix))
+}
+
+fn url_matches_prefix(url: &Url, prefix: &Url) -> bool {
+    if url.scheme() != prefix.scheme()
+        || url.host() != prefix.host()
+        || url.port_or_known_default() != prefix.port_or_known_default()
+        || url.username() != prefix.username()
+        || url.password() != prefix.password()
+    {
+        return false;
+    }
+
+    let prefix_path = prefix.path().trim_end_matches('/');
+    url.path() == prefix_path
+        || url
+            .path()
+            .strip_prefix(prefix_path)
+            .is_some_and(|rest| rest.starts_with('/'))
 }
 
 pub fn get_request_costs(source: &str, json_rpc_payload: usize, max_response_bytes: u64) -> u128 {
@@ -228,3 +904,501 @@ pub fn get_request_costs(source: &str, json_rpc_payload: usize, max_response_byt
         + HTTP_OUTCALL_REQUEST_COST
         + HTTP_OUTCALL_BYTE_RECEIVED_COST * (ingress_bytes + max_response_bytes as u128)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{AggregateOp, PriceParseMode};
+
+    #[test]
+    fn test_validate_https_url() {
+        assert!(validate_https_url("https://api.coinbase.com/v2/prices/BTC-ETH/spot").is_ok());
+        assert_eq!(
+            validate_https_url("http://api.coinbase.com/v2/prices/BTC-ETH/spot"),
+            Err(Error::Http("only HTTPS URLs are allowed".into()))
+        );
+        assert_eq!(
+            validate_https_url("ftp://example.com/price"),
+            Err(Error::Http("only HTTPS URLs are allowed".into()))
+        );
+        assert!(validate_https_url("example.com").is_err());
+    }
+
+    #[test]
+    fn test_hmac_sha256_hex() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            hmac_sha256_hex(b"Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_hmac_signing_headers() {
+        let config = HmacSigningConfig {
+            secret_key_id: 1,
+            algorithm: HmacAlgorithm::Sha256,
+            header_name: String::from("X-Signature"),
+        };
+        assert!(config.validate().is_ok());
+
+        let headers = config.headers(
+            b"secret",
+            1_700_000_000,
+            "https://example.com/price",
+            br#"{"pair":"BTC-USD"}"#,
+        );
+        assert_eq!(
+            headers,
+            vec![
+                HttpHeader {
+                    name: HMAC_TIMESTAMP_HEADER.to_string(),
+                    value: String::from("1700000000"),
+                },
+                HttpHeader {
+                    name: String::from("X-Signature"),
+                    value: String::from(
+                        "5350b703d38febd61c2d71a1923ad270a6ed74784d308d60d006644d94219d2e"
+                    ),
+                },
+            ]
+        );
+
+        let config = HmacSigningConfig {
+            header_name: String::from(" "),
+            ..config
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_url_origin() {
+        assert_eq!(
+            url_origin("https://eth-mainnet.example.com/v2/secret-key?foo=bar"),
+            "https://eth-mainnet.example.com"
+        );
+        assert_eq!(
+            url_origin("https://127.0.0.1:8545/"),
+            "https://127.0.0.1:8545"
+        );
+        assert_eq!(url_origin("example.com"), "invalid url");
+    }
+
+    #[test]
+    fn test_url_allowlist() {
+        let allowlist = vec![
+            "https://api.coinbase.com/v2/".to_string(),
+            "HTTPS://Example.COM".to_string(),
+        ];
+
+        assert!(is_url_allowed(
+            "https://api.coinbase.com/v2/prices/BTC-ETH/spot",
+            &allowlist
+        ));
+        assert!(is_url_allowed(
+            "HTTPS://API.COINBASE.COM/v2/prices/BTC-ETH/spot",
+            &allowlist
+        ));
+        assert!(is_url_allowed("https://example.com/price", &allowlist));
+
+        // The path is case-sensitive
+        assert!(!is_url_allowed(
+            "https://api.coinbase.com/V2/prices/BTC-ETH/spot",
+            &allowlist
+        ));
+        assert!(!is_url_allowed("https://api.binance.com/", &allowlist));
+        assert!(!is_url_allowed("https://example.com/price", &[]));
+
+        // The path only matches on segment boundaries
+        assert!(is_url_allowed("https://api.coinbase.com/v2", &allowlist));
+        assert!(!is_url_allowed(
+            "https://api.coinbase.com/v2evil/prices",
+            &allowlist
+        ));
+
+        // The host must be equal
+        assert!(!is_url_allowed(
+            "https://example.com.evil.io/price",
+            &allowlist
+        ));
+        assert!(!is_url_allowed(
+            "https://example.com@evil.io/price",
+            &allowlist
+        ));
+        assert!(!is_url_allowed(
+            "https://user@example.com/price",
+            &allowlist
+        ));
+
+        // The scheme and port must be equal
+        assert!(!is_url_allowed("http://example.com/price", &allowlist));
+        assert!(!is_url_allowed(
+            "https://example.com:8443/price",
+            &allowlist
+        ));
+        assert!(is_url_allowed("https://example.com:443/price", &allowlist));
+        assert!(!is_url_allowed("not a url", &allowlist));
+    }
+
+    #[test]
+    fn test_batch_request_body() {
+        let body = batch_request_body(vec![
+            (
+                "eth_getTransactionCount",
+                serde_json::json!(["0x01", "latest"]),
+            ),
+            ("eth_gasPrice", Value::Null),
+        ]);
+
+        assert_eq!(
+            body,
+            serde_json::json!([
+                {
+                    "jsonrpc": "2.0",
+                    "id": 0,
+                    "method": "eth_getTransactionCount",
+                    "params": ["0x01", "latest"],
+                },
+                {
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "method": "eth_gasPrice",
+                    "params": null,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_match_batch_outputs() {
+        let outputs: Vec<Output> = serde_json::from_value(serde_json::json!([
+            { "jsonrpc": "2.0", "id": 1, "result": "0x3b9aca00" },
+            { "jsonrpc": "2.0", "id": 0, "result": "0x5" },
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            match_batch_outputs(outputs, 2).unwrap(),
+            vec![
+                Value::String("0x5".to_string()),
+                Value::String("0x3b9aca00".to_string())
+            ]
+        );
+
+        let outputs: Vec<Output> = serde_json::from_value(serde_json::json!([
+            { "jsonrpc": "2.0", "id": 0, "result": "0x5" },
+        ]))
+        .unwrap();
+        assert!(match_batch_outputs(outputs, 2).is_err());
+
+        let outputs: Vec<Output> = serde_json::from_value(serde_json::json!([
+            { "jsonrpc": "2.0", "id": 0, "result": "0x5" },
+            { "jsonrpc": "2.0", "id": 1, "error": { "code": -32000, "message": "boom" } },
+        ]))
+        .unwrap();
+        assert_eq!(
+            match_batch_outputs(outputs, 2),
+            Err(Error::Http("JSON-RPC error: boom".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_http_auth_header() {
+        let bearer = HttpAuth::Bearer(String::from("secret-token"));
+        assert_eq!(
+            bearer.header(),
+            HttpHeader {
+                name: String::from("Authorization"),
+                value: String::from("Bearer secret-token"),
+            }
+        );
+
+        let api_key = HttpAuth::ApiKey {
+            header_name: String::from("X-CMC_PRO_API_KEY"),
+            key: String::from("secret-key"),
+        };
+        assert_eq!(
+            api_key.header(),
+            HttpHeader {
+                name: String::from("X-CMC_PRO_API_KEY"),
+                value: String::from("secret-key"),
+            }
+        );
+
+        // The credentials are not logged
+        assert!(!format!("{bearer:?}").contains("secret-token"));
+        assert!(!format!("{api_key:?}").contains("secret-key"));
+        assert!(format!("{api_key:?}").contains("X-CMC_PRO_API_KEY"));
+    }
+
+    #[test]
+    fn test_is_valid_status() {
+        let status = |code: u64| candid::Nat::from(code);
+
+        assert!(is_valid_status(&status(200), DEFAULT_VALID_STATUS_CODES));
+        assert!(!is_valid_status(&status(202), DEFAULT_VALID_STATUS_CODES));
+        assert!(!is_valid_status(&status(400), DEFAULT_VALID_STATUS_CODES));
+
+        // Proxied endpoints accepting the request
+        let valid_status_codes = [200, 202, 206];
+        assert!(is_valid_status(&status(200), &valid_status_codes));
+        assert!(is_valid_status(&status(202), &valid_status_codes));
+        assert!(!is_valid_status(&status(400), &valid_status_codes));
+    }
+
+    #[test]
+    fn test_check_valid_status_codes() {
+        assert!(check_valid_status_codes(DEFAULT_VALID_STATUS_CODES).is_ok());
+        assert!(check_valid_status_codes(&[100, 202, 599]).is_ok());
+        assert!(check_valid_status_codes(&[]).is_err());
+        assert!(check_valid_status_codes(&[200, 99]).is_err());
+        assert!(check_valid_status_codes(&[600]).is_err());
+    }
+
+    #[test]
+    fn test_extract_value() {
+        let json_body = serde_json::json!({"bid": "41900", "ask": "42100"});
+        let json_paths = vec![String::from("bid"), String::from("ask")];
+
+        let value = extract_value(&json_body, "", &json_paths, false).unwrap();
+        assert_eq!(value, serde_json::json!(["41900", "42100"]));
+        assert_eq!(
+            aggregate_price(&value, AggregateOp::Average, 0, PriceParseMode::Auto),
+            Ok(U256::from(42_000u64))
+        );
+        assert_eq!(
+            extract_value(&json_body, "", &json_paths, true).unwrap(),
+            value
+        );
+
+        // The JSON path takes precedence over the JSON paths
+        assert_eq!(
+            extract_value(&json_body, "bid", &json_paths, false).unwrap(),
+            serde_json::json!("41900")
+        );
+        assert_eq!(
+            extract_value(&json_body, "bid", &[], false).unwrap(),
+            serde_json::json!("41900")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_timeout() {
+        assert_eq!(
+            with_timeout(async { Ok(42) }, 100, future::pending()).await,
+            Ok(42)
+        );
+
+        // The timer fires before the delayed response arrives
+        let delayed_response = async {
+            tokio::task::yield_now().await;
+            Ok(42)
+        };
+        assert_eq!(
+            with_timeout(delayed_response, 100, async {}).await,
+            Err(Error::Timeout(100))
+        );
+
+        // Errors of the request are returned as is
+        assert_eq!(
+            with_timeout(
+                async { Err::<u32, _>(Error::Http("rejected".to_string())) },
+                100,
+                future::pending()
+            )
+            .await,
+            Err(Error::Http("rejected".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_check_paths() {
+        let forbidden_paths = vec![String::from("error")];
+        let required_paths = vec![String::from("data.amount")];
+
+        assert_eq!(
+            check_paths(
+                &serde_json::json!({"error": "xxx"}),
+                &forbidden_paths,
+                &[],
+                false
+            ),
+            Err(Error::ResponseForbiddenField {
+                path: String::from("error"),
+                value: String::from("\"xxx\""),
+            })
+        );
+        assert!(matches!(
+            check_paths(
+                &serde_json::json!({"data": null}),
+                &[],
+                &required_paths,
+                false
+            ),
+            Err(Error::SchemaValidationFailed(_))
+        ));
+
+        // The value of the required paths is not checked
+        let json_body = serde_json::json!({"data": {"amount": null}});
+        assert_eq!(
+            check_paths(&json_body, &forbidden_paths, &required_paths, false),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_cache_max_age() {
+        let headers = |value: &str| {
+            vec![HttpHeader {
+                name: "Cache-Control".to_string(),
+                value: value.to_string(),
+            }]
+        };
+
+        assert_eq!(cache_max_age(&headers("max-age=30")), Some(30));
+        assert_eq!(cache_max_age(&headers("public, MAX-AGE=60")), Some(60));
+        assert_eq!(cache_max_age(&headers("max-age=0")), None);
+        assert_eq!(cache_max_age(&headers("no-store, max-age=30")), None);
+        assert_eq!(cache_max_age(&headers("max-age=30, no-cache")), None);
+        assert_eq!(cache_max_age(&headers("public")), None);
+        assert_eq!(cache_max_age(&headers("max-age=soon")), None);
+        assert_eq!(cache_max_age(&[]), None);
+
+        let lowercase = vec![HttpHeader {
+            name: "cache-control".to_string(),
+            value: "max-age=10".to_string(),
+        }];
+        assert_eq!(cache_max_age(&lowercase), Some(10));
+    }
+
+    #[test]
+    fn test_check_response_rejects_error_envelope() {
+        let validators = vec![
+            ResponseSchema::ForbidField("error".to_string()),
+            ResponseSchema::RequireField("data.amount".to_string()),
+        ];
+
+        let price = serde_json::json!({ "data": { "amount": "42.5" } });
+        assert!(check_response(&price, &validators).is_ok());
+        assert!(check_response(&price, &[]).is_ok());
+
+        // The first failing validator is reported
+        let envelope = serde_json::json!({ "error": "rate_limited" });
+        assert_eq!(
+            check_response(&envelope, &validators),
+            Err(Error::SchemaValidationFailed(
+                "forbidden field 'error' found".to_string()
+            ))
+        );
+        assert_eq!(
+            check_response(&envelope, &validators[1..]),
+            Err(Error::SchemaValidationFailed(
+                "missing field 'data.amount'".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_find_cookie() {
+        let headers = vec![
+            HttpHeader {
+                name: "Content-Type".to_string(),
+                value: "application/json".to_string(),
+            },
+            HttpHeader {
+                name: "Set-Cookie".to_string(),
+                value: "tracking=1; Path=/".to_string(),
+            },
+            HttpHeader {
+                name: "set-cookie".to_string(),
+                value: "session=abc123; Path=/; HttpOnly; Secure".to_string(),
+            },
+        ];
+
+        assert_eq!(find_cookie(&headers, "session"), Some("abc123".to_string()));
+        assert_eq!(find_cookie(&headers, "tracking"), Some("1".to_string()));
+        assert_eq!(find_cookie(&headers, "sess"), None);
+        assert_eq!(find_cookie(&headers[..1], "session"), None);
+    }
+
+    #[test]
+    fn test_transform_login() {
+        let response = MHttpResponse {
+            status: 200u64.into(),
+            headers: vec![
+                HttpHeader {
+                    name: "Date".to_string(),
+                    value: "Mon, 01 Jan 2024 00:00:00 GMT".to_string(),
+                },
+                HttpHeader {
+                    name: "Set-Cookie".to_string(),
+                    value: "tracking=1; Path=/".to_string(),
+                },
+                HttpHeader {
+                    name: "Set-Cookie".to_string(),
+                    value: "session=abc123; Expires=Mon, 01 Jan 2024 01:00:00 GMT".to_string(),
+                },
+            ],
+            body: b"{\"ok\":true}".to_vec(),
+        };
+
+        // Only the session cookie is kept, without its attributes
+        let transformed = transform_login(TransformArgs {
+            response,
+            context: b"session".to_vec(),
+        });
+        assert_eq!(transformed.status, candid::Nat::from(200u64));
+        assert_eq!(
+            transformed.headers,
+            vec![HttpHeader {
+                name: "set-cookie".to_string(),
+                value: "session=abc123".to_string(),
+            }]
+        );
+        assert!(transformed.body.is_empty());
+    }
+
+    #[test]
+    fn test_cookie_config() {
+        let config = CookieConfig {
+            login_url: "https://example.com/login".to_string(),
+            login_body: "{\"password\":\"secret\"}".to_string(),
+            cookie_header_name: "session".to_string(),
+            cookie_ttl_secs: 3_600,
+        };
+        assert!(config.validate().is_ok());
+
+        // The login body is kept out of the logs
+        let debug = format!("{config:?}");
+        assert!(!debug.contains("secret"));
+        assert!(debug.contains("https://example.com/login"));
+
+        for name in ["", "session id", "session=1", "a;b"] {
+            let config = CookieConfig {
+                cookie_header_name: name.to_string(),
+                ..config.clone()
+            };
+            assert!(config.validate().is_err());
+        }
+
+        // The sessions of different credentials or cookies are not shared
+        let other_body = CookieConfig {
+            login_body: "{\"password\":\"other\"}".to_string(),
+            ..config.clone()
+        };
+        let other_cookie = CookieConfig {
+            cookie_header_name: "token".to_string(),
+            ..config.clone()
+        };
+        assert_eq!(config.session_key(), config.clone().session_key());
+        assert_ne!(config.session_key(), other_body.session_key());
+        assert_ne!(config.session_key(), other_cookie.session_key());
+        assert!(!config.session_key().contains("secret"));
+
+        let config = CookieConfig {
+            cookie_ttl_secs: 0,
+            ..config
+        };
+        assert!(config.validate().is_err());
+    }
+}
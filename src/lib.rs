@@ -1,7 +1,9 @@
 pub mod canister;
 pub mod constants;
 mod context;
+pub mod eip712;
 pub mod error;
+pub mod events;
 pub mod http;
 pub mod log;
 mod memory;
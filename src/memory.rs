@@ -1,4 +1,4 @@
-use ic_stable_structures::stable_structures::DefaultMemoryImpl;
+use ic_stable_structures::stable_structures::{DefaultMemoryImpl, Memory};
 use ic_stable_structures::{MemoryId, MemoryManager, VirtualMemory};
 
 thread_local! {
@@ -9,3 +9,14 @@ pub type MemoryType = VirtualMemory<DefaultMemoryImpl>;
 
 pub const SETTINGS_MEMORY_ID: MemoryId = MemoryId::new(1);
 pub const ORACLE_STORAGE_MEMORY_ID: MemoryId = MemoryId::new(2);
+pub const USER_ORACLE_COUNT_MEMORY_ID: MemoryId = MemoryId::new(3);
+pub const PROVIDER_REGISTRY_MEMORY_ID: MemoryId = MemoryId::new(4);
+pub const DEAD_LETTER_MEMORY_ID: MemoryId = MemoryId::new(5);
+pub const PROVIDER_CREDENTIALS_MEMORY_ID: MemoryId = MemoryId::new(6);
+pub const FUNCTION_REGISTRY_MEMORY_ID: MemoryId = MemoryId::new(7);
+pub const PROOF_NONCES_MEMORY_ID: MemoryId = MemoryId::new(8);
+
+/// Returns the number of 64 KiB pages allocated to the memory
+pub fn memory_pages(memory_id: MemoryId) -> u64 {
+    MEMORY_MANAGER.with(|mm| mm.get(memory_id).size())
+}
@@ -1,39 +1,622 @@
 use candid::CandidType;
+use did::U256;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Number, Value};
 use thiserror::Error;
 
-#[derive(Debug, CandidType, Serialize, Deserialize, Error, PartialEq, Eq)]
+#[derive(Debug, Clone, CandidType, Serialize, Deserialize, Error, PartialEq, Eq)]
 pub enum ParseError {
     #[error("Key '{0}' not found")]
     KeyNotFound(String),
     #[error("'{0}' is not an object")]
     NotAnObject(String),
+    #[error("wildcard '*' applied to a value that is not an object")]
+    WildcardOnNonObject,
+    #[error("invalid price: {0}")]
+    InvalidPrice(String),
+    #[error("no element matching the filter '{0}'")]
+    NoMatchingFilter(String),
+    #[error("invalid filter expression: {0}")]
+    InvalidFilter(String),
+    #[error("aggregation applied to a value that is not an array")]
+    AggregateOnNonArray,
+    #[error("invalid arithmetic expression: {0}")]
+    InvalidExpression(String),
+    #[error("division by zero")]
+    DivisionByZero,
 }
 
+/// Path component matching every field of an object
+const WILDCARD: &str = "*";
+
+/// Reads values from a JSON response
+///
+/// Both parsers also evaluate arithmetic expressions over paths and number
+/// literals, e.g. `data.price/1000` or `data.numerator/data.denominator`. A path
+/// resolving as is is never evaluated, so that keys containing an operator keep
+/// working. `*` and `/` take precedence over `+` and `-`, and the operands must be
+/// JSON numbers.
 pub trait ValueParser {
-    fn parse(&self, dot_path: &str) -> Result<&Value, ParseError>;
+    /// Returns the value at the given dot path
+    ///
+    /// A `*` component matches every field of an object; when the path has
+    /// wildcards, all the matching values are returned in an array.
+    fn parse(&self, dot_path: &str) -> Result<Value, ParseError>;
+
+    /// Returns the first value matching the given path, which can contain
+    /// `[?key=value]` and `[?key!=value]` filters on arrays and `[index]` indexes
+    ///
+    /// The filter value is a quoted string or a number, e.g.
+    /// `items[?status='ok'][0].price`. An index following a filter selects among
+    /// the matching elements.
+    fn parse_filtered(&self, path: &str) -> Result<Value, ParseError>;
 }
 
 impl ValueParser for Value {
-    fn parse(&self, dot_path: &str) -> Result<&Value, ParseError> {
-        let mut current_value = self;
-
-        for key in dot_path.split('.') {
-            match current_value {
-                Value::Object(map) => {
-                    current_value = map
-                        .get(key)
-                        .ok_or(ParseError::KeyNotFound(key.to_string()))?;
+    fn parse(&self, dot_path: &str) -> Result<Value, ParseError> {
+        parse_expression(dot_path, |dot_path| {
+            let keys = dot_path.split('.').collect::<Vec<_>>();
+
+            let mut matches = Vec::new();
+            collect_matches(self, &keys, &mut matches)?;
+
+            if keys.contains(&WILDCARD) {
+                return Ok(Value::Array(matches.into_iter().cloned().collect()));
+            }
+
+            // Without wildcards, the path matches exactly one value
+            Ok(matches.pop().cloned().unwrap_or_default())
+        })
+    }
+
+    fn parse_filtered(&self, path: &str) -> Result<Value, ParseError> {
+        parse_expression(path, |path| {
+            let segments = parse_segments(path)?;
+
+            let matches = collect_filtered_matches(self, &segments)?;
+
+            Ok(matches.into_iter().next().cloned().unwrap_or_default())
+        })
+    }
+}
+
+/// Resolves the path with `resolve`, or evaluates it as an arithmetic expression
+/// whose operands are resolved with `resolve` when it does not resolve as is
+fn parse_expression(
+    path: &str,
+    resolve: impl Fn(&str) -> Result<Value, ParseError>,
+) -> Result<Value, ParseError> {
+    let value = resolve(path);
+    if value.is_ok() {
+        return value;
+    }
+
+    match split_expression(path) {
+        Some((operands, operators)) => {
+            evaluate_expression(&operands, &operators, resolve).map(Value::Number)
+        }
+        None => value,
+    }
+}
+
+/// Arithmetic operator of a path expression
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl Operator {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            '+' => Some(Self::Add),
+            '-' => Some(Self::Subtract),
+            '*' => Some(Self::Multiply),
+            '/' => Some(Self::Divide),
+            _ => None,
+        }
+    }
+
+    /// Applies the operator, keeping integers when the result is an integer
+    /// that fits an `i64` and using floats otherwise
+    fn apply(self, lhs: &Number, rhs: &Number) -> Result<Number, ParseError> {
+        if self == Self::Divide && rhs.as_f64() == Some(0.0) {
+            return Err(ParseError::DivisionByZero);
+        }
+
+        if let (Some(lhs), Some(rhs)) = (lhs.as_i64(), rhs.as_i64()) {
+            let result = match self {
+                Self::Add => lhs.checked_add(rhs),
+                Self::Subtract => lhs.checked_sub(rhs),
+                Self::Multiply => lhs.checked_mul(rhs),
+                Self::Divide => lhs
+                    .checked_rem(rhs)
+                    .filter(|rem| *rem == 0)
+                    .and_then(|_| lhs.checked_div(rhs)),
+            };
+            if let Some(result) = result {
+                return Ok(result.into());
+            }
+        }
+
+        let (lhs, rhs) = (as_f64(lhs)?, as_f64(rhs)?);
+        let result = match self {
+            Self::Add => lhs + rhs,
+            Self::Subtract => lhs - rhs,
+            Self::Multiply => lhs * rhs,
+            Self::Divide => lhs / rhs,
+        };
+
+        Number::from_f64(result).ok_or_else(|| {
+            ParseError::InvalidExpression(format!("{result} is not a finite number"))
+        })
+    }
+}
+
+fn as_f64(number: &Number) -> Result<f64, ParseError> {
+    number
+        .as_f64()
+        .ok_or_else(|| ParseError::InvalidExpression(format!("{number} is not a float")))
+}
+
+/// Splits an arithmetic expression into its operands and the operators between
+/// them, or returns `None` when the path has no operator
+///
+/// The operators inside brackets are part of the filters. A `*` component is a
+/// wildcard, and a `-` starting an operand is the sign of a literal.
+fn split_expression(path: &str) -> Option<(Vec<&str>, Vec<Operator>)> {
+    let mut operands = Vec::new();
+    let mut operators = Vec::new();
+    let mut start = 0;
+    let mut brackets = 0usize;
+
+    for (i, c) in path.char_indices() {
+        match c {
+            '[' => brackets += 1,
+            ']' => brackets = brackets.saturating_sub(1),
+            _ if brackets > 0 => {}
+            c => {
+                let Some(operator) = Operator::from_char(c) else {
+                    continue;
+                };
+
+                let operand = path[start..i].trim();
+                let is_sign = operator == Operator::Subtract && operand.is_empty();
+                let is_wildcard = operator == Operator::Multiply
+                    && matches!(path[..i].chars().last(), None | Some('.'))
+                    && matches!(path[i + 1..].chars().next(), None | Some('.'));
+                if is_sign || is_wildcard {
+                    continue;
+                }
+
+                operands.push(operand);
+                operators.push(operator);
+                start = i + 1;
+            }
+        }
+    }
+    operands.push(path[start..].trim());
+
+    (!operators.is_empty()).then_some((operands, operators))
+}
+
+/// Evaluates the expression, resolving the operands that are not number literals
+/// with `resolve`
+fn evaluate_expression(
+    operands: &[&str],
+    operators: &[Operator],
+    resolve: impl Fn(&str) -> Result<Value, ParseError>,
+) -> Result<Number, ParseError> {
+    let mut values = operands.iter().map(|operand| {
+        if operand.is_empty() {
+            return Err(ParseError::InvalidExpression("missing operand".to_string()));
+        }
+        if let Ok(literal) = serde_json::from_str::<Number>(operand) {
+            return Ok(literal);
+        }
+
+        match resolve(operand)? {
+            Value::Number(number) => Ok(number),
+            value => Err(ParseError::InvalidExpression(format!(
+                "'{operand}' is {value}, not a number"
+            ))),
+        }
+    });
+
+    let Some(first) = values.next() else {
+        return Err(ParseError::InvalidExpression("missing operand".to_string()));
+    };
+
+    // The terms are summed once their products and quotients are computed
+    let mut sum: Option<(Number, Operator)> = None;
+    let mut term = first?;
+    for (operator, value) in operators.iter().zip(values) {
+        let value = value?;
+        match operator {
+            Operator::Multiply | Operator::Divide => term = operator.apply(&term, &value)?,
+            Operator::Add | Operator::Subtract => {
+                let total = match sum {
+                    Some((total, pending)) => pending.apply(&total, &term)?,
+                    None => term,
+                };
+                sum = Some((total, *operator));
+                term = value;
+            }
+        }
+    }
+
+    match sum {
+        Some((total, pending)) => pending.apply(&total, &term),
+        None => Ok(term),
+    }
+}
+
+/// Checks the syntax of a path with filter expressions
+pub fn check_filtered_path(path: &str) -> Result<(), ParseError> {
+    parse_segments(path).map(|_| ())
+}
+
+/// Component of a path with filter expressions
+#[derive(Debug, PartialEq)]
+enum Segment<'a> {
+    Key(&'a str),
+    Wildcard,
+    Index(usize),
+    Filter(Filter<'a>),
+}
+
+/// Filter selecting the elements of an array whose `key` field equals, or differs
+/// from, a literal
+#[derive(Debug, PartialEq)]
+struct Filter<'a> {
+    expression: &'a str,
+    key: &'a str,
+    negated: bool,
+    literal: Value,
+}
+
+impl<'a> Filter<'a> {
+    /// Parses a filter expression, without its `[?` and `]` delimiters
+    fn parse(expression: &'a str) -> Result<Self, ParseError> {
+        let (key, literal, negated) = match expression.split_once("!=") {
+            Some((key, literal)) => (key, literal, true),
+            None => {
+                let (key, literal) = expression.split_once('=').ok_or_else(|| {
+                    ParseError::InvalidFilter(format!("missing comparison in '{expression}'"))
+                })?;
+                (key, literal, false)
+            }
+        };
+
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(ParseError::InvalidFilter(format!(
+                "missing key in '{expression}'"
+            )));
+        }
+
+        Ok(Self {
+            expression,
+            key,
+            negated,
+            literal: parse_literal(literal.trim())?,
+        })
+    }
+
+    fn matches(&self, value: &Value) -> bool {
+        let Some(field) = value.get(self.key) else {
+            return false;
+        };
+
+        let equal = match (&self.literal, field) {
+            (Value::Number(literal), Value::Number(field)) => literal.as_f64() == field.as_f64(),
+            (literal, field) => literal == field,
+        };
+
+        equal != self.negated
+    }
+}
+
+/// Parses the literal of a filter, a quoted string or a number
+fn parse_literal(literal: &str) -> Result<Value, ParseError> {
+    for quote in ['\'', '"'] {
+        if let Some(string) = literal
+            .strip_prefix(quote)
+            .and_then(|literal| literal.strip_suffix(quote))
+        {
+            return Ok(Value::String(string.to_string()));
+        }
+    }
+
+    literal
+        .parse::<f64>()
+        .ok()
+        .and_then(serde_json::Number::from_f64)
+        .map(Value::Number)
+        .ok_or_else(|| ParseError::InvalidFilter(format!("invalid value '{literal}'")))
+}
+
+/// Splits a path with filter expressions into its segments
+fn parse_segments(path: &str) -> Result<Vec<Segment<'_>>, ParseError> {
+    let mut segments = Vec::new();
+    let mut rest = path;
+
+    while !rest.is_empty() {
+        if let Some(bracketed) = rest.strip_prefix('[') {
+            let end = bracketed.find(']').ok_or_else(|| {
+                ParseError::InvalidFilter(format!("unclosed bracket in '{path}'"))
+            })?;
+            let content = &bracketed[..end];
+
+            segments.push(match content.strip_prefix('?') {
+                Some(expression) => Segment::Filter(Filter::parse(expression)?),
+                None => Segment::Index(content.parse().map_err(|_| {
+                    ParseError::InvalidFilter(format!("invalid index '{content}'"))
+                })?),
+            });
+            rest = &bracketed[end + 1..];
+        } else {
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            let key = &rest[..end];
+
+            segments.push(if key == WILDCARD {
+                Segment::Wildcard
+            } else {
+                Segment::Key(key)
+            });
+            rest = &rest[end..];
+        }
+
+        rest = rest.strip_prefix('.').unwrap_or(rest);
+    }
+
+    Ok(segments)
+}
+
+/// Returns the values matching the path `segments` from `value`
+///
+/// A filter matching no element is an error.
+fn collect_filtered_matches<'a>(
+    value: &'a Value,
+    segments: &[Segment],
+) -> Result<Vec<&'a Value>, ParseError> {
+    let mut matches = vec![value];
+    let mut after_filter = false;
+
+    for segment in segments {
+        matches = match segment {
+            Segment::Key(key) => matches
+                .into_iter()
+                .map(|value| match value {
+                    Value::Object(map) => map
+                        .get(*key)
+                        .ok_or(ParseError::KeyNotFound(key.to_string())),
+                    _ => Err(ParseError::NotAnObject(key.to_string())),
+                })
+                .collect::<Result<_, _>>()?,
+            Segment::Wildcard => {
+                let mut children = Vec::new();
+                for value in matches {
+                    let Value::Object(map) = value else {
+                        return Err(ParseError::WildcardOnNonObject);
+                    };
+                    children.extend(map.values());
+                }
+
+                children
+            }
+            // An index following a filter selects among the matching elements
+            Segment::Index(index) if after_filter => {
+                let value = matches
+                    .get(*index)
+                    .ok_or(ParseError::KeyNotFound(format!("[{index}]")))?;
+
+                vec![*value]
+            }
+            Segment::Index(index) => matches
+                .into_iter()
+                .map(|value| {
+                    value
+                        .as_array()
+                        .and_then(|elements| elements.get(*index))
+                        .ok_or(ParseError::KeyNotFound(format!("[{index}]")))
+                })
+                .collect::<Result<_, _>>()?,
+            Segment::Filter(filter) => {
+                let mut selected = Vec::new();
+                for value in matches {
+                    let Value::Array(elements) = value else {
+                        return Err(ParseError::InvalidFilter(format!(
+                            "'{}' applied to a value that is not an array",
+                            filter.expression
+                        )));
+                    };
+                    selected.extend(elements.iter().filter(|element| filter.matches(element)));
+                }
+
+                if selected.is_empty() {
+                    return Err(ParseError::NoMatchingFilter(filter.expression.to_string()));
                 }
-                _ => return Err(ParseError::NotAnObject(key.to_string())),
+
+                selected
+            }
+        };
+
+        after_filter = matches!(segment, Segment::Filter(_));
+    }
+
+    Ok(matches)
+}
+
+/// Pushes the values matching the `keys` path from `value` to `matches`
+fn collect_matches<'a>(
+    value: &'a Value,
+    keys: &[&str],
+    matches: &mut Vec<&'a Value>,
+) -> Result<(), ParseError> {
+    let Some((key, rest)) = keys.split_first() else {
+        matches.push(value);
+        return Ok(());
+    };
+
+    match value {
+        Value::Object(map) if *key == WILDCARD => {
+            for child in map.values() {
+                collect_matches(child, rest, matches)?;
             }
+
+            Ok(())
+        }
+        Value::Object(map) => {
+            let child = map
+                .get(*key)
+                .ok_or(ParseError::KeyNotFound(key.to_string()))?;
+
+            collect_matches(child, rest, matches)
+        }
+        _ if *key == WILDCARD => Err(ParseError::WildcardOnNonObject),
+        _ => Err(ParseError::NotAnObject(key.to_string())),
+    }
+}
+
+/// How the price is read from the JSON value returned by an HTTP origin
+#[derive(Debug, Default, Clone, Copy, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub enum PriceParseMode {
+    /// Coerces the value to a price: tries a decimal string, then a JSON number
+    #[default]
+    Auto,
+    /// Only accepts a decimal string, e.g. `"42000.5"`
+    String,
+    /// Only accepts a JSON number, e.g. `42000.5`
+    Float,
+    /// An integer already expressed with the decimals of the oracle
+    Integer,
+}
+
+/// Converts a price to an integer with the given number of decimals
+///
+/// With `PriceParseMode::Integer` the price is returned as is.
+pub fn coerce_to_price(
+    value: &Value,
+    decimals: u8,
+    mode: PriceParseMode,
+) -> Result<U256, ParseError> {
+    let price = match mode {
+        PriceParseMode::Integer => {
+            return value
+                .as_u64()
+                .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+                .map(U256::from)
+                .ok_or_else(|| ParseError::InvalidPrice(format!("{value} is not an integer")));
+        }
+        mode => parse_f64(value, mode)?,
+    };
+
+    Ok(scale_price(price, decimals))
+}
+
+/// Reduction of the array of values matched by the JSON path of an HTTP origin
+/// to a single price
+#[derive(Debug, Clone, Copy, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub enum AggregateOp {
+    Sum,
+    Average,
+    Min,
+    Max,
+    Median,
+    /// The number of elements, returned as is
+    Count,
+    First,
+    Last,
+}
+
+/// Reduces an array of values to a price with the given number of decimals
+///
+/// The elements are read as with `coerce_to_price`, except that with
+/// `PriceParseMode::Integer` the numbers are reduced as decimals and the result
+/// is rounded. `AggregateOp::Count` returns the number of elements as is.
+pub fn aggregate_price(
+    value: &Value,
+    op: AggregateOp,
+    decimals: u8,
+    mode: PriceParseMode,
+) -> Result<U256, ParseError> {
+    let Value::Array(elements) = value else {
+        return Err(ParseError::AggregateOnNonArray);
+    };
+
+    let empty = || ParseError::InvalidPrice("aggregation of an empty array".to_string());
+    let element = match op {
+        AggregateOp::Count => return Ok(U256::from(elements.len() as u64)),
+        AggregateOp::First => Some(elements.first()),
+        AggregateOp::Last => Some(elements.last()),
+        _ => None,
+    };
+    if let Some(element) = element {
+        return coerce_to_price(element.ok_or_else(empty)?, decimals, mode);
+    }
+
+    let mut values = elements
+        .iter()
+        .map(|element| parse_f64(element, mode))
+        .collect::<Result<Vec<_>, _>>()?;
+    if values.is_empty() {
+        return Err(empty());
+    }
+    values.sort_by(f64::total_cmp);
+
+    let len = values.len();
+    let aggregated = match op {
+        AggregateOp::Sum => values.iter().sum(),
+        AggregateOp::Average => values.iter().sum::<f64>() / len as f64,
+        AggregateOp::Min => values[0],
+        AggregateOp::Max => values[len - 1],
+        AggregateOp::Median if len % 2 == 0 => (values[len / 2 - 1] + values[len / 2]) / 2.0,
+        AggregateOp::Median => values[len / 2],
+        AggregateOp::Count | AggregateOp::First | AggregateOp::Last => {
+            unreachable!("handled above")
         }
+    };
+
+    if mode == PriceParseMode::Integer {
+        return Ok(U256::from(aggregated.round() as u64));
+    }
 
-        Ok(current_value)
+    Ok(scale_price(aggregated, decimals))
+}
+
+/// Reads a price as f64; numbers and decimal strings are both accepted, except
+/// with `PriceParseMode::String` and `PriceParseMode::Float`
+fn parse_f64(value: &Value, mode: PriceParseMode) -> Result<f64, ParseError> {
+    match mode {
+        PriceParseMode::String => parse_decimal_str(value),
+        PriceParseMode::Auto | PriceParseMode::Integer if value.is_string() => {
+            parse_decimal_str(value)
+        }
+        _ => value
+            .as_f64()
+            .ok_or_else(|| ParseError::InvalidPrice(format!("{value} is not a number"))),
     }
 }
 
+/// Converts a price to an integer with the given number of decimals
+fn scale_price(price: f64, decimals: u8) -> U256 {
+    let multiplier = 10_f64.powi(decimals as i32);
+
+    U256::from((price * multiplier).round() as u64)
+}
+
+/// Parses a decimal string as f64
+fn parse_decimal_str(value: &Value) -> Result<f64, ParseError> {
+    value
+        .as_str()
+        .ok_or_else(|| ParseError::InvalidPrice(format!("{value} is not a string")))?
+        .parse::<f64>()
+        .map_err(|e| ParseError::InvalidPrice(format!("{value}: {e}")))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -54,7 +637,7 @@ mod tests {
 
         assert_eq!(
             parsed_data.parse(dot_notations).unwrap(),
-            &Value::Number(100.into())
+            Value::Number(100.into())
         )
     }
 
@@ -76,7 +659,7 @@ mod tests {
 
         assert_eq!(
             parsed_data.parse(dot_notations).unwrap(),
-            &Value::Number(100.into())
+            Value::Number(100.into())
         )
     }
 
@@ -100,7 +683,402 @@ mod tests {
 
         assert_eq!(
             parsed_data.parse(dot_notations).unwrap(),
-            &Value::Number(800.into())
+            Value::Number(800.into())
         )
     }
+
+    #[test]
+    fn test_wildcard_parser() {
+        let data = serde_json::json!({
+            "data": {
+                "a": { "price": 1 },
+                "b": { "price": 2 }
+            }
+        });
+
+        assert_eq!(
+            data.parse("data.*.price").unwrap(),
+            serde_json::json!([1, 2])
+        );
+    }
+
+    #[test]
+    fn test_nested_wildcard_parser() {
+        let data = serde_json::json!({
+            "bitcoin": { "usd": { "price": 42000 }, "eur": { "price": 38000 } },
+            "ethereum": { "usd": { "price": 2200 } }
+        });
+
+        assert_eq!(
+            data.parse("*.*.price").unwrap(),
+            serde_json::json!([38000, 42000, 2200])
+        );
+        assert_eq!(
+            data.parse("bitcoin.*").unwrap(),
+            serde_json::json!([{ "price": 38000 }, { "price": 42000 }])
+        );
+
+        // A missing key in any of the matched objects is an error
+        assert_eq!(
+            data.parse("*.eur.price"),
+            Err(ParseError::KeyNotFound("eur".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_wildcard_parser_on_empty_object() {
+        let data = serde_json::json!({ "data": {} });
+
+        assert_eq!(data.parse("data.*.price").unwrap(), serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_wildcard_parser_on_non_object() {
+        let data = serde_json::json!({ "data": [1, 2], "price": 100 });
+
+        assert_eq!(data.parse("data.*"), Err(ParseError::WildcardOnNonObject));
+        assert_eq!(data.parse("price.*"), Err(ParseError::WildcardOnNonObject));
+    }
+
+    #[test]
+    fn test_arithmetic_expressions() {
+        let data = serde_json::json!({
+            "data": {
+                "price": 42500,
+                "float_price": 2.5,
+                "numerator": 300,
+                "denominator": 4,
+                "zero": 0,
+                "label": "BTC",
+                "usd-coin": 1,
+            },
+            "items": [{ "symbol": "BTC-USD", "price": 10 }],
+        });
+
+        // Literal operands
+        assert_eq!(data.parse("data.price/1000"), Ok(serde_json::json!(42.5)));
+        assert_eq!(data.parse("data.price / 100"), Ok(serde_json::json!(425)));
+        assert_eq!(
+            data.parse("data.float_price*1000"),
+            Ok(serde_json::json!(2500.0))
+        );
+        assert_eq!(data.parse("data.price-500"), Ok(serde_json::json!(42000)));
+        assert_eq!(data.parse("data.price*-1"), Ok(serde_json::json!(-42500)));
+
+        // Path operands
+        assert_eq!(
+            data.parse("data.numerator/data.denominator"),
+            Ok(serde_json::json!(75))
+        );
+        assert_eq!(
+            data.parse("data.numerator*data.denominator"),
+            Ok(serde_json::json!(1200))
+        );
+        assert_eq!(
+            data.parse("data.numerator-data.denominator"),
+            Ok(serde_json::json!(296))
+        );
+        assert_eq!(
+            data.parse("data.float_price-data.denominator"),
+            Ok(serde_json::json!(-1.5))
+        );
+
+        // Products and quotients are computed before sums and differences
+        assert_eq!(
+            data.parse("data.numerator-data.denominator*25"),
+            Ok(serde_json::json!(200))
+        );
+
+        assert_eq!(
+            data.parse("data.price/data.zero"),
+            Err(ParseError::DivisionByZero)
+        );
+        assert_eq!(data.parse("data.price/0"), Err(ParseError::DivisionByZero));
+        assert!(matches!(
+            data.parse("data.label*2"),
+            Err(ParseError::InvalidExpression(_))
+        ));
+        assert!(matches!(
+            data.parse("data.price/"),
+            Err(ParseError::InvalidExpression(_))
+        ));
+        assert_eq!(
+            data.parse("data.missing/2"),
+            Err(ParseError::KeyNotFound("missing".to_string()))
+        );
+
+        // Keys containing an operator and wildcards are not expressions
+        assert_eq!(data.parse("data.usd-coin"), Ok(serde_json::json!(1)));
+        assert!(data.parse("data.*").unwrap().is_array());
+
+        // Operators inside filters are part of the filter
+        assert_eq!(
+            data.parse_filtered("items[?symbol='BTC-USD'].price*2"),
+            Ok(serde_json::json!(20))
+        );
+    }
+
+    #[test]
+    fn test_coerce_to_price_decimals() {
+        let price = Value::String("1.2345678".to_string());
+
+        assert_eq!(
+            coerce_to_price(&price, 8, PriceParseMode::Auto).unwrap(),
+            U256::from(123_456_780u64)
+        );
+        assert_eq!(
+            coerce_to_price(&price, 6, PriceParseMode::Auto).unwrap(),
+            U256::from(1_234_568u64)
+        );
+        assert_eq!(
+            coerce_to_price(&price, 10, PriceParseMode::Auto).unwrap(),
+            U256::from(12_345_678_000u64)
+        );
+        assert_eq!(
+            coerce_to_price(&Value::String("42.5".to_string()), 6, PriceParseMode::Auto).unwrap(),
+            U256::from(42_500_000u64)
+        );
+    }
+
+    #[test]
+    fn test_coerce_to_price_modes() {
+        let string = serde_json::json!("1.5");
+        let integer_string = serde_json::json!("42000");
+        let float = serde_json::json!(1.5);
+        let integer = serde_json::json!(42000);
+        let negative = serde_json::json!(-1);
+        let invalid = [
+            serde_json::json!(true),
+            Value::Null,
+            serde_json::json!("abc"),
+            serde_json::json!([1]),
+        ];
+
+        // Auto coerces strings and numbers
+        let auto = |value| coerce_to_price(value, 2, PriceParseMode::Auto);
+        assert_eq!(auto(&string), Ok(U256::from(150u64)));
+        assert_eq!(auto(&integer_string), Ok(U256::from(4_200_000u64)));
+        assert_eq!(auto(&float), Ok(U256::from(150u64)));
+        assert_eq!(auto(&integer), Ok(U256::from(4_200_000u64)));
+        for value in &invalid {
+            assert!(auto(value).is_err());
+        }
+
+        // String and Float only accept their own type
+        let string_mode = |value| coerce_to_price(value, 2, PriceParseMode::String);
+        assert_eq!(string_mode(&string), Ok(U256::from(150u64)));
+        assert_eq!(string_mode(&integer_string), Ok(U256::from(4_200_000u64)));
+        assert!(string_mode(&float).is_err());
+        assert!(string_mode(&integer).is_err());
+        for value in &invalid {
+            assert!(string_mode(value).is_err());
+        }
+
+        let float_mode = |value| coerce_to_price(value, 2, PriceParseMode::Float);
+        assert!(float_mode(&string).is_err());
+        assert!(float_mode(&integer_string).is_err());
+        assert_eq!(float_mode(&float), Ok(U256::from(150u64)));
+        assert_eq!(float_mode(&integer), Ok(U256::from(4_200_000u64)));
+        for value in &invalid {
+            assert!(float_mode(value).is_err());
+        }
+
+        // Integer prices are not scaled by the decimals
+        let integer_mode = |value| coerce_to_price(value, 2, PriceParseMode::Integer);
+        assert!(integer_mode(&string).is_err());
+        assert_eq!(integer_mode(&integer_string), Ok(U256::from(42000u64)));
+        assert!(integer_mode(&float).is_err());
+        assert_eq!(integer_mode(&integer), Ok(U256::from(42000u64)));
+        assert!(integer_mode(&negative).is_err());
+        for value in &invalid {
+            assert!(integer_mode(value).is_err());
+        }
+    }
+
+    #[test]
+    fn test_filtered_parser() {
+        let data = serde_json::json!({
+            "items": [
+                { "status": "stale", "price": 1, "volume": 10 },
+                { "status": "ok", "price": 2, "volume": 20.0 },
+                { "status": "ok", "price": 3, "volume": 30 }
+            ]
+        });
+
+        // String equality
+        assert_eq!(
+            data.parse_filtered("items[?status='ok'][0].price"),
+            Ok(serde_json::json!(2))
+        );
+        assert_eq!(
+            data.parse_filtered("items[?status=\"ok\"][1].price"),
+            Ok(serde_json::json!(3))
+        );
+        // The first matching element is used without index
+        assert_eq!(
+            data.parse_filtered("items[?status='ok'].price"),
+            Ok(serde_json::json!(2))
+        );
+
+        // Numeric equality, regardless of the number representation
+        assert_eq!(
+            data.parse_filtered("items[?volume=20].price"),
+            Ok(serde_json::json!(2))
+        );
+        assert_eq!(
+            data.parse_filtered("items[?price=3.0].volume"),
+            Ok(serde_json::json!(30))
+        );
+
+        // Inequality
+        assert_eq!(
+            data.parse_filtered("items[?status!='ok'].price"),
+            Ok(serde_json::json!(1))
+        );
+
+        // Plain indexes and dot paths
+        assert_eq!(
+            data.parse_filtered("items[2].price"),
+            Ok(serde_json::json!(3))
+        );
+
+        assert_eq!(
+            data.parse_filtered("items[?status='error'].price"),
+            Err(ParseError::NoMatchingFilter("status='error'".to_string()))
+        );
+        assert_eq!(
+            data.parse_filtered("items[?status='ok'][2].price"),
+            Err(ParseError::KeyNotFound("[2]".to_string()))
+        );
+        assert!(matches!(
+            data.parse_filtered("items[0][?status='ok']"),
+            Err(ParseError::InvalidFilter(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_filtered_path() {
+        assert!(check_filtered_path("items[?status='ok'][0].price").is_ok());
+        assert!(check_filtered_path("data.*.price").is_ok());
+
+        for path in [
+            "items[?status='ok'",
+            "items[?status]",
+            "items[?='ok']",
+            "items[?status=ok]",
+            "items[first]",
+        ] {
+            assert!(
+                matches!(check_filtered_path(path), Err(ParseError::InvalidFilter(_))),
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_aggregate_price() {
+        let values = serde_json::json!([1.5, "2.5", 3, "4"]);
+        let aggregate = |value, op| aggregate_price(value, op, 2, PriceParseMode::Auto);
+
+        assert_eq!(
+            aggregate(&values, AggregateOp::Sum),
+            Ok(U256::from(1100u64))
+        );
+        assert_eq!(
+            aggregate(&values, AggregateOp::Average),
+            Ok(U256::from(275u64))
+        );
+        assert_eq!(aggregate(&values, AggregateOp::Min), Ok(U256::from(150u64)));
+        assert_eq!(aggregate(&values, AggregateOp::Max), Ok(U256::from(400u64)));
+        assert_eq!(
+            aggregate(&values, AggregateOp::Median),
+            Ok(U256::from(275u64))
+        );
+        assert_eq!(
+            aggregate(&serde_json::json!([3, "1", 2.0]), AggregateOp::Median),
+            Ok(U256::from(200u64))
+        );
+        assert_eq!(
+            aggregate(&values, AggregateOp::First),
+            Ok(U256::from(150u64))
+        );
+        assert_eq!(
+            aggregate(&values, AggregateOp::Last),
+            Ok(U256::from(400u64))
+        );
+
+        // The count is not scaled by the decimals and ignores the element types
+        let mixed = serde_json::json!([1, "a", null, { "price": 2 }]);
+        assert_eq!(aggregate(&mixed, AggregateOp::Count), Ok(U256::from(4u64)));
+        assert_eq!(
+            aggregate(&mixed, AggregateOp::First),
+            Ok(U256::from(100u64))
+        );
+        assert!(aggregate(&mixed, AggregateOp::Last).is_err());
+        assert!(aggregate(&mixed, AggregateOp::Sum).is_err());
+        assert!(aggregate(&serde_json::json!([1, null]), AggregateOp::Max).is_err());
+
+        let empty = serde_json::json!([]);
+        assert_eq!(aggregate(&empty, AggregateOp::Count), Ok(U256::zero()));
+        for op in [AggregateOp::Sum, AggregateOp::Median, AggregateOp::First] {
+            assert!(aggregate(&empty, op).is_err());
+        }
+
+        assert_eq!(
+            aggregate(&serde_json::json!("1.5"), AggregateOp::Sum),
+            Err(ParseError::AggregateOnNonArray)
+        );
+        assert_eq!(
+            aggregate(&serde_json::json!({ "a": 1 }), AggregateOp::Count),
+            Err(ParseError::AggregateOnNonArray)
+        );
+    }
+
+    #[test]
+    fn test_aggregate_price_modes() {
+        let values = serde_json::json!([1.5, "2.5"]);
+
+        // String and Float only accept their own type
+        assert!(aggregate_price(&values, AggregateOp::Sum, 2, PriceParseMode::String).is_err());
+        assert!(aggregate_price(&values, AggregateOp::Sum, 2, PriceParseMode::Float).is_err());
+        assert_eq!(
+            aggregate_price(
+                &serde_json::json!(["1.5", "2.5"]),
+                AggregateOp::Sum,
+                2,
+                PriceParseMode::String
+            ),
+            Ok(U256::from(400u64))
+        );
+
+        // Integer prices are rounded but not scaled by the decimals
+        let integers = serde_json::json!([100, "251"]);
+        assert_eq!(
+            aggregate_price(&integers, AggregateOp::Average, 2, PriceParseMode::Integer),
+            Ok(U256::from(176u64))
+        );
+        assert_eq!(
+            aggregate_price(&integers, AggregateOp::Last, 2, PriceParseMode::Integer),
+            Ok(U256::from(251u64))
+        );
+    }
+
+    #[test]
+    fn test_aggregate_wildcard_matches() {
+        let data = serde_json::json!({
+            "binance": { "price": "100.5" },
+            "coinbase": { "price": 101.5 },
+            "kraken": { "price": 102 },
+        });
+
+        let prices = data.parse("*.price").unwrap();
+        assert_eq!(
+            aggregate_price(&prices, AggregateOp::Median, 1, PriceParseMode::Auto),
+            Ok(U256::from(1015u64))
+        );
+        assert_eq!(
+            aggregate_price(&prices, AggregateOp::Count, 1, PriceParseMode::Auto),
+            Ok(U256::from(3u64))
+        );
+    }
 }
@@ -1,32 +1,149 @@
 use std::cell::RefCell;
+use std::future::Future;
 use std::rc::Rc;
 
 use candid::CandidType;
-use did::{H160, U256};
+use did::{H160, H256, U256};
 use eth_signer::sign_strategy::TransactionSigner;
-use ethers_core::abi::{Function, Param, ParamType, StateMutability};
+use ethers_core::abi::param_type::Reader;
+use ethers_core::abi::{Function, Param, ParamType, StateMutability, Token};
 use ethers_core::types::transaction::eip2718::TypedTransaction;
+use ethers_core::types::Signature;
+use ethers_core::utils::keccak256;
+use ic_exports::ic_kit::ic;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
+use crate::constants::{API_KEY_PLACEHOLDER, REPLACEMENT_GAS_PRICE_PERCENT};
 use crate::context::Context;
-use crate::error::{Error, Result};
+use crate::error::{Error, OracleStage, PipelineResult, Result, WithStage};
 use crate::http;
+use crate::state::{FunctionRegistry, ProviderCredentials};
 
 #[derive(Debug, CandidType, Deserialize, Serialize, Clone, PartialEq, Eq)]
 pub struct Provider {
     pub chain_id: u64,
-    pub hostname: String,
+    /// JSON-RPC endpoints of the provider, tried in order
+    pub endpoints: Vec<String>,
 }
 
+impl Provider {
+    /// Checks that the provider has at least one endpoint
+    pub fn validate(&self) -> Result<()> {
+        if self.endpoints.is_empty() {
+            return Err(Error::Internal(
+                "provider must have at least one endpoint".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the first endpoint of the provider
+    pub fn primary_endpoint(&self) -> &str {
+        self.endpoints
+            .first()
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+
+    /// Returns the URL of the endpoint, with the API key of the chain in place of
+    /// its `{API_KEY}` placeholder
+    fn endpoint_url(&self, endpoint: &str) -> String {
+        if !endpoint.contains(API_KEY_PLACEHOLDER) {
+            return endpoint.to_string();
+        }
+
+        match ProviderCredentials::default().get(self.chain_id) {
+            Some(api_key) => endpoint.replace(API_KEY_PLACEHOLDER, &api_key),
+            None => {
+                log::warn!("no api key set for the endpoint {endpoint}");
+                endpoint.to_string()
+            }
+        }
+    }
+
+    /// Calls the JSON-RPC method on the provider endpoints in order and
+    /// returns the first successful response
+    pub async fn call_jsonrpc(
+        &self,
+        method: &str,
+        params: Value,
+        max_response_bytes: Option<u64>,
+    ) -> Result<Value> {
+        call_with_fallback(&self.endpoints, |endpoint| {
+            let url = self.endpoint_url(endpoint);
+            let params = params.clone();
+            async move { http::call_jsonrpc(&url, method, params, max_response_bytes).await }
+        })
+        .await
+    }
+
+    /// Calls several JSON-RPC methods with a single batch request on the provider
+    /// endpoints in order and returns the results of the first successful batch
+    pub async fn call_jsonrpc_batch(
+        &self,
+        requests: Vec<(&str, Value)>,
+        max_response_bytes: Option<u64>,
+    ) -> Result<Vec<Value>> {
+        call_with_fallback(&self.endpoints, |endpoint| {
+            let url = self.endpoint_url(endpoint);
+            let requests = requests.clone();
+            async move { http::call_jsonrpc_batch(&url, requests, max_response_bytes).await }
+        })
+        .await
+    }
+}
+
+/// Runs `call` on each endpoint until one succeeds
+///
+/// When every endpoint fails, the individual errors are returned in a
+/// `ProviderFallbackError`.
+async fn call_with_fallback<'a, T, F, Fut>(endpoints: &'a [String], mut call: F) -> Result<T>
+where
+    F: FnMut(&'a str) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    if endpoints.is_empty() {
+        return Err(Error::Internal(
+            "provider must have at least one endpoint".to_string(),
+        ));
+    }
+
+    let mut errors = Vec::with_capacity(endpoints.len());
+    for endpoint in endpoints {
+        match call(endpoint).await {
+            Ok(res) => return Ok(res),
+            Err(e) => {
+                log::warn!("call to endpoint {endpoint} failed: {e}");
+                errors.push(format!("{endpoint}: {e}"));
+            }
+        }
+    }
+
+    Err(Error::ProviderFallbackError(errors))
+}
+
+/// Returns the signed transaction of the oracle signer
+///
+/// Fails with `InsufficientBalance` when the balance of the signer is below
+/// `min_balance`, and with `GasPriceTooHigh` before estimating the gas when the
+/// gas price is above `max_gas_price_gwei`. The errors are tagged with the
+/// `GasEstimation` or `TransactionSigning` stage.
+#[allow(clippy::too_many_arguments)]
 pub async fn get_transaction(
     user_address: H160,
+    rotation_count: u32,
+    derivation_suffix: &[Vec<u8>],
     provider: Provider,
     to: Option<H160>,
     value: U256,
     data: Vec<u8>,
+    max_gas_price_gwei: Option<u64>,
+    min_balance: Option<&U256>,
     context: &Rc<RefCell<dyn Context>>,
-) -> Result<ethers_core::types::Transaction> {
+) -> PipelineResult<ethers_core::types::Transaction> {
     // NOTE: this is a workaround for clippy "borrow reference held across await point"
     // For some reason clippy produces a false warning for the code
     // let context = context.borrow();
@@ -34,7 +151,11 @@ pub async fn get_transaction(
     // drop(context); // before the first await point
     let signer = {
         let context = context.borrow();
-        let signer = context.get_state().signer.get_oracle_signer(user_address);
+        let signer = context.get_state().signer.get_rotated_oracle_signer(
+            user_address,
+            rotation_count,
+            derivation_suffix,
+        );
 
         signer
     };
@@ -42,45 +163,121 @@ pub async fn get_transaction(
     let from = signer
         .get_address()
         .await
-        .map_err(|e| Error::from(format!("failed to get address: {e}")))?;
+        .map_err(|e| Error::from(format!("failed to get address: {e}")))
+        .at_stage(OracleStage::TransactionSigning)?;
 
-    let nonce = http::call_jsonrpc(
-        &provider.hostname,
-        "eth_getTransactionCount",
-        serde_json::json!([from, "latest"]),
-        Some(8000),
+    let chain_id = provider.chain_id;
+    let transaction = build_transaction(
+        &from,
+        provider,
+        to,
+        value,
+        data,
+        max_gas_price_gwei,
+        min_balance,
+        context,
     )
-    .await?;
+    .await
+    .at_stage(OracleStage::GasEstimation);
 
-    let nonce: U256 = serde_json::from_value(nonce)?;
+    let result = match transaction {
+        Ok(transaction) => sign_transaction(&signer, transaction)
+            .await
+            .at_stage(OracleStage::TransactionSigning),
+        Err(e) => Err(e),
+    };
 
-    let gas_price = http::call_jsonrpc(
-        &provider.hostname,
-        "eth_gasPrice",
-        serde_json::Value::Null,
-        Some(8000),
-    )
-    .await?;
+    // The reserved nonce is not used, so the next transaction fetches it again
+    if result.is_err() {
+        context
+            .borrow()
+            .get_state()
+            .nonce_cache()
+            .invalidate(from, chain_id);
+    }
+
+    result
+}
+
+/// Returns the unsigned transaction of the signer at `from`, with its nonce, gas
+/// price and estimated gas
+#[allow(clippy::too_many_arguments)]
+async fn build_transaction(
+    from: &H160,
+    provider: Provider,
+    to: Option<H160>,
+    value: U256,
+    data: Vec<u8>,
+    max_gas_price_gwei: Option<u64>,
+    min_balance: Option<&U256>,
+    context: &Rc<RefCell<dyn Context>>,
+) -> Result<ethers_core::types::Transaction> {
+    if let Some(min_balance) = min_balance {
+        let balance = get_balance(&provider, from).await?;
+        check_balance(&balance, min_balance)?;
+    }
+
+    let cached_nonce =
+        context
+            .borrow()
+            .get_state()
+            .nonce_cache()
+            .reserve(from, provider.chain_id, ic::time());
+
+    let (nonce, gas_price) = match cached_nonce {
+        Some(nonce) => {
+            let gas_price = provider
+                .call_jsonrpc("eth_gasPrice", serde_json::Value::Null, Some(8000))
+                .await?;
+
+            (nonce, gas_price)
+        }
+        None => {
+            // fetch the nonce and the gas price with a single HTTP outcall
+            let results = provider
+                .call_jsonrpc_batch(
+                    vec![
+                        (
+                            "eth_getTransactionCount",
+                            serde_json::json!([from, "latest"]),
+                        ),
+                        ("eth_gasPrice", serde_json::Value::Null),
+                    ],
+                    Some(8000),
+                )
+                .await?;
+
+            let [nonce, gas_price]: [Value; 2] = results
+                .try_into()
+                .map_err(|_| Error::Internal("unexpected batch response length".to_string()))?;
+
+            let nonce: U256 = serde_json::from_value(nonce)?;
+            let nonce = context.borrow().get_state().nonce_cache().reserve_fetched(
+                from.clone(),
+                provider.chain_id,
+                nonce,
+                ic::time(),
+            );
+
+            (nonce, gas_price)
+        }
+    };
 
     let gas_price: U256 = serde_json::from_value(gas_price)?;
+    check_gas_price(&gas_price, max_gas_price_gwei)?;
 
-    let gas = http::call_jsonrpc(
-        &provider.hostname,
-        "eth_estimateGas",
-        serde_json::json!([{
-            "from": from,
-            "to": to,
-            "value": value,
-            "data": hex::encode(data.clone()),
-        }]),
-        Some(8000),
-    )
-    .await?;
+    let gas = provider
+        .call_jsonrpc(
+            "eth_estimateGas",
+            serde_json::json!([call_params(from, to.as_ref(), &value, &data)]),
+            Some(8000),
+        )
+        .await?;
 
     let gas: U256 = serde_json::from_value(gas)?;
 
-    let mut transaction = ethers_core::types::Transaction {
-        from: from.into(),
+    Ok(ethers_core::types::Transaction {
+        from: from.clone().into(),
         to: to.map(Into::into),
         nonce: nonce.0,
         value: value.0,
@@ -89,7 +286,104 @@ pub async fn get_transaction(
         input: data.into(),
         chain_id: Some(provider.chain_id.into()),
         ..Default::default()
+    })
+}
+
+/// Checks that the gas price does not exceed `max_gas_price_gwei`
+fn check_gas_price(gas_price: &U256, max_gas_price_gwei: Option<u64>) -> Result<()> {
+    let Some(max_gwei) = max_gas_price_gwei else {
+        return Ok(());
+    };
+
+    let gwei = ethers_core::types::U256::exp10(9);
+    if gas_price.0 > ethers_core::types::U256::from(max_gwei) * gwei {
+        let current_gwei = gas_price.0 / gwei;
+        return Err(Error::GasPriceTooHigh {
+            current_gwei: current_gwei.try_into().unwrap_or(u64::MAX),
+            max_gwei,
+        });
+    }
+
+    Ok(())
+}
+
+/// Returns the balance in wei of the address at the latest block
+pub async fn get_balance(provider: &Provider, address: &H160) -> Result<U256> {
+    let balance = provider
+        .call_jsonrpc(
+            "eth_getBalance",
+            serde_json::json!([address, "latest"]),
+            Some(8000),
+        )
+        .await?;
+
+    Ok(serde_json::from_value(balance)?)
+}
+
+/// Checks that the balance is at least `min_balance`
+fn check_balance(balance: &U256, min_balance: &U256) -> Result<()> {
+    if balance < min_balance {
+        return Err(Error::InsufficientBalance {
+            balance: balance.clone(),
+            min_balance: min_balance.clone(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Returns the signed replacement of a stuck transaction
+///
+/// The replacement has the same nonce and call, and pays
+/// `REPLACEMENT_GAS_PRICE_PERCENT` percent of the gas price of the stuck transaction.
+pub async fn get_replacement_transaction(
+    transaction: &ethers_core::types::Transaction,
+    user_address: H160,
+    rotation_count: u32,
+    derivation_suffix: &[Vec<u8>],
+    context: &Rc<RefCell<dyn Context>>,
+) -> Result<ethers_core::types::Transaction> {
+    let signer = {
+        let context = context.borrow();
+        let signer = context.get_state().signer.get_rotated_oracle_signer(
+            user_address,
+            rotation_count,
+            derivation_suffix,
+        );
+
+        signer
     };
+
+    sign_transaction(&signer, unsigned_replacement(transaction)).await
+}
+
+/// Returns an unsigned copy of the transaction with a bumped gas price
+fn unsigned_replacement(
+    transaction: &ethers_core::types::Transaction,
+) -> ethers_core::types::Transaction {
+    let gas_price = transaction.gas_price.unwrap_or_default();
+
+    ethers_core::types::Transaction {
+        gas_price: Some(replacement_gas_price(gas_price)),
+        r: Default::default(),
+        s: Default::default(),
+        v: Default::default(),
+        hash: Default::default(),
+        ..transaction.clone()
+    }
+}
+
+/// Returns `REPLACEMENT_GAS_PRICE_PERCENT` percent of the gas price, at least one wei more
+fn replacement_gas_price(gas_price: ethers_core::types::U256) -> ethers_core::types::U256 {
+    let bumped = gas_price.saturating_mul(REPLACEMENT_GAS_PRICE_PERCENT.into()) / 100;
+
+    bumped.max(gas_price.saturating_add(1.into()))
+}
+
+async fn sign_transaction(
+    signer: &impl TransactionSigner,
+    mut transaction: ethers_core::types::Transaction,
+) -> Result<ethers_core::types::Transaction> {
     let typed_transaction: TypedTransaction = (&transaction).into();
 
     let signature = signer
@@ -106,6 +400,152 @@ pub async fn get_transaction(
     Ok(transaction)
 }
 
+/// Sends a signed transaction and returns its hash
+///
+/// The nonce of the transaction is reserved in the cache when it is built; the
+/// cached nonce of the sender is invalidated when the transaction is not sent.
+pub async fn send_raw_transaction(
+    transaction: &ethers_core::types::Transaction,
+    provider: &Provider,
+    context: &Rc<RefCell<dyn Context>>,
+) -> Result<H256> {
+    let from: H160 = transaction.from.into();
+    let params = serde_json::json!([format!("0x{}", hex::encode(transaction.rlp()))]);
+
+    let result = provider
+        .call_jsonrpc("eth_sendRawTransaction", params, Some(80000))
+        .await;
+
+    if result.is_err() {
+        context
+            .borrow()
+            .get_state()
+            .nonce_cache()
+            .invalidate(from, provider.chain_id);
+    }
+
+    Ok(serde_json::from_value(result?)?)
+}
+
+/// Sends the replacement of a stuck transaction and returns its hash
+///
+/// The replacement reuses the nonce of the stuck transaction, so the cached nonce
+/// of the sender is left unchanged.
+pub async fn send_replacement_transaction(
+    transaction: &ethers_core::types::Transaction,
+    provider: &Provider,
+) -> Result<H256> {
+    let params = serde_json::json!([format!("0x{}", hex::encode(transaction.rlp()))]);
+
+    let tx_hash = provider
+        .call_jsonrpc("eth_sendRawTransaction", params, Some(80000))
+        .await?;
+
+    Ok(serde_json::from_value(tx_hash)?)
+}
+
+/// Returns the `eth_call` parameters replaying the call of a transaction
+pub fn transaction_call(transaction: &ethers_core::types::Transaction) -> Value {
+    let from: H160 = transaction.from.into();
+    let to: Option<H160> = transaction.to.map(Into::into);
+    call_params(
+        &from,
+        to.as_ref(),
+        &transaction.value.into(),
+        &transaction.input,
+    )
+}
+
+/// Returns the `eth_call` and `eth_estimateGas` parameters of a call
+fn call_params(from: &H160, to: Option<&H160>, value: &U256, data: &[u8]) -> Value {
+    serde_json::json!({
+        "from": from,
+        "to": to,
+        "value": value,
+        "data": format!("0x{}", hex::encode(data)),
+    })
+}
+
+/// Simulates a signed transaction with `eth_call` and returns the hex encoded return data
+pub async fn simulate_transaction(
+    transaction: &ethers_core::types::Transaction,
+    provider: &Provider,
+) -> Result<String> {
+    let params = serde_json::json!([transaction_call(transaction), "latest"]);
+
+    let result = provider
+        .call_jsonrpc("eth_call", params, Some(80000))
+        .await?;
+
+    Ok(serde_json::from_value(result)?)
+}
+
+/// Replays a reverted call with `eth_call` on the state of the given block and returns
+/// the revert reason reported by the node
+pub async fn get_revert_reason(provider: &Provider, call: &Value, block: Value) -> String {
+    let params = serde_json::json!([call, block]);
+
+    match provider.call_jsonrpc("eth_call", params, Some(80000)).await {
+        Ok(_) => "unknown reason".to_string(),
+        Err(e) => e.to_string(),
+    }
+}
+
+/// Parses the ABI return types of an EVM origin, a single `uint256` when empty
+fn parse_return_types(return_types: &[String]) -> Result<Vec<ParamType>> {
+    if return_types.is_empty() {
+        return Ok(vec![ParamType::Uint(256)]);
+    }
+
+    return_types
+        .iter()
+        .map(|return_type| {
+            Reader::read(return_type).map_err(|e| {
+                Error::AbiDecodeError(format!("invalid return type {return_type}: {e}"))
+            })
+        })
+        .collect()
+}
+
+/// Checks that the return types are valid ABI types and that the index is in range
+pub fn check_return_types(return_types: &[String], return_value_index: usize) -> Result<()> {
+    let param_types = parse_return_types(return_types)?;
+    if return_value_index >= param_types.len() {
+        return Err(Error::AbiDecodeError(format!(
+            "return value index {return_value_index} out of range"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Decodes the return data of an `eth_call` and returns the value at `return_value_index`
+///
+/// The value must be an unsigned or a non-negative signed integer.
+pub fn decode_return_value(
+    return_data: &[u8],
+    return_types: &[String],
+    return_value_index: usize,
+) -> Result<U256> {
+    let param_types = parse_return_types(return_types)?;
+    let tokens = ethers_core::abi::decode(&param_types, return_data)
+        .map_err(|e| Error::AbiDecodeError(e.to_string()))?;
+
+    match tokens.into_iter().nth(return_value_index) {
+        Some(Token::Uint(value)) => Ok(U256(value)),
+        Some(Token::Int(value)) if !value.bit(255) => Ok(U256(value)),
+        Some(Token::Int(_)) => Err(Error::AbiDecodeError(
+            "return value is negative".to_string(),
+        )),
+        Some(token) => Err(Error::AbiDecodeError(format!(
+            "return value is not an integer: {token}"
+        ))),
+        None => Err(Error::AbiDecodeError(format!(
+            "return value index {return_value_index} out of range"
+        ))),
+    }
+}
+
 #[allow(deprecated)]
 pub static UPDATE_PRICE: Lazy<Function> = Lazy::new(|| Function {
     name: "updatePrice".into(),
@@ -119,6 +559,336 @@ pub static UPDATE_PRICE: Lazy<Function> = Lazy::new(|| Function {
     state_mutability: StateMutability::NonPayable,
 });
 
+#[allow(deprecated)]
+pub static UPDATE_PRICE_WITH_ROUND_ID: Lazy<Function> = Lazy::new(|| Function {
+    name: "updatePrice".into(),
+    inputs: vec![
+        Param {
+            name: "_price".into(),
+            kind: ParamType::Int(256),
+            internal_type: None,
+        },
+        Param {
+            name: "_roundId".into(),
+            kind: ParamType::Uint(256),
+            internal_type: None,
+        },
+    ],
+    outputs: vec![],
+    constant: None,
+    state_mutability: StateMutability::NonPayable,
+});
+
+#[allow(deprecated)]
+pub static UPDATE_PRICE_FALLBACK: Lazy<Function> = Lazy::new(|| Function {
+    name: "updatePriceFallback".into(),
+    inputs: vec![Param {
+        name: "_price".into(),
+        kind: ParamType::Uint(256),
+        internal_type: None,
+    }],
+    outputs: vec![],
+    constant: None,
+    state_mutability: StateMutability::NonPayable,
+});
+
+#[allow(deprecated)]
+pub static UPDATE_PRICE_SIGNED: Lazy<Function> = Lazy::new(|| Function {
+    name: "updatePriceSigned".into(),
+    inputs: vec![
+        Param {
+            name: "_price".into(),
+            kind: ParamType::Uint(256),
+            internal_type: None,
+        },
+        Param {
+            name: "_timestamp".into(),
+            kind: ParamType::Uint(256),
+            internal_type: None,
+        },
+        Param {
+            name: "v".into(),
+            kind: ParamType::Uint(8),
+            internal_type: None,
+        },
+        Param {
+            name: "r".into(),
+            kind: ParamType::FixedBytes(32),
+            internal_type: None,
+        },
+        Param {
+            name: "s".into(),
+            kind: ParamType::FixedBytes(32),
+            internal_type: None,
+        },
+    ],
+    outputs: vec![],
+    constant: None,
+    state_mutability: StateMutability::NonPayable,
+});
+
+#[allow(deprecated)]
+pub static BATCH_UPDATE_PRICE: Lazy<Function> = Lazy::new(|| Function {
+    name: "batchUpdatePrice".into(),
+    inputs: vec![
+        Param {
+            name: "contracts".into(),
+            kind: ParamType::Array(Box::new(ParamType::Address)),
+            internal_type: None,
+        },
+        Param {
+            name: "prices".into(),
+            kind: ParamType::Array(Box::new(ParamType::Int(256))),
+            internal_type: None,
+        },
+    ],
+    outputs: vec![],
+    constant: None,
+    state_mutability: StateMutability::NonPayable,
+});
+
+/// Encodes the `updatePrice` call of an oracle update, with the round id when given
+pub fn encode_update_price(price: &U256, round_id: Option<u128>) -> Result<Vec<u8>> {
+    let price = Token::Int(price.0);
+
+    let data = match round_id {
+        Some(round_id) => {
+            UPDATE_PRICE_WITH_ROUND_ID.encode_input(&[price, Token::Uint(round_id.into())])?
+        }
+        None => UPDATE_PRICE.encode_input(&[price])?,
+    };
+
+    Ok(data)
+}
+
+/// Encodes the `updatePriceFallback` call of an oracle update submitting its fallback price
+pub fn encode_update_price_fallback(price: &U256) -> Result<Vec<u8>> {
+    Ok(UPDATE_PRICE_FALLBACK.encode_input(&[Token::Uint(price.0)])?)
+}
+
+/// Returns the digest signed by the oracle for a signed price update, i.e.
+/// `keccak256(abi.encode(price, timestamp, contract))`
+pub fn signed_price_digest(price: &U256, timestamp: u64, contract: &H160) -> [u8; 32] {
+    keccak256(ethers_core::abi::encode(&[
+        Token::Uint(price.0),
+        Token::Uint(timestamp.into()),
+        Token::Address(contract.0),
+    ]))
+}
+
+/// Encodes the `updatePriceSigned` call of an oracle update, with the signature of
+/// the price digest
+pub fn encode_update_price_signed(
+    price: &U256,
+    timestamp: u64,
+    signature: &Signature,
+) -> Result<Vec<u8>> {
+    // `ecrecover` expects the recovery id offset by 27
+    let v = if signature.v < 27 {
+        signature.v + 27
+    } else {
+        signature.v
+    };
+
+    let mut r = [0u8; 32];
+    signature.r.to_big_endian(&mut r);
+    let mut s = [0u8; 32];
+    signature.s.to_big_endian(&mut s);
+
+    Ok(UPDATE_PRICE_SIGNED.encode_input(&[
+        Token::Uint(price.0),
+        Token::Uint(timestamp.into()),
+        Token::Uint(v.into()),
+        Token::FixedBytes(r.to_vec()),
+        Token::FixedBytes(s.to_vec()),
+    ])?)
+}
+
+/// Encodes the `batchUpdatePrice` call updating the price of each contract
+pub fn encode_batch_update_price(updates: &[(H160, U256)]) -> Result<Vec<u8>> {
+    let (contracts, prices) = updates
+        .iter()
+        .map(|(contract, price)| (Token::Address(contract.0), Token::Int(price.0)))
+        .unzip();
+
+    Ok(BATCH_UPDATE_PRICE.encode_input(&[Token::Array(contracts), Token::Array(prices)])?)
+}
+
+/// Function of a non-standard price feed contract called instead of `updatePrice`
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Eq)]
+pub struct CustomUpdateFn {
+    pub function: FunctionRef,
+    /// Index of the price among the parameters of the function
+    pub price_param_index: u8,
+    /// Fixed values of the other parameters, in order, e.g. a pair identifier
+    pub extra_params: Vec<AbiToken>,
+}
+
+/// Definition of a custom update function
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Eq)]
+pub enum FunctionRef {
+    /// Name of the function, whose parameters are the `int256` price and the extra parameters
+    Inline(String),
+    /// Name of a function of the `FunctionRegistry`, whose price parameter is an
+    /// `int256` or a `uint256`
+    Registered(String),
+}
+
+impl CustomUpdateFn {
+    pub fn validate(&self) -> Result<()> {
+        if self
+            .extra_params
+            .iter()
+            .any(|param| matches!(param, AbiToken::FixedBytes32(bytes) if bytes.len() > 32))
+        {
+            return Err(Error::InvalidUpdateFunction(
+                "bytes32 parameter longer than 32 bytes".to_string(),
+            ));
+        }
+
+        let total_params = self.extra_params.len() + 1;
+        if self.price_param_index as usize >= total_params {
+            return Err(Error::InvalidUpdateFunction(format!(
+                "price parameter index {} out of the {total_params} parameters",
+                self.price_param_index
+            )));
+        }
+
+        match &self.function {
+            FunctionRef::Inline(name) => {
+                let is_identifier = name
+                    .chars()
+                    .next()
+                    .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                if !is_identifier {
+                    return Err(Error::InvalidUpdateFunction(format!(
+                        "invalid function name '{name}'"
+                    )));
+                }
+            }
+            FunctionRef::Registered(name) => {
+                self.registered_function(name)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the registered function, checking that its parameters match the extra
+    /// parameters and the price
+    fn registered_function(&self, name: &str) -> Result<Function> {
+        let function = FunctionRegistry::default().get(name)?.function()?;
+
+        let total_params = self.extra_params.len() + 1;
+        if function.inputs.len() != total_params {
+            return Err(Error::InvalidUpdateFunction(format!(
+                "function '{name}' has {} parameters instead of {total_params}",
+                function.inputs.len()
+            )));
+        }
+
+        let price_param = &function.inputs[self.price_param_index as usize];
+        if !matches!(price_param.kind, ParamType::Int(256) | ParamType::Uint(256)) {
+            return Err(Error::InvalidUpdateFunction(format!(
+                "price parameter of function '{name}' is not an int256 or a uint256"
+            )));
+        }
+
+        Ok(function)
+    }
+
+    /// Encodes the call of the function submitting the price
+    pub fn encode(&self, price: &U256) -> Result<Vec<u8>> {
+        self.validate()?;
+
+        let (function, price_token) = match &self.function {
+            FunctionRef::Inline(name) => {
+                let mut kinds = self
+                    .extra_params
+                    .iter()
+                    .map(AbiToken::param_type)
+                    .collect::<Vec<_>>();
+                kinds.insert(self.price_param_index as usize, ParamType::Int(256));
+
+                let params = kinds
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, kind)| Param {
+                        name: format!("_param{index}"),
+                        kind,
+                        internal_type: None,
+                    })
+                    .collect::<Vec<_>>();
+
+                (function_selector(name, &params), Token::Int(price.0))
+            }
+            FunctionRef::Registered(name) => {
+                let function = self.registered_function(name)?;
+                let price_token = match function.inputs[self.price_param_index as usize].kind {
+                    ParamType::Uint(_) => Token::Uint(price.0),
+                    _ => Token::Int(price.0),
+                };
+
+                (function, price_token)
+            }
+        };
+
+        let mut tokens = self
+            .extra_params
+            .iter()
+            .map(AbiToken::to_token)
+            .collect::<Vec<_>>();
+        tokens.insert(self.price_param_index as usize, price_token);
+
+        Ok(function.encode_input(&tokens)?)
+    }
+}
+
+/// ABI value of a parameter of a custom update function
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Eq)]
+pub enum AbiToken {
+    Address(H160),
+    /// `bytes32` value, shorter values are padded with zeros
+    FixedBytes32(Vec<u8>),
+    Bytes(Vec<u8>),
+    /// `int256` value in two's complement
+    Int(U256),
+    Uint(U256),
+    Bool(bool),
+    String(String),
+}
+
+impl AbiToken {
+    pub fn param_type(&self) -> ParamType {
+        match self {
+            Self::Address(_) => ParamType::Address,
+            Self::FixedBytes32(_) => ParamType::FixedBytes(32),
+            Self::Bytes(_) => ParamType::Bytes,
+            Self::Int(_) => ParamType::Int(256),
+            Self::Uint(_) => ParamType::Uint(256),
+            Self::Bool(_) => ParamType::Bool,
+            Self::String(_) => ParamType::String,
+        }
+    }
+
+    pub fn to_token(&self) -> Token {
+        match self {
+            Self::Address(address) => Token::Address(address.0),
+            Self::FixedBytes32(bytes) => {
+                let mut padded = bytes.clone();
+                padded.resize(32, 0);
+                Token::FixedBytes(padded)
+            }
+            Self::Bytes(bytes) => Token::Bytes(bytes.clone()),
+            Self::Int(value) => Token::Int(value.0),
+            Self::Uint(value) => Token::Uint(value.0),
+            Self::Bool(value) => Token::Bool(*value),
+            Self::String(value) => Token::String(value.clone()),
+        }
+    }
+}
+
 #[allow(deprecated)]
 /// Returns the function selector for the given function name and parameters.
 pub fn function_selector(name: &str, params: &[Param]) -> Function {
@@ -130,3 +900,502 @@ pub fn function_selector(name: &str, params: &[Param]) -> Function {
         state_mutability: StateMutability::NonPayable,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ethers_core::k256::ecdsa::SigningKey;
+    use ethers_core::utils::secret_key_to_address;
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::state::SerializedFunction;
+
+    #[test]
+    fn test_call_with_fallback() {
+        let endpoints = vec![
+            String::from("https://primary.example.com"),
+            String::from("https://secondary.example.com"),
+        ];
+
+        let res = block_on(call_with_fallback(&endpoints, |endpoint| async move {
+            if endpoint == "https://primary.example.com" {
+                Err(Error::Http("primary is down".to_string()))
+            } else {
+                Ok(endpoint.to_string())
+            }
+        }));
+        assert_eq!(res, Ok(String::from("https://secondary.example.com")));
+
+        let res = block_on(call_with_fallback(&endpoints, |_| async {
+            Err::<(), _>(Error::Http("down".to_string()))
+        }));
+        assert_eq!(
+            res,
+            Err(Error::ProviderFallbackError(vec![
+                String::from("https://primary.example.com: http error : down"),
+                String::from("https://secondary.example.com: http error : down"),
+            ]))
+        );
+
+        let res = block_on(call_with_fallback(&[], |_| async { Ok(()) }));
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_validate_provider() {
+        let provider = Provider {
+            chain_id: 1,
+            endpoints: vec![],
+        };
+        assert!(provider.validate().is_err());
+
+        let provider = Provider {
+            chain_id: 1,
+            endpoints: vec![String::from("https://example.com")],
+        };
+        assert!(provider.validate().is_ok());
+        assert_eq!(provider.primary_endpoint(), "https://example.com");
+    }
+
+    #[test]
+    fn test_endpoint_url() {
+        let credentials = ProviderCredentials::default();
+        credentials.clear();
+
+        let provider = Provider {
+            chain_id: 1,
+            endpoints: vec![],
+        };
+        let endpoint = "https://eth-mainnet.example.com/v2/{API_KEY}";
+
+        // The placeholder is kept until a key is set
+        assert_eq!(provider.endpoint_url(endpoint), endpoint);
+        assert_eq!(
+            provider.endpoint_url("https://example.com"),
+            "https://example.com"
+        );
+
+        credentials.insert(1, String::from("secret"));
+        credentials.insert(2, String::from("other"));
+        assert_eq!(
+            provider.endpoint_url(endpoint),
+            "https://eth-mainnet.example.com/v2/secret"
+        );
+    }
+
+    #[test]
+    fn test_decode_return_value() {
+        use ethers_core::abi::encode;
+        use ethers_core::types::U256 as EthU256;
+
+        let types = |types: &[&str]| types.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+
+        // A single uint256 is decoded when no return types are given
+        let data = encode(&[Token::Uint(EthU256::from(42))]);
+        assert_eq!(decode_return_value(&data, &[], 0), Ok(U256::from(42u64)));
+
+        // Multi-value tuple, e.g. Uniswap V3 `slot0()`
+        let data = encode(&[
+            Token::Uint(EthU256::from(1_000_000u64)),
+            Token::Int(EthU256::from(200)),
+            Token::Uint(EthU256::from(1)),
+            Token::Bool(true),
+        ]);
+        let slot0_types = types(&["uint160", "int24", "uint16", "bool"]);
+        assert_eq!(
+            decode_return_value(&data, &slot0_types, 0),
+            Ok(U256::from(1_000_000u64))
+        );
+        assert_eq!(
+            decode_return_value(&data, &slot0_types, 1),
+            Ok(U256::from(200u64))
+        );
+        assert!(matches!(
+            decode_return_value(&data, &slot0_types, 3),
+            Err(Error::AbiDecodeError(_))
+        ));
+        assert!(matches!(
+            decode_return_value(&data, &slot0_types, 4),
+            Err(Error::AbiDecodeError(_))
+        ));
+
+        // Struct returned before the price
+        let data = encode(&[
+            Token::Tuple(vec![
+                Token::Address(Default::default()),
+                Token::Uint(EthU256::from(7)),
+            ]),
+            Token::Uint(EthU256::from(99)),
+        ]);
+        let struct_types = types(&["(address,uint256)", "uint256"]);
+        assert_eq!(
+            decode_return_value(&data, &struct_types, 1),
+            Ok(U256::from(99u64))
+        );
+
+        // Dynamic array returned before the price
+        let data = encode(&[
+            Token::Array(vec![
+                Token::Uint(EthU256::from(1)),
+                Token::Uint(EthU256::from(2)),
+            ]),
+            Token::Uint(EthU256::from(3)),
+        ]);
+        let array_types = types(&["uint256[]", "uint256"]);
+        assert_eq!(
+            decode_return_value(&data, &array_types, 1),
+            Ok(U256::from(3u64))
+        );
+        assert!(matches!(
+            decode_return_value(&data, &array_types, 0),
+            Err(Error::AbiDecodeError(_))
+        ));
+
+        // Negative prices are rejected
+        let data = encode(&[Token::Int(EthU256::MAX)]);
+        assert!(matches!(
+            decode_return_value(&data, &types(&["int256"]), 0),
+            Err(Error::AbiDecodeError(_))
+        ));
+
+        // Truncated return data
+        assert!(matches!(
+            decode_return_value(&[0; 16], &[], 0),
+            Err(Error::AbiDecodeError(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_return_types() {
+        assert!(check_return_types(&[], 0).is_ok());
+        assert!(check_return_types(&[], 1).is_err());
+        assert!(check_return_types(&[String::from("uint160"), String::from("int24")], 1).is_ok());
+        assert!(check_return_types(&[String::from("uint160")], 1).is_err());
+        assert!(check_return_types(&[String::from("uintx")], 0).is_err());
+    }
+
+    #[test]
+    fn test_encode_batch_update_price() {
+        let updates = vec![
+            (H160::from_slice(&[1; 20]), U256::from(42u64)),
+            (H160::from_slice(&[2; 20]), U256::from(7u64)),
+        ];
+
+        let data = encode_batch_update_price(&updates).unwrap();
+        assert_eq!(data[..4], BATCH_UPDATE_PRICE.short_signature());
+        assert_eq!(
+            ethers_core::abi::decode(
+                &[
+                    ParamType::Array(Box::new(ParamType::Address)),
+                    ParamType::Array(Box::new(ParamType::Int(256)))
+                ],
+                &data[4..]
+            )
+            .unwrap(),
+            vec![
+                Token::Array(vec![
+                    Token::Address(H160::from_slice(&[1; 20]).0),
+                    Token::Address(H160::from_slice(&[2; 20]).0)
+                ]),
+                Token::Array(vec![Token::Int(42.into()), Token::Int(7.into())])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encode_update_price_signed() {
+        let key = SigningKey::from_bytes(&[1u8; 32].into()).unwrap();
+        let contract = H160::from_slice(&[2; 20]);
+        let price = U256::from(42u64);
+        let timestamp = 1_700_000_000;
+
+        let digest = signed_price_digest(&price, timestamp, &contract);
+        let (signature, recovery_id) = key.sign_prehash_recoverable(&digest).unwrap();
+        let signature = Signature {
+            r: ethers_core::types::U256::from_big_endian(&signature.r().to_bytes()),
+            s: ethers_core::types::U256::from_big_endian(&signature.s().to_bytes()),
+            v: recovery_id.to_byte().into(),
+        };
+
+        let data = encode_update_price_signed(&price, timestamp, &signature).unwrap();
+        assert_eq!(data[..4], UPDATE_PRICE_SIGNED.short_signature());
+
+        let tokens = UPDATE_PRICE_SIGNED.decode_input(&data[4..]).unwrap();
+        assert_eq!(tokens[0], Token::Uint(42.into()));
+        assert_eq!(tokens[1], Token::Uint(timestamp.into()));
+
+        // The submitted signature recovers the address of the oracle signer
+        let submitted = Signature {
+            v: tokens[2].clone().into_uint().unwrap().as_u64(),
+            r: ethers_core::types::U256::from_big_endian(
+                &tokens[3].clone().into_fixed_bytes().unwrap(),
+            ),
+            s: ethers_core::types::U256::from_big_endian(
+                &tokens[4].clone().into_fixed_bytes().unwrap(),
+            ),
+        };
+        assert_eq!(submitted.v, 27 + u64::from(recovery_id.to_byte()));
+        assert_eq!(
+            submitted
+                .recover(ethers_core::types::H256::from(digest))
+                .unwrap(),
+            secret_key_to_address(&key)
+        );
+    }
+
+    #[test]
+    fn test_encode_update_price() {
+        let price = U256::from(42u64);
+
+        let data = encode_update_price(&price, None).unwrap();
+        assert_eq!(data[..4], UPDATE_PRICE.short_signature());
+        assert_eq!(data.len(), 4 + 32);
+
+        let data = encode_update_price(&price, Some(7)).unwrap();
+        assert_eq!(data[..4], UPDATE_PRICE_WITH_ROUND_ID.short_signature());
+        assert_eq!(
+            ethers_core::abi::decode(&[ParamType::Int(256), ParamType::Uint(256)], &data[4..])
+                .unwrap(),
+            vec![Token::Int(42.into()), Token::Uint(7.into())]
+        );
+
+        let data = encode_update_price_fallback(&price).unwrap();
+        assert_eq!(data[..4], UPDATE_PRICE_FALLBACK.short_signature());
+        assert_eq!(
+            ethers_core::abi::decode(&[ParamType::Uint(256)], &data[4..]).unwrap(),
+            vec![Token::Uint(42.into())]
+        );
+    }
+
+    #[test]
+    fn test_custom_update_fn() {
+        let price = U256::from(42u64);
+
+        // The standard functions encoded as custom ones
+        let update_price = CustomUpdateFn {
+            function: FunctionRef::Inline("updatePrice".to_string()),
+            price_param_index: 0,
+            extra_params: vec![],
+        };
+        assert_eq!(
+            update_price.encode(&price).unwrap(),
+            encode_update_price(&price, None).unwrap()
+        );
+
+        let update_price_with_round_id = CustomUpdateFn {
+            extra_params: vec![AbiToken::Uint(U256::from(7u64))],
+            ..update_price.clone()
+        };
+        assert_eq!(
+            update_price_with_round_id.encode(&price).unwrap(),
+            encode_update_price(&price, Some(7)).unwrap()
+        );
+
+        // The price is inserted among the extra parameters
+        let set_pair_price = CustomUpdateFn {
+            function: FunctionRef::Inline("setPairPrice".to_string()),
+            price_param_index: 1,
+            extra_params: vec![
+                AbiToken::FixedBytes32(b"ETH/USD".to_vec()),
+                AbiToken::Bool(true),
+            ],
+        };
+        let data = set_pair_price.encode(&price).unwrap();
+        assert_eq!(
+            data[..4],
+            ethers_core::utils::id("setPairPrice(bytes32,int256,bool)")
+        );
+
+        let mut pair = b"ETH/USD".to_vec();
+        pair.resize(32, 0);
+        assert_eq!(
+            ethers_core::abi::decode(
+                &[
+                    ParamType::FixedBytes(32),
+                    ParamType::Int(256),
+                    ParamType::Bool
+                ],
+                &data[4..]
+            )
+            .unwrap(),
+            vec![
+                Token::FixedBytes(pair),
+                Token::Int(42.into()),
+                Token::Bool(true)
+            ]
+        );
+
+        let out_of_range = CustomUpdateFn {
+            price_param_index: 3,
+            ..set_pair_price.clone()
+        };
+        assert!(matches!(
+            out_of_range.validate(),
+            Err(Error::InvalidUpdateFunction(_))
+        ));
+        assert!(out_of_range.encode(&price).is_err());
+
+        for function_name in ["", "1update", "update price", "update(int256)"] {
+            let invalid_name = CustomUpdateFn {
+                function: FunctionRef::Inline(function_name.to_string()),
+                ..update_price.clone()
+            };
+            assert!(invalid_name.validate().is_err(), "{function_name}");
+        }
+    }
+
+    #[test]
+    fn test_registered_update_fn() {
+        let registry = FunctionRegistry::default();
+        registry.clear();
+
+        let set_rate = CustomUpdateFn {
+            function: FunctionRef::Registered("setRate".to_string()),
+            price_param_index: 0,
+            extra_params: vec![AbiToken::FixedBytes32(b"ETH/USD".to_vec())],
+        };
+        assert!(matches!(
+            set_rate.validate(),
+            Err(Error::InvalidUpdateFunction(_))
+        ));
+
+        let abi_json = r#"{"type":"function","name":"setRate","inputs":[{"name":"rate","type":"uint256"},{"name":"pair","type":"bytes32"}],"outputs":[],"stateMutability":"nonpayable"}"#;
+        registry.insert(
+            "setRate".to_string(),
+            SerializedFunction::parse(abi_json.to_string()).unwrap(),
+        );
+
+        let data = set_rate.encode(&U256::from(42u64)).unwrap();
+        assert_eq!(
+            data[..4],
+            ethers_core::utils::id("setRate(uint256,bytes32)")
+        );
+
+        let mut pair = b"ETH/USD".to_vec();
+        pair.resize(32, 0);
+        assert_eq!(
+            ethers_core::abi::decode(
+                &[ParamType::Uint(256), ParamType::FixedBytes(32)],
+                &data[4..]
+            )
+            .unwrap(),
+            vec![Token::Uint(42.into()), Token::FixedBytes(pair)]
+        );
+
+        // The parameters must match the registered function
+        let missing_param = CustomUpdateFn {
+            extra_params: vec![],
+            ..set_rate.clone()
+        };
+        assert!(missing_param.validate().is_err());
+
+        let bytes_price = CustomUpdateFn {
+            price_param_index: 1,
+            extra_params: vec![AbiToken::Uint(U256::from(7u64))],
+            ..set_rate.clone()
+        };
+        assert!(bytes_price.validate().is_err());
+    }
+
+    #[test]
+    fn test_check_gas_price() {
+        // 2000 gwei during a congestion
+        let gas_price: U256 = serde_json::from_value(serde_json::json!("0x1d1a94a2000")).unwrap();
+
+        assert_eq!(check_gas_price(&gas_price, None), Ok(()));
+        assert_eq!(check_gas_price(&gas_price, Some(2000)), Ok(()));
+        assert_eq!(
+            check_gas_price(&gas_price, Some(100)),
+            Err(Error::GasPriceTooHigh {
+                current_gwei: 2000,
+                max_gwei: 100,
+            })
+        );
+
+        // A fraction of gwei above the maximum is rejected
+        let gas_price = U256::from(2_000_000_000_001u64);
+        assert_eq!(
+            check_gas_price(&gas_price, Some(2000)),
+            Err(Error::GasPriceTooHigh {
+                current_gwei: 2000,
+                max_gwei: 2000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_balance() {
+        let min_balance = U256::from(1_000u64);
+        assert!(check_balance(&U256::from(1_000u64), &min_balance).is_ok());
+        assert!(check_balance(&U256::from(2_000u64), &min_balance).is_ok());
+        assert_eq!(
+            check_balance(&U256::from(999u64), &min_balance),
+            Err(Error::InsufficientBalance {
+                balance: U256::from(999u64),
+                min_balance,
+            })
+        );
+    }
+
+    #[test]
+    fn test_transaction_call() {
+        let transaction = ethers_core::types::Transaction {
+            from: ethers_core::types::H160::repeat_byte(1),
+            to: Some(ethers_core::types::H160::repeat_byte(2)),
+            value: 1_000_000_000_000_000u64.into(),
+            input: vec![1, 2, 3].into(),
+            ..Default::default()
+        };
+
+        let call = transaction_call(&transaction);
+        assert_eq!(call["from"], serde_json::json!(H160::from_slice(&[1; 20])));
+        assert_eq!(call["to"], serde_json::json!(H160::from_slice(&[2; 20])));
+        assert_eq!(
+            call["value"],
+            serde_json::json!(U256::from(1_000_000_000_000_000u64))
+        );
+        assert_eq!(call["data"], serde_json::json!("0x010203"));
+    }
+
+    #[test]
+    fn test_unsigned_replacement() {
+        let transaction = ethers_core::types::Transaction {
+            value: 1_000.into(),
+            nonce: 7.into(),
+            gas: 50_000.into(),
+            gas_price: Some(1_000_000_000u64.into()),
+            input: vec![1, 2, 3].into(),
+            chain_id: Some(1.into()),
+            r: 1.into(),
+            s: 2.into(),
+            v: 27.into(),
+            hash: ethers_core::types::H256::repeat_byte(1),
+            ..Default::default()
+        };
+
+        // Same nonce and call with a higher gas price, to be signed again
+        let replacement = unsigned_replacement(&transaction);
+        assert_eq!(replacement.nonce, transaction.nonce);
+        assert_eq!(replacement.value, transaction.value);
+        assert_eq!(replacement.gas, transaction.gas);
+        assert_eq!(replacement.input, transaction.input);
+        assert_eq!(replacement.chain_id, transaction.chain_id);
+        assert_eq!(replacement.gas_price, Some(1_200_000_000u64.into()));
+        assert!(replacement.r.is_zero());
+        assert!(replacement.s.is_zero());
+        assert!(replacement.v.is_zero());
+        assert!(replacement.hash.is_zero());
+    }
+
+    #[test]
+    fn test_replacement_gas_price() {
+        assert_eq!(replacement_gas_price(100.into()), 120.into());
+        // The replacement always pays more than the stuck transaction
+        assert_eq!(replacement_gas_price(3.into()), 4.into());
+        assert_eq!(replacement_gas_price(0.into()), 1.into());
+        assert_eq!(
+            replacement_gas_price(ethers_core::types::U256::MAX),
+            ethers_core::types::U256::MAX
+        );
+    }
+}
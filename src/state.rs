@@ -1,13 +1,39 @@
+mod active_updates;
+mod cookie_cache;
+mod creation_timestamps;
+mod dead_letter_queue;
+mod function_registry;
+mod nonce_cache;
 pub mod oracle_storage;
+mod pending_creations;
+mod proof_nonces;
+mod provider_credentials;
+mod provider_registry;
+mod response_cache;
 mod settings;
 mod signer;
 
 use candid::Principal;
 pub use oracle_storage::UpdateOracleMetadata;
 
+use self::active_updates::ActiveUpdates;
+pub use self::cookie_cache::CookieCache;
+use self::creation_timestamps::UserCreationTimestamps;
+pub use self::dead_letter_queue::{DeadLetterEntry, DeadLetterQueue};
+pub use self::function_registry::{FunctionRegistry, SerializedFunction};
+use self::nonce_cache::NonceCache;
 use self::oracle_storage::OracleStorage;
-pub use self::settings::Settings;
+use self::pending_creations::PendingCreations;
+use self::proof_nonces::ProofNonces;
+pub use self::provider_credentials::ProviderCredentials;
+use self::provider_registry::ProviderRegistry;
+pub use self::response_cache::ResponseCache;
+pub use self::settings::{OracleSignerConfig, RateLimit, Settings};
 use self::signer::SignerInfo;
+use crate::constants::{
+    DEFAULT_MAX_CONCURRENT_UPDATES, DEFAULT_MAX_STARTUP_JITTER_SECS, MAX_ADMINS,
+};
+use crate::error::{Error, Result};
 
 #[derive(Debug, Default, Clone)]
 pub struct State {
@@ -15,6 +41,28 @@ pub struct State {
     pub signer: SignerInfo,
     /// Pair storage.
     pub oracle_storage: OracleStorage,
+    /// Nonces of the oracle signers.
+    pub nonce_cache: NonceCache,
+    /// Last nonces of the signature proofs.
+    pub proof_nonces: ProofNonces,
+    /// Providers registered for each chain.
+    pub provider_registry: ProviderRegistry,
+    /// API keys of the providers of each chain.
+    pub provider_credentials: ProviderCredentials,
+    /// Update functions shared by the oracles.
+    pub function_registry: FunctionRegistry,
+    /// Recent oracle creation times of each user.
+    pub user_creation_timestamps: UserCreationTimestamps,
+    /// Oracles reserved by the batch creations in progress.
+    pub pending_creations: PendingCreations,
+    /// Cached responses of the HTTP origins.
+    pub response_cache: ResponseCache,
+    /// Number of oracle updates in progress.
+    pub active_updates: ActiveUpdates,
+    /// Session cookies of the HTTP origins requiring a login.
+    pub cookie_cache: CookieCache,
+    /// Oracle updates which failed after all their retries.
+    pub dead_letter_queue: DeadLetterQueue,
 }
 
 impl State {
@@ -23,6 +71,17 @@ impl State {
         Settings::update(|s| *s = settings.clone());
 
         self.oracle_storage.clear();
+        self.nonce_cache.clear();
+        self.proof_nonces.clear();
+        self.provider_registry.clear();
+        self.provider_credentials.clear();
+        self.function_registry.clear();
+        self.user_creation_timestamps.clear();
+        self.pending_creations.clear();
+        self.response_cache.clear();
+        self.active_updates.clear();
+        self.cookie_cache.clear();
+        self.dead_letter_queue.clear();
     }
 
     pub fn owner(&self) -> Principal {
@@ -33,6 +92,119 @@ impl State {
         Settings::update(|s| s.owner = owner);
     }
 
+    pub fn admins(&self) -> Vec<Principal> {
+        Settings::read(|s| s.admins.clone().unwrap_or_default())
+    }
+
+    pub fn is_admin(&self, principal: Principal) -> bool {
+        Settings::read(|s| {
+            s.admins
+                .as_ref()
+                .is_some_and(|admins| admins.contains(&principal))
+        })
+    }
+
+    pub fn add_admin(&mut self, admin: Principal) -> Result<()> {
+        Settings::update(|s| {
+            let admins = s.admins.get_or_insert_with(Vec::new);
+            if admins.contains(&admin) {
+                return Err(Error::Internal("admin already exists".to_string()));
+            }
+            if admins.len() >= MAX_ADMINS {
+                return Err(Error::Internal(format!(
+                    "admins limit of {MAX_ADMINS} reached"
+                )));
+            }
+
+            admins.push(admin);
+            Ok(())
+        })
+    }
+
+    pub fn remove_admin(&mut self, admin: Principal) -> Result<()> {
+        Settings::update(|s| {
+            let admins = s.admins.get_or_insert_with(Vec::new);
+            let len = admins.len();
+            admins.retain(|a| *a != admin);
+
+            if admins.len() == len {
+                return Err(Error::Internal("admin not found".to_string()));
+            }
+
+            Ok(())
+        })
+    }
+
+    pub fn url_allowlist(&self) -> Option<Vec<String>> {
+        Settings::read(|s| s.url_allowlist.clone())
+    }
+
+    pub fn set_url_allowlist(&mut self, url_allowlist: Option<Vec<String>>) {
+        Settings::update(|s| s.url_allowlist = url_allowlist);
+    }
+
+    pub fn max_startup_jitter_secs(&self) -> u64 {
+        Settings::read(|s| {
+            s.max_startup_jitter_secs
+                .unwrap_or(DEFAULT_MAX_STARTUP_JITTER_SECS)
+        })
+    }
+
+    pub fn set_max_startup_jitter_secs(&mut self, max_startup_jitter_secs: u64) {
+        Settings::update(|s| s.max_startup_jitter_secs = Some(max_startup_jitter_secs));
+    }
+
+    pub fn oracle_creation_rate_limit(&self) -> Option<RateLimit> {
+        Settings::read(|s| s.oracle_creation_rate_limit.clone())
+    }
+
+    pub fn set_oracle_creation_rate_limit(&mut self, limit: Option<RateLimit>) {
+        Settings::update(|s| s.oracle_creation_rate_limit = limit);
+    }
+
+    pub fn signer_config(&self) -> OracleSignerConfig {
+        Settings::read(|s| s.signer_config.clone().unwrap_or_default())
+    }
+
+    pub fn set_signer_config(&mut self, config: OracleSignerConfig) {
+        Settings::update(|s| s.signer_config = Some(config));
+    }
+
+    pub fn derivation_prefix(&self) -> Vec<Vec<u8>> {
+        Settings::read(|s| s.derivation_prefix.clone().unwrap_or_default())
+    }
+
+    pub fn set_derivation_prefix(&mut self, prefix: Vec<Vec<u8>>) {
+        Settings::update(|s| s.derivation_prefix = Some(prefix));
+    }
+
+    pub fn stable_memory_alert_pages(&self) -> Option<u64> {
+        Settings::read(|s| s.stable_memory_alert_pages)
+    }
+
+    pub fn set_stable_memory_alert_pages(&mut self, pages: Option<u64>) {
+        Settings::update(|s| s.stable_memory_alert_pages = pages);
+    }
+
+    pub fn skip_chain_id_validation(&self) -> bool {
+        Settings::read(|s| s.skip_chain_id_validation.unwrap_or_default())
+    }
+
+    pub fn set_skip_chain_id_validation(&mut self, skip: bool) {
+        Settings::update(|s| s.skip_chain_id_validation = Some(skip));
+    }
+
+    pub fn max_concurrent_updates(&self) -> u32 {
+        Settings::read(|s| {
+            s.max_concurrent_updates
+                .unwrap_or(DEFAULT_MAX_CONCURRENT_UPDATES)
+        })
+    }
+
+    pub fn set_max_concurrent_updates(&mut self, limit: u32) {
+        Settings::update(|s| s.max_concurrent_updates = Some(limit));
+    }
+
     pub fn mut_oracle_storage(&mut self) -> &mut OracleStorage {
         &mut self.oracle_storage
     }
@@ -41,6 +213,50 @@ impl State {
         &self.oracle_storage
     }
 
+    pub fn nonce_cache(&self) -> &NonceCache {
+        &self.nonce_cache
+    }
+
+    pub fn proof_nonces(&self) -> &ProofNonces {
+        &self.proof_nonces
+    }
+
+    pub fn provider_registry(&self) -> &ProviderRegistry {
+        &self.provider_registry
+    }
+
+    pub fn provider_credentials(&self) -> &ProviderCredentials {
+        &self.provider_credentials
+    }
+
+    pub fn function_registry(&self) -> &FunctionRegistry {
+        &self.function_registry
+    }
+
+    pub fn user_creation_timestamps(&self) -> &UserCreationTimestamps {
+        &self.user_creation_timestamps
+    }
+
+    pub fn pending_creations(&self) -> &PendingCreations {
+        &self.pending_creations
+    }
+
+    pub fn response_cache(&self) -> &ResponseCache {
+        &self.response_cache
+    }
+
+    pub fn active_updates(&self) -> &ActiveUpdates {
+        &self.active_updates
+    }
+
+    pub fn cookie_cache(&self) -> &CookieCache {
+        &self.cookie_cache
+    }
+
+    pub fn dead_letter_queue(&self) -> &DeadLetterQueue {
+        &self.dead_letter_queue
+    }
+
     pub fn signer(&self) -> &SignerInfo {
         &self.signer
     }
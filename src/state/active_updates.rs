@@ -0,0 +1,121 @@
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeSet;
+
+use did::H160;
+
+/// Number of oracle updates in progress, used to cap the concurrent updates, and
+/// the oracles waiting for the retry of a throttled update
+///
+/// The counter and the retries live in the heap memory, so it is reset on canister upgrade.
+#[derive(Debug, Default, Clone)]
+pub struct ActiveUpdates {}
+
+impl ActiveUpdates {
+    /// Counts a new update in progress, unless `limit` updates are already running
+    ///
+    /// Returns whether the update can proceed.
+    pub fn try_acquire(&self, limit: u32) -> bool {
+        ACTIVE_UPDATES.with(|active| {
+            if active.get() >= limit {
+                return false;
+            }
+
+            active.set(active.get() + 1);
+            true
+        })
+    }
+
+    /// Counts the end of an update acquired with `try_acquire`
+    pub fn release(&self) {
+        ACTIVE_UPDATES.with(|active| active.set(active.get().saturating_sub(1)));
+    }
+
+    /// Returns the number of updates in progress
+    pub fn count(&self) -> u32 {
+        ACTIVE_UPDATES.with(Cell::get)
+    }
+
+    /// Records a pending retry for the oracle, unless one is already pending
+    ///
+    /// Returns whether the retry must be scheduled.
+    pub fn try_schedule_retry(&self, user_address: H160, contract_address: H160) -> bool {
+        PENDING_RETRIES.with(|retries| {
+            retries
+                .borrow_mut()
+                .insert((user_address, contract_address))
+        })
+    }
+
+    /// Removes the pending retry of the oracle when it runs
+    pub fn finish_retry(&self, user_address: &H160, contract_address: &H160) {
+        PENDING_RETRIES.with(|retries| {
+            retries
+                .borrow_mut()
+                .remove(&(user_address.clone(), contract_address.clone()))
+        });
+    }
+
+    pub fn clear(&self) {
+        ACTIVE_UPDATES.with(|active| active.set(0));
+        PENDING_RETRIES.with(|retries| retries.borrow_mut().clear());
+    }
+}
+
+thread_local! {
+    static ACTIVE_UPDATES: Cell<u32> = const { Cell::new(0) };
+    static PENDING_RETRIES: RefCell<BTreeSet<(H160, H160)>> =
+        const { RefCell::new(BTreeSet::new()) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_active_updates() {
+        let active_updates = ActiveUpdates::default();
+        active_updates.clear();
+
+        // Out of N + 1 simultaneous updates, exactly N proceed
+        let limit = 10;
+        let proceeding = (0..=limit)
+            .filter(|_| active_updates.try_acquire(limit))
+            .count();
+        assert_eq!(proceeding, limit as usize);
+        assert_eq!(active_updates.count(), limit);
+
+        // A finished update frees a slot
+        active_updates.release();
+        assert!(active_updates.try_acquire(limit));
+        assert!(!active_updates.try_acquire(limit));
+
+        for _ in 0..limit {
+            active_updates.release();
+        }
+        assert_eq!(active_updates.count(), 0);
+        active_updates.release();
+        assert_eq!(active_updates.count(), 0);
+    }
+
+    #[test]
+    fn test_pending_retries() {
+        let active_updates = ActiveUpdates::default();
+        active_updates.clear();
+
+        let user = H160::from_slice(&[1; 20]);
+        let contract = H160::from_slice(&[2; 20]);
+        let other_contract = H160::from_slice(&[3; 20]);
+
+        // A single retry is pending per oracle
+        assert!(active_updates.try_schedule_retry(user.clone(), contract.clone()));
+        assert!(!active_updates.try_schedule_retry(user.clone(), contract.clone()));
+        assert!(active_updates.try_schedule_retry(user.clone(), other_contract.clone()));
+
+        // Once the retry runs, a new one can be scheduled
+        active_updates.finish_retry(&user, &contract);
+        assert!(active_updates.try_schedule_retry(user.clone(), contract.clone()));
+
+        active_updates.clear();
+        assert!(active_updates.try_schedule_retry(user, other_contract));
+    }
+}
@@ -0,0 +1,74 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+/// Session cookies of the HTTP origins requiring a login, keyed by the session key
+/// of their cookie config
+///
+/// The cookies are never exposed by the canister endpoints. The cache lives in the
+/// heap memory, so it is lost on canister upgrade and the origins log in again.
+#[derive(Debug, Default, Clone)]
+pub struct CookieCache {}
+
+impl CookieCache {
+    /// Returns the cookie of the session, unless it expired at `now`
+    pub fn get(&self, session_key: &str, now: u64) -> Option<String> {
+        COOKIE_CACHE.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            let (cookie, expires_at) = cache.get(session_key)?;
+            if *expires_at <= now {
+                cache.remove(session_key);
+                return None;
+            }
+
+            Some(cookie.clone())
+        })
+    }
+
+    /// Caches the cookie of the session until `expires_at`
+    pub fn insert(&self, session_key: String, cookie: String, expires_at: u64) {
+        COOKIE_CACHE.with(|cache| {
+            cache.borrow_mut().insert(session_key, (cookie, expires_at));
+        });
+    }
+
+    /// Drops the cookie of the session, e.g. after the session was rejected
+    pub fn remove(&self, session_key: &str) {
+        COOKIE_CACHE.with(|cache| {
+            cache.borrow_mut().remove(session_key);
+        });
+    }
+
+    pub fn clear(&self) {
+        COOKIE_CACHE.with(|cache| cache.borrow_mut().clear());
+    }
+}
+
+thread_local! {
+    static COOKIE_CACHE: RefCell<BTreeMap<String, (String, u64)>> = RefCell::new(BTreeMap::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cookie_cache() {
+        let cache = CookieCache::default();
+        cache.clear();
+
+        let login_url = "https://example.com/login";
+        assert_eq!(cache.get(login_url, 0), None);
+
+        cache.insert(login_url.to_string(), "abc".to_string(), 100);
+        assert_eq!(cache.get(login_url, 99), Some("abc".to_string()));
+        assert_eq!(cache.get("https://other.example.com/login", 99), None);
+
+        cache.remove(login_url);
+        assert_eq!(cache.get(login_url, 99), None);
+
+        // Expired cookies are dropped
+        cache.insert(login_url.to_string(), "def".to_string(), 100);
+        assert_eq!(cache.get(login_url, 100), None);
+        assert_eq!(cache.get(login_url, 0), None);
+    }
+}
@@ -0,0 +1,86 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+
+use did::H160;
+
+use crate::constants::ORACLE_CREATION_RATE_WINDOW_SECS;
+
+/// Creation times of the recent oracles of each user, used to rate limit the
+/// oracle creation
+///
+/// The timestamps live in the heap memory, so they are lost on canister upgrade.
+#[derive(Debug, Default, Clone)]
+pub struct UserCreationTimestamps {}
+
+impl UserCreationTimestamps {
+    /// Drops the creation times of the user older than the rate limit window
+    /// ending at `now` and returns the number of the remaining ones
+    pub fn count_recent(&self, user_address: &H160, now: u64) -> u64 {
+        let window = ORACLE_CREATION_RATE_WINDOW_SECS * 1_000_000_000;
+
+        USER_CREATION_TIMESTAMPS.with(|timestamps| {
+            let mut timestamps = timestamps.borrow_mut();
+            let Some(user_timestamps) = timestamps.get_mut(user_address) else {
+                return 0;
+            };
+
+            while user_timestamps
+                .front()
+                .is_some_and(|created_at| now.saturating_sub(*created_at) >= window)
+            {
+                user_timestamps.pop_front();
+            }
+
+            let count = user_timestamps.len() as u64;
+            if count == 0 {
+                timestamps.remove(user_address);
+            }
+
+            count
+        })
+    }
+
+    /// Records the creation of an oracle of the user at `now`
+    pub fn record(&self, user_address: H160, now: u64) {
+        USER_CREATION_TIMESTAMPS.with(|timestamps| {
+            timestamps
+                .borrow_mut()
+                .entry(user_address)
+                .or_default()
+                .push_back(now);
+        });
+    }
+
+    pub fn clear(&self) {
+        USER_CREATION_TIMESTAMPS.with(|timestamps| timestamps.borrow_mut().clear());
+    }
+}
+
+thread_local! {
+    static USER_CREATION_TIMESTAMPS: RefCell<BTreeMap<H160, VecDeque<u64>>> = RefCell::new(BTreeMap::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_creation_timestamps() {
+        let timestamps = UserCreationTimestamps::default();
+        timestamps.clear();
+
+        let user_address = H160::from_slice(&[1; 20]);
+        let window = ORACLE_CREATION_RATE_WINDOW_SECS * 1_000_000_000;
+
+        assert_eq!(timestamps.count_recent(&user_address, 0), 0);
+
+        timestamps.record(user_address.clone(), 100);
+        timestamps.record(user_address.clone(), 200);
+        assert_eq!(timestamps.count_recent(&user_address, 200), 2);
+        assert_eq!(timestamps.count_recent(&H160::from_slice(&[2; 20]), 200), 0);
+
+        // The creations older than the window are dropped
+        assert_eq!(timestamps.count_recent(&user_address, 100 + window), 1);
+        assert_eq!(timestamps.count_recent(&user_address, 200 + window), 0);
+    }
+}
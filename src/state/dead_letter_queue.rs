@@ -0,0 +1,174 @@
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+
+use candid::CandidType;
+use did::{codec, H160, U256};
+use ic_stable_structures::{
+    Bound, ChunkSize, SlicedStorable, StableUnboundedMap, Storable, UnboundedMapStructure,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::constants::MAX_DEAD_LETTER_ENTRIES;
+use crate::memory::{MemoryType, DEAD_LETTER_MEMORY_ID, MEMORY_MANAGER};
+
+/// Failed oracle updates, keyed by an increasing sequence number
+///
+/// The oldest entries are dropped once the queue holds `MAX_DEAD_LETTER_ENTRIES`.
+#[derive(Debug, Default, Clone)]
+pub struct DeadLetterQueue {}
+
+impl DeadLetterQueue {
+    /// Appends the entry to the queue and returns its sequence number
+    pub fn push(&self, entry: DeadLetterEntry) -> u64 {
+        DEAD_LETTER_QUEUE.with(|queue| {
+            let mut queue = queue.borrow_mut();
+
+            let sequence = NEXT_SEQUENCE.with(|next| {
+                // the sequence is restored from the last entry after an upgrade
+                let sequence = next
+                    .get()
+                    .unwrap_or_else(|| queue.iter().last().map_or(0, |(sequence, _)| sequence + 1));
+                next.set(Some(sequence + 1));
+                sequence
+            });
+
+            queue.insert(&sequence, &entry);
+
+            while queue.len() > MAX_DEAD_LETTER_ENTRIES {
+                let Some((oldest, _)) = queue.iter().next() else {
+                    break;
+                };
+                queue.remove(&oldest);
+            }
+
+            sequence
+        })
+    }
+
+    /// Returns the `limit` most recent entries, most recent first
+    pub fn get_entries(&self, limit: usize) -> Vec<(u64, DeadLetterEntry)> {
+        DEAD_LETTER_QUEUE.with(|queue| {
+            let entries = queue.borrow().iter().collect::<Vec<_>>();
+            entries.into_iter().rev().take(limit).collect()
+        })
+    }
+
+    /// Returns the number of entries in the queue
+    pub fn count(&self) -> u64 {
+        DEAD_LETTER_QUEUE.with(|queue| queue.borrow().len())
+    }
+
+    /// Drops all the entries and returns their number
+    ///
+    /// The sequence numbers keep increasing from the last dropped entry.
+    pub fn drain(&self) -> u64 {
+        DEAD_LETTER_QUEUE.with(|queue| {
+            let mut queue = queue.borrow_mut();
+            let len = queue.len();
+            queue.clear();
+            len
+        })
+    }
+
+    pub fn clear(&self) {
+        DEAD_LETTER_QUEUE.with(|queue| queue.borrow_mut().clear());
+        NEXT_SEQUENCE.with(|next| next.set(None));
+    }
+}
+
+/// Oracle update which failed after all its retries
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Eq)]
+pub struct DeadLetterEntry {
+    /// IC time of the failure in nanoseconds
+    pub timestamp: u64,
+    pub user_address: H160,
+    pub contract_address: H160,
+    pub error: String,
+    /// Price the update tried to submit, when it was fetched
+    pub price_attempted: Option<U256>,
+    /// Last price submitted by the oracle before the failure
+    pub last_submitted_price: Option<U256>,
+}
+
+impl Storable for DeadLetterEntry {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        codec::encode(&self).into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        codec::decode(&bytes)
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl SlicedStorable for DeadLetterEntry {
+    const CHUNK_SIZE: ChunkSize = 64;
+}
+
+thread_local! {
+    static DEAD_LETTER_QUEUE: RefCell<StableUnboundedMap<u64, DeadLetterEntry, MemoryType>> = RefCell::new(StableUnboundedMap::new(MEMORY_MANAGER.with(|mm|mm.get(DEAD_LETTER_MEMORY_ID))));
+
+    /// Sequence number of the next entry, `None` until the first entry is pushed
+    static NEXT_SEQUENCE: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(error: &str) -> DeadLetterEntry {
+        DeadLetterEntry {
+            timestamp: 100,
+            user_address: H160::from_slice(&[1; 20]),
+            contract_address: H160::from_slice(&[2; 20]),
+            error: error.to_string(),
+            price_attempted: None,
+            last_submitted_price: None,
+        }
+    }
+
+    #[test]
+    fn test_dead_letter_queue() {
+        let queue = DeadLetterQueue::default();
+        queue.clear();
+
+        assert_eq!(queue.push(entry("first")), 0);
+        assert_eq!(queue.push(entry("second")), 1);
+        assert_eq!(queue.count(), 2);
+
+        assert_eq!(
+            queue.get_entries(1),
+            vec![(1, entry("second"))],
+            "most recent entries first"
+        );
+        assert_eq!(
+            queue.get_entries(10),
+            vec![(1, entry("second")), (0, entry("first"))]
+        );
+
+        // The sequence keeps increasing after the queue is drained
+        assert_eq!(queue.drain(), 2);
+        assert_eq!(queue.get_entries(10), vec![]);
+        assert_eq!(queue.push(entry("third")), 2);
+    }
+
+    #[test]
+    fn test_dead_letter_queue_drops_oldest_entries() {
+        let queue = DeadLetterQueue::default();
+        queue.clear();
+
+        for i in 0..MAX_DEAD_LETTER_ENTRIES + 2 {
+            queue.push(entry(&i.to_string()));
+        }
+
+        assert_eq!(queue.count(), MAX_DEAD_LETTER_ENTRIES);
+        assert_eq!(
+            queue.get_entries(1),
+            vec![(
+                MAX_DEAD_LETTER_ENTRIES + 1,
+                entry(&(MAX_DEAD_LETTER_ENTRIES + 1).to_string())
+            )]
+        );
+    }
+}
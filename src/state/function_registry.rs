@@ -0,0 +1,129 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+use did::codec;
+use ethers_core::abi::Function;
+use ic_stable_structures::{BTreeMapStructure, Bound, StableBTreeMap, Storable};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::memory::{MemoryType, FUNCTION_REGISTRY_MEMORY_ID, MEMORY_MANAGER};
+
+/// Update functions registered by the canister owner, keyed by name
+///
+/// Custom update functions of the oracles refer to them with `FunctionRef::Registered`.
+#[derive(Debug, Default, Clone)]
+pub struct FunctionRegistry {}
+
+impl FunctionRegistry {
+    /// Returns the function registered under the name
+    pub fn get(&self, name: &str) -> Result<SerializedFunction> {
+        FUNCTION_REGISTRY.with(|registry| {
+            registry.borrow().get(&name.to_string()).ok_or_else(|| {
+                Error::InvalidUpdateFunction(format!("function '{name}' not registered"))
+            })
+        })
+    }
+
+    /// Registers the function under the name, replacing the previous one
+    pub fn insert(&self, name: String, function: SerializedFunction) {
+        FUNCTION_REGISTRY.with(|registry| {
+            registry.borrow_mut().insert(name, function);
+        });
+    }
+
+    /// Returns the names of the registered functions, in alphabetical order
+    pub fn list(&self) -> Vec<String> {
+        FUNCTION_REGISTRY.with(|registry| registry.borrow().iter().map(|(name, _)| name).collect())
+    }
+
+    pub fn clear(&self) {
+        FUNCTION_REGISTRY.with(|registry| registry.borrow_mut().clear());
+    }
+}
+
+/// JSON ABI of a registered function
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SerializedFunction {
+    abi_json: String,
+}
+
+impl SerializedFunction {
+    /// Checks that the JSON is the ABI of a function
+    pub fn parse(abi_json: String) -> Result<Self> {
+        let function = Self { abi_json };
+        function.function()?;
+
+        Ok(function)
+    }
+
+    /// Returns the function described by the ABI
+    pub fn function(&self) -> Result<Function> {
+        serde_json::from_str(&self.abi_json).map_err(|e| Error::InvalidAbi(e.to_string()))
+    }
+
+    pub fn abi_json(&self) -> &str {
+        &self.abi_json
+    }
+}
+
+impl Storable for SerializedFunction {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        codec::encode(&self).into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        codec::decode(&bytes)
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+thread_local! {
+    static FUNCTION_REGISTRY: RefCell<StableBTreeMap<String, SerializedFunction, MemoryType>> = RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm|mm.get(FUNCTION_REGISTRY_MEMORY_ID))));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SET_RATE_ABI: &str = r#"{"type":"function","name":"setRate","inputs":[{"name":"rate","type":"uint256"},{"name":"pair","type":"bytes32"}],"outputs":[],"stateMutability":"nonpayable"}"#;
+
+    #[test]
+    fn test_serialized_function() {
+        let function = SerializedFunction::parse(SET_RATE_ABI.to_string()).unwrap();
+        assert_eq!(
+            function.function().unwrap().signature(),
+            "setRate(uint256,bytes32)"
+        );
+        assert_eq!(function.abi_json(), SET_RATE_ABI);
+
+        for abi_json in ["", "not json", r#"{"name":"setRate"}"#, r#"{"name":1}"#] {
+            assert!(
+                matches!(
+                    SerializedFunction::parse(abi_json.to_string()),
+                    Err(Error::InvalidAbi(_))
+                ),
+                "{abi_json}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_function_registry() {
+        let registry = FunctionRegistry::default();
+        registry.clear();
+
+        assert!(registry.get("setRate").is_err());
+
+        let function = SerializedFunction::parse(SET_RATE_ABI.to_string()).unwrap();
+        registry.insert(String::from("setRate"), function.clone());
+        registry.insert(String::from("anotherRate"), function.clone());
+
+        assert_eq!(registry.get("setRate").unwrap(), function);
+        assert_eq!(
+            registry.list(),
+            vec![String::from("anotherRate"), String::from("setRate")]
+        );
+    }
+}
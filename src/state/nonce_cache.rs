@@ -0,0 +1,136 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use did::{H160, U256};
+
+use crate::constants::NONCE_CACHE_TTL_SECS;
+
+/// Cache of the transaction nonces of the oracle signers
+///
+/// The key is the `(address, chain_id)` pair. The cache lives in the heap
+/// memory, so it is lost on canister upgrade.
+#[derive(Debug, Default, Clone)]
+pub struct NonceCache {}
+
+impl NonceCache {
+    /// Returns the cached nonce and increments it for the next transaction, unless
+    /// it was fetched more than `NONCE_CACHE_TTL_SECS` seconds before `now`
+    ///
+    /// The nonce is reserved before any `await`, so that the transactions built at
+    /// the same time by the same signer get distinct nonces.
+    pub fn reserve(&self, address: &H160, chain_id: u64, now: u64) -> Option<U256> {
+        NONCE_CACHE.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            let entry = cache
+                .get_mut(&(address.clone(), chain_id))
+                .filter(|entry| entry.is_fresh(now))?;
+
+            let nonce = entry.nonce.clone();
+            entry.nonce = (nonce.0 + 1).into();
+            Some(nonce)
+        })
+    }
+
+    /// Reserves a nonce after fetching the transaction count of the signer from
+    /// the EVM at `now`
+    ///
+    /// The nonces reserved by the transactions built while the count was fetched
+    /// are not reused, unless their entry expired.
+    pub fn reserve_fetched(&self, address: H160, chain_id: u64, fetched: U256, now: u64) -> U256 {
+        NONCE_CACHE.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            let nonce = match cache.get(&(address.clone(), chain_id)) {
+                Some(entry) if entry.is_fresh(now) && entry.nonce > fetched => entry.nonce.clone(),
+                _ => fetched,
+            };
+
+            cache.insert(
+                (address, chain_id),
+                CachedNonce {
+                    nonce: (nonce.0 + 1).into(),
+                    fetched_at: now,
+                },
+            );
+
+            nonce
+        })
+    }
+
+    /// Removes the cached nonce, so that the next transaction fetches it again
+    pub fn invalidate(&self, address: H160, chain_id: u64) {
+        NONCE_CACHE.with(|cache| {
+            cache.borrow_mut().remove(&(address, chain_id));
+        });
+    }
+
+    pub fn clear(&self) {
+        NONCE_CACHE.with(|cache| cache.borrow_mut().clear());
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedNonce {
+    nonce: U256,
+    /// IC time in nanoseconds when the nonce was fetched from the EVM
+    fetched_at: u64,
+}
+
+impl CachedNonce {
+    /// Returns whether the nonce was fetched less than `NONCE_CACHE_TTL_SECS`
+    /// seconds before `now`
+    fn is_fresh(&self, now: u64) -> bool {
+        now.saturating_sub(self.fetched_at) < NONCE_CACHE_TTL_SECS * 1_000_000_000
+    }
+}
+
+thread_local! {
+    static NONCE_CACHE: RefCell<BTreeMap<(H160, u64), CachedNonce>> = RefCell::new(BTreeMap::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nonce_cache() {
+        let nonce_cache = NonceCache::default();
+        nonce_cache.clear();
+
+        let address = H160::from_slice(&[1; 20]);
+        let ttl = NONCE_CACHE_TTL_SECS * 1_000_000_000;
+
+        assert_eq!(nonce_cache.reserve(&address, 1, 0), None);
+
+        assert_eq!(
+            nonce_cache.reserve_fetched(address.clone(), 1, U256::from(5u64), 100),
+            U256::from(5u64)
+        );
+        assert_eq!(nonce_cache.reserve(&address, 2, 100), None);
+
+        // Each reservation gets the next nonce
+        assert_eq!(
+            nonce_cache.reserve(&address, 1, 100),
+            Some(U256::from(6u64))
+        );
+        assert_eq!(
+            nonce_cache.reserve(&address, 1, 100),
+            Some(U256::from(7u64))
+        );
+
+        // A stale count fetched concurrently does not reuse the reserved nonces
+        assert_eq!(
+            nonce_cache.reserve_fetched(address.clone(), 1, U256::from(5u64), 100),
+            U256::from(8u64)
+        );
+
+        // The entry expires after the TTL
+        assert_eq!(
+            nonce_cache.reserve(&address, 1, 100 + ttl - 1),
+            Some(U256::from(9u64))
+        );
+        assert_eq!(nonce_cache.reserve(&address, 1, 100 + ttl), None);
+
+        nonce_cache.invalidate(address.clone(), 1);
+        assert_eq!(nonce_cache.reserve(&address, 1, 100), None);
+    }
+}
@@ -1,18 +1,35 @@
 use std::borrow::Cow;
 use std::cell::RefCell;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
 
 use candid::CandidType;
-use did::H160;
+use did::{H160, H256, U256};
 use ic_exports::ic_cdk_timers::TimerId;
 use ic_stable_structures::{
-    Bound, ChunkSize, SlicedStorable, StableUnboundedMap, Storable, UnboundedMapStructure,
+    BTreeMapStructure, Bound, ChunkSize, SlicedStorable, StableBTreeMap, StableUnboundedMap,
+    Storable, UnboundedMapStructure,
 };
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use self::legacy::LegacyMetadataCollection;
+use crate::canister::{EvmDestination, Origin, OriginType};
+use crate::constants::{
+    DEFAULT_CONFIRMATION_BLOCKS, DEFAULT_CONFIRMATION_DELAY_SECS, DEFAULT_ERROR_THRESHOLD,
+    DEFAULT_RECEIPT_MAX_WAIT_SECS, DEFAULT_RETRY_BASE_DELAY_SECS, MAX_CYCLES_PER_CALL_HISTORY,
+    MAX_ORACLE_EVENTS, MAX_ORACLE_TAGS, MAX_PRICE_HISTORY,
+};
+use crate::eip712::{Eip712DomainConfig, SignedOracleData};
+use crate::error::{Error, PipelineError, Result};
+use crate::events::OracleEvent;
+use crate::memory::{
+    MemoryType, MEMORY_MANAGER, ORACLE_STORAGE_MEMORY_ID, USER_ORACLE_COUNT_MEMORY_ID,
+};
+use crate::parser::ValueParser;
+use crate::provider::{CustomUpdateFn, Provider};
 
-use crate::canister::{EvmDestination, Origin};
-use crate::error::{Error, Result};
-use crate::memory::{MemoryType, MEMORY_MANAGER, ORACLE_STORAGE_MEMORY_ID};
+mod legacy;
 
 /// Storage for Oracle metadata
 #[derive(Debug, Default, Clone)]
@@ -28,20 +45,27 @@ impl OracleStorage {
         timer_id: TimerId,
         evm: EvmDestination,
     ) {
+        self.insert_oracle(StorableOracleMetadata::new(
+            user_address,
+            origin,
+            timestamp,
+            timer_id,
+            evm,
+        ));
+    }
+
+    /// Saves the oracle, replacing any oracle of the owner with the same contract address
+    pub fn insert_oracle(&self, metadata: StorableOracleMetadata) {
         ORACLE_STORAGE.with(|storage| {
             let mut storage = storage.borrow_mut();
-            let metadata = StorableOracleMetadata {
-                origin,
-                timer_id,
-                timer_interval: timestamp,
-                evm: evm.clone(),
-                owner: user_address.clone(),
-            };
+            let user_address = metadata.owner.clone();
 
             let mut map = storage.get(&user_address).unwrap_or_default();
 
-            map.0.insert(evm.contract, metadata);
+            map.0.insert(metadata.evm.contract.clone(), metadata);
             storage.insert(&user_address, &map);
+
+            set_user_oracle_count(user_address, map.0.len());
         });
     }
 
@@ -95,6 +119,177 @@ impl OracleStorage {
         })
     }
 
+    /// Returns the oracles of the user with the given origin type
+    pub fn get_user_oracles_by_type(
+        &self,
+        user_address: H160,
+        origin_type: OriginType,
+    ) -> Result<Vec<(H160, OracleMetadata)>> {
+        ORACLE_STORAGE.with(|storage| {
+            let storage = storage.borrow();
+
+            let vec = storage.get(&user_address).ok_or(Error::UserNotFound)?;
+
+            Ok(vec
+                .0
+                .into_iter()
+                .filter(|(_, v)| OriginType::from(&v.origin) == origin_type)
+                .map(|(k, v)| (k, v.into()))
+                .collect())
+        })
+    }
+
+    /// Returns the oracles of the user whose label contains `label`, ignoring case
+    pub fn find_user_oracles_by_label(
+        &self,
+        user_address: H160,
+        label: &str,
+    ) -> Result<Vec<(H160, OracleMetadata)>> {
+        let label = label.to_lowercase();
+
+        ORACLE_STORAGE.with(|storage| {
+            let storage = storage.borrow();
+
+            let vec = storage.get(&user_address).ok_or(Error::UserNotFound)?;
+
+            Ok(vec
+                .0
+                .into_iter()
+                .filter(|(_, v)| {
+                    v.label
+                        .as_ref()
+                        .is_some_and(|l| l.to_lowercase().contains(&label))
+                })
+                .map(|(k, v)| (k, v.into()))
+                .collect())
+        })
+    }
+
+    /// Returns the oracles of the user with the given tag
+    pub fn get_user_oracles_by_tag(
+        &self,
+        user_address: H160,
+        tag: &str,
+    ) -> Result<Vec<(H160, OracleMetadata)>> {
+        ORACLE_STORAGE.with(|storage| {
+            let storage = storage.borrow();
+
+            let vec = storage.get(&user_address).ok_or(Error::UserNotFound)?;
+
+            Ok(vec
+                .0
+                .into_iter()
+                .filter(|(_, v)| v.tags.iter().any(|t| t == tag))
+                .map(|(k, v)| (k, v.into()))
+                .collect())
+        })
+    }
+
+    /// Returns the user address, contract address and metadata of the oracles
+    /// of all the users with the given tag
+    pub fn get_oracles_by_tag(&self, tag: &str) -> Vec<(H160, H160, OracleMetadata)> {
+        ORACLE_STORAGE.with(|storage| {
+            storage
+                .borrow()
+                .iter()
+                .flat_map(|(user_address, metadata_collection)| {
+                    metadata_collection
+                        .0
+                        .into_iter()
+                        .filter(|(_, v)| v.tags.iter().any(|t| t == tag))
+                        .map(move |(k, v)| (user_address.clone(), k, v.into()))
+                })
+                .collect()
+        })
+    }
+
+    /// Returns the number of oracles registered by the user
+    ///
+    /// The count is read from an index, without loading the oracles of the user.
+    pub fn get_user_oracle_count(&self, user_address: H160) -> u64 {
+        USER_ORACLE_COUNT.with(|index| index.borrow().get(&user_address).unwrap_or_default())
+    }
+
+    /// Returns the number of oracles of all the users, read from the user index
+    pub fn get_oracle_count(&self) -> u64 {
+        USER_ORACLE_COUNT.with(|index| index.borrow().iter().map(|(_, count)| count).sum())
+    }
+
+    /// Returns the status of every oracle, without converting the oracles
+    pub fn get_oracle_statuses(&self) -> Vec<OracleStatus> {
+        ORACLE_STORAGE.with(|storage| {
+            storage
+                .borrow()
+                .iter()
+                .flat_map(|(_, collection)| {
+                    collection.0.into_values().map(|metadata| metadata.status)
+                })
+                .collect()
+        })
+    }
+
+    /// Returns the number of users stored in the oracle storage
+    ///
+    /// Only the keys of the storage are counted, the oracles are not loaded.
+    pub fn get_storage_entry_count(&self) -> u64 {
+        ORACLE_STORAGE.with(|storage| storage.borrow().len())
+    }
+
+    /// Returns the addresses of the users with at least one oracle
+    pub fn get_users(&self) -> Vec<H160> {
+        USER_ORACLE_COUNT.with(|index| index.borrow().iter().map(|(user, _)| user).collect())
+    }
+
+    /// Fills the user oracle count index after an upgrade from a release without it
+    ///
+    /// The index is updated along with the oracles, so the oracles are only loaded
+    /// when the index has fewer users than the storage.
+    pub fn init_user_index(&self) {
+        let indexed_users = USER_ORACLE_COUNT.with(|index| index.borrow().len());
+        if indexed_users == self.get_storage_entry_count() {
+            return;
+        }
+
+        ORACLE_STORAGE.with(|storage| {
+            for (user_address, collection) in storage.borrow().iter() {
+                set_user_oracle_count(user_address, collection.0.len());
+            }
+        });
+    }
+
+    /// Returns the stored metadata of all the oracles
+    pub fn get_storable_oracles(&self) -> Vec<StorableOracleMetadata> {
+        ORACLE_STORAGE.with(|storage| {
+            storage
+                .borrow()
+                .iter()
+                .flat_map(|(_, collection)| collection.0.into_values())
+                .collect()
+        })
+    }
+
+    /// Replaces all the oracles with the given ones, rebuilding the user index
+    pub fn replace_oracles(&self, oracles: Vec<StorableOracleMetadata>) {
+        self.clear();
+
+        let mut collections = BTreeMap::<H160, MetadataCollection>::new();
+        for metadata in oracles {
+            collections
+                .entry(metadata.owner.clone())
+                .or_default()
+                .0
+                .insert(metadata.evm.contract.clone(), metadata);
+        }
+
+        ORACLE_STORAGE.with(|storage| {
+            let mut storage = storage.borrow_mut();
+            for (user_address, collection) in collections {
+                storage.insert(&user_address, &collection);
+                set_user_oracle_count(user_address, collection.0.len());
+            }
+        });
+    }
+
     pub fn get_oracles(&self) -> Vec<(H160, BTreeMap<H160, OracleMetadata>)> {
         ORACLE_STORAGE.with(|storage| {
             let storage = storage.borrow();
@@ -112,6 +307,29 @@ impl OracleStorage {
         })
     }
 
+    /// Returns the oracles with the given origin type, skipping the users without any
+    pub fn get_oracles_by_type(
+        &self,
+        origin_type: OriginType,
+    ) -> Vec<(H160, BTreeMap<H160, OracleMetadata>)> {
+        ORACLE_STORAGE.with(|storage| {
+            let storage = storage.borrow();
+            storage
+                .iter()
+                .map(|(k, v)| {
+                    (
+                        k,
+                        v.0.into_iter()
+                            .filter(|(_, v)| OriginType::from(&v.origin) == origin_type)
+                            .map(|(k, v)| (k, v.into()))
+                            .collect::<BTreeMap<_, _>>(),
+                    )
+                })
+                .filter(|(_, oracles)| !oracles.is_empty())
+                .collect()
+        })
+    }
+
     pub fn remove_oracle_by_address(
         &self,
         user_address: H160,
@@ -131,10 +349,74 @@ impl OracleStorage {
                 storage.insert(&user_address, &map);
             }
 
+            set_user_oracle_count(user_address, map.0.len());
+
             Ok(())
         })
     }
 
+    /// Moves the oracle from the collection of `from` to the collection of `to`
+    pub fn transfer_oracle(
+        &self,
+        from: H160,
+        to: H160,
+        evm_contract_address: H160,
+    ) -> Result<StorableOracleMetadata> {
+        ORACLE_STORAGE.with(|storage| {
+            let mut storage = storage.borrow_mut();
+
+            let mut from_collection = storage.get(&from).ok_or(Error::UserNotFound)?;
+            let mut to_collection = storage.get(&to).unwrap_or_default();
+
+            if to_collection.0.contains_key(&evm_contract_address) {
+                return Err(Error::OracleAlreadyExists);
+            }
+
+            let mut metadata = from_collection
+                .0
+                .remove(&evm_contract_address)
+                .ok_or(Error::OracleNotFound)?;
+            metadata.owner = to.clone();
+
+            to_collection
+                .0
+                .insert(evm_contract_address, metadata.clone());
+
+            if from_collection.0.is_empty() {
+                storage.remove(&from).expect("User should exist");
+            } else {
+                storage.insert(&from, &from_collection);
+            }
+            storage.insert(&to, &to_collection);
+
+            // The triggers of the other oracles follow the transferred oracle
+            let moved = (from.clone(), evm_contract_address.clone());
+            let collections = storage.iter().collect::<Vec<_>>();
+            for (user_address, mut metadata_collection) in collections {
+                let mut changed = false;
+                for trigger in metadata_collection
+                    .0
+                    .values_mut()
+                    .flat_map(|metadata| metadata.triggers.iter_mut())
+                {
+                    if *trigger == moved {
+                        *trigger = (to.clone(), evm_contract_address.clone());
+                        changed = true;
+                    }
+                }
+
+                if changed {
+                    storage.insert(&user_address, &metadata_collection);
+                }
+            }
+
+            set_user_oracle_count(from, from_collection.0.len());
+            set_user_oracle_count(to.clone(), to_collection.0.len());
+
+            Ok(metadata)
+        })
+    }
+
     pub fn update_oracle_metadata(
         &self,
         user_address: H160,
@@ -155,14 +437,131 @@ impl OracleStorage {
             if let Some(origin) = update_metadata.origin {
                 metadata.origin = origin;
             }
+            if let Some(decimals) = update_metadata.decimals {
+                let Origin::Http(origin) = &mut metadata.origin else {
+                    return Err(Error::Internal(
+                        "decimals can only be set on HTTP origins".to_string(),
+                    ));
+                };
+                origin.decimals = Some(decimals);
+            }
+            if let Some(max_response_bytes) = update_metadata.max_response_bytes {
+                let Origin::Http(origin) = &mut metadata.origin else {
+                    return Err(Error::Internal(
+                        "max response bytes can only be set on HTTP origins".to_string(),
+                    ));
+                };
+                origin.max_response_bytes = Some(max_response_bytes);
+            }
             if let Some(timestamp) = update_metadata.timestamp {
                 metadata.timer_interval = timestamp;
             }
             if let Some(evm) = update_metadata.evm {
                 metadata.evm = evm;
             }
+            if let Some(error_threshold) = update_metadata.error_threshold {
+                metadata.error_threshold = error_threshold;
+            }
+            if let Some(expires_at) = update_metadata.expires_at {
+                metadata.expires_at = Some(expires_at);
+            }
+            if let Some(verify_receipt) = update_metadata.verify_receipt {
+                metadata.verify_receipt = verify_receipt;
+            }
+            if let Some(confirmation_delay_secs) = update_metadata.confirmation_delay_secs {
+                metadata.confirmation_delay_secs = confirmation_delay_secs;
+            }
+            if let Some(confirmation_blocks) = update_metadata.confirmation_blocks {
+                metadata.confirmation_blocks = confirmation_blocks;
+            }
+            if let Some(max_wait_secs) = update_metadata.max_wait_secs {
+                metadata.max_wait_secs = max_wait_secs;
+            }
+            if let Some(cycle_budget) = update_metadata.cycle_budget {
+                metadata.cycle_budget = Some(cycle_budget);
+            }
+            if let Some(resume_threshold_cycles) = update_metadata.resume_threshold_cycles {
+                metadata.resume_threshold_cycles = Some(resume_threshold_cycles);
+            }
+            if let Some(triggers) = update_metadata.triggers {
+                metadata.triggers = triggers;
+            }
+            if let Some(price_bounds) = update_metadata.price_bounds {
+                metadata.price_bounds = Some(price_bounds);
+            }
+            if let Some(simulation_mode) = update_metadata.simulation_mode {
+                metadata.simulation_mode = simulation_mode;
+            }
+            if let Some(max_gas_price_gwei) = update_metadata.max_gas_price_gwei {
+                metadata.max_gas_price_gwei = Some(max_gas_price_gwei);
+            }
+            if let Some(min_eth_balance_check) = update_metadata.min_eth_balance_check {
+                metadata.min_eth_balance_check = Some(min_eth_balance_check);
+            }
+            if let Some(suppress_duplicates) = update_metadata.suppress_duplicates {
+                metadata.suppress_duplicates = suppress_duplicates;
+            }
+            if let Some(include_round_id) = update_metadata.include_round_id {
+                metadata.include_round_id = include_round_id;
+            }
+            if let Some(include_signed_price) = update_metadata.include_signed_price {
+                metadata.include_signed_price = include_signed_price;
+            }
+            if let Some(custom_update_fn) = update_metadata.custom_update_fn {
+                metadata.custom_update_fn = Some(custom_update_fn);
+            }
+            if let Some(circuit_breaker) = update_metadata.circuit_breaker {
+                metadata.circuit_breaker = Some(circuit_breaker);
+            }
+            if let Some(label) = update_metadata.label {
+                metadata.label = Some(label);
+            }
+            if let Some(description) = update_metadata.description {
+                metadata.description = Some(description);
+            }
+            if let Some(tags) = update_metadata.tags {
+                metadata.tags = tags;
+            }
+            if let Some(max_executions) = update_metadata.max_executions {
+                metadata.max_executions = Some(max_executions);
+            }
+            if let Some(execution_count) = update_metadata.execution_count {
+                metadata.execution_count = execution_count;
+            }
+            if let Some(fallback_price) = update_metadata.fallback_price {
+                metadata.fallback_price = Some(fallback_price);
+            }
+            if let Some(transforms) = update_metadata.transforms {
+                metadata.transforms = transforms;
+            }
+            if let Some(outlier_config) = update_metadata.outlier_config {
+                metadata.outlier_config = Some(outlier_config);
+            }
+            if let Some(max_change_rate_bps) = update_metadata.max_change_rate_bps {
+                metadata.max_change_rate_bps = Some(max_change_rate_bps);
+            }
+            if let Some(response_validators) = update_metadata.response_validators {
+                metadata.response_validators = response_validators;
+            }
+            if let Some(max_retries) = update_metadata.max_retries {
+                metadata.max_retries = max_retries;
+            }
+            if let Some(retry_base_delay_secs) = update_metadata.retry_base_delay_secs {
+                metadata.retry_base_delay_secs = retry_base_delay_secs;
+            }
+            if let Some(tx_deadline_secs) = update_metadata.tx_deadline_secs {
+                metadata.tx_deadline_secs = Some(tx_deadline_secs);
+            }
+            if let Some(max_history_entries) = update_metadata.max_history_entries {
+                metadata.max_history_entries = Some(max_history_entries);
+                metadata.prune_price_history(metadata.max_price_history());
+            }
+            // A pending oracle is activated by its first tick
             if let Some(timer_id) = new_timer_id {
                 metadata.timer_id = timer_id;
+                if metadata.status != OracleStatus::Pending {
+                    metadata.status = OracleStatus::Active;
+                }
             }
 
             storage.insert(&user_address, &metadata_collection);
@@ -184,97 +583,2505 @@ impl OracleStorage {
         })
     }
 
-    pub fn clear(&self) {
+    /// Returns the `(user_address, contract_address)` pairs of the oracles expired at `now`
+    pub fn get_expired_oracles(&self, now: u64) -> Vec<(H160, H160)> {
         ORACLE_STORAGE.with(|storage| {
-            let mut storage = storage.borrow_mut();
-            storage.clear();
-        });
+            storage
+                .borrow()
+                .iter()
+                .flat_map(|(user_address, collection)| {
+                    collection
+                        .0
+                        .into_iter()
+                        .filter(move |(_, metadata)| {
+                            metadata
+                                .expires_at
+                                .is_some_and(|expires_at| now >= expires_at)
+                        })
+                        .map(move |(contract_address, _)| (user_address.clone(), contract_address))
+                })
+                .collect()
+        })
     }
-}
 
-thread_local! {
-    static ORACLE_STORAGE: RefCell<StableUnboundedMap<H160, MetadataCollection, MemoryType>> = RefCell::new(StableUnboundedMap::new(MEMORY_MANAGER.with(|mm|mm.get(ORACLE_STORAGE_MEMORY_ID))));
-}
+    /// Counts a timer execution of the oracle and returns whether it reached its
+    /// maximum number of executions
+    ///
+    /// An oracle that already reached the maximum is not counted again, and
+    /// `Ok(None)` is returned to skip the execution.
+    pub fn record_execution(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+    ) -> Result<Option<bool>> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            if metadata
+                .max_executions
+                .is_some_and(|max| metadata.execution_count >= max)
+            {
+                return None;
+            }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct StorableOracleMetadata {
-    pub origin: Origin,
-    pub timer_interval: u64,
-    pub timer_id: TimerId,
-    pub evm: EvmDestination,
-    /// Owner of the oracle
-    pub owner: H160,
-}
+            metadata.execution_count = metadata.execution_count.saturating_add(1);
 
-impl Storable for MetadataCollection {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        did::codec::bincode_encode(&self).into()
+            Some(
+                metadata
+                    .max_executions
+                    .is_some_and(|max| metadata.execution_count >= max),
+            )
+        })
     }
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        did::codec::bincode_decode(&bytes)
+    /// Sets the number of rotations of the signing key of the oracle
+    pub fn set_rotation_count(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        rotation_count: u32,
+    ) -> Result<()> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            metadata.rotation_count = rotation_count;
+        })
     }
 
-    const BOUND: Bound = Bound::Unbounded;
-}
-
-/// Collection of oracle metadata
-/// The key is the EVM contract address
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
-pub struct MetadataCollection(BTreeMap<H160, StorableOracleMetadata>);
+    /// Records the start of an update attempt at `now`
+    pub fn record_update_attempt(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        now: u64,
+    ) -> Result<()> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            metadata.total_updates = metadata.total_updates.saturating_add(1);
+            metadata.last_attempt_at = now;
+        })
+    }
 
-impl SlicedStorable for MetadataCollection {
-    const CHUNK_SIZE: ChunkSize = 64;
-}
+    /// Records the price pushed by a successful update at `now` and starts a new round
+    ///
+    /// The consecutive error counter is reset and an oracle in the `Error` state
+    /// or with an open circuit is moved back to `Active`.
+    pub fn record_update_success(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        price: U256,
+        now: u64,
+    ) -> Result<()> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            metadata.price_history.push_back(price.clone());
+            metadata.prune_price_history(metadata.max_price_history());
+            metadata.last_price = Some(price);
+            metadata.last_updated_at = now;
+            metadata.round_id += 1;
+            metadata.consecutive_errors = 0;
+            if matches!(
+                metadata.status,
+                OracleStatus::Error | OracleStatus::CircuitOpen { .. }
+            ) {
+                metadata.status = OracleStatus::Active;
+            }
+        })
+    }
 
-/// Struct used to store the oracle metadata
-#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
-pub struct OracleMetadata {
-    /// The origin of the oracle
-    pub origin: Origin,
-    /// The interval at which the oracle should be called
-    pub timer_interval: u64,
-    /// The destination of the oracle
-    pub evm: EvmDestination,
-    /// Owner of the oracle
-    pub owner: H160,
-}
+    /// Increments the error counters after a failed update, stores its error message
+    /// and returns the new status
+    ///
+    /// When the consecutive errors reach the error threshold the oracle enters
+    /// the `Error` state. Otherwise, when they reach the failure threshold of its
+    /// circuit breaker, the circuit is opened, or opened again, at `now`.
+    pub fn record_update_failure(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        error: PipelineError,
+        now: u64,
+    ) -> Result<OracleStatus> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            metadata.last_error = Some(error);
+            metadata.consecutive_errors = metadata.consecutive_errors.saturating_add(1);
+            metadata.total_errors = metadata.total_errors.saturating_add(1);
+
+            if metadata.consecutive_errors >= metadata.error_threshold {
+                metadata.status = OracleStatus::Error;
+            } else if let Some(circuit_breaker) = metadata.circuit_breaker.as_ref().filter(|cb| {
+                metadata.status.has_running_timer()
+                    && metadata.consecutive_errors >= cb.failure_threshold
+            }) {
+                metadata.status = circuit_breaker.open(now);
+            }
 
-impl From<StorableOracleMetadata> for OracleMetadata {
-    fn from(storable: StorableOracleMetadata) -> Self {
-        Self {
-            origin: storable.origin,
-            timer_interval: storable.timer_interval,
-            evm: storable.evm,
-            owner: storable.owner,
-        }
+            metadata.status.clone()
+        })
     }
-}
 
-/// Struct used to update the oracle metadata
-#[derive(Debug, Clone, Default, Serialize, Deserialize, CandidType)]
-pub struct UpdateOracleMetadata {
-    pub origin: Option<Origin>,
-    pub evm: Option<EvmDestination>,
-    pub timestamp: Option<u64>,
+    /// Counts an update which panicked
+    ///
+    /// The update is counted as failed separately, with `record_update_failure`.
+    pub fn record_update_panic(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+    ) -> Result<()> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            metadata.panic_count = metadata.panic_count.saturating_add(1);
+        })
+    }
+
+    /// Counts an update skipped before its price was submitted, like a price
+    /// rejected as an outlier or a throttled update, as a failed update and
+    /// returns the status of the oracle
+    ///
+    /// The consecutive errors are left unchanged, as a skipped update is not a
+    /// failure of the origin.
+    pub fn record_skipped_update(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        error: PipelineError,
+        reason: OracleSkipReason,
+    ) -> Result<OracleStatus> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            metadata.last_error = Some(error);
+            metadata.last_skip_reason = Some(reason);
+            metadata.total_errors = metadata.total_errors.saturating_add(1);
+
+            metadata.status.clone()
+        })
+    }
+
+    /// Resets the error counters and the last error of the oracle, closing its circuit
+    pub fn reset_oracle_errors(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+    ) -> Result<()> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            metadata.consecutive_errors = 0;
+            metadata.total_errors = 0;
+            metadata.last_error = None;
+            if matches!(metadata.status, OracleStatus::CircuitOpen { .. }) {
+                metadata.status = OracleStatus::Active;
+            }
+        })
+    }
+
+    /// Adds a tag to the oracle, failing when it already has `MAX_ORACLE_TAGS` tags
+    pub fn add_oracle_tag(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        tag: String,
+    ) -> Result<()> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            if metadata.tags.contains(&tag) {
+                return Ok(());
+            }
+            if metadata.tags.len() >= MAX_ORACLE_TAGS {
+                return Err(Error::Internal(format!(
+                    "an oracle can have at most {MAX_ORACLE_TAGS} tags"
+                )));
+            }
+
+            metadata.tags.push(tag);
+            Ok(())
+        })?
+    }
+
+    /// Removes a tag from the oracle
+    pub fn remove_oracle_tag(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        tag: &str,
+    ) -> Result<()> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            metadata.tags.retain(|t| t != tag);
+        })
+    }
+
+    /// Sets the status of the oracle
+    ///
+    /// The status replaces the one to restore after an emergency pause, so the
+    /// oracle is left untouched by `resume_emergency_paused_oracles`.
+    pub fn set_oracle_status(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        status: OracleStatus,
+        timer_id: Option<TimerId>,
+    ) -> Result<()> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            metadata.status = status;
+            metadata.status_before_emergency = None;
+            if let Some(timer_id) = timer_id {
+                metadata.timer_id = timer_id;
+            }
+        })
+    }
+
+    /// Adds the cycles consumed by an oracle update and returns whether the oracle
+    /// exhausted its cycle budget
+    ///
+    /// The cost of the update is also kept in the cost history of the oracle, which
+    /// holds at most `MAX_CYCLES_PER_CALL_HISTORY` entries.
+    pub fn add_consumed_cycles(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        cycles: u128,
+    ) -> Result<bool> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            metadata.cycles_consumed = metadata.cycles_consumed.saturating_add(cycles);
+
+            let history = &mut metadata.cycles_per_call_history;
+            history.push_back(u64::try_from(cycles).unwrap_or(u64::MAX));
+            while history.len() > MAX_CYCLES_PER_CALL_HISTORY {
+                history.pop_front();
+            }
+
+            metadata
+                .cycle_budget
+                .is_some_and(|budget| metadata.cycles_consumed >= budget)
+        })
+    }
+
+    /// Returns the cycles consumed by all the oracles
+    pub fn get_total_cycles_consumed(&self) -> u128 {
+        ORACLE_STORAGE.with(|storage| {
+            storage
+                .borrow()
+                .iter()
+                .flat_map(|(_, collection)| {
+                    collection
+                        .0
+                        .into_values()
+                        .map(|metadata| metadata.cycles_consumed)
+                })
+                .fold(0, u128::saturating_add)
+        })
+    }
+
+    /// Returns the cycles consumed by the oracles of the user
+    pub fn get_user_cycles_consumed(&self, user_address: H160) -> u128 {
+        ORACLE_STORAGE.with(|storage| {
+            storage
+                .borrow()
+                .get(&user_address)
+                .map(|collection| {
+                    collection
+                        .0
+                        .values()
+                        .map(|metadata| metadata.cycles_consumed)
+                        .fold(0, u128::saturating_add)
+                })
+                .unwrap_or_default()
+        })
+    }
+
+    /// Credits the deposited cycles to the oracle and returns its remaining budget
+    ///
+    /// The deposit is deducted from the consumed cycles, the part exceeding them
+    /// increasing the budget instead.
+    pub fn deposit_cycles(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        cycles: u128,
+    ) -> Result<u128> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            let Some(budget) = metadata.cycle_budget else {
+                return Err(Error::Internal(
+                    "the oracle has no cycle budget".to_string(),
+                ));
+            };
+
+            let refunded = cycles.min(metadata.cycles_consumed);
+            metadata.cycles_consumed -= refunded;
+            let budget = budget.saturating_add(cycles - refunded);
+            metadata.cycle_budget = Some(budget);
+
+            Ok(budget.saturating_sub(metadata.cycles_consumed))
+        })?
+    }
+
+    /// Resets the consumed cycles counter of the oracle
+    pub fn reset_cycles_consumed(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+    ) -> Result<()> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            metadata.cycles_consumed = 0;
+        })
+    }
+
+    /// Sets the id of the timer updating the oracle, activating the oracle when it
+    /// was pending its first tick
+    pub fn set_oracle_timer_id(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        timer_id: TimerId,
+    ) -> Result<()> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            metadata.timer_id = timer_id;
+            if metadata.status == OracleStatus::Pending {
+                metadata.status = OracleStatus::Active;
+            }
+        })
+    }
+
+    /// Stores the last EIP-712 attestation of the oracle price
+    pub fn set_last_attestation(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        attestation: SignedOracleData,
+    ) -> Result<()> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            metadata.last_attestation = Some(attestation);
+        })
+    }
+
+    /// Stores the result of the last simulated update transaction of the oracle
+    pub fn set_simulation_result(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        result: SimulationResult,
+    ) -> Result<()> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            metadata.last_simulated_result = Some(result);
+        })
+    }
+
+    /// Sets the results of the update transactions sent to each destination of the oracle
+    pub fn set_broadcast_results(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        results: Vec<DestinationResult>,
+    ) -> Result<()> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            metadata.last_broadcast_results = results;
+        })
+    }
+
+    /// Records the price of a submitted update transaction and whether it used
+    /// the fallback of a combined origin or the fallback price
+    pub fn record_submission(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        price: U256,
+        using_fallback: bool,
+    ) -> Result<()> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            metadata.last_submitted_price = Some(price);
+            metadata.using_fallback = using_fallback;
+        })
+    }
+
+    /// Updates the status of the last transaction sent by the oracle, unless
+    /// another transaction was sent since, and returns whether it was updated
+    pub fn update_last_tx_status(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        tx_hash: &H256,
+        f: impl FnOnce(&mut TxStatus),
+    ) -> Result<bool> {
+        self.with_oracle_mut(
+            user_address,
+            evm_contract_address,
+            |metadata| match &mut metadata.last_tx_status {
+                Some(tx_status) if &tx_status.hash == tx_hash => {
+                    f(tx_status);
+                    true
+                }
+                _ => false,
+            },
+        )
+    }
+
+    /// Sets the status of the last transaction sent by the oracle
+    pub fn set_last_tx_status(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        tx_status: TxStatus,
+    ) -> Result<()> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            metadata.last_tx_status = Some(tx_status);
+        })
+    }
+
+    /// Sets the hash of the last transaction submitted by the oracle and returns
+    /// the previous one
+    pub fn swap_last_tx_hash(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        tx_hash: Option<H256>,
+    ) -> Result<Option<H256>> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            std::mem::replace(&mut metadata.last_tx_hash, tx_hash)
+        })
+    }
+
+    /// Replaces the destination provider of the oracles bound to the chain of the
+    /// registered provider, along with the providers of the extra destinations on
+    /// that chain, and returns the number of updated oracles
+    pub fn update_registered_provider(&self, provider: &Provider) -> u64 {
+        ORACLE_STORAGE.with(|storage| {
+            let mut storage = storage.borrow_mut();
+
+            let collections = storage.iter().collect::<Vec<_>>();
+
+            let mut updated = 0;
+            for (user_address, mut metadata_collection) in collections {
+                let mut changed = false;
+                for metadata in metadata_collection.0.values_mut() {
+                    let mut oracle_changed = false;
+                    if metadata.evm.chain_id == Some(provider.chain_id) {
+                        metadata.evm.provider = provider.clone();
+                        oracle_changed = true;
+                    }
+                    for extra_destination in metadata.evm.extra_destinations.iter_mut() {
+                        if extra_destination.provider.chain_id == provider.chain_id {
+                            extra_destination.provider = provider.clone();
+                            oracle_changed = true;
+                        }
+                    }
+
+                    if oracle_changed {
+                        changed = true;
+                        updated += 1;
+                    }
+                }
+
+                if changed {
+                    storage.insert(&user_address, &metadata_collection);
+                }
+            }
+
+            updated
+        })
+    }
+
+    /// Pauses all the active oracles, marking them as emergency paused, and
+    /// returns their timers so that they can be cleared
+    pub fn pause_active_oracles(&self) -> Vec<(H160, H160, TimerId)> {
+        ORACLE_STORAGE.with(|storage| {
+            let mut storage = storage.borrow_mut();
+
+            let collections = storage.iter().collect::<Vec<_>>();
+
+            let mut paused = vec![];
+            for (user_address, mut metadata_collection) in collections {
+                let mut changed = false;
+                for (contract_address, metadata) in metadata_collection.0.iter_mut() {
+                    if metadata.status.has_running_timer() {
+                        metadata.status_before_emergency = Some(std::mem::replace(
+                            &mut metadata.status,
+                            OracleStatus::Paused,
+                        ));
+                        paused.push((
+                            user_address.clone(),
+                            contract_address.clone(),
+                            metadata.timer_id,
+                        ));
+                        changed = true;
+                    }
+                }
+
+                if changed {
+                    storage.insert(&user_address, &metadata_collection);
+                }
+            }
+
+            paused
+        })
+    }
+
+    /// Drops the oldest prices of the history of the oracle beyond `keep_entries`
+    /// and returns the number of removed prices
+    pub fn prune_price_history(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        keep_entries: usize,
+    ) -> Result<usize> {
+        self.with_oracle_mut(user_address, evm_contract_address, |metadata| {
+            metadata.prune_price_history(keep_entries)
+        })
+    }
+
+    /// Drops the oldest prices of the histories of all the oracles beyond
+    /// `keep_entries` and returns the number of removed prices
+    ///
+    /// Only the collections of the users with pruned histories are written back.
+    pub fn prune_all_price_histories(&self, keep_entries: usize) -> u64 {
+        ORACLE_STORAGE.with(|storage| {
+            let mut storage = storage.borrow_mut();
+
+            let collections = storage.iter().collect::<Vec<_>>();
+
+            let mut removed = 0;
+            for (user_address, mut metadata_collection) in collections {
+                let user_removed = metadata_collection
+                    .0
+                    .values_mut()
+                    .map(|metadata| metadata.prune_price_history(keep_entries))
+                    .sum::<usize>();
+
+                if user_removed > 0 {
+                    storage.insert(&user_address, &metadata_collection);
+                    removed += user_removed as u64;
+                }
+            }
+
+            removed
+        })
+    }
+
+    /// Returns the user address, contract address and metadata of the oracles
+    /// paused by `pause_active_oracles`
+    pub fn get_emergency_paused_oracles(&self) -> Vec<(H160, H160, OracleMetadata)> {
+        ORACLE_STORAGE.with(|storage| {
+            storage
+                .borrow()
+                .iter()
+                .flat_map(|(user_address, metadata_collection)| {
+                    metadata_collection
+                        .0
+                        .into_iter()
+                        .filter(|(_, metadata)| metadata.status_before_emergency.is_some())
+                        .map(move |(contract_address, metadata)| {
+                            (user_address.clone(), contract_address, metadata.into())
+                        })
+                })
+                .collect()
+        })
+    }
+
+    /// Restores the status of the emergency paused oracles, with their new timers
+    pub fn resume_emergency_paused_oracles(&self, timers: Vec<(H160, H160, TimerId)>) {
+        ORACLE_STORAGE.with(|storage| {
+            let mut storage = storage.borrow_mut();
+
+            let mut collections = BTreeMap::new();
+            for (user_address, contract_address, timer_id) in timers {
+                if !collections.contains_key(&user_address) {
+                    let Some(metadata_collection) = storage.get(&user_address) else {
+                        continue;
+                    };
+                    collections.insert(user_address.clone(), metadata_collection);
+                }
+
+                let metadata = collections
+                    .get_mut(&user_address)
+                    .and_then(|collection| collection.0.get_mut(&contract_address));
+                if let Some(metadata) = metadata {
+                    if let Some(status) = metadata.status_before_emergency.take() {
+                        metadata.status = status;
+                        metadata.timer_id = timer_id;
+                    }
+                }
+            }
+
+            for (user_address, metadata_collection) in collections {
+                storage.insert(&user_address, &metadata_collection);
+            }
+        })
+    }
+
+    /// Replaces the providers of the same chain in the origins and the destinations,
+    /// extra destinations included, of all the oracles, returning the user and
+    /// contract addresses of the updated oracles
+    pub fn update_chain_provider(
+        &self,
+        provider: &Provider,
+        update_origins: bool,
+        update_destinations: bool,
+    ) -> Vec<(H160, H160)> {
+        ORACLE_STORAGE.with(|storage| {
+            let mut storage = storage.borrow_mut();
+
+            let collections = storage.iter().collect::<Vec<_>>();
+
+            let mut updated = vec![];
+            for (user_address, mut metadata_collection) in collections {
+                let mut changed = false;
+                for (contract_address, metadata) in metadata_collection.0.iter_mut() {
+                    let mut providers = vec![];
+                    if update_origins {
+                        providers.extend(metadata.origin.evm_providers_mut());
+                    }
+                    if update_destinations {
+                        providers.push(&mut metadata.evm.provider);
+                        providers.extend(
+                            metadata
+                                .evm
+                                .extra_destinations
+                                .iter_mut()
+                                .map(|destination| &mut destination.provider),
+                        );
+                    }
+
+                    let mut oracle_changed = false;
+                    for old_provider in providers {
+                        if old_provider.chain_id == provider.chain_id {
+                            *old_provider = provider.clone();
+                            oracle_changed = true;
+                        }
+                    }
+
+                    if oracle_changed {
+                        updated.push((user_address.clone(), contract_address.clone()));
+                        changed = true;
+                    }
+                }
+
+                if changed {
+                    storage.insert(&user_address, &metadata_collection);
+                }
+            }
+
+            updated
+        })
+    }
+
+    /// Stores an event of the oracle, dropping the oldest one when
+    /// `MAX_ORACLE_EVENTS` events are already stored
+    pub fn push_event(&self, event: OracleEvent) -> Result<()> {
+        self.with_oracle_mut(
+            event.user_address.clone(),
+            event.contract_address.clone(),
+            |metadata| {
+                if metadata.events.len() >= MAX_ORACLE_EVENTS {
+                    metadata.events.pop_front();
+                }
+                metadata.events.push_back(event);
+            },
+        )
+    }
+
+    /// Returns the `limit` most recent events of the oracle, most recent first
+    pub fn get_oracle_events(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        limit: usize,
+    ) -> Result<Vec<OracleEvent>> {
+        ORACLE_STORAGE.with(|storage| {
+            let storage = storage.borrow();
+
+            let vec = storage.get(&user_address).ok_or(Error::UserNotFound)?;
+
+            vec.0
+                .get(&evm_contract_address)
+                .map(|metadata| metadata.events.iter().rev().take(limit).cloned().collect())
+                .ok_or(Error::OracleNotFound)
+        })
+    }
+
+    /// Applies `f` to the metadata of the given oracle and writes it back
+    fn with_oracle_mut<R>(
+        &self,
+        user_address: H160,
+        evm_contract_address: H160,
+        f: impl FnOnce(&mut StorableOracleMetadata) -> R,
+    ) -> Result<R> {
+        ORACLE_STORAGE.with(|storage| {
+            let mut storage = storage.borrow_mut();
+
+            let mut metadata_collection = storage.get(&user_address).ok_or(Error::UserNotFound)?;
+
+            let metadata = metadata_collection
+                .0
+                .get_mut(&evm_contract_address)
+                .ok_or(Error::OracleNotFound)?;
+
+            let result = f(metadata);
+
+            storage.insert(&user_address, &metadata_collection);
+
+            Ok(result)
+        })
+    }
+
+    pub fn clear(&self) {
+        ORACLE_STORAGE.with(|storage| {
+            let mut storage = storage.borrow_mut();
+            storage.clear();
+        });
+        USER_ORACLE_COUNT.with(|index| index.borrow_mut().clear());
+    }
+}
+
+/// Updates the oracle count of the user, removing the users without oracles
+fn set_user_oracle_count(user_address: H160, count: usize) {
+    USER_ORACLE_COUNT.with(|index| {
+        let mut index = index.borrow_mut();
+        if count == 0 {
+            index.remove(&user_address);
+        } else {
+            index.insert(user_address, count as u64);
+        }
+    });
+}
+
+thread_local! {
+    static ORACLE_STORAGE: RefCell<StableUnboundedMap<H160, MetadataCollection, MemoryType>> = RefCell::new(StableUnboundedMap::new(MEMORY_MANAGER.with(|mm|mm.get(ORACLE_STORAGE_MEMORY_ID))));
+
+    /// Number of oracles of each user, kept apart from the oracles to list and count them cheaply
+    static USER_ORACLE_COUNT: RefCell<StableBTreeMap<H160, u64, MemoryType>> = RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm|mm.get(USER_ORACLE_COUNT_MEMORY_ID))));
 }
 
-impl UpdateOracleMetadata {
-    pub fn is_none(&self) -> bool {
-        self.origin.is_none() && self.evm.is_none() && self.timestamp.is_none()
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorableOracleMetadata {
+    pub origin: Origin,
+    pub timer_interval: u64,
+    pub timer_id: TimerId,
+    pub evm: EvmDestination,
+    /// Owner of the oracle
+    pub owner: H160,
+    /// Current status of the oracle
+    pub status: OracleStatus,
+    /// Number of failed updates since the last successful one
+    pub consecutive_errors: u32,
+    /// Total number of failed updates
+    pub total_errors: u64,
+    /// Number of updates which panicked, also counted as failed updates
+    pub panic_count: u64,
+    /// Number of consecutive errors after which the oracle enters the `Error` state
+    pub error_threshold: u32,
+    /// IC time in nanoseconds after which the oracle is paused
+    pub expires_at: Option<u64>,
+    /// IC time in nanoseconds of the first timer tick of an oracle created with an
+    /// initial delay
+    pub first_tick_at: Option<u64>,
+    /// The last price pushed by the oracle
+    pub last_price: Option<U256>,
+    /// The price of the last update transaction sent by the oracle, set before
+    /// its receipt is checked
+    pub last_submitted_price: Option<U256>,
+    /// IC time in nanoseconds of the last successful update
+    pub last_updated_at: u64,
+    /// Number of successful updates, used as round id
+    pub round_id: u128,
+    /// Whether the receipt of each update transaction is checked on chain, the
+    /// update only counting as a success once the receipt is found
+    pub verify_receipt: bool,
+    /// Seconds to wait before checking the receipt of an update transaction,
+    /// doubled on each retry
+    pub confirmation_delay_secs: u64,
+    /// Blocks on top of which the block of a verified update transaction must be
+    /// included before the transaction is final; the block is checked for reorgs
+    /// until then when greater than 1
+    pub confirmation_blocks: u8,
+    /// Seconds after which a missing receipt is considered a failure
+    pub max_wait_secs: u64,
+    /// Status of the last update transaction
+    pub last_tx_status: Option<TxStatus>,
+    /// Hash of the last update transaction submitted to the primary destination
+    pub last_tx_hash: Option<H256>,
+    /// Cycles after which the oracle is paused
+    pub cycle_budget: Option<u128>,
+    /// Cycles consumed by the oracle updates, measured from the canister balance
+    pub cycles_consumed: u128,
+    /// Cycles consumed by each of the most recent updates, oldest first
+    pub cycles_per_call_history: VecDeque<u64>,
+    /// Remaining budget above which an oracle paused by its cycle budget is resumed
+    /// by a deposit; `None` resumes it as soon as any budget remains
+    pub resume_threshold_cycles: Option<u128>,
+    /// The last EIP-712 attestation of the oracle price
+    pub last_attestation: Option<SignedOracleData>,
+    /// Name and version of the EIP-712 domain of the attestations
+    pub eip712_domain: Eip712DomainConfig,
+    /// Number of attempted updates
+    pub total_updates: u64,
+    /// Last error of the oracle, along with the update stage it occurred in
+    pub last_error: Option<PipelineError>,
+    /// Reason of the last update skipped before its price was submitted
+    pub last_skip_reason: Option<OracleSkipReason>,
+    /// IC time in nanoseconds of the last attempted update
+    pub last_attempt_at: u64,
+    /// `(user_address, contract_address)` of the oracles updated after each
+    /// successful update of this oracle
+    pub triggers: Vec<(H160, H160)>,
+    /// Range of the prices that can be pushed by the oracle
+    pub price_bounds: Option<PriceBounds>,
+    /// Whether the update transactions are simulated with `eth_call` instead of being sent
+    pub simulation_mode: bool,
+    /// Gas price in gwei above which the updates are skipped
+    pub max_gas_price_gwei: Option<u64>,
+    /// Balance in wei of the oracle signer below which the updates are skipped
+    pub min_eth_balance_check: Option<U256>,
+    /// Whether the updates fetching the last submitted price from the origin are
+    /// skipped instead of sending the same price again
+    pub suppress_duplicates: bool,
+    /// Result of the last simulated update transaction
+    pub last_simulated_result: Option<SimulationResult>,
+    /// Results of the update transactions sent to each destination by the last update
+    pub last_broadcast_results: Vec<DestinationResult>,
+    /// Whether the last update used the fallback of a combined origin or the
+    /// fallback price
+    pub using_fallback: bool,
+    /// Status of the oracle before it was paused by `pause_all_oracles`, restored
+    /// by `resume_all_oracles`; `None` if the oracle was not paused by it
+    pub status_before_emergency: Option<OracleStatus>,
+    /// Whether the round id is pushed along with the price
+    pub include_round_id: bool,
+    /// Whether the price is pushed with a signature of the oracle over the price, the
+    /// timestamp and the contract address, instead of the round id
+    pub include_signed_price: bool,
+    /// Function called instead of `updatePrice` on non-standard contracts, without
+    /// the round id; signed and fallback prices still use their own functions
+    pub custom_update_fn: Option<CustomUpdateFn>,
+    /// Circuit breaker skipping the updates while the origin keeps failing
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    /// Short human readable name of the oracle
+    pub label: Option<String>,
+    pub description: Option<String>,
+    /// Tags grouping the oracle with others, e.g. by asset class
+    pub tags: Vec<String>,
+    /// Number of timer executions after which the oracle is paused
+    pub max_executions: Option<u64>,
+    /// Number of timer executions of the oracle
+    pub execution_count: u64,
+    /// Price submitted when the origin fails with an HTTP or a parse error
+    pub fallback_price: Option<U256>,
+    /// Number of rotations of the signing key of the oracle
+    pub rotation_count: u32,
+    /// Components appended to the derivation path of the signer of the oracle,
+    /// isolating its key from the other oracles of the user
+    pub derivation_suffix: Vec<Vec<u8>>,
+    /// Transforms applied in order to the fetched price before it is pushed
+    pub transforms: Vec<Transform>,
+    /// Number of times an update failing with an HTTP error is retried
+    pub max_retries: u8,
+    /// Seconds to wait before the first retry of a failed update, doubled on each retry
+    pub retry_base_delay_secs: u64,
+    /// Seconds after which an update transaction without receipt is replaced by one
+    /// with a higher gas price; `None` never replaces it
+    pub tx_deadline_secs: Option<u64>,
+    /// Rejection of the prices far from the recent ones
+    pub outlier_config: Option<OutlierConfig>,
+    /// Maximum change in basis points of a price from the last submitted one
+    pub max_change_rate_bps: Option<u32>,
+    /// The most recent prices pushed by the oracle, oldest first
+    pub price_history: VecDeque<U256>,
+    /// Number of prices kept in the history, at most `MAX_PRICE_HISTORY`, which is
+    /// also used when `None`
+    pub max_history_entries: Option<u32>,
+    /// Checks of the HTTP responses, run in order before the price is extracted
+    pub response_validators: Vec<ResponseSchema>,
+    /// The most recent lifecycle events of the oracle, oldest first
+    pub events: VecDeque<OracleEvent>,
+}
+
+impl StorableOracleMetadata {
+    pub fn new(
+        user_address: H160,
+        origin: Origin,
+        timestamp: u64,
+        timer_id: TimerId,
+        evm: EvmDestination,
+    ) -> Self {
+        Self {
+            origin,
+            timer_id,
+            timer_interval: timestamp,
+            evm,
+            owner: user_address,
+            status: OracleStatus::Active,
+            consecutive_errors: 0,
+            total_errors: 0,
+            panic_count: 0,
+            error_threshold: DEFAULT_ERROR_THRESHOLD,
+            expires_at: None,
+            first_tick_at: None,
+            last_price: None,
+            last_submitted_price: None,
+            last_updated_at: 0,
+            round_id: 0,
+            verify_receipt: false,
+            confirmation_delay_secs: DEFAULT_CONFIRMATION_DELAY_SECS,
+            confirmation_blocks: DEFAULT_CONFIRMATION_BLOCKS,
+            max_wait_secs: DEFAULT_RECEIPT_MAX_WAIT_SECS,
+            last_tx_status: None,
+            last_tx_hash: None,
+            cycle_budget: None,
+            cycles_consumed: 0,
+            cycles_per_call_history: VecDeque::new(),
+            resume_threshold_cycles: None,
+            last_attestation: None,
+            eip712_domain: Eip712DomainConfig::default(),
+            total_updates: 0,
+            last_error: None,
+            last_skip_reason: None,
+            last_attempt_at: 0,
+            triggers: Vec::new(),
+            price_bounds: None,
+            simulation_mode: false,
+            max_gas_price_gwei: None,
+            min_eth_balance_check: None,
+            suppress_duplicates: false,
+            last_simulated_result: None,
+            last_broadcast_results: vec![],
+            using_fallback: false,
+            status_before_emergency: None,
+            include_round_id: false,
+            include_signed_price: false,
+            custom_update_fn: None,
+            circuit_breaker: None,
+            label: None,
+            description: None,
+            tags: vec![],
+            max_executions: None,
+            execution_count: 0,
+            fallback_price: None,
+            rotation_count: 0,
+            derivation_suffix: vec![],
+            transforms: vec![],
+            max_retries: 0,
+            retry_base_delay_secs: DEFAULT_RETRY_BASE_DELAY_SECS,
+            tx_deadline_secs: None,
+            outlier_config: None,
+            max_change_rate_bps: None,
+            price_history: VecDeque::new(),
+            max_history_entries: None,
+            response_validators: vec![],
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Returns the number of prices kept in the history of the oracle
+    pub fn max_price_history(&self) -> usize {
+        self.max_history_entries
+            .map_or(MAX_PRICE_HISTORY, |entries| {
+                (entries as usize).min(MAX_PRICE_HISTORY)
+            })
+    }
+
+    /// Drops the oldest prices of the history beyond `keep_entries` and returns
+    /// the number of removed prices
+    pub fn prune_price_history(&mut self, keep_entries: usize) -> usize {
+        let removed = self.price_history.len().saturating_sub(keep_entries);
+        self.price_history.drain(..removed);
+        removed
+    }
+}
+
+impl Storable for MetadataCollection {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = VERSIONED_COLLECTION_MARKER.to_vec();
+        bytes.extend(did::codec::bincode_encode(
+            &VersionedMetadataCollection::V2(Cow::Borrowed(self)),
+        ));
+
+        bytes.into()
+    }
+
+    /// Decodes the collection, migrating the layouts stored by the previous releases
+    ///
+    /// The collections stored before the versioned layout are decoded with the
+    /// current layout, or with the layout of the first release.
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        if let Some(versioned) = bytes.strip_prefix(&VERSIONED_COLLECTION_MARKER) {
+            return match did::codec::bincode_decode::<VersionedMetadataCollection>(versioned) {
+                VersionedMetadataCollection::V1(legacy) => legacy.into(),
+                VersionedMetadataCollection::V2(collection) => collection.into_owned(),
+            };
+        }
+
+        bincode::deserialize(&bytes).unwrap_or_else(|_| {
+            did::codec::bincode_decode::<LegacyMetadataCollection>(&bytes).into()
+        })
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Prefix of the versioned collections; the unversioned ones start with the
+/// number of their oracles, which is never `u64::MAX`
+const VERSIONED_COLLECTION_MARKER: [u8; 8] = u64::MAX.to_le_bytes();
+
+/// Layouts of the stored metadata collections, the last one being the current
+///
+/// A change of the stored layout adds a variant, decoded into the current one.
+#[derive(Serialize, Deserialize)]
+enum VersionedMetadataCollection<'a> {
+    /// Layout of the first release
+    V1(LegacyMetadataCollection),
+    /// Current layout
+    V2(Cow<'a, MetadataCollection>),
+}
+
+/// Collection of oracle metadata
+/// The key is the EVM contract address
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetadataCollection(BTreeMap<H160, StorableOracleMetadata>);
+
+impl SlicedStorable for MetadataCollection {
+    const CHUNK_SIZE: ChunkSize = 64;
+}
+
+/// Struct used to store the oracle metadata
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct OracleMetadata {
+    /// The origin of the oracle
+    pub origin: Origin,
+    /// The interval at which the oracle should be called
+    pub timer_interval: u64,
+    /// The destination of the oracle
+    pub evm: EvmDestination,
+    /// Owner of the oracle
+    pub owner: H160,
+    /// Current status of the oracle
+    pub status: OracleStatus,
+    /// Number of failed updates since the last successful one
+    pub consecutive_errors: u32,
+    /// Total number of failed updates
+    pub total_errors: u64,
+    /// Number of updates which panicked, also counted as failed updates
+    pub panic_count: u64,
+    /// Number of consecutive errors after which the oracle enters the `Error` state
+    pub error_threshold: u32,
+    /// IC time in nanoseconds after which the oracle is paused
+    pub expires_at: Option<u64>,
+    /// IC time in nanoseconds of the first timer tick of an oracle created with an
+    /// initial delay
+    pub first_tick_at: Option<u64>,
+    /// The last price pushed by the oracle
+    pub last_price: Option<U256>,
+    /// The price of the last update transaction sent by the oracle, set before
+    /// its receipt is checked
+    pub last_submitted_price: Option<U256>,
+    /// IC time in nanoseconds of the last successful update
+    pub last_updated_at: u64,
+    /// Number of successful updates, used as round id
+    pub round_id: u128,
+    /// Whether the receipt of each update transaction is checked on chain, the
+    /// update only counting as a success once the receipt is found
+    pub verify_receipt: bool,
+    /// Seconds to wait before checking the receipt of an update transaction,
+    /// doubled on each retry
+    pub confirmation_delay_secs: u64,
+    /// Blocks on top of which the block of a verified update transaction must be
+    /// included before the transaction is final; the block is checked for reorgs
+    /// until then when greater than 1
+    pub confirmation_blocks: u8,
+    /// Seconds after which a missing receipt is considered a failure
+    pub max_wait_secs: u64,
+    /// Status of the last update transaction
+    pub last_tx_status: Option<TxStatus>,
+    /// Hash of the last update transaction submitted to the primary destination
+    pub last_tx_hash: Option<H256>,
+    /// Cycles after which the oracle is paused
+    pub cycle_budget: Option<u128>,
+    /// Cycles consumed by the oracle updates, measured from the canister balance
+    pub cycles_consumed: u128,
+    /// Cycles consumed by each of the most recent updates, oldest first
+    pub cycles_per_call_history: VecDeque<u64>,
+    /// Remaining budget above which an oracle paused by its cycle budget is resumed
+    /// by a deposit; `None` resumes it as soon as any budget remains
+    pub resume_threshold_cycles: Option<u128>,
+    /// The last EIP-712 attestation of the oracle price
+    pub last_attestation: Option<SignedOracleData>,
+    /// Name and version of the EIP-712 domain of the attestations
+    pub eip712_domain: Eip712DomainConfig,
+    /// Number of attempted updates
+    pub total_updates: u64,
+    /// Last error of the oracle, along with the update stage it occurred in
+    pub last_error: Option<PipelineError>,
+    /// Reason of the last update skipped before its price was submitted
+    pub last_skip_reason: Option<OracleSkipReason>,
+    /// IC time in nanoseconds of the last attempted update
+    pub last_attempt_at: u64,
+    /// `(user_address, contract_address)` of the oracles updated after each
+    /// successful update of this oracle
+    pub triggers: Vec<(H160, H160)>,
+    /// Range of the prices that can be pushed by the oracle
+    pub price_bounds: Option<PriceBounds>,
+    /// Whether the update transactions are simulated with `eth_call` instead of being sent
+    pub simulation_mode: bool,
+    /// Gas price in gwei above which the updates are skipped
+    pub max_gas_price_gwei: Option<u64>,
+    /// Balance in wei of the oracle signer below which the updates are skipped
+    pub min_eth_balance_check: Option<U256>,
+    /// Whether the updates fetching the last submitted price from the origin are
+    /// skipped instead of sending the same price again
+    pub suppress_duplicates: bool,
+    /// Result of the last simulated update transaction
+    pub last_simulated_result: Option<SimulationResult>,
+    /// Results of the update transactions sent to each destination by the last update
+    pub last_broadcast_results: Vec<DestinationResult>,
+    /// Whether the last update used the fallback of a combined origin or the
+    /// fallback price
+    pub using_fallback: bool,
+    /// Status of the oracle before it was paused by `pause_all_oracles`, restored
+    /// by `resume_all_oracles`; `None` if the oracle was not paused by it
+    pub status_before_emergency: Option<OracleStatus>,
+    /// Whether the round id is pushed along with the price
+    pub include_round_id: bool,
+    /// Whether the price is pushed with a signature of the oracle over the price, the
+    /// timestamp and the contract address, instead of the round id
+    pub include_signed_price: bool,
+    /// Function called instead of `updatePrice` on non-standard contracts, without
+    /// the round id; signed and fallback prices still use their own functions
+    pub custom_update_fn: Option<CustomUpdateFn>,
+    /// Circuit breaker skipping the updates while the origin keeps failing
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    /// Short human readable name of the oracle
+    pub label: Option<String>,
+    pub description: Option<String>,
+    /// Tags grouping the oracle with others, e.g. by asset class
+    pub tags: Vec<String>,
+    /// Number of timer executions after which the oracle is paused
+    pub max_executions: Option<u64>,
+    /// Number of timer executions of the oracle
+    pub execution_count: u64,
+    /// Price submitted when the origin fails with an HTTP or a parse error
+    pub fallback_price: Option<U256>,
+    /// Number of rotations of the signing key of the oracle
+    pub rotation_count: u32,
+    /// Components appended to the derivation path of the signer of the oracle,
+    /// isolating its key from the other oracles of the user
+    pub derivation_suffix: Vec<Vec<u8>>,
+    /// Transforms applied in order to the fetched price before it is pushed
+    pub transforms: Vec<Transform>,
+    /// Number of times an update failing with an HTTP error is retried
+    pub max_retries: u8,
+    /// Seconds to wait before the first retry of a failed update, doubled on each retry
+    pub retry_base_delay_secs: u64,
+    /// Seconds after which an update transaction without receipt is replaced by one
+    /// with a higher gas price; `None` never replaces it
+    pub tx_deadline_secs: Option<u64>,
+    /// Rejection of the prices far from the recent ones
+    pub outlier_config: Option<OutlierConfig>,
+    /// Maximum change in basis points of a price from the last submitted one
+    pub max_change_rate_bps: Option<u32>,
+    /// The most recent prices pushed by the oracle, oldest first
+    pub price_history: VecDeque<U256>,
+    /// Number of prices kept in the history, at most `MAX_PRICE_HISTORY`, which is
+    /// also used when `None`
+    pub max_history_entries: Option<u32>,
+    /// Checks of the HTTP responses, run in order before the price is extracted
+    pub response_validators: Vec<ResponseSchema>,
+}
+
+impl From<StorableOracleMetadata> for OracleMetadata {
+    fn from(storable: StorableOracleMetadata) -> Self {
+        Self {
+            origin: storable.origin,
+            timer_interval: storable.timer_interval,
+            evm: storable.evm,
+            owner: storable.owner,
+            status: storable.status,
+            consecutive_errors: storable.consecutive_errors,
+            total_errors: storable.total_errors,
+            panic_count: storable.panic_count,
+            error_threshold: storable.error_threshold,
+            expires_at: storable.expires_at,
+            first_tick_at: storable.first_tick_at,
+            last_price: storable.last_price,
+            last_submitted_price: storable.last_submitted_price,
+            last_updated_at: storable.last_updated_at,
+            round_id: storable.round_id,
+            verify_receipt: storable.verify_receipt,
+            confirmation_delay_secs: storable.confirmation_delay_secs,
+            confirmation_blocks: storable.confirmation_blocks,
+            max_wait_secs: storable.max_wait_secs,
+            last_tx_status: storable.last_tx_status,
+            last_tx_hash: storable.last_tx_hash,
+            cycle_budget: storable.cycle_budget,
+            cycles_consumed: storable.cycles_consumed,
+            cycles_per_call_history: storable.cycles_per_call_history,
+            resume_threshold_cycles: storable.resume_threshold_cycles,
+            last_attestation: storable.last_attestation,
+            eip712_domain: storable.eip712_domain,
+            total_updates: storable.total_updates,
+            last_error: storable.last_error,
+            last_skip_reason: storable.last_skip_reason,
+            last_attempt_at: storable.last_attempt_at,
+            triggers: storable.triggers,
+            price_bounds: storable.price_bounds,
+            simulation_mode: storable.simulation_mode,
+            max_gas_price_gwei: storable.max_gas_price_gwei,
+            min_eth_balance_check: storable.min_eth_balance_check,
+            suppress_duplicates: storable.suppress_duplicates,
+            last_simulated_result: storable.last_simulated_result,
+            last_broadcast_results: storable.last_broadcast_results,
+            using_fallback: storable.using_fallback,
+            status_before_emergency: storable.status_before_emergency,
+            include_round_id: storable.include_round_id,
+            include_signed_price: storable.include_signed_price,
+            custom_update_fn: storable.custom_update_fn,
+            circuit_breaker: storable.circuit_breaker,
+            label: storable.label,
+            description: storable.description,
+            tags: storable.tags,
+            max_executions: storable.max_executions,
+            execution_count: storable.execution_count,
+            fallback_price: storable.fallback_price,
+            rotation_count: storable.rotation_count,
+            derivation_suffix: storable.derivation_suffix,
+            transforms: storable.transforms,
+            max_retries: storable.max_retries,
+            retry_base_delay_secs: storable.retry_base_delay_secs,
+            tx_deadline_secs: storable.tx_deadline_secs,
+            outlier_config: storable.outlier_config,
+            max_change_rate_bps: storable.max_change_rate_bps,
+            price_history: storable.price_history,
+            max_history_entries: storable.max_history_entries,
+            response_validators: storable.response_validators,
+        }
+    }
+}
+
+/// Status of an oracle
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Eq)]
+pub enum OracleStatus {
+    /// The oracle is updated on every timer tick
+    Active,
+    /// The oracle timer is stopped
+    Paused,
+    /// The oracle reached its error threshold and its timer is stopped
+    Error,
+    /// The origin keeps failing, the updates are skipped until `resets_at`, when
+    /// a single update is attempted
+    CircuitOpen { since: u64, resets_at: u64 },
+    /// The oracle waits for its first timer tick at `first_tick_at`, after which it
+    /// becomes active
+    Pending,
+}
+
+impl OracleStatus {
+    /// Returns whether the timer of the oracle is running
+    pub fn has_running_timer(&self) -> bool {
+        matches!(
+            self,
+            Self::Active | Self::CircuitOpen { .. } | Self::Pending
+        )
+    }
+}
+
+/// Reason of an oracle update skipped before its price was submitted
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Eq)]
+pub enum OracleSkipReason {
+    /// The price was rejected as an outlier of the price history
+    PriceOutlier(U256),
+    /// The limit of concurrent updates was reached
+    ConcurrentUpdatesExceeded(u32),
+    /// The gas price of the destination was above the maximum of the oracle
+    GasPriceTooHigh { current_gwei: u64, max_gwei: u64 },
+    /// The balance of the oracle signer was below the minimum of the oracle
+    InsufficientBalance { balance: U256, min_balance: U256 },
+    /// The price moved further from the last submitted one than the maximum change
+    /// rate of the oracle
+    PriceChangeTooLarge { change_bps: u32, max_bps: u32 },
+    /// The update transaction was the last one submitted by the oracle
+    DuplicateTransaction(H256),
+}
+
+impl OracleSkipReason {
+    /// Returns the skip reason of an update error, if the update was skipped
+    pub fn from_error(error: &Error) -> Option<Self> {
+        match error {
+            Error::PriceOutlier(price) => Some(Self::PriceOutlier(price.clone())),
+            Error::ConcurrentUpdatesExceeded(limit) => {
+                Some(Self::ConcurrentUpdatesExceeded(*limit))
+            }
+            Error::GasPriceTooHigh {
+                current_gwei,
+                max_gwei,
+            } => Some(Self::GasPriceTooHigh {
+                current_gwei: *current_gwei,
+                max_gwei: *max_gwei,
+            }),
+            Error::InsufficientBalance {
+                balance,
+                min_balance,
+            } => Some(Self::InsufficientBalance {
+                balance: balance.clone(),
+                min_balance: min_balance.clone(),
+            }),
+            Error::PriceChangeTooLarge {
+                change_bps,
+                max_bps,
+                ..
+            } => Some(Self::PriceChangeTooLarge {
+                change_bps: *change_bps,
+                max_bps: *max_bps,
+            }),
+            Error::DuplicateTransaction(tx_hash) => {
+                Some(Self::DuplicateTransaction(tx_hash.clone()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Circuit breaker skipping the updates of an oracle whose origin keeps failing
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Eq)]
+pub struct CircuitBreakerConfig {
+    /// Number of consecutive errors after which the circuit opens
+    pub failure_threshold: u32,
+    /// Number of seconds after which a single update is attempted again
+    pub reset_after_secs: u64,
+}
+
+impl CircuitBreakerConfig {
+    /// Returns the status of an oracle whose circuit opens at `now`
+    pub fn open(&self, now: u64) -> OracleStatus {
+        OracleStatus::CircuitOpen {
+            since: now,
+            resets_at: now
+                .saturating_add(Duration::from_secs(self.reset_after_secs).as_nanos() as u64),
+        }
+    }
+}
+
+/// Result of an update transaction simulated with `eth_call`
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Eq)]
+pub struct SimulationResult {
+    /// The price that would have been pushed
+    pub price: U256,
+    /// Hex encoded data returned by the `eth_call`
+    pub return_data: String,
+    /// Gas estimated for the update transaction
+    pub estimated_gas: U256,
+    /// IC time in nanoseconds of the simulation
+    pub simulated_at: u64,
+}
+
+/// Update transaction sent to one of the destinations of an oracle
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Eq)]
+pub struct DestinationResult {
+    /// The contract updated by the transaction
+    pub contract: H160,
+    pub chain_id: u64,
+    pub result: TxResult,
+}
+
+/// Outcome of sending an update transaction to a destination
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Eq)]
+pub enum TxResult {
+    /// The transaction was sent with the given hash
+    Sent(H256),
+    /// The transaction could not be built or sent
+    Failed(String),
+}
+
+/// Inclusive range of the prices accepted by an oracle
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Eq)]
+pub struct PriceBounds {
+    pub min_price: U256,
+    pub max_price: U256,
+}
+
+impl PriceBounds {
+    /// Checks that the price is within the bounds
+    pub fn check(&self, price: &U256) -> Result<()> {
+        if price < &self.min_price || price > &self.max_price {
+            return Err(Error::PriceOutOfBounds {
+                price: price.clone(),
+                min: self.min_price.clone(),
+                max: self.max_price.clone(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Rejection of the prices far from the price history of an oracle
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub struct OutlierConfig {
+    /// Number of prices in the history from which the prices are checked
+    pub min_history: u8,
+    /// Number of interquartile ranges of the history a price can be away from its median
+    pub iqr_multiplier: f64,
+}
+
+impl OutlierConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.min_history < 4 {
+            return Err(Error::Internal(
+                "outlier detection needs a min history of at least 4 prices".to_string(),
+            ));
+        }
+        if !self.iqr_multiplier.is_finite() || self.iqr_multiplier <= 0.0 {
+            return Err(Error::Internal(
+                "outlier iqr multiplier must be a positive number".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the price is within `iqr_multiplier` interquartile ranges of the
+    /// median of the history, once the history has `min_history` prices
+    ///
+    /// With a history of identical prices, any other price is an outlier.
+    pub fn check(&self, price: &U256, history: &VecDeque<U256>) -> Result<()> {
+        if history.len() < self.min_history as usize {
+            return Ok(());
+        }
+
+        let mut prices = history.iter().map(u256_to_f64).collect::<Vec<_>>();
+        prices.sort_by(f64::total_cmp);
+
+        let median = quantile(&prices, 0.5);
+        let iqr = quantile(&prices, 0.75) - quantile(&prices, 0.25);
+
+        if (u256_to_f64(price) - median).abs() > self.iqr_multiplier * iqr {
+            return Err(Error::PriceOutlier(price.clone()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the quantile of sorted values, interpolating linearly between them
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let position = (sorted.len() - 1) as f64 * q;
+    let lower = sorted[position.floor() as usize];
+    let upper = sorted[position.ceil() as usize];
+
+    lower + (upper - lower) * position.fract()
+}
+
+fn u256_to_f64(value: &U256) -> f64 {
+    let ethers_core::types::U256(limbs) = value.0;
+
+    limbs
+        .iter()
+        .rev()
+        .fold(0.0, |acc, limb| acc * 2_f64.powi(64) + *limb as f64)
+}
+
+/// Check of the JSON response of an HTTP origin, rejecting e.g. error envelopes
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Eq)]
+pub enum ResponseSchema {
+    /// The dot path must exist in the response
+    RequireField(String),
+    /// The dot path must not exist in the response
+    ForbidField(String),
+    /// The `status` field of the response must be the given string
+    RequireStatus(String),
+    /// The value at the dot path must have the expected type
+    RequireType {
+        path: String,
+        expected_type: JsonType,
+    },
+}
+
+/// Type of a JSON value
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, CandidType, PartialEq, Eq)]
+pub enum JsonType {
+    Null,
+    Bool,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl JsonType {
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::Bool(_) => Self::Bool,
+            Value::Number(_) => Self::Number,
+            Value::String(_) => Self::String,
+            Value::Array(_) => Self::Array,
+            Value::Object(_) => Self::Object,
+        }
+    }
+}
+
+impl ResponseSchema {
+    /// Checks that the response matches the schema
+    pub fn check(&self, response: &Value) -> Result<()> {
+        let failed = |reason: String| Err(Error::SchemaValidationFailed(reason));
+
+        match self {
+            Self::RequireField(path) if response.parse(path).is_err() => {
+                failed(format!("missing field '{path}'"))
+            }
+            Self::ForbidField(path) if response.parse(path).is_ok() => {
+                failed(format!("forbidden field '{path}' found"))
+            }
+            Self::RequireStatus(status) => match response.get("status") {
+                Some(Value::String(actual)) if actual == status => Ok(()),
+                Some(Value::Number(actual)) if actual.to_string() == *status => Ok(()),
+                actual => failed(format!(
+                    "expected status '{status}', got {}",
+                    actual.map_or_else(|| "none".to_string(), Value::to_string)
+                )),
+            },
+            Self::RequireType {
+                path,
+                expected_type,
+            } => {
+                let value = response.parse(path).map_err(|_| {
+                    Error::SchemaValidationFailed(format!("missing field '{path}'"))
+                })?;
+                let actual_type = JsonType::of(&value);
+                if actual_type != *expected_type {
+                    return failed(format!(
+                        "field '{path}' is {actual_type:?}, expected {expected_type:?}"
+                    ));
+                }
+
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Transform of the price fetched by an oracle
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Eq)]
+pub enum Transform {
+    Multiply(U256),
+    Divide(U256),
+    Add(i64),
+    Subtract(u64),
+    /// Inverts the price, keeping its number of decimals
+    Reciprocal,
+    /// Limits the price to the inclusive range
+    Clamp {
+        min: U256,
+        max: U256,
+    },
+}
+
+impl Transform {
+    /// Checks that the transform can be applied to a price
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Self::Divide(divisor) if divisor.0.is_zero() => {
+                Err(Error::InvalidTransform("division by zero".to_string()))
+            }
+            Self::Clamp { min, max } if min > max => Err(Error::InvalidTransform(
+                "clamp min must not be greater than max".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Applies the transform to a price with `decimals` decimals
+    pub fn apply(&self, price: U256, decimals: u8) -> Result<U256> {
+        self.validate()?;
+
+        let overflow = || Error::InvalidTransform(format!("{self:?} out of range"));
+        let value = match self {
+            Self::Multiply(factor) => price.0.checked_mul(factor.0).ok_or_else(overflow)?,
+            Self::Divide(divisor) => price.0 / divisor.0,
+            Self::Add(amount) if *amount >= 0 => price
+                .0
+                .checked_add(amount.unsigned_abs().into())
+                .ok_or_else(overflow)?,
+            Self::Add(amount) => price
+                .0
+                .checked_sub(amount.unsigned_abs().into())
+                .ok_or_else(overflow)?,
+            Self::Subtract(amount) => price.0.checked_sub((*amount).into()).ok_or_else(overflow)?,
+            Self::Reciprocal => {
+                if price.0.is_zero() {
+                    return Err(Error::InvalidTransform(
+                        "reciprocal of a zero price".to_string(),
+                    ));
+                }
+                let one = ethers_core::types::U256::exp10(decimals as usize);
+                one.checked_mul(one).ok_or_else(overflow)? / price.0
+            }
+            Self::Clamp { min, max } => price.0.clamp(min.0, max.0),
+        };
+
+        Ok(U256(value))
+    }
+}
+
+/// Status of an update transaction sent by an oracle
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Eq)]
+pub struct TxStatus {
+    /// The transaction hash
+    pub hash: H256,
+    /// The receipt status of the transaction
+    pub status: TxReceiptStatus,
+    /// Hash of the stuck transaction replaced by this one, with the same nonce
+    pub replaced: Option<H256>,
+    /// Block including the transaction, once its receipt is found
+    pub confirmed_block: Option<ConfirmedBlock>,
+}
+
+/// Block including a confirmed update transaction
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Eq)]
+pub struct ConfirmedBlock {
+    pub number: U256,
+    pub hash: H256,
+    /// Whether the block reached the confirmation blocks of the oracle, after
+    /// which it is no longer checked for reorgs
+    pub finalized: bool,
+}
+
+/// Receipt status of an update transaction
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Eq)]
+pub enum TxReceiptStatus {
+    /// The receipt has not been checked yet
+    Pending,
+    /// The transaction was executed successfully
+    Success,
+    /// The transaction reverted
+    Failed,
+    /// No receipt was found before the max wait time
+    Timeout,
+    /// The block including the transaction was orphaned by a chain reorganization
+    Reorged,
+}
+
+/// Struct used to update the oracle metadata
+#[derive(Debug, Clone, Default, Serialize, Deserialize, CandidType)]
+pub struct UpdateOracleMetadata {
+    pub origin: Option<Origin>,
+    pub evm: Option<EvmDestination>,
+    pub timestamp: Option<u64>,
+    pub error_threshold: Option<u32>,
+    pub expires_at: Option<u64>,
+    pub verify_receipt: Option<bool>,
+    pub confirmation_delay_secs: Option<u64>,
+    pub confirmation_blocks: Option<u8>,
+    pub max_wait_secs: Option<u64>,
+    pub decimals: Option<u8>,
+    pub cycle_budget: Option<u128>,
+    pub resume_threshold_cycles: Option<u128>,
+    pub max_response_bytes: Option<u64>,
+    pub triggers: Option<Vec<(H160, H160)>>,
+    pub price_bounds: Option<PriceBounds>,
+    pub simulation_mode: Option<bool>,
+    pub max_gas_price_gwei: Option<u64>,
+    pub min_eth_balance_check: Option<U256>,
+    pub suppress_duplicates: Option<bool>,
+    pub include_round_id: Option<bool>,
+    pub include_signed_price: Option<bool>,
+    pub custom_update_fn: Option<CustomUpdateFn>,
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    pub label: Option<String>,
+    pub description: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub max_executions: Option<u64>,
+    pub execution_count: Option<u64>,
+    pub fallback_price: Option<U256>,
+    pub transforms: Option<Vec<Transform>>,
+    pub max_retries: Option<u8>,
+    pub retry_base_delay_secs: Option<u64>,
+    pub tx_deadline_secs: Option<u64>,
+    pub outlier_config: Option<OutlierConfig>,
+    pub max_change_rate_bps: Option<u32>,
+    pub response_validators: Option<Vec<ResponseSchema>>,
+    pub max_history_entries: Option<u32>,
+}
+
+impl UpdateOracleMetadata {
+    pub fn is_none(&self) -> bool {
+        self.origin.is_none()
+            && self.evm.is_none()
+            && self.timestamp.is_none()
+            && self.error_threshold.is_none()
+            && self.expires_at.is_none()
+            && self.verify_receipt.is_none()
+            && self.confirmation_delay_secs.is_none()
+            && self.confirmation_blocks.is_none()
+            && self.max_wait_secs.is_none()
+            && self.decimals.is_none()
+            && self.cycle_budget.is_none()
+            && self.resume_threshold_cycles.is_none()
+            && self.max_response_bytes.is_none()
+            && self.triggers.is_none()
+            && self.price_bounds.is_none()
+            && self.simulation_mode.is_none()
+            && self.max_gas_price_gwei.is_none()
+            && self.min_eth_balance_check.is_none()
+            && self.suppress_duplicates.is_none()
+            && self.include_round_id.is_none()
+            && self.include_signed_price.is_none()
+            && self.custom_update_fn.is_none()
+            && self.circuit_breaker.is_none()
+            && self.label.is_none()
+            && self.description.is_none()
+            && self.tags.is_none()
+            && self.max_executions.is_none()
+            && self.execution_count.is_none()
+            && self.fallback_price.is_none()
+            && self.transforms.is_none()
+            && self.max_retries.is_none()
+            && self.retry_base_delay_secs.is_none()
+            && self.tx_deadline_secs.is_none()
+            && self.outlier_config.is_none()
+            && self.max_change_rate_bps.is_none()
+            && self.response_validators.is_none()
+            && self.max_history_entries.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use slotmap::KeyData;
+
+    use super::*;
+    use crate::canister::{EvmOrigin, HttpOrigin, SingleEvmDestination};
+    use crate::error::OracleStage;
+    use crate::events::OracleEventType;
+
+    fn update_error(message: &str) -> PipelineError {
+        PipelineError::new(OracleStage::DataFetch, Error::Http(message.to_string()))
+    }
+
+    #[test]
+    fn clear_oracle_storage() {
+        let oracle_storage = OracleStorage::default();
+
+        let user_address = H160::from_slice(&[1; 20]);
+        let evm_contract_address = H160::from_slice(&[2; 20]);
+
+        let origin = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
+        });
+
+        let destination = EvmDestination {
+            contract: evm_contract_address.clone(),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+
+        oracle_storage.add_oracle(
+            user_address.clone(),
+            origin.clone(),
+            100,
+            TimerId::default(),
+            destination.clone(),
+        );
+
+        oracle_storage.clear();
+
+        let oracle_metadata = oracle_storage
+            .get_oracle_by_address(user_address, evm_contract_address)
+            .unwrap_err();
+
+        assert_eq!(oracle_metadata, Error::UserNotFound);
+    }
+
+    #[test]
+    fn test_add_oracle() {
+        let oracle_storage = OracleStorage::default();
+
+        let user_address = H160::from_slice(&[1; 20]);
+        let evm_contract_address = H160::from_slice(&[2; 20]);
+
+        let origin = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
+        });
+
+        let destination = EvmDestination {
+            contract: evm_contract_address.clone(),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+
+        oracle_storage.add_oracle(
+            user_address.clone(),
+            origin.clone(),
+            100,
+            TimerId::default(),
+            destination.clone(),
+        );
+
+        let oracle_metadata = oracle_storage
+            .get_oracle_by_address(user_address, evm_contract_address)
+            .unwrap();
+
+        assert_eq!(oracle_metadata.origin, origin);
+        assert_eq!(oracle_metadata.timer_interval, 100);
+        assert_eq!(oracle_metadata.evm, destination);
+    }
+
+    #[test]
+    fn test_add_multiple_oracles() {
+        let oracle_storage = OracleStorage::default();
+
+        let user_address = H160::from_slice(&[1; 20]);
+        let evm_contract_address = H160::from_slice(&[2; 20]);
+
+        let origin1 = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
+        });
+
+        let destination1 = EvmDestination {
+            contract: evm_contract_address.clone(),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+
+        oracle_storage.add_oracle(
+            user_address.clone(),
+            origin1.clone(),
+            100,
+            TimerId::default(),
+            destination1.clone(),
+        );
+
+        let origin2 = Origin::Evm(EvmOrigin {
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            target_address: H160::from_slice(&[3; 20]),
+            method: String::from("getPrice"),
+            return_types: vec![],
+            return_value_index: 0,
+        });
+
+        let destination2 = EvmDestination {
+            contract: H160::from_slice(&[4; 20]),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+
+        oracle_storage.add_oracle(
+            user_address.clone(),
+            origin2.clone(),
+            50,
+            TimerId::default(),
+            destination2.clone(),
+        );
+
+        let oracle_metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), evm_contract_address)
+            .unwrap();
+
+        assert_eq!(oracle_metadata.origin, origin1);
+        assert_eq!(oracle_metadata.timer_interval, 100);
+        assert_eq!(oracle_metadata.evm, destination1);
+
+        let oracle_metadata = oracle_storage
+            .get_oracle_by_address(user_address, H160::from_slice(&[4; 20]))
+            .unwrap();
+
+        assert_eq!(oracle_metadata.origin, origin2);
+        assert_eq!(oracle_metadata.timer_interval, 50);
+        assert_eq!(oracle_metadata.evm, destination2);
+    }
+
+    #[test]
+    fn test_update_oracle_metadata() {
+        let oracle_storage = OracleStorage::default();
+
+        let user_address = H160::from_slice(&[1; 20]);
+        let evm_contract_address = H160::from_slice(&[2; 20]);
+
+        let origin1 = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
+        });
+
+        let destination1 = EvmDestination {
+            contract: evm_contract_address.clone(),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+
+        oracle_storage.add_oracle(
+            user_address.clone(),
+            origin1.clone(),
+            100,
+            TimerId::default(),
+            destination1.clone(),
+        );
+
+        // Assert that the oracle metadata is correct
+        let oracle_metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), evm_contract_address.clone())
+            .unwrap();
+
+        assert_eq!(oracle_metadata.origin, origin1);
+
+        let new_origin = Origin::Evm(EvmOrigin {
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            target_address: H160::from_slice(&[3; 20]),
+            method: String::from("getPrice"),
+            return_types: vec![],
+            return_value_index: 0,
+        });
+
+        // Update the oracle metadata
+        let update_metadata = UpdateOracleMetadata {
+            origin: Some(new_origin.clone()),
+            ..Default::default()
+        };
+
+        oracle_storage
+            .update_oracle_metadata(
+                user_address.clone(),
+                evm_contract_address.clone(),
+                None,
+                update_metadata,
+            )
+            .unwrap();
+
+        // Assert that the oracle metadata is updated
+        let oracle_metadata = oracle_storage
+            .get_oracle_by_address(user_address, evm_contract_address)
+            .unwrap();
+
+        assert_eq!(oracle_metadata.origin, new_origin,);
+    }
+
+    #[test]
+    fn test_remove_single_oracle_by_address() {
+        let oracle_storage = OracleStorage::default();
+
+        let user_address = H160::from_slice(&[1; 20]);
+        let evm_contract_address = H160::from_slice(&[2; 20]);
+
+        let origin1 = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
+        });
+
+        let destination1 = EvmDestination {
+            contract: evm_contract_address.clone(),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+
+        oracle_storage.add_oracle(
+            user_address.clone(),
+            origin1.clone(),
+            100,
+            TimerId::default(),
+            destination1.clone(),
+        );
+
+        // Assert that the oracle metadata is correct
+        let oracle_metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), evm_contract_address.clone())
+            .unwrap();
+
+        assert_eq!(oracle_metadata.origin, origin1);
+
+        // Remove the oracle
+        oracle_storage
+            .remove_oracle_by_address(user_address.clone(), evm_contract_address.clone())
+            .unwrap();
+
+        // Assert that the oracle is removed
+        let oracle_metadata = oracle_storage
+            .get_oracle_by_address(user_address, evm_contract_address)
+            .unwrap_err();
+
+        assert_eq!(oracle_metadata, Error::UserNotFound); // If the user has no oracles, the user is removed
+    }
+
+    #[test]
+    fn test_remove_multiple_oracles_by_address() {
+        let oracle_storage = OracleStorage::default();
+
+        let user_address = H160::from_slice(&[1; 20]);
+        let evm_contract_address1 = H160::from_slice(&[2; 20]);
+        let evm_contract_address2 = H160::from_slice(&[3; 20]);
+
+        let origin1 = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
+        });
+
+        let destination1 = EvmDestination {
+            contract: evm_contract_address1.clone(),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+
+        oracle_storage.add_oracle(
+            user_address.clone(),
+            origin1.clone(),
+            100,
+            TimerId::default(),
+            destination1.clone(),
+        );
+
+        let origin2 = Origin::Evm(EvmOrigin {
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            target_address: H160::from_slice(&[3; 20]),
+            method: String::from("getPrice"),
+            return_types: vec![],
+            return_value_index: 0,
+        });
+
+        let destination2 = EvmDestination {
+            contract: evm_contract_address2.clone(),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+
+        oracle_storage.add_oracle(
+            user_address.clone(),
+            origin2.clone(),
+            50,
+            TimerId::default(),
+            destination2.clone(),
+        );
+
+        // Assert that the oracle metadata is correct
+        let oracle_metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), evm_contract_address1.clone())
+            .unwrap();
+
+        assert_eq!(oracle_metadata.origin, origin1);
+
+        let oracle_metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), evm_contract_address2.clone())
+            .unwrap();
+
+        assert_eq!(oracle_metadata.origin, origin2);
+
+        // Remove the oracle
+        oracle_storage
+            .remove_oracle_by_address(user_address.clone(), evm_contract_address1.clone())
+            .unwrap();
+
+        // Assert that the oracle is removed
+        let oracle_metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), evm_contract_address1)
+            .unwrap_err();
+
+        assert_eq!(oracle_metadata, Error::OracleNotFound);
+
+        // Assert that the other oracle is still there
+        let oracle_metadata = oracle_storage
+            .get_oracle_by_address(user_address, evm_contract_address2)
+            .unwrap();
+
+        assert_eq!(oracle_metadata.origin, origin2);
+    }
+
+    #[test]
+    fn test_get_user_oracles() {
+        let oracle_storage = OracleStorage::default();
+
+        let user_address1 = H160::from_slice(&[1; 20]);
+        let user_address2 = H160::from_slice(&[2; 20]);
+
+        let evm_contract_address1 = H160::from_slice(&[3; 20]);
+        let evm_contract_address2 = H160::from_slice(&[4; 20]);
+
+        let origin1 = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
+        });
+
+        let destination1 = EvmDestination {
+            contract: evm_contract_address1.clone(),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+
+        let origin2 = Origin::Evm(EvmOrigin {
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            target_address: H160::from_slice(&[3; 20]),
+            method: String::from("getPrice"),
+            return_types: vec![],
+            return_value_index: 0,
+        });
+
+        let destination2 = EvmDestination {
+            contract: evm_contract_address2.clone(),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+
+        oracle_storage.add_oracle(
+            user_address1.clone(),
+            origin1.clone(),
+            100,
+            TimerId::default(),
+            destination1.clone(),
+        );
+
+        oracle_storage.add_oracle(
+            user_address2.clone(),
+            origin2.clone(),
+            50,
+            TimerId::default(),
+            destination1.clone(),
+        );
+
+        oracle_storage.add_oracle(
+            user_address2.clone(),
+            origin1.clone(),
+            50,
+            TimerId::default(),
+            destination2.clone(),
+        );
+
+        let user_oracles = oracle_storage.get_user_oracles(user_address1).unwrap();
+
+        assert_eq!(user_oracles.len(), 1);
+
+        let user_oracles = oracle_storage.get_user_oracles(user_address2).unwrap();
+
+        assert_eq!(user_oracles.len(), 2);
+
+        let user_oracles = oracle_storage
+            .get_user_oracles(H160::from_slice(&[5; 20]))
+            .unwrap_err();
+
+        assert_eq!(user_oracles, Error::UserNotFound);
+
+        let user_oracles = oracle_storage.get_oracles();
+
+        assert_eq!(user_oracles.len(), 2);
+    }
+
+    #[test]
+    fn test_get_timer_id_by_address() {
+        let oracle_storage = OracleStorage::default();
+
+        let user_address = H160::from_slice(&[1; 20]);
+        let evm_contract_address = H160::from_slice(&[2; 20]);
+
+        let origin1 = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
+        });
+
+        let destination1 = EvmDestination {
+            contract: evm_contract_address.clone(),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+
+        let key: KeyData = serde_json::from_str(r#"{"idx":1,"version":1}"#).unwrap();
+
+        let timer = TimerId::from(key);
+
+        oracle_storage.add_oracle(
+            user_address.clone(),
+            origin1.clone(),
+            100,
+            timer,
+            destination1.clone(),
+        );
+
+        let timer_id = oracle_storage
+            .get_timer_id_by_address(user_address, evm_contract_address)
+            .unwrap();
+
+        assert_eq!(timer_id, timer);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use slotmap::KeyData;
+    #[test]
+    fn test_oracle_addition_with_timer_id() {
+        let oracle_storage = OracleStorage::default();
 
-    use super::*;
-    use crate::canister::{EvmOrigin, HttpOrigin};
-    use crate::provider::Provider;
+        let user_address = H160::from_slice(&[1; 20]);
+        let evm_contract_address = H160::from_slice(&[2; 20]);
+
+        let origin1 = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
+        });
+
+        let destination1 = EvmDestination {
+            contract: evm_contract_address.clone(),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+
+        let key: KeyData = serde_json::from_str(r#"{"idx":1,"version":1}"#).unwrap();
+
+        let timer = TimerId::from(key);
+
+        oracle_storage.add_oracle(
+            user_address.clone(),
+            origin1.clone(),
+            100,
+            timer,
+            destination1.clone(),
+        );
+
+        let oracle_metadata = oracle_storage
+            .get_timer_id_by_address(user_address, evm_contract_address)
+            .unwrap();
+
+        assert_eq!(oracle_metadata, timer);
+    }
 
     #[test]
-    fn clear_oracle_storage() {
+    fn test_get_oracle_owner() {
+        let oracle_storage = OracleStorage::default();
+
+        let user_address = H160::from_slice(&[1; 20]);
+        let user_address2 = H160::from_slice(&[2; 20]);
+        let evm_contract_address = H160::from_slice(&[2; 20]);
+
+        let origin1 = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
+        });
+
+        let destination1 = EvmDestination {
+            contract: evm_contract_address.clone(),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+
+        oracle_storage.add_oracle(
+            user_address.clone(),
+            origin1.clone(),
+            100,
+            TimerId::default(),
+            destination1.clone(),
+        );
+
+        let owner = oracle_storage
+            .get_oracle_owner(user_address.clone(), evm_contract_address.clone())
+            .unwrap();
+
+        assert_eq!(owner, user_address);
+
+        let owner = oracle_storage
+            .get_oracle_owner(user_address2, evm_contract_address)
+            .unwrap_err();
+
+        assert_eq!(owner, Error::UserNotFound);
+    }
+
+    #[test]
+    fn test_record_update_failure_reaches_error_threshold() {
         let oracle_storage = OracleStorage::default();
 
         let user_address = H160::from_slice(&[1; 20]);
@@ -283,543 +3090,1917 @@ mod tests {
         let origin = Origin::Http(HttpOrigin {
             url: String::from("https://example.com"),
             json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
         });
 
         let destination = EvmDestination {
             contract: evm_contract_address.clone(),
             provider: Provider {
                 chain_id: 1,
-                hostname: String::from("https://example.com"),
+                endpoints: vec![String::from("https://example.com")],
             },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
         };
 
         oracle_storage.add_oracle(
             user_address.clone(),
-            origin.clone(),
+            origin,
             100,
             TimerId::default(),
-            destination.clone(),
+            destination,
         );
 
-        oracle_storage.clear();
+        oracle_storage
+            .update_oracle_metadata(
+                user_address.clone(),
+                evm_contract_address.clone(),
+                None,
+                UpdateOracleMetadata {
+                    error_threshold: Some(2),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        oracle_storage
+            .record_update_attempt(user_address.clone(), evm_contract_address.clone(), 500)
+            .unwrap();
+
+        let status = oracle_storage
+            .record_update_failure(
+                user_address.clone(),
+                evm_contract_address.clone(),
+                update_error("first error"),
+                500,
+            )
+            .unwrap();
+        assert_eq!(status, OracleStatus::Active);
+
+        let status = oracle_storage
+            .record_update_failure(
+                user_address.clone(),
+                evm_contract_address.clone(),
+                update_error("second error"),
+                500,
+            )
+            .unwrap();
+        assert_eq!(status, OracleStatus::Error);
 
         let oracle_metadata = oracle_storage
-            .get_oracle_by_address(user_address, evm_contract_address)
-            .unwrap_err();
+            .get_oracle_by_address(user_address.clone(), evm_contract_address.clone())
+            .unwrap();
 
-        assert_eq!(oracle_metadata, Error::UserNotFound);
+        assert_eq!(oracle_metadata.consecutive_errors, 2);
+        assert_eq!(oracle_metadata.total_errors, 2);
+        assert_eq!(
+            oracle_metadata.last_error,
+            Some(update_error("second error"))
+        );
+        assert_eq!(oracle_metadata.total_updates, 1);
+        assert_eq!(oracle_metadata.last_attempt_at, 500);
+
+        // A successful update resets the consecutive errors only
+        oracle_storage
+            .record_update_success(
+                user_address.clone(),
+                evm_contract_address.clone(),
+                U256::from(42u64),
+                1_000,
+            )
+            .unwrap();
+
+        let oracle_metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), evm_contract_address.clone())
+            .unwrap();
+
+        assert_eq!(oracle_metadata.status, OracleStatus::Active);
+        assert_eq!(oracle_metadata.last_price, Some(U256::from(42u64)));
+        assert_eq!(oracle_metadata.last_updated_at, 1_000);
+        assert_eq!(oracle_metadata.round_id, 1);
+        assert_eq!(oracle_metadata.consecutive_errors, 0);
+        assert_eq!(oracle_metadata.total_errors, 2);
+
+        oracle_storage
+            .reset_oracle_errors(user_address.clone(), evm_contract_address.clone())
+            .unwrap();
+
+        let oracle_metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), evm_contract_address.clone())
+            .unwrap();
+
+        assert_eq!(oracle_metadata.total_errors, 0);
+        assert_eq!(oracle_metadata.last_error, None);
+
+        // Every successful update starts a new round, failures do not
+        let mut last_round_id = oracle_metadata.round_id;
+        for tick in 0..5u64 {
+            if tick % 2 == 1 {
+                oracle_storage
+                    .record_update_failure(
+                        user_address.clone(),
+                        evm_contract_address.clone(),
+                        update_error("error"),
+                        2_000 + tick,
+                    )
+                    .unwrap();
+            }
+            oracle_storage
+                .record_update_success(
+                    user_address.clone(),
+                    evm_contract_address.clone(),
+                    U256::from(tick),
+                    2_000 + tick,
+                )
+                .unwrap();
+
+            let round_id = oracle_storage
+                .get_oracle_by_address(user_address.clone(), evm_contract_address.clone())
+                .unwrap()
+                .round_id;
+            assert_eq!(round_id, last_round_id + 1);
+            last_round_id = round_id;
+        }
     }
 
     #[test]
-    fn test_add_oracle() {
+    fn test_get_expired_oracles() {
         let oracle_storage = OracleStorage::default();
 
         let user_address = H160::from_slice(&[1; 20]);
-        let evm_contract_address = H160::from_slice(&[2; 20]);
 
         let origin = Origin::Http(HttpOrigin {
             url: String::from("https://example.com"),
             json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
         });
 
+        for (i, expires_at) in [(2u8, Some(100)), (3, Some(200)), (4, None)] {
+            let destination = EvmDestination {
+                contract: H160::from_slice(&[i; 20]),
+                provider: Provider {
+                    chain_id: 1,
+                    endpoints: vec![String::from("https://example.com")],
+                },
+                chain_id: None,
+                extra_destinations: vec![],
+                value_wei: None,
+            };
+
+            let mut metadata = StorableOracleMetadata::new(
+                user_address.clone(),
+                origin.clone(),
+                100,
+                TimerId::default(),
+                destination,
+            );
+            metadata.expires_at = expires_at;
+
+            oracle_storage.insert_oracle(metadata);
+        }
+
+        assert!(oracle_storage.get_expired_oracles(99).is_empty());
+        assert_eq!(
+            oracle_storage.get_expired_oracles(100),
+            vec![(user_address.clone(), H160::from_slice(&[2; 20]))]
+        );
+        assert_eq!(oracle_storage.get_expired_oracles(u64::MAX).len(), 2);
+    }
+
+    #[test]
+    fn test_price_bounds() {
+        let bounds = PriceBounds {
+            min_price: U256::from(1_000u64),
+            max_price: U256::from(2_000u64),
+        };
+
+        assert!(bounds.check(&U256::from(1_000u64)).is_ok());
+        assert!(bounds.check(&U256::from(1_500u64)).is_ok());
+        assert!(bounds.check(&U256::from(2_000u64)).is_ok());
+        assert_eq!(
+            bounds.check(&U256::from(999u64)),
+            Err(Error::PriceOutOfBounds {
+                price: U256::from(999u64),
+                min: U256::from(1_000u64),
+                max: U256::from(2_000u64),
+            })
+        );
+        assert!(bounds.check(&U256::from(2_001u64)).is_err());
+    }
+
+    #[test]
+    fn test_set_last_tx_status() {
+        let oracle_storage = OracleStorage::default();
+
+        let user_address = H160::from_slice(&[1; 20]);
+        let evm_contract_address = H160::from_slice(&[2; 20]);
+
+        let origin = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
+        });
         let destination = EvmDestination {
             contract: evm_contract_address.clone(),
             provider: Provider {
                 chain_id: 1,
-                hostname: String::from("https://example.com"),
+                endpoints: vec![String::from("https://example.com")],
             },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
         };
 
         oracle_storage.add_oracle(
             user_address.clone(),
+            origin,
+            100,
+            TimerId::default(),
+            destination,
+        );
+
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), evm_contract_address.clone())
+            .unwrap();
+        assert!(!metadata.verify_receipt);
+        assert_eq!(metadata.last_tx_status, None);
+
+        let tx_status = TxStatus {
+            hash: H256::from_slice(&[3; 32]),
+            status: TxReceiptStatus::Failed,
+            replaced: None,
+            confirmed_block: None,
+        };
+        oracle_storage
+            .set_last_tx_status(
+                user_address.clone(),
+                evm_contract_address.clone(),
+                tx_status.clone(),
+            )
+            .unwrap();
+
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), evm_contract_address.clone())
+            .unwrap();
+        assert_eq!(metadata.last_tx_status, Some(tx_status.clone()));
+
+        // Only the status of the last transaction is updated
+        let reorg = |tx_status: &mut TxStatus| tx_status.status = TxReceiptStatus::Reorged;
+        assert_eq!(
+            oracle_storage.update_last_tx_status(
+                user_address.clone(),
+                evm_contract_address.clone(),
+                &H256::from_slice(&[4; 32]),
+                reorg,
+            ),
+            Ok(false)
+        );
+        assert_eq!(
+            oracle_storage.update_last_tx_status(
+                user_address.clone(),
+                evm_contract_address.clone(),
+                &tx_status.hash,
+                reorg,
+            ),
+            Ok(true)
+        );
+
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address, evm_contract_address)
+            .unwrap();
+        assert_eq!(
+            metadata.last_tx_status.map(|tx_status| tx_status.status),
+            Some(TxReceiptStatus::Reorged)
+        );
+    }
+
+    #[test]
+    fn test_get_oracles_by_type() {
+        let oracle_storage = OracleStorage::default();
+
+        let user_address = H160::from_slice(&[1; 20]);
+        let provider = Provider {
+            chain_id: 1,
+            endpoints: vec![String::from("https://example.com")],
+        };
+
+        let origins = [
+            Origin::Http(HttpOrigin {
+                url: String::from("https://example.com"),
+                json_path: String::from("data"),
+                json_paths: vec![],
+                forbidden_paths: vec![],
+                required_paths: vec![],
+                decimals: None,
+                max_response_bytes: None,
+                parse_mode: None,
+                auth: None,
+                enable_filter_expressions: false,
+                skip_cache: false,
+                json_aggregate: None,
+                session_cookie: None,
+                valid_status_codes: vec![200],
+                signing_config: None,
+                timeout_ms: None,
+            }),
+            Origin::Evm(EvmOrigin {
+                provider: provider.clone(),
+                target_address: H160::from_slice(&[9; 20]),
+                method: String::from("latestAnswer"),
+                return_types: vec![],
+                return_value_index: 0,
+            }),
+            Origin::Http(HttpOrigin {
+                url: String::from("https://example.org"),
+                json_path: String::from("data"),
+                json_paths: vec![],
+                forbidden_paths: vec![],
+                required_paths: vec![],
+                decimals: None,
+                max_response_bytes: None,
+                parse_mode: None,
+                auth: None,
+                enable_filter_expressions: false,
+                skip_cache: false,
+                json_aggregate: None,
+                session_cookie: None,
+                valid_status_codes: vec![200],
+                signing_config: None,
+                timeout_ms: None,
+            }),
+        ];
+
+        for (i, origin) in origins.into_iter().enumerate() {
+            oracle_storage.add_oracle(
+                user_address.clone(),
+                origin,
+                100,
+                TimerId::default(),
+                EvmDestination {
+                    contract: H160::from_slice(&[i as u8 + 2; 20]),
+                    provider: provider.clone(),
+                    chain_id: None,
+                    extra_destinations: vec![],
+                    value_wei: None,
+                },
+            );
+        }
+
+        let http_oracles = oracle_storage
+            .get_user_oracles_by_type(user_address.clone(), OriginType::Http)
+            .unwrap();
+        assert_eq!(http_oracles.len(), 2);
+
+        let evm_oracles = oracle_storage
+            .get_user_oracles_by_type(user_address.clone(), OriginType::Evm)
+            .unwrap();
+        assert_eq!(evm_oracles.len(), 1);
+        assert_eq!(evm_oracles[0].0, H160::from_slice(&[3; 20]));
+
+        let all_evm_oracles = oracle_storage.get_oracles_by_type(OriginType::Evm);
+        assert_eq!(all_evm_oracles.len(), 1);
+        assert_eq!(all_evm_oracles[0].0, user_address);
+        assert_eq!(all_evm_oracles[0].1.len(), 1);
+    }
+
+    #[test]
+    fn test_transfer_oracle() {
+        let oracle_storage = OracleStorage::default();
+
+        let from = H160::from_slice(&[1; 20]);
+        let to = H160::from_slice(&[2; 20]);
+        let evm_contract_address = H160::from_slice(&[3; 20]);
+
+        let origin = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
+        });
+        let destination = EvmDestination {
+            contract: evm_contract_address.clone(),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+
+        oracle_storage.add_oracle(
+            from.clone(),
             origin.clone(),
             100,
             TimerId::default(),
             destination.clone(),
         );
 
-        let oracle_metadata = oracle_storage
-            .get_oracle_by_address(user_address, evm_contract_address)
+        // Another oracle triggering the transferred one
+        let other_user = H160::from_slice(&[4; 20]);
+        let other_contract = H160::from_slice(&[5; 20]);
+        oracle_storage.add_oracle(
+            other_user.clone(),
+            origin,
+            100,
+            TimerId::default(),
+            EvmDestination {
+                contract: other_contract.clone(),
+                ..destination
+            },
+        );
+        oracle_storage
+            .update_oracle_metadata(
+                other_user.clone(),
+                other_contract.clone(),
+                None,
+                UpdateOracleMetadata {
+                    triggers: Some(vec![(from.clone(), evm_contract_address.clone())]),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let metadata = oracle_storage
+            .transfer_oracle(from.clone(), to.clone(), evm_contract_address.clone())
             .unwrap();
+        assert_eq!(metadata.owner, to);
+
+        let other = oracle_storage
+            .get_oracle_by_address(other_user, other_contract)
+            .unwrap();
+        assert_eq!(
+            other.triggers,
+            vec![(to.clone(), evm_contract_address.clone())]
+        );
+
+        assert_eq!(
+            oracle_storage.get_user_oracles(from.clone()).unwrap_err(),
+            Error::UserNotFound
+        );
 
-        assert_eq!(oracle_metadata.origin, origin);
-        assert_eq!(oracle_metadata.timer_interval, 100);
-        assert_eq!(oracle_metadata.evm, destination);
+        let oracles = oracle_storage.get_user_oracles(to.clone()).unwrap();
+        assert_eq!(oracles.len(), 1);
+        assert_eq!(oracles[0].0, evm_contract_address);
+        assert_eq!(oracles[0].1.owner, to);
+
+        // The oracle is not owned by `from` anymore
+        assert_eq!(
+            oracle_storage
+                .transfer_oracle(from, to, evm_contract_address)
+                .unwrap_err(),
+            Error::UserNotFound
+        );
     }
 
     #[test]
-    fn test_add_multiple_oracles() {
+    fn test_add_consumed_cycles() {
         let oracle_storage = OracleStorage::default();
 
         let user_address = H160::from_slice(&[1; 20]);
         let evm_contract_address = H160::from_slice(&[2; 20]);
 
-        let origin1 = Origin::Http(HttpOrigin {
+        let origin = Origin::Http(HttpOrigin {
             url: String::from("https://example.com"),
             json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
         });
-
-        let destination1 = EvmDestination {
+        let destination = EvmDestination {
             contract: evm_contract_address.clone(),
             provider: Provider {
                 chain_id: 1,
-                hostname: String::from("https://example.com"),
+                endpoints: vec![String::from("https://example.com")],
             },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
         };
 
         oracle_storage.add_oracle(
             user_address.clone(),
-            origin1.clone(),
+            origin,
             100,
             TimerId::default(),
-            destination1.clone(),
+            destination,
         );
 
-        let origin2 = Origin::Evm(EvmOrigin {
-            provider: Provider {
-                chain_id: 1,
-                hostname: String::from("https://example.com"),
-            },
-            target_address: H160::from_slice(&[3; 20]),
-            method: String::from("getPrice"),
-        });
+        // Without a budget the oracle is never exhausted
+        assert!(!oracle_storage
+            .add_consumed_cycles(user_address.clone(), evm_contract_address.clone(), 1_000)
+            .unwrap());
+        assert!(oracle_storage
+            .deposit_cycles(user_address.clone(), evm_contract_address.clone(), 1_000)
+            .is_err());
 
-        let destination2 = EvmDestination {
-            contract: H160::from_slice(&[4; 20]),
+        oracle_storage
+            .update_oracle_metadata(
+                user_address.clone(),
+                evm_contract_address.clone(),
+                None,
+                UpdateOracleMetadata {
+                    cycle_budget: Some(2_500),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert!(!oracle_storage
+            .add_consumed_cycles(user_address.clone(), evm_contract_address.clone(), 1_000)
+            .unwrap());
+        assert!(oracle_storage
+            .add_consumed_cycles(user_address.clone(), evm_contract_address.clone(), 1_000)
+            .unwrap());
+
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), evm_contract_address.clone())
+            .unwrap();
+        assert_eq!(metadata.cycles_consumed, 3_000);
+        assert_eq!(
+            metadata.cycles_per_call_history,
+            [1_000, 1_000, 1_000].into()
+        );
+        assert_eq!(oracle_storage.get_total_cycles_consumed(), 3_000);
+        assert_eq!(
+            oracle_storage.get_user_cycles_consumed(user_address.clone()),
+            3_000
+        );
+        assert_eq!(
+            oracle_storage.get_user_cycles_consumed(H160::from_slice(&[9; 20])),
+            0
+        );
+
+        // Deposits are deducted from the consumed cycles, then added to the budget
+        assert_eq!(
+            oracle_storage
+                .deposit_cycles(user_address.clone(), evm_contract_address.clone(), 1_000)
+                .unwrap(),
+            500
+        );
+        assert_eq!(
+            oracle_storage
+                .deposit_cycles(user_address.clone(), evm_contract_address.clone(), 2_500)
+                .unwrap(),
+            3_000
+        );
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), evm_contract_address.clone())
+            .unwrap();
+        assert_eq!(metadata.cycles_consumed, 0);
+        assert_eq!(metadata.cycle_budget, Some(3_000));
+
+        oracle_storage
+            .reset_cycles_consumed(user_address.clone(), evm_contract_address.clone())
+            .unwrap();
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address, evm_contract_address)
+            .unwrap();
+        assert_eq!(metadata.cycles_consumed, 0);
+    }
+
+    #[test]
+    fn test_user_oracle_count_index() {
+        let oracle_storage = OracleStorage::default();
+
+        let alice = H160::from_slice(&[1; 20]);
+        let bob = H160::from_slice(&[2; 20]);
+
+        let origin = Origin::Http(HttpOrigin {
+            url: String::from("https://example.com"),
+            json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
+        });
+        let destination = |i: u8| EvmDestination {
+            contract: H160::from_slice(&[i; 20]),
             provider: Provider {
                 chain_id: 1,
-                hostname: String::from("https://example.com"),
+                endpoints: vec![String::from("https://example.com")],
             },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
         };
 
-        oracle_storage.add_oracle(
-            user_address.clone(),
-            origin2.clone(),
-            50,
-            TimerId::default(),
-            destination2.clone(),
+        for (user, i) in [(alice.clone(), 3), (alice.clone(), 4), (bob.clone(), 5)] {
+            oracle_storage.add_oracle(
+                user,
+                origin.clone(),
+                100,
+                TimerId::default(),
+                destination(i),
+            );
+        }
+
+        assert_eq!(oracle_storage.get_user_oracle_count(alice.clone()), 2);
+        assert_eq!(oracle_storage.get_user_oracle_count(bob.clone()), 1);
+        assert_eq!(oracle_storage.get_users(), vec![alice.clone(), bob.clone()]);
+        assert_eq!(oracle_storage.get_oracle_count(), 3);
+        assert_eq!(oracle_storage.get_storage_entry_count(), 2);
+        assert_eq!(
+            oracle_storage.get_oracle_statuses(),
+            vec![OracleStatus::Active; 3]
         );
 
-        let oracle_metadata = oracle_storage
-            .get_oracle_by_address(user_address.clone(), evm_contract_address)
+        oracle_storage
+            .remove_oracle_by_address(bob.clone(), H160::from_slice(&[5; 20]))
             .unwrap();
+        assert_eq!(oracle_storage.get_user_oracle_count(bob.clone()), 0);
+        assert_eq!(oracle_storage.get_users(), vec![alice.clone()]);
+        assert_eq!(oracle_storage.get_oracle_count(), 2);
 
-        assert_eq!(oracle_metadata.origin, origin1);
-        assert_eq!(oracle_metadata.timer_interval, 100);
-        assert_eq!(oracle_metadata.evm, destination1);
-
-        let oracle_metadata = oracle_storage
-            .get_oracle_by_address(user_address, H160::from_slice(&[4; 20]))
+        oracle_storage
+            .transfer_oracle(alice.clone(), bob.clone(), H160::from_slice(&[3; 20]))
             .unwrap();
-
-        assert_eq!(oracle_metadata.origin, origin2);
-        assert_eq!(oracle_metadata.timer_interval, 50);
-        assert_eq!(oracle_metadata.evm, destination2);
+        assert_eq!(oracle_storage.get_user_oracle_count(alice.clone()), 1);
+        assert_eq!(oracle_storage.get_user_oracle_count(bob.clone()), 1);
+
+        // The missing users are added back to the index
+        USER_ORACLE_COUNT.with(|index| index.borrow_mut().remove(&bob));
+        assert_eq!(oracle_storage.get_users(), vec![alice.clone()]);
+        oracle_storage.init_user_index();
+        assert_eq!(oracle_storage.get_users(), vec![alice, bob.clone()]);
+        assert_eq!(oracle_storage.get_user_oracle_count(bob), 1);
     }
 
     #[test]
-    fn test_update_oracle_metadata() {
+    fn test_oracle_events_ring_buffer() {
         let oracle_storage = OracleStorage::default();
 
         let user_address = H160::from_slice(&[1; 20]);
         let evm_contract_address = H160::from_slice(&[2; 20]);
 
-        let origin1 = Origin::Http(HttpOrigin {
+        let origin = Origin::Http(HttpOrigin {
             url: String::from("https://example.com"),
             json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
         });
-
-        let destination1 = EvmDestination {
+        let destination = EvmDestination {
             contract: evm_contract_address.clone(),
             provider: Provider {
                 chain_id: 1,
-                hostname: String::from("https://example.com"),
+                endpoints: vec![String::from("https://example.com")],
             },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
         };
 
         oracle_storage.add_oracle(
             user_address.clone(),
-            origin1.clone(),
+            origin,
             100,
             TimerId::default(),
-            destination1.clone(),
+            destination,
         );
 
-        // Assert that the oracle metadata is correct
-        let oracle_metadata = oracle_storage
-            .get_oracle_by_address(user_address.clone(), evm_contract_address.clone())
-            .unwrap();
-
-        assert_eq!(oracle_metadata.origin, origin1);
-
-        let new_origin = Origin::Evm(EvmOrigin {
-            provider: Provider {
-                chain_id: 1,
-                hostname: String::from("https://example.com"),
-            },
-            target_address: H160::from_slice(&[3; 20]),
-            method: String::from("getPrice"),
-        });
-
-        // Update the oracle metadata
-        let update_metadata = UpdateOracleMetadata {
-            origin: Some(new_origin.clone()),
-            evm: None,
-            timestamp: None,
+        let event = |timestamp| OracleEvent {
+            timestamp,
+            user_address: user_address.clone(),
+            contract_address: evm_contract_address.clone(),
+            event_type: OracleEventType::Updated,
+            details: None,
         };
 
-        oracle_storage
-            .update_oracle_metadata(
+        for timestamp in 0..MAX_ORACLE_EVENTS as u64 + 5 {
+            oracle_storage.push_event(event(timestamp)).unwrap();
+        }
+
+        let events = oracle_storage
+            .get_oracle_events(
                 user_address.clone(),
                 evm_contract_address.clone(),
-                None,
-                update_metadata,
+                usize::MAX,
             )
             .unwrap();
+        assert_eq!(events.len(), MAX_ORACLE_EVENTS);
+        assert_eq!(events[0].timestamp, MAX_ORACLE_EVENTS as u64 + 4);
+        assert_eq!(events[MAX_ORACLE_EVENTS - 1].timestamp, 5);
 
-        // Assert that the oracle metadata is updated
-        let oracle_metadata = oracle_storage
-            .get_oracle_by_address(user_address, evm_contract_address)
+        let events = oracle_storage
+            .get_oracle_events(user_address.clone(), evm_contract_address, 2)
             .unwrap();
+        assert_eq!(
+            events,
+            vec![
+                event(MAX_ORACLE_EVENTS as u64 + 4),
+                event(MAX_ORACLE_EVENTS as u64 + 3)
+            ]
+        );
 
-        assert_eq!(oracle_metadata.origin, new_origin,);
+        // Events of missing oracles are not stored
+        let mut missing = event(0);
+        missing.contract_address = H160::from_slice(&[3; 20]);
+        assert_eq!(
+            oracle_storage.push_event(missing),
+            Err(Error::OracleNotFound)
+        );
     }
 
     #[test]
-    fn test_remove_single_oracle_by_address() {
+    fn test_update_registered_provider() {
         let oracle_storage = OracleStorage::default();
 
         let user_address = H160::from_slice(&[1; 20]);
-        let evm_contract_address = H160::from_slice(&[2; 20]);
 
-        let origin1 = Origin::Http(HttpOrigin {
+        let origin = Origin::Http(HttpOrigin {
             url: String::from("https://example.com"),
             json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
         });
-
-        let destination1 = EvmDestination {
-            contract: evm_contract_address.clone(),
+        let destination = |contract: u8, chain_id| EvmDestination {
+            contract: H160::from_slice(&[contract; 20]),
             provider: Provider {
                 chain_id: 1,
-                hostname: String::from("https://example.com"),
+                endpoints: vec![String::from("https://example.com")],
             },
+            chain_id,
+            extra_destinations: vec![],
+            value_wei: None,
         };
 
+        for (contract, chain_id) in [(2, Some(1)), (3, None), (4, Some(2))] {
+            oracle_storage.add_oracle(
+                user_address.clone(),
+                origin.clone(),
+                100,
+                TimerId::default(),
+                destination(contract, chain_id),
+            );
+        }
+
+        // An oracle of another chain with an extra destination on the updated chain
+        let mut extra = destination(5, None);
+        extra.provider.chain_id = 2;
+        extra.extra_destinations = vec![SingleEvmDestination {
+            contract: H160::from_slice(&[6; 20]),
+            provider: Provider {
+                chain_id: 1,
+                endpoints: vec![String::from("https://example.com")],
+            },
+        }];
         oracle_storage.add_oracle(
             user_address.clone(),
-            origin1.clone(),
+            origin.clone(),
             100,
             TimerId::default(),
-            destination1.clone(),
+            extra,
         );
 
-        // Assert that the oracle metadata is correct
-        let oracle_metadata = oracle_storage
-            .get_oracle_by_address(user_address.clone(), evm_contract_address.clone())
+        let provider = Provider {
+            chain_id: 1,
+            endpoints: vec![String::from("https://new.example.com")],
+        };
+        assert_eq!(oracle_storage.update_registered_provider(&provider), 2);
+
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), H160::from_slice(&[5; 20]))
             .unwrap();
+        assert_eq!(metadata.evm.provider.endpoints[0], "https://example.com");
+        assert_eq!(metadata.evm.extra_destinations[0].provider, provider);
+
+        let oracles = oracle_storage.get_user_oracles(user_address).unwrap();
+        let providers = oracles
+            .into_iter()
+            .map(|(_, metadata)| metadata.evm.provider.endpoints[0].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            providers,
+            vec![
+                "https://new.example.com".to_string(),
+                "https://example.com".to_string(),
+                "https://example.com".to_string(),
+                "https://example.com".to_string(),
+            ]
+        );
+    }
 
-        assert_eq!(oracle_metadata.origin, origin1);
+    #[test]
+    fn test_pause_and_resume_all_oracles() {
+        let oracle_storage = OracleStorage::default();
 
-        // Remove the oracle
+        let user_address = H160::from_slice(&[1; 20]);
+        let active_contract = H160::from_slice(&[2; 20]);
+        let paused_contract = H160::from_slice(&[3; 20]);
+        let pending_contract = H160::from_slice(&[4; 20]);
+
+        for contract in [&active_contract, &paused_contract, &pending_contract] {
+            oracle_storage.add_oracle(
+                user_address.clone(),
+                Origin::Http(HttpOrigin {
+                    url: String::from("https://example.com"),
+                    json_path: String::from("data"),
+                    json_paths: vec![],
+                    forbidden_paths: vec![],
+                    required_paths: vec![],
+                    decimals: None,
+                    max_response_bytes: None,
+                    parse_mode: None,
+                    auth: None,
+                    enable_filter_expressions: false,
+                    skip_cache: false,
+                    json_aggregate: None,
+                    session_cookie: None,
+                    valid_status_codes: vec![200],
+                    signing_config: None,
+                    timeout_ms: None,
+                }),
+                100,
+                TimerId::default(),
+                EvmDestination {
+                    contract: contract.clone(),
+                    provider: Provider {
+                        chain_id: 1,
+                        endpoints: vec![String::from("https://example.com")],
+                    },
+                    chain_id: None,
+                    extra_destinations: vec![],
+                    value_wei: None,
+                },
+            );
+        }
         oracle_storage
-            .remove_oracle_by_address(user_address.clone(), evm_contract_address.clone())
+            .set_oracle_status(
+                user_address.clone(),
+                paused_contract.clone(),
+                OracleStatus::Paused,
+                None,
+            )
+            .unwrap();
+        oracle_storage
+            .set_oracle_status(
+                user_address.clone(),
+                pending_contract.clone(),
+                OracleStatus::Pending,
+                None,
+            )
             .unwrap();
 
-        // Assert that the oracle is removed
-        let oracle_metadata = oracle_storage
-            .get_oracle_by_address(user_address, evm_contract_address)
-            .unwrap_err();
+        let paused = oracle_storage.pause_active_oracles();
+        assert_eq!(paused.len(), 2);
 
-        assert_eq!(oracle_metadata, Error::UserNotFound); // If the user has no oracles, the user is removed
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), active_contract.clone())
+            .unwrap();
+        assert_eq!(metadata.status, OracleStatus::Paused);
+        assert_eq!(metadata.status_before_emergency, Some(OracleStatus::Active));
+
+        // Oracles paused before the emergency are not resumed
+        let to_resume = oracle_storage
+            .get_emergency_paused_oracles()
+            .into_iter()
+            .map(|(user_address, contract_address, _)| (user_address, contract_address))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            to_resume,
+            vec![
+                (user_address.clone(), active_contract.clone()),
+                (user_address.clone(), pending_contract.clone()),
+            ]
+        );
+
+        oracle_storage.resume_emergency_paused_oracles(
+            to_resume
+                .into_iter()
+                .map(|(user_address, contract_address)| {
+                    (user_address, contract_address, TimerId::default())
+                })
+                .collect(),
+        );
+
+        // The oracles get back the status they had before the emergency
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), active_contract)
+            .unwrap();
+        assert_eq!(metadata.status, OracleStatus::Active);
+        assert_eq!(metadata.status_before_emergency, None);
+
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), pending_contract)
+            .unwrap();
+        assert_eq!(metadata.status, OracleStatus::Pending);
+
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address, paused_contract)
+            .unwrap();
+        assert_eq!(metadata.status, OracleStatus::Paused);
+        assert!(oracle_storage.get_emergency_paused_oracles().is_empty());
     }
 
     #[test]
-    fn test_remove_multiple_oracles_by_address() {
+    fn test_circuit_breaker() {
         let oracle_storage = OracleStorage::default();
 
         let user_address = H160::from_slice(&[1; 20]);
-        let evm_contract_address1 = H160::from_slice(&[2; 20]);
-        let evm_contract_address2 = H160::from_slice(&[3; 20]);
-
-        let origin1 = Origin::Http(HttpOrigin {
-            url: String::from("https://example.com"),
-            json_path: String::from("data"),
-        });
-
-        let destination1 = EvmDestination {
-            contract: evm_contract_address1.clone(),
-            provider: Provider {
-                chain_id: 1,
-                hostname: String::from("https://example.com"),
-            },
-        };
+        let evm_contract_address = H160::from_slice(&[2; 20]);
 
         oracle_storage.add_oracle(
             user_address.clone(),
-            origin1.clone(),
+            Origin::Http(HttpOrigin {
+                url: String::from("https://example.com"),
+                json_path: String::from("data"),
+                json_paths: vec![],
+                forbidden_paths: vec![],
+                required_paths: vec![],
+                decimals: None,
+                max_response_bytes: None,
+                parse_mode: None,
+                auth: None,
+                enable_filter_expressions: false,
+                skip_cache: false,
+                json_aggregate: None,
+                session_cookie: None,
+                valid_status_codes: vec![200],
+                signing_config: None,
+                timeout_ms: None,
+            }),
             100,
             TimerId::default(),
-            destination1.clone(),
-        );
-
-        let origin2 = Origin::Evm(EvmOrigin {
-            provider: Provider {
-                chain_id: 1,
-                hostname: String::from("https://example.com"),
+            EvmDestination {
+                contract: evm_contract_address.clone(),
+                provider: Provider {
+                    chain_id: 1,
+                    endpoints: vec![String::from("https://example.com")],
+                },
+                chain_id: None,
+                extra_destinations: vec![],
+                value_wei: None,
             },
-            target_address: H160::from_slice(&[3; 20]),
-            method: String::from("getPrice"),
-        });
+        );
+        oracle_storage
+            .update_oracle_metadata(
+                user_address.clone(),
+                evm_contract_address.clone(),
+                None,
+                UpdateOracleMetadata {
+                    error_threshold: Some(10),
+                    circuit_breaker: Some(CircuitBreakerConfig {
+                        failure_threshold: 2,
+                        reset_after_secs: 60,
+                    }),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
 
-        let destination2 = EvmDestination {
-            contract: evm_contract_address2.clone(),
-            provider: Provider {
-                chain_id: 1,
-                hostname: String::from("https://example.com"),
-            },
+        let record_failure = |now| {
+            oracle_storage
+                .record_update_failure(
+                    user_address.clone(),
+                    evm_contract_address.clone(),
+                    update_error("origin is down"),
+                    now,
+                )
+                .unwrap()
         };
 
-        oracle_storage.add_oracle(
-            user_address.clone(),
-            origin2.clone(),
-            50,
-            TimerId::default(),
-            destination2.clone(),
+        assert_eq!(record_failure(1_000), OracleStatus::Active);
+        assert_eq!(
+            record_failure(2_000),
+            OracleStatus::CircuitOpen {
+                since: 2_000,
+                resets_at: 2_000 + 60_000_000_000,
+            }
         );
 
-        // Assert that the oracle metadata is correct
-        let oracle_metadata = oracle_storage
-            .get_oracle_by_address(user_address.clone(), evm_contract_address1.clone())
-            .unwrap();
-
-        assert_eq!(oracle_metadata.origin, origin1);
+        // A failed half-open attempt opens the circuit again
+        assert_eq!(
+            record_failure(70_000_000_000),
+            OracleStatus::CircuitOpen {
+                since: 70_000_000_000,
+                resets_at: 130_000_000_000,
+            }
+        );
 
-        let oracle_metadata = oracle_storage
-            .get_oracle_by_address(user_address.clone(), evm_contract_address2.clone())
+        // A successful half-open attempt closes the circuit
+        oracle_storage
+            .record_update_success(
+                user_address.clone(),
+                evm_contract_address.clone(),
+                U256::from(42u64),
+                140_000_000_000,
+            )
             .unwrap();
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), evm_contract_address.clone())
+            .unwrap();
+        assert_eq!(metadata.status, OracleStatus::Active);
+        assert_eq!(metadata.consecutive_errors, 0);
 
-        assert_eq!(oracle_metadata.origin, origin2);
-
-        // Remove the oracle
+        // A paused oracle does not open its circuit
         oracle_storage
-            .remove_oracle_by_address(user_address.clone(), evm_contract_address1.clone())
+            .set_oracle_status(
+                user_address.clone(),
+                evm_contract_address.clone(),
+                OracleStatus::Paused,
+                None,
+            )
             .unwrap();
+        record_failure(150_000_000_000);
+        assert_eq!(record_failure(160_000_000_000), OracleStatus::Paused);
+    }
 
-        // Assert that the oracle is removed
-        let oracle_metadata = oracle_storage
-            .get_oracle_by_address(user_address.clone(), evm_contract_address1)
-            .unwrap_err();
+    #[test]
+    fn test_find_user_oracles_by_label() {
+        let oracle_storage = OracleStorage::default();
 
-        assert_eq!(oracle_metadata, Error::OracleNotFound);
+        let user_address = H160::from_slice(&[1; 20]);
+
+        for (contract, label) in [
+            (2, Some("BTC/USD Mainnet")),
+            (3, Some("ETH/USD")),
+            (4, None),
+        ] {
+            let contract = H160::from_slice(&[contract; 20]);
+            oracle_storage.add_oracle(
+                user_address.clone(),
+                Origin::Http(HttpOrigin {
+                    url: String::from("https://example.com"),
+                    json_path: String::from("data"),
+                    json_paths: vec![],
+                    forbidden_paths: vec![],
+                    required_paths: vec![],
+                    decimals: None,
+                    max_response_bytes: None,
+                    parse_mode: None,
+                    auth: None,
+                    enable_filter_expressions: false,
+                    skip_cache: false,
+                    json_aggregate: None,
+                    session_cookie: None,
+                    valid_status_codes: vec![200],
+                    signing_config: None,
+                    timeout_ms: None,
+                }),
+                100,
+                TimerId::default(),
+                EvmDestination {
+                    contract: contract.clone(),
+                    provider: Provider {
+                        chain_id: 1,
+                        endpoints: vec![String::from("https://example.com")],
+                    },
+                    chain_id: None,
+                    extra_destinations: vec![],
+                    value_wei: None,
+                },
+            );
+            oracle_storage
+                .update_oracle_metadata(
+                    user_address.clone(),
+                    contract,
+                    None,
+                    UpdateOracleMetadata {
+                        label: label.map(String::from),
+                        description: Some(String::from("price feed")),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+        }
+
+        let oracles = oracle_storage
+            .find_user_oracles_by_label(user_address.clone(), "btc/usd")
+            .unwrap();
+        assert_eq!(oracles.len(), 1);
+        assert_eq!(oracles[0].0, H160::from_slice(&[2; 20]));
+        assert_eq!(oracles[0].1.label, Some(String::from("BTC/USD Mainnet")));
+        assert_eq!(oracles[0].1.description, Some(String::from("price feed")));
 
-        // Assert that the other oracle is still there
-        let oracle_metadata = oracle_storage
-            .get_oracle_by_address(user_address, evm_contract_address2)
+        let oracles = oracle_storage
+            .find_user_oracles_by_label(user_address.clone(), "USD")
             .unwrap();
+        assert_eq!(oracles.len(), 2);
 
-        assert_eq!(oracle_metadata.origin, origin2);
+        assert_eq!(
+            oracle_storage
+                .find_user_oracles_by_label(H160::from_slice(&[9; 20]), "USD")
+                .unwrap_err(),
+            Error::UserNotFound
+        );
     }
 
     #[test]
-    fn test_get_user_oracles() {
+    fn test_update_chain_provider() {
         let oracle_storage = OracleStorage::default();
 
-        let user_address1 = H160::from_slice(&[1; 20]);
-        let user_address2 = H160::from_slice(&[2; 20]);
-
-        let evm_contract_address1 = H160::from_slice(&[3; 20]);
-        let evm_contract_address2 = H160::from_slice(&[4; 20]);
-
-        let origin1 = Origin::Http(HttpOrigin {
-            url: String::from("https://example.com"),
-            json_path: String::from("data"),
-        });
-
-        let destination1 = EvmDestination {
-            contract: evm_contract_address1.clone(),
-            provider: Provider {
-                chain_id: 1,
-                hostname: String::from("https://example.com"),
-            },
-        };
-
-        let origin2 = Origin::Evm(EvmOrigin {
-            provider: Provider {
-                chain_id: 1,
-                hostname: String::from("https://example.com"),
-            },
-            target_address: H160::from_slice(&[3; 20]),
-            method: String::from("getPrice"),
-        });
+        let user_address = H160::from_slice(&[1; 20]);
+        let evm_contract_address = H160::from_slice(&[2; 20]);
+        let http_contract_address = H160::from_slice(&[3; 20]);
 
-        let destination2 = EvmDestination {
-            contract: evm_contract_address2.clone(),
-            provider: Provider {
-                chain_id: 1,
-                hostname: String::from("https://example.com"),
-            },
+        let provider = |chain_id, endpoint: &str| Provider {
+            chain_id,
+            endpoints: vec![endpoint.to_string()],
         };
 
         oracle_storage.add_oracle(
-            user_address1.clone(),
-            origin1.clone(),
+            user_address.clone(),
+            Origin::Evm(EvmOrigin {
+                provider: provider(1, "https://old.example.com"),
+                target_address: H160::from_slice(&[9; 20]),
+                method: String::from("latestAnswer"),
+                return_types: vec![],
+                return_value_index: 0,
+            }),
             100,
             TimerId::default(),
-            destination1.clone(),
+            EvmDestination {
+                contract: evm_contract_address.clone(),
+                provider: provider(1, "https://old.example.com"),
+                chain_id: None,
+                extra_destinations: vec![],
+                value_wei: None,
+            },
         );
-
         oracle_storage.add_oracle(
-            user_address2.clone(),
-            origin2.clone(),
-            50,
+            user_address.clone(),
+            Origin::Http(HttpOrigin {
+                url: String::from("https://example.com"),
+                json_path: String::from("data"),
+                json_paths: vec![],
+                forbidden_paths: vec![],
+                required_paths: vec![],
+                decimals: None,
+                max_response_bytes: None,
+                parse_mode: None,
+                auth: None,
+                enable_filter_expressions: false,
+                skip_cache: false,
+                json_aggregate: None,
+                session_cookie: None,
+                valid_status_codes: vec![200],
+                signing_config: None,
+                timeout_ms: None,
+            }),
+            100,
             TimerId::default(),
-            destination1.clone(),
+            EvmDestination {
+                contract: http_contract_address.clone(),
+                provider: provider(2, "https://other.example.com"),
+                chain_id: None,
+                extra_destinations: vec![SingleEvmDestination {
+                    contract: H160::from_slice(&[4; 20]),
+                    provider: provider(1, "https://old.example.com"),
+                }],
+                value_wei: None,
+            },
         );
 
-        oracle_storage.add_oracle(
-            user_address2.clone(),
-            origin1.clone(),
-            50,
-            TimerId::default(),
-            destination2.clone(),
+        let new_provider = provider(1, "https://new.example.com");
+
+        // Only the origin providers are updated
+        let updated = oracle_storage.update_chain_provider(&new_provider, true, false);
+        assert_eq!(
+            updated,
+            vec![(user_address.clone(), evm_contract_address.clone())]
         );
 
-        let user_oracles = oracle_storage.get_user_oracles(user_address1).unwrap();
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), evm_contract_address.clone())
+            .unwrap();
+        assert!(matches!(&metadata.origin, Origin::Evm(origin) if origin.provider == new_provider));
+        assert_eq!(
+            metadata.evm.provider,
+            provider(1, "https://old.example.com")
+        );
 
-        assert_eq!(user_oracles.len(), 1);
+        let updated = oracle_storage.update_chain_provider(&new_provider, false, true);
+        assert_eq!(updated.len(), 2);
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), evm_contract_address)
+            .unwrap();
+        assert_eq!(metadata.evm.provider, new_provider);
 
-        let user_oracles = oracle_storage.get_user_oracles(user_address2).unwrap();
+        // Only the extra destination of the oracle of another chain is updated
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address, http_contract_address)
+            .unwrap();
+        assert_eq!(
+            metadata.evm.provider,
+            provider(2, "https://other.example.com")
+        );
+        assert_eq!(metadata.evm.extra_destinations[0].provider, new_provider);
+    }
 
-        assert_eq!(user_oracles.len(), 2);
+    #[test]
+    fn test_oracle_tags() {
+        let oracle_storage = OracleStorage::default();
 
-        let user_oracles = oracle_storage
-            .get_user_oracles(H160::from_slice(&[5; 20]))
-            .unwrap_err();
+        let alice = H160::from_slice(&[1; 20]);
+        let bob = H160::from_slice(&[2; 20]);
 
-        assert_eq!(user_oracles, Error::UserNotFound);
+        for (user_address, contract) in [(&alice, 3), (&alice, 4), (&bob, 5)] {
+            oracle_storage.add_oracle(
+                user_address.clone(),
+                Origin::Http(HttpOrigin {
+                    url: String::from("https://example.com"),
+                    json_path: String::from("data"),
+                    json_paths: vec![],
+                    forbidden_paths: vec![],
+                    required_paths: vec![],
+                    decimals: None,
+                    max_response_bytes: None,
+                    parse_mode: None,
+                    auth: None,
+                    enable_filter_expressions: false,
+                    skip_cache: false,
+                    json_aggregate: None,
+                    session_cookie: None,
+                    valid_status_codes: vec![200],
+                    signing_config: None,
+                    timeout_ms: None,
+                }),
+                100,
+                TimerId::default(),
+                EvmDestination {
+                    contract: H160::from_slice(&[contract; 20]),
+                    provider: Provider {
+                        chain_id: 1,
+                        endpoints: vec![String::from("https://example.com")],
+                    },
+                    chain_id: None,
+                    extra_destinations: vec![],
+                    value_wei: None,
+                },
+            );
+        }
 
-        let user_oracles = oracle_storage.get_oracles();
+        let crypto = String::from("crypto");
+        oracle_storage
+            .add_oracle_tag(alice.clone(), H160::from_slice(&[3; 20]), crypto.clone())
+            .unwrap();
+        // Adding a tag twice keeps a single copy
+        oracle_storage
+            .add_oracle_tag(alice.clone(), H160::from_slice(&[3; 20]), crypto.clone())
+            .unwrap();
+        oracle_storage
+            .add_oracle_tag(bob.clone(), H160::from_slice(&[5; 20]), crypto.clone())
+            .unwrap();
+        oracle_storage
+            .update_oracle_metadata(
+                alice.clone(),
+                H160::from_slice(&[4; 20]),
+                None,
+                UpdateOracleMetadata {
+                    tags: Some(vec![String::from("forex")]),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
 
-        assert_eq!(user_oracles.len(), 2);
+        let oracles = oracle_storage
+            .get_user_oracles_by_tag(alice.clone(), &crypto)
+            .unwrap();
+        assert_eq!(oracles.len(), 1);
+        assert_eq!(oracles[0].1.tags, vec![crypto.clone()]);
+
+        let oracles = oracle_storage.get_oracles_by_tag(&crypto);
+        assert_eq!(
+            oracles
+                .iter()
+                .map(|(user, contract, _)| (user.clone(), contract.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (alice.clone(), H160::from_slice(&[3; 20])),
+                (bob.clone(), H160::from_slice(&[5; 20])),
+            ]
+        );
+
+        oracle_storage
+            .remove_oracle_tag(bob, H160::from_slice(&[5; 20]), &crypto)
+            .unwrap();
+        assert_eq!(oracle_storage.get_oracles_by_tag(&crypto).len(), 1);
+        assert_eq!(oracle_storage.get_oracles_by_tag("forex").len(), 1);
+
+        for index in 0..MAX_ORACLE_TAGS - 1 {
+            oracle_storage
+                .add_oracle_tag(
+                    alice.clone(),
+                    H160::from_slice(&[3; 20]),
+                    format!("tag-{index}"),
+                )
+                .unwrap();
+        }
+        assert!(oracle_storage
+            .add_oracle_tag(alice, H160::from_slice(&[3; 20]), String::from("extra"))
+            .is_err());
     }
 
     #[test]
-    fn test_get_timer_id_by_address() {
+    fn test_record_execution() {
         let oracle_storage = OracleStorage::default();
 
         let user_address = H160::from_slice(&[1; 20]);
         let evm_contract_address = H160::from_slice(&[2; 20]);
 
-        let origin1 = Origin::Http(HttpOrigin {
+        let origin = Origin::Http(HttpOrigin {
             url: String::from("https://example.com"),
             json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
         });
-
-        let destination1 = EvmDestination {
+        let destination = EvmDestination {
             contract: evm_contract_address.clone(),
             provider: Provider {
                 chain_id: 1,
-                hostname: String::from("https://example.com"),
+                endpoints: vec![String::from("https://example.com")],
             },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
         };
 
-        let key: KeyData = serde_json::from_str(r#"{"idx":1,"version":1}"#).unwrap();
-
-        let timer = TimerId::from(key);
-
         oracle_storage.add_oracle(
             user_address.clone(),
-            origin1.clone(),
+            origin,
             100,
-            timer,
-            destination1.clone(),
+            TimerId::default(),
+            destination,
         );
 
-        let timer_id = oracle_storage
-            .get_timer_id_by_address(user_address, evm_contract_address)
+        // Without a limit the executions are only counted
+        assert_eq!(
+            oracle_storage
+                .record_execution(user_address.clone(), evm_contract_address.clone())
+                .unwrap(),
+            Some(false)
+        );
+
+        oracle_storage
+            .update_oracle_metadata(
+                user_address.clone(),
+                evm_contract_address.clone(),
+                None,
+                UpdateOracleMetadata {
+                    max_executions: Some(3),
+                    ..Default::default()
+                },
+            )
             .unwrap();
 
-        assert_eq!(timer_id, timer);
+        assert_eq!(
+            oracle_storage
+                .record_execution(user_address.clone(), evm_contract_address.clone())
+                .unwrap(),
+            Some(false)
+        );
+        assert_eq!(
+            oracle_storage
+                .record_execution(user_address.clone(), evm_contract_address.clone())
+                .unwrap(),
+            Some(true)
+        );
+        assert_eq!(
+            oracle_storage
+                .record_execution(user_address.clone(), evm_contract_address.clone())
+                .unwrap(),
+            None
+        );
+
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), evm_contract_address.clone())
+            .unwrap();
+        assert_eq!(metadata.execution_count, 3);
+        assert_eq!(metadata.max_executions, Some(3));
+
+        // Resetting the counter allows new executions
+        oracle_storage
+            .update_oracle_metadata(
+                user_address.clone(),
+                evm_contract_address.clone(),
+                None,
+                UpdateOracleMetadata {
+                    execution_count: Some(0),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            oracle_storage
+                .record_execution(user_address, evm_contract_address)
+                .unwrap(),
+            Some(false)
+        );
     }
 
     #[test]
-    fn test_oracle_addition_with_timer_id() {
-        let oracle_storage = OracleStorage::default();
-
+    fn test_metadata_collection_keeps_error_state() {
         let user_address = H160::from_slice(&[1; 20]);
-        let evm_contract_address = H160::from_slice(&[2; 20]);
+        let statuses = [
+            OracleStatus::Active,
+            OracleStatus::Paused,
+            OracleStatus::Error,
+            OracleStatus::CircuitOpen {
+                since: 1_000,
+                resets_at: 2_000,
+            },
+        ];
 
-        let origin1 = Origin::Http(HttpOrigin {
-            url: String::from("https://example.com"),
-            json_path: String::from("data"),
-        });
+        let mut collection = MetadataCollection::default();
+        for (i, status) in statuses.iter().enumerate() {
+            let contract_address = H160::from_slice(&[i as u8 + 2; 20]);
+            let mut metadata = StorableOracleMetadata::new(
+                user_address.clone(),
+                Origin::Http(HttpOrigin {
+                    url: String::from("https://example.com"),
+                    json_path: String::from("data"),
+                    json_paths: vec![],
+                    forbidden_paths: vec![],
+                    required_paths: vec![],
+                    decimals: None,
+                    max_response_bytes: None,
+                    parse_mode: None,
+                    auth: None,
+                    enable_filter_expressions: false,
+                    skip_cache: false,
+                    json_aggregate: None,
+                    session_cookie: None,
+                    valid_status_codes: vec![200],
+                    signing_config: None,
+                    timeout_ms: None,
+                }),
+                100,
+                TimerId::default(),
+                EvmDestination {
+                    contract: contract_address.clone(),
+                    provider: Provider {
+                        chain_id: 1,
+                        endpoints: vec![String::from("https://example.com")],
+                    },
+                    chain_id: None,
+                    extra_destinations: vec![],
+                    value_wei: None,
+                },
+            );
+            metadata.status = status.clone();
+            metadata.last_error = Some(update_error(&format!("error {i}")));
+            metadata.consecutive_errors = i as u32 + 1;
+            metadata.total_errors = i as u64 + 10;
+            metadata.last_attempt_at = i as u64 + 100;
+            metadata.last_updated_at = i as u64 + 50;
+
+            collection.0.insert(contract_address, metadata);
+        }
 
-        let destination1 = EvmDestination {
-            contract: evm_contract_address.clone(),
-            provider: Provider {
-                chain_id: 1,
-                hostname: String::from("https://example.com"),
-            },
+        // The status and error counters survive the encoding used across upgrades
+        let decoded = MetadataCollection::from_bytes(collection.to_bytes());
+
+        assert_eq!(decoded.0.len(), statuses.len());
+        for (i, status) in statuses.iter().enumerate() {
+            let metadata = decoded
+                .0
+                .get(&H160::from_slice(&[i as u8 + 2; 20]))
+                .unwrap();
+            assert_eq!(&metadata.status, status);
+            assert_eq!(
+                metadata.last_error,
+                Some(update_error(&format!("error {i}")))
+            );
+            assert_eq!(metadata.consecutive_errors, i as u32 + 1);
+            assert_eq!(metadata.total_errors, i as u64 + 10);
+            assert_eq!(metadata.last_attempt_at, i as u64 + 100);
+            assert_eq!(metadata.last_updated_at, i as u64 + 50);
+        }
+    }
+
+    #[test]
+    fn test_outlier_config() {
+        let config = OutlierConfig {
+            min_history: 5,
+            iqr_multiplier: 3.0,
         };
+        assert!(config.validate().is_ok());
 
-        let key: KeyData = serde_json::from_str(r#"{"idx":1,"version":1}"#).unwrap();
+        let history = [98u64, 100, 101, 99, 102, 100, 97, 103]
+            .into_iter()
+            .map(U256::from)
+            .collect::<VecDeque<_>>();
 
-        let timer = TimerId::from(key);
+        // Prices close to the history are accepted
+        assert!(config.check(&U256::from(100u64), &history).is_ok());
+        assert!(config.check(&U256::from(106u64), &history).is_ok());
 
-        oracle_storage.add_oracle(
-            user_address.clone(),
-            origin1.clone(),
-            100,
-            timer,
-            destination1.clone(),
+        // A price 10x the median is rejected, as is a sudden drop
+        assert_eq!(
+            config.check(&U256::from(1_000u64), &history),
+            Err(Error::PriceOutlier(U256::from(1_000u64)))
         );
+        assert!(config.check(&U256::from(10u64), &history).is_err());
 
-        let oracle_metadata = oracle_storage
-            .get_timer_id_by_address(user_address, evm_contract_address)
-            .unwrap();
+        // The prices are not checked until the history is long enough
+        let short_history = history.iter().take(4).cloned().collect();
+        assert!(config.check(&U256::from(1_000u64), &short_history).is_ok());
 
-        assert_eq!(oracle_metadata, timer);
+        for invalid in [
+            OutlierConfig {
+                min_history: 3,
+                iqr_multiplier: 3.0,
+            },
+            OutlierConfig {
+                min_history: 5,
+                iqr_multiplier: 0.0,
+            },
+            OutlierConfig {
+                min_history: 5,
+                iqr_multiplier: f64::NAN,
+            },
+        ] {
+            assert!(invalid.validate().is_err());
+        }
     }
 
     #[test]
-    fn test_get_oracle_owner() {
+    fn test_price_history_and_rejected_prices() {
         let oracle_storage = OracleStorage::default();
 
         let user_address = H160::from_slice(&[1; 20]);
-        let user_address2 = H160::from_slice(&[2; 20]);
         let evm_contract_address = H160::from_slice(&[2; 20]);
 
-        let origin1 = Origin::Http(HttpOrigin {
+        let origin = Origin::Http(HttpOrigin {
             url: String::from("https://example.com"),
             json_path: String::from("data"),
+            json_paths: vec![],
+            forbidden_paths: vec![],
+            required_paths: vec![],
+            decimals: None,
+            max_response_bytes: None,
+            parse_mode: None,
+            auth: None,
+            enable_filter_expressions: false,
+            skip_cache: false,
+            json_aggregate: None,
+            session_cookie: None,
+            valid_status_codes: vec![200],
+            signing_config: None,
+            timeout_ms: None,
         });
-
-        let destination1 = EvmDestination {
+        let destination = EvmDestination {
             contract: evm_contract_address.clone(),
             provider: Provider {
                 chain_id: 1,
-                hostname: String::from("https://example.com"),
+                endpoints: vec![String::from("https://example.com")],
             },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
         };
 
         oracle_storage.add_oracle(
             user_address.clone(),
-            origin1.clone(),
+            origin,
             100,
             TimerId::default(),
-            destination1.clone(),
+            destination,
         );
 
-        let owner = oracle_storage
-            .get_oracle_owner(user_address.clone(), evm_contract_address.clone())
+        // The history keeps the most recent prices
+        for price in 0..MAX_PRICE_HISTORY as u64 + 3 {
+            oracle_storage
+                .record_update_success(
+                    user_address.clone(),
+                    evm_contract_address.clone(),
+                    U256::from(price),
+                    price,
+                )
+                .unwrap();
+        }
+
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), evm_contract_address.clone())
             .unwrap();
+        assert_eq!(metadata.price_history.len(), MAX_PRICE_HISTORY);
+        assert_eq!(metadata.price_history.front(), Some(&U256::from(3u64)));
 
-        assert_eq!(owner, user_address);
+        oracle_storage
+            .record_update_failure(
+                user_address.clone(),
+                evm_contract_address.clone(),
+                update_error("origin is down"),
+                0,
+            )
+            .unwrap();
 
-        let owner = oracle_storage
-            .get_oracle_owner(user_address2, evm_contract_address)
-            .unwrap_err();
+        // A rejected price is a failed update, but not a consecutive error
+        let status = oracle_storage
+            .record_skipped_update(
+                user_address.clone(),
+                evm_contract_address.clone(),
+                PipelineError::new(
+                    OracleStage::DataParse,
+                    Error::PriceOutlier(U256::from(1u64)),
+                ),
+                OracleSkipReason::PriceOutlier(U256::from(1u64)),
+            )
+            .unwrap();
+        assert_eq!(status, OracleStatus::Active);
 
-        assert_eq!(owner, Error::UserNotFound);
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address, evm_contract_address)
+            .unwrap();
+        assert_eq!(metadata.consecutive_errors, 1);
+        assert_eq!(metadata.total_errors, 2);
+        assert_eq!(
+            metadata.last_error.map(|error| error.stage),
+            Some(OracleStage::DataParse)
+        );
+        assert_eq!(
+            metadata.last_skip_reason,
+            Some(OracleSkipReason::PriceOutlier(U256::from(1u64)))
+        );
+    }
+
+    #[test]
+    fn test_prune_price_history() {
+        let oracle_storage = OracleStorage::default();
+        oracle_storage.clear();
+
+        let user_address = H160::from_slice(&[1; 20]);
+        let contract_addresses = [H160::from_slice(&[2; 20]), H160::from_slice(&[3; 20])];
+
+        for evm_contract_address in &contract_addresses {
+            let origin = Origin::Http(HttpOrigin {
+                url: String::from("https://example.com"),
+                json_path: String::from("data"),
+                json_paths: vec![],
+                forbidden_paths: vec![],
+                required_paths: vec![],
+                decimals: None,
+                max_response_bytes: None,
+                parse_mode: None,
+                auth: None,
+                enable_filter_expressions: false,
+                skip_cache: false,
+                json_aggregate: None,
+                session_cookie: None,
+                valid_status_codes: vec![200],
+                signing_config: None,
+                timeout_ms: None,
+            });
+            let destination = EvmDestination {
+                contract: evm_contract_address.clone(),
+                provider: Provider {
+                    chain_id: 1,
+                    endpoints: vec![String::from("https://example.com")],
+                },
+                chain_id: None,
+                extra_destinations: vec![],
+                value_wei: None,
+            };
+
+            oracle_storage.add_oracle(
+                user_address.clone(),
+                origin,
+                100,
+                TimerId::default(),
+                destination,
+            );
+
+            for price in 0..10u64 {
+                oracle_storage
+                    .record_update_success(
+                        user_address.clone(),
+                        evm_contract_address.clone(),
+                        U256::from(price),
+                        price,
+                    )
+                    .unwrap();
+            }
+        }
+
+        assert_eq!(
+            oracle_storage.prune_price_history(
+                user_address.clone(),
+                contract_addresses[0].clone(),
+                4
+            ),
+            Ok(6)
+        );
+        assert_eq!(
+            oracle_storage.prune_price_history(user_address.clone(), H160::from_slice(&[4; 20]), 4),
+            Err(Error::OracleNotFound)
+        );
+
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address.clone(), contract_addresses[0].clone())
+            .unwrap();
+        assert_eq!(
+            metadata.price_history,
+            (6..10u64).map(U256::from).collect::<VecDeque<_>>()
+        );
+
+        // Only the histories longer than the kept entries are pruned
+        assert_eq!(oracle_storage.prune_all_price_histories(5), 5);
+        assert_eq!(oracle_storage.prune_all_price_histories(5), 0);
+        assert_eq!(oracle_storage.prune_all_price_histories(0), 9);
+
+        // The history is capped by the maximum number of entries of the oracle
+        oracle_storage
+            .update_oracle_metadata(
+                user_address.clone(),
+                contract_addresses[1].clone(),
+                None,
+                UpdateOracleMetadata {
+                    max_history_entries: Some(2),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        for price in 0..5u64 {
+            oracle_storage
+                .record_update_success(
+                    user_address.clone(),
+                    contract_addresses[1].clone(),
+                    U256::from(price),
+                    price,
+                )
+                .unwrap();
+        }
+
+        let metadata = oracle_storage
+            .get_oracle_by_address(user_address, contract_addresses[1].clone())
+            .unwrap();
+        assert_eq!(
+            metadata.price_history,
+            VecDeque::from([U256::from(3u64), U256::from(4u64)])
+        );
+    }
+
+    #[test]
+    fn test_response_schema() {
+        let response = serde_json::json!({
+            "status": "ok",
+            "code": 200,
+            "data": { "amount": "42.5", "items": [] },
+        });
+
+        assert!(ResponseSchema::RequireField("data.amount".to_string())
+            .check(&response)
+            .is_ok());
+        assert_eq!(
+            ResponseSchema::RequireField("data.price".to_string()).check(&response),
+            Err(Error::SchemaValidationFailed(
+                "missing field 'data.price'".to_string()
+            ))
+        );
+
+        assert!(ResponseSchema::ForbidField("error".to_string())
+            .check(&response)
+            .is_ok());
+        assert_eq!(
+            ResponseSchema::ForbidField("data".to_string()).check(&response),
+            Err(Error::SchemaValidationFailed(
+                "forbidden field 'data' found".to_string()
+            ))
+        );
+
+        assert!(ResponseSchema::RequireStatus("ok".to_string())
+            .check(&response)
+            .is_ok());
+        assert_eq!(
+            ResponseSchema::RequireStatus("success".to_string()).check(&response),
+            Err(Error::SchemaValidationFailed(
+                "expected status 'success', got \"ok\"".to_string()
+            ))
+        );
+        assert!(ResponseSchema::RequireStatus("200".to_string())
+            .check(&serde_json::json!({ "status": 200 }))
+            .is_ok());
+
+        let require_type = |path: &str, expected_type| ResponseSchema::RequireType {
+            path: path.to_string(),
+            expected_type,
+        };
+        assert!(require_type("data.amount", JsonType::String)
+            .check(&response)
+            .is_ok());
+        assert!(require_type("data.items", JsonType::Array)
+            .check(&response)
+            .is_ok());
+        assert_eq!(
+            require_type("code", JsonType::String).check(&response),
+            Err(Error::SchemaValidationFailed(
+                "field 'code' is Number, expected String".to_string()
+            ))
+        );
+        assert_eq!(
+            require_type("data.price", JsonType::Number).check(&response),
+            Err(Error::SchemaValidationFailed(
+                "missing field 'data.price'".to_string()
+            ))
+        );
     }
 }
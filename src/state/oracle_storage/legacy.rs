@@ -0,0 +1,256 @@
+//! Layout of the oracle metadata stored by the first release of the canister
+
+use std::collections::BTreeMap;
+
+use did::H160;
+use ic_exports::ic_cdk_timers::TimerId;
+use serde::{Deserialize, Serialize};
+
+use super::{MetadataCollection, StorableOracleMetadata};
+use crate::canister::{EvmDestination, EvmOrigin, HttpOrigin, Origin};
+use crate::constants::DEFAULT_VALID_STATUS_CODES;
+use crate::provider::Provider;
+
+#[derive(Serialize, Deserialize)]
+pub struct LegacyMetadataCollection(BTreeMap<H160, LegacyOracleMetadata>);
+
+impl From<LegacyMetadataCollection> for MetadataCollection {
+    fn from(legacy: LegacyMetadataCollection) -> Self {
+        Self(
+            legacy
+                .0
+                .into_iter()
+                .map(|(contract, metadata)| (contract, metadata.into()))
+                .collect(),
+        )
+    }
+}
+
+/// Oracle metadata as stored before the oracles kept any runtime state
+#[derive(Serialize, Deserialize)]
+struct LegacyOracleMetadata {
+    origin: LegacyOrigin,
+    timer_interval: u64,
+    timer_id: TimerId,
+    evm: LegacyEvmDestination,
+    owner: H160,
+}
+
+impl From<LegacyOracleMetadata> for StorableOracleMetadata {
+    fn from(legacy: LegacyOracleMetadata) -> Self {
+        StorableOracleMetadata::new(
+            legacy.owner,
+            legacy.origin.into(),
+            legacy.timer_interval,
+            legacy.timer_id,
+            legacy.evm.into(),
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum LegacyOrigin {
+    Evm(LegacyEvmOrigin),
+    Http(LegacyHttpOrigin),
+}
+
+impl From<LegacyOrigin> for Origin {
+    fn from(legacy: LegacyOrigin) -> Self {
+        match legacy {
+            LegacyOrigin::Evm(origin) => Origin::Evm(EvmOrigin {
+                provider: origin.provider.into(),
+                target_address: origin.target_address,
+                method: origin.method,
+                return_types: vec![],
+                return_value_index: 0,
+            }),
+            LegacyOrigin::Http(origin) => Origin::Http(HttpOrigin {
+                url: origin.url,
+                json_path: origin.json_path,
+                json_paths: vec![],
+                forbidden_paths: vec![],
+                required_paths: vec![],
+                decimals: None,
+                max_response_bytes: None,
+                parse_mode: None,
+                auth: None,
+                enable_filter_expressions: false,
+                skip_cache: false,
+                json_aggregate: None,
+                session_cookie: None,
+                valid_status_codes: DEFAULT_VALID_STATUS_CODES.to_vec(),
+                signing_config: None,
+                timeout_ms: None,
+            }),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct LegacyHttpOrigin {
+    url: String,
+    json_path: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LegacyEvmOrigin {
+    provider: LegacyProvider,
+    target_address: H160,
+    method: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LegacyEvmDestination {
+    contract: H160,
+    provider: LegacyProvider,
+}
+
+impl From<LegacyEvmDestination> for EvmDestination {
+    fn from(legacy: LegacyEvmDestination) -> Self {
+        Self {
+            contract: legacy.contract,
+            provider: legacy.provider.into(),
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct LegacyProvider {
+    chain_id: u64,
+    hostname: String,
+}
+
+impl From<LegacyProvider> for Provider {
+    fn from(legacy: LegacyProvider) -> Self {
+        Self {
+            chain_id: legacy.chain_id,
+            endpoints: vec![legacy.hostname],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use ic_stable_structures::Storable;
+
+    use super::*;
+    use crate::constants::DEFAULT_ERROR_THRESHOLD;
+    use crate::state::oracle_storage::{
+        OracleStatus, VersionedMetadataCollection, VERSIONED_COLLECTION_MARKER,
+    };
+
+    /// Builds a collection with the fields stored by the first release: an
+    /// HTTP and an EVM oracle whose providers only had a hostname
+    fn baseline_collection() -> LegacyMetadataCollection {
+        let provider = || LegacyProvider {
+            chain_id: 1,
+            hostname: String::from("https://example.com"),
+        };
+
+        LegacyMetadataCollection(BTreeMap::from([
+            (
+                H160::from_slice(&[2; 20]),
+                LegacyOracleMetadata {
+                    origin: LegacyOrigin::Http(LegacyHttpOrigin {
+                        url: String::from("https://example.com"),
+                        json_path: String::from("data"),
+                    }),
+                    timer_interval: 100,
+                    timer_id: TimerId::default(),
+                    evm: LegacyEvmDestination {
+                        contract: H160::from_slice(&[2; 20]),
+                        provider: provider(),
+                    },
+                    owner: H160::from_slice(&[1; 20]),
+                },
+            ),
+            (
+                H160::from_slice(&[4; 20]),
+                LegacyOracleMetadata {
+                    origin: LegacyOrigin::Evm(LegacyEvmOrigin {
+                        provider: provider(),
+                        target_address: H160::from_slice(&[3; 20]),
+                        method: String::from("latestAnswer"),
+                    }),
+                    timer_interval: 200,
+                    timer_id: TimerId::default(),
+                    evm: LegacyEvmDestination {
+                        contract: H160::from_slice(&[4; 20]),
+                        provider: provider(),
+                    },
+                    owner: H160::from_slice(&[1; 20]),
+                },
+            ),
+        ]))
+    }
+
+    #[test]
+    fn test_decode_baseline_metadata_collection() {
+        let bytes = did::codec::bincode_encode(&baseline_collection());
+        let collection = MetadataCollection::from_bytes(Cow::Owned(bytes));
+
+        assert_eq!(collection.0.len(), 2);
+        let expected_provider = Provider {
+            chain_id: 1,
+            endpoints: vec![String::from("https://example.com")],
+        };
+
+        let http = collection.0.get(&H160::from_slice(&[2; 20])).unwrap();
+        assert!(matches!(
+            &http.origin,
+            Origin::Http(origin) if origin.url == "https://example.com" && origin.json_path == "data"
+        ));
+        assert_eq!(http.evm.provider, expected_provider);
+        assert_eq!(http.timer_interval, 100);
+        assert_eq!(http.owner, H160::from_slice(&[1; 20]));
+        assert_eq!(http.status, OracleStatus::Active);
+        assert_eq!(http.error_threshold, DEFAULT_ERROR_THRESHOLD);
+
+        let evm = collection.0.get(&H160::from_slice(&[4; 20])).unwrap();
+        assert!(matches!(&evm.origin, Origin::Evm(origin) if origin.provider == expected_provider));
+        assert_eq!(evm.evm.provider, expected_provider);
+        assert_eq!(evm.timer_interval, 200);
+    }
+
+    #[test]
+    fn test_current_metadata_collection_roundtrip() {
+        let collection: MetadataCollection = baseline_collection().into();
+
+        let decoded = MetadataCollection::from_bytes(collection.to_bytes());
+
+        assert_eq!(decoded.0.len(), 2);
+        assert_eq!(
+            decoded
+                .0
+                .get(&H160::from_slice(&[4; 20]))
+                .unwrap()
+                .timer_interval,
+            200
+        );
+
+        // The collections stored before the versioned layout are still decoded
+        let unversioned = did::codec::bincode_encode(&collection);
+        let decoded = MetadataCollection::from_bytes(Cow::Owned(unversioned));
+        assert_eq!(decoded.0.len(), 2);
+    }
+
+    #[test]
+    fn test_decode_versioned_baseline_metadata_collection() {
+        let mut bytes = VERSIONED_COLLECTION_MARKER.to_vec();
+        bytes.extend(did::codec::bincode_encode(
+            &VersionedMetadataCollection::V1(baseline_collection()),
+        ));
+
+        let collection = MetadataCollection::from_bytes(Cow::Owned(bytes));
+
+        assert_eq!(collection.0.len(), 2);
+        let http = collection.0.get(&H160::from_slice(&[2; 20])).unwrap();
+        assert_eq!(http.timer_interval, 100);
+        assert_eq!(http.status, OracleStatus::Active);
+    }
+}
@@ -0,0 +1,84 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use did::H160;
+
+/// Number of oracles of each user reserved by the batch creations in progress
+///
+/// A batch reserves its oracles before its first `await`, so that the oracles
+/// created by concurrent calls are counted against the same limits. The counters
+/// live in the heap memory, so they are reset on canister upgrade.
+#[derive(Debug, Default, Clone)]
+pub struct PendingCreations {}
+
+impl PendingCreations {
+    /// Returns the number of oracles reserved for the user
+    pub fn count(&self, user_address: &H160) -> u64 {
+        PENDING_CREATIONS.with(|pending| {
+            pending
+                .borrow()
+                .get(user_address)
+                .copied()
+                .unwrap_or_default()
+        })
+    }
+
+    /// Reserves `count` oracles for the user
+    pub fn reserve(&self, user_address: H160, count: u64) {
+        PENDING_CREATIONS.with(|pending| {
+            *pending.borrow_mut().entry(user_address).or_default() += count;
+        });
+    }
+
+    /// Releases `count` oracles reserved with `reserve`
+    pub fn release(&self, user_address: &H160, count: u64) {
+        PENDING_CREATIONS.with(|pending| {
+            let mut pending = pending.borrow_mut();
+            let Some(reserved) = pending.get_mut(user_address) else {
+                return;
+            };
+
+            *reserved = reserved.saturating_sub(count);
+            if *reserved == 0 {
+                pending.remove(user_address);
+            }
+        });
+    }
+
+    pub fn clear(&self) {
+        PENDING_CREATIONS.with(|pending| pending.borrow_mut().clear());
+    }
+}
+
+thread_local! {
+    static PENDING_CREATIONS: RefCell<BTreeMap<H160, u64>> = RefCell::new(BTreeMap::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending_creations() {
+        let pending_creations = PendingCreations::default();
+        pending_creations.clear();
+
+        let alice = H160::from_slice(&[1; 20]);
+        let bob = H160::from_slice(&[2; 20]);
+
+        assert_eq!(pending_creations.count(&alice), 0);
+
+        pending_creations.reserve(alice.clone(), 3);
+        pending_creations.reserve(alice.clone(), 2);
+        pending_creations.reserve(bob.clone(), 1);
+        assert_eq!(pending_creations.count(&alice), 5);
+        assert_eq!(pending_creations.count(&bob), 1);
+
+        pending_creations.release(&alice, 4);
+        assert_eq!(pending_creations.count(&alice), 1);
+
+        pending_creations.release(&alice, 2);
+        assert_eq!(pending_creations.count(&alice), 0);
+        assert_eq!(pending_creations.count(&bob), 1);
+    }
+}
@@ -0,0 +1,69 @@
+use std::cell::RefCell;
+
+use did::H160;
+use ic_stable_structures::{BTreeMapStructure, StableBTreeMap};
+
+use crate::memory::{MemoryType, MEMORY_MANAGER, PROOF_NONCES_MEMORY_ID};
+
+/// Last nonce used by the signature proofs of each address
+///
+/// The nonces of an address only increase, so a proof cannot be used twice. They
+/// are kept in the stable memory, so that the proofs cannot be replayed after an
+/// upgrade.
+#[derive(Debug, Default, Clone)]
+pub struct ProofNonces {}
+
+impl ProofNonces {
+    /// Returns the last nonce used by the proofs of the address
+    pub fn last(&self, address: &H160) -> Option<u64> {
+        PROOF_NONCES.with(|nonces| nonces.borrow().get(address))
+    }
+
+    /// Records the nonce of a proof of the address, returns `false` if it is not
+    /// greater than the last used one
+    pub fn try_use(&self, address: H160, nonce: u64) -> bool {
+        PROOF_NONCES.with(|nonces| {
+            let mut nonces = nonces.borrow_mut();
+            if nonces.get(&address).is_some_and(|last| nonce <= last) {
+                return false;
+            }
+
+            nonces.insert(address, nonce);
+            true
+        })
+    }
+
+    pub fn clear(&self) {
+        PROOF_NONCES.with(|nonces| nonces.borrow_mut().clear());
+    }
+}
+
+thread_local! {
+    static PROOF_NONCES: RefCell<StableBTreeMap<H160, u64, MemoryType>> = RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm|mm.get(PROOF_NONCES_MEMORY_ID))));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proof_nonces() {
+        let proof_nonces = ProofNonces::default();
+        proof_nonces.clear();
+
+        let alice = H160::from_slice(&[1; 20]);
+        let bob = H160::from_slice(&[2; 20]);
+
+        assert_eq!(proof_nonces.last(&alice), None);
+
+        assert!(proof_nonces.try_use(alice.clone(), 1));
+        assert!(!proof_nonces.try_use(alice.clone(), 1));
+        assert!(proof_nonces.try_use(alice.clone(), 5));
+        assert!(!proof_nonces.try_use(alice.clone(), 3));
+        assert_eq!(proof_nonces.last(&alice), Some(5));
+
+        // The nonces of each address are independent
+        assert!(proof_nonces.try_use(bob.clone(), 1));
+        assert_eq!(proof_nonces.last(&bob), Some(1));
+    }
+}
@@ -0,0 +1,70 @@
+use std::cell::RefCell;
+
+use ic_stable_structures::{BTreeMapStructure, StableBTreeMap};
+
+use crate::error::{Error, Result};
+use crate::memory::{MemoryType, MEMORY_MANAGER, PROVIDER_CREDENTIALS_MEMORY_ID};
+
+/// API keys of the providers, keyed by chain id
+///
+/// The keys are interpolated in the provider endpoints when calling them and
+/// are never returned by the canister.
+#[derive(Debug, Default, Clone)]
+pub struct ProviderCredentials {}
+
+impl ProviderCredentials {
+    /// Returns the API key of the chain
+    pub fn get(&self, chain_id: u64) -> Option<String> {
+        PROVIDER_CREDENTIALS.with(|credentials| credentials.borrow().get(&chain_id))
+    }
+
+    /// Sets the API key of the chain, replacing the previous one
+    pub fn insert(&self, chain_id: u64, api_key: String) {
+        PROVIDER_CREDENTIALS.with(|credentials| {
+            credentials.borrow_mut().insert(chain_id, api_key);
+        });
+    }
+
+    /// Removes the API key of the chain
+    pub fn remove(&self, chain_id: u64) -> Result<()> {
+        PROVIDER_CREDENTIALS.with(|credentials| {
+            credentials
+                .borrow_mut()
+                .remove(&chain_id)
+                .map(|_| ())
+                .ok_or_else(|| Error::Internal(format!("no api key for chain {chain_id}")))
+        })
+    }
+
+    pub fn clear(&self) {
+        PROVIDER_CREDENTIALS.with(|credentials| credentials.borrow_mut().clear());
+    }
+}
+
+thread_local! {
+    static PROVIDER_CREDENTIALS: RefCell<StableBTreeMap<u64, String, MemoryType>> = RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm|mm.get(PROVIDER_CREDENTIALS_MEMORY_ID))));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_credentials() {
+        let credentials = ProviderCredentials::default();
+        credentials.clear();
+
+        assert_eq!(credentials.get(1), None);
+
+        credentials.insert(1, String::from("key"));
+        assert_eq!(credentials.get(1), Some(String::from("key")));
+        assert_eq!(credentials.get(2), None);
+
+        credentials.insert(1, String::from("new-key"));
+        assert_eq!(credentials.get(1), Some(String::from("new-key")));
+
+        credentials.remove(1).unwrap();
+        assert_eq!(credentials.get(1), None);
+        assert!(credentials.remove(1).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+use did::codec;
+use ic_stable_structures::{BTreeMapStructure, Bound, StableBTreeMap, Storable};
+
+use crate::error::{Error, Result};
+use crate::memory::{MemoryType, MEMORY_MANAGER, PROVIDER_REGISTRY_MEMORY_ID};
+use crate::provider::Provider;
+
+/// Providers registered by the canister owner, keyed by chain id
+#[derive(Debug, Default, Clone)]
+pub struct ProviderRegistry {}
+
+impl ProviderRegistry {
+    /// Returns the provider registered for the chain
+    pub fn get(&self, chain_id: u64) -> Result<Provider> {
+        PROVIDER_REGISTRY.with(|registry| {
+            registry
+                .borrow()
+                .get(&chain_id)
+                .ok_or(Error::ProviderNotFound(chain_id))
+        })
+    }
+
+    /// Registers the provider of its chain, replacing the previous one
+    pub fn insert(&self, provider: Provider) {
+        PROVIDER_REGISTRY.with(|registry| {
+            registry.borrow_mut().insert(provider.chain_id, provider);
+        });
+    }
+
+    /// Returns the registered providers, ordered by chain id
+    pub fn list(&self) -> Vec<Provider> {
+        PROVIDER_REGISTRY.with(|registry| {
+            registry
+                .borrow()
+                .iter()
+                .map(|(_, provider)| provider)
+                .collect()
+        })
+    }
+
+    pub fn clear(&self) {
+        PROVIDER_REGISTRY.with(|registry| registry.borrow_mut().clear());
+    }
+}
+
+impl Storable for Provider {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        codec::encode(&self).into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        codec::decode(&bytes)
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+thread_local! {
+    static PROVIDER_REGISTRY: RefCell<StableBTreeMap<u64, Provider, MemoryType>> = RefCell::new(StableBTreeMap::new(MEMORY_MANAGER.with(|mm|mm.get(PROVIDER_REGISTRY_MEMORY_ID))));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_registry() {
+        let registry = ProviderRegistry::default();
+        registry.clear();
+
+        assert_eq!(registry.get(1), Err(Error::ProviderNotFound(1)));
+
+        let provider = Provider {
+            chain_id: 1,
+            endpoints: vec![String::from("https://example.com")],
+        };
+        registry.insert(provider.clone());
+        assert_eq!(registry.get(1).unwrap(), provider);
+        assert_eq!(registry.get(2), Err(Error::ProviderNotFound(2)));
+
+        let new_provider = Provider {
+            chain_id: 1,
+            endpoints: vec![String::from("https://new.example.com")],
+        };
+        registry.insert(new_provider.clone());
+        assert_eq!(registry.get(1).unwrap(), new_provider);
+
+        let other_provider = Provider {
+            chain_id: 2,
+            endpoints: vec![String::from("https://other.example.com")],
+        };
+        registry.insert(other_provider.clone());
+        assert_eq!(registry.list(), vec![new_provider, other_provider]);
+    }
+}
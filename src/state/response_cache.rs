@@ -0,0 +1,71 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+/// Cache of the HTTP origin responses, keyed by URL
+///
+/// The responses are cached for the `max-age` of their `Cache-Control` header.
+/// The cache lives in the heap memory, so it is lost on canister upgrade.
+#[derive(Debug, Default, Clone)]
+pub struct ResponseCache {}
+
+impl ResponseCache {
+    /// Returns the cached body of the URL, unless it expired at `now`
+    pub fn get(&self, url: &str, now: u64) -> Option<Vec<u8>> {
+        RESPONSE_CACHE.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            let entry = cache.get(url)?;
+            if entry.expires_at <= now {
+                cache.remove(url);
+                return None;
+            }
+
+            Some(entry.body.clone())
+        })
+    }
+
+    /// Caches the body of the URL until `expires_at`
+    pub fn insert(&self, url: String, body: Vec<u8>, expires_at: u64) {
+        RESPONSE_CACHE.with(|cache| {
+            cache
+                .borrow_mut()
+                .insert(url, CachedResponse { body, expires_at });
+        });
+    }
+
+    pub fn clear(&self) {
+        RESPONSE_CACHE.with(|cache| cache.borrow_mut().clear());
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedResponse {
+    body: Vec<u8>,
+    /// IC time in nanoseconds after which the response is fetched again
+    expires_at: u64,
+}
+
+thread_local! {
+    static RESPONSE_CACHE: RefCell<BTreeMap<String, CachedResponse>> = RefCell::new(BTreeMap::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_cache() {
+        let cache = ResponseCache::default();
+        cache.clear();
+
+        let url = "https://example.com/price";
+        assert_eq!(cache.get(url, 0), None);
+
+        cache.insert(url.to_string(), b"{\"price\":1}".to_vec(), 100);
+        assert_eq!(cache.get(url, 99), Some(b"{\"price\":1}".to_vec()));
+        assert_eq!(cache.get("https://example.com/other", 99), None);
+
+        // Expired responses are dropped
+        assert_eq!(cache.get(url, 100), None);
+        assert_eq!(cache.get(url, 0), None);
+    }
+}
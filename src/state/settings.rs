@@ -3,6 +3,7 @@ use std::cell::RefCell;
 
 use candid::{CandidType, Principal};
 use did::codec;
+use eth_signer::ic_sign::SigningKeyId;
 use ic_stable_structures::{Bound, CellStructure, StableCell, Storable};
 use serde::{Deserialize, Serialize};
 
@@ -11,19 +12,86 @@ use crate::memory::{MemoryType, MEMORY_MANAGER, SETTINGS_MEMORY_ID};
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
 pub struct Settings {
     pub owner: Principal,
+    /// Principals allowed to manage the canister on behalf of the owner;
+    /// `None` if no admin was ever added
+    pub admins: Option<Vec<Principal>>,
+    /// URL prefixes allowed for HTTP origins; `None` allows any URL
+    pub url_allowlist: Option<Vec<String>>,
+    /// Maximum delay in seconds used to stagger the oracle timers restored after an upgrade;
+    /// `None` uses `DEFAULT_MAX_STARTUP_JITTER_SECS`
+    pub max_startup_jitter_secs: Option<u64>,
+    /// Maximum rate of oracle creations per user; `None` disables the rate limit
+    pub oracle_creation_rate_limit: Option<RateLimit>,
+    /// Threshold ECDSA key used by the oracle signers; `None` uses the default one
+    pub signer_config: Option<OracleSignerConfig>,
+    /// Whether the chain id of the destination providers is not checked on oracle
+    /// creation; `None` checks it
+    pub skip_chain_id_validation: Option<bool>,
+    /// Maximum number of oracle updates run at once by the timers;
+    /// `None` uses `DEFAULT_MAX_CONCURRENT_UPDATES`
+    pub max_concurrent_updates: Option<u32>,
+    /// Components prepended to the derivation path of every oracle signer;
+    /// `None` prepends nothing
+    pub derivation_prefix: Option<Vec<Vec<u8>>>,
+    /// Stable memory size in 64 KiB pages above which a warning is logged on
+    /// oracle creation; `None` disables the warning
+    pub stable_memory_alert_pages: Option<u64>,
+}
+
+/// Threshold ECDSA key and derivation path used by the oracle signers
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct OracleSignerConfig {
+    pub key_id: SigningKeyId,
+    /// Components appended to the derivation path of each oracle signer
+    pub derivation_suffix: Vec<Vec<u8>>,
+}
+
+impl Default for OracleSignerConfig {
+    fn default() -> Self {
+        Self {
+            key_id: SigningKeyId::Dfx,
+            derivation_suffix: vec![],
+        }
+    }
+}
+
+/// Maximum number of oracles a user can create in an hour
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Eq)]
+pub struct RateLimit {
+    pub max_per_hour: u32,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
             owner: Principal::management_canister(),
+            admins: None,
+            url_allowlist: None,
+            max_startup_jitter_secs: None,
+            oracle_creation_rate_limit: None,
+            signer_config: None,
+            skip_chain_id_validation: None,
+            max_concurrent_updates: None,
+            derivation_prefix: None,
+            stable_memory_alert_pages: None,
         }
     }
 }
 
 impl Settings {
     pub fn new(owner: Principal) -> Self {
-        Self { owner }
+        Self {
+            owner,
+            admins: None,
+            url_allowlist: None,
+            max_startup_jitter_secs: None,
+            oracle_creation_rate_limit: None,
+            signer_config: None,
+            skip_chain_id_validation: None,
+            max_concurrent_updates: None,
+            derivation_prefix: None,
+            stable_memory_alert_pages: None,
+        }
     }
 
     pub fn read<F, T>(f: F) -> T
@@ -57,10 +125,7 @@ impl Storable for Settings {
         codec::decode(&bytes)
     }
 
-    const BOUND: ic_stable_structures::Bound = Bound::Bounded {
-        max_size: 55,
-        is_fixed_size: true,
-    };
+    const BOUND: ic_stable_structures::Bound = Bound::Unbounded;
 }
 
 thread_local! {
@@ -68,3 +133,28 @@ thread_local! {
         RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(SETTINGS_MEMORY_ID)), Settings::default()).expect("failed to initialize settings"))
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Settings as stored by the first release of the canister
+    #[derive(Serialize, CandidType)]
+    struct BaselineSettings {
+        owner: Principal,
+    }
+
+    #[test]
+    fn test_decode_baseline_settings() {
+        let owner = Principal::from_slice(&[1; 29]);
+        let bytes = codec::encode(&BaselineSettings { owner });
+
+        let settings = Settings::from_bytes(Cow::Owned(bytes));
+
+        assert_eq!(settings.owner, owner);
+        assert_eq!(settings.admins, None);
+        assert_eq!(settings.url_allowlist, None);
+        assert_eq!(settings.max_startup_jitter_secs, None);
+        assert_eq!(settings.max_concurrent_updates, None);
+    }
+}
@@ -7,28 +7,74 @@ use eth_signer::sign_strategy::{IcSigner, TransactionSigner};
 use ethers_core::types::transaction::eip2718::TypedTransaction;
 use serde::Deserialize;
 
+use super::settings::{OracleSignerConfig, Settings};
+
 /// A component that provides the access to the signer
 #[derive(Debug, Default, Clone)]
 pub struct SignerInfo;
 
 impl SignerInfo {
     pub fn get_oracle_signer(&self, user_address: H160) -> impl TransactionSigner {
-        OracleSigner::new(user_address)
+        self.get_rotated_oracle_signer(user_address, 0, &[])
+    }
+
+    /// Returns the signer of an oracle of the user after `rotation_count` key
+    /// rotations, with the derivation suffix of the oracle
+    pub fn get_rotated_oracle_signer(
+        &self,
+        user_address: H160,
+        rotation_count: u32,
+        derivation_suffix: &[Vec<u8>],
+    ) -> impl TransactionSigner {
+        let (config, prefix) = Settings::read(|s| {
+            (
+                s.signer_config.clone().unwrap_or_default(),
+                s.derivation_prefix.clone().unwrap_or_default(),
+            )
+        });
+
+        OracleSigner::new(
+            user_address,
+            rotation_count,
+            derivation_suffix,
+            &prefix,
+            &config,
+        )
     }
 }
 
 #[derive(CandidType, Clone, Deserialize, Debug)]
 pub struct OracleSigner {
-    pub(super) key_id: SigningKeyId,
-    pub(super) derivation_path: DerivationPath,
+    pub key_id: SigningKeyId,
+    pub derivation_path: DerivationPath,
 }
 
 impl OracleSigner {
-    fn new(address: H160) -> Self {
-        let address_to_bytes = address.0.as_bytes().to_vec();
+    /// Creates the signer of the address after `rotation_count` key rotations,
+    /// using the key and derivation suffix of the config
+    ///
+    /// The derivation path is `prefix`, the address, the rotation count, the suffix of
+    /// the config and the suffix of the oracle. Without rotation, the rotation count is
+    /// left out of the derivation path, so that the signer keeps the address it had
+    /// before key rotations were introduced.
+    fn new(
+        address: H160,
+        rotation_count: u32,
+        derivation_suffix: &[Vec<u8>],
+        prefix: &[Vec<u8>],
+        config: &OracleSignerConfig,
+    ) -> Self {
+        let mut derivation_path = prefix.to_vec();
+        derivation_path.push(address.0.as_bytes().to_vec());
+        if rotation_count > 0 {
+            derivation_path.push(rotation_count.to_be_bytes().to_vec());
+        }
+        derivation_path.extend(config.derivation_suffix.iter().cloned());
+        derivation_path.extend(derivation_suffix.iter().cloned());
+
         Self {
-            key_id: SigningKeyId::Dfx,
-            derivation_path: vec![address_to_bytes],
+            key_id: config.key_id,
+            derivation_path,
         }
     }
 }
@@ -78,3 +124,102 @@ impl TransactionSigner for OracleSigner {
             .map(Into::into)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotated_signer_derivation_path() {
+        let address = H160::from_slice(&[1; 20]);
+
+        let config = OracleSignerConfig::default();
+
+        let signer = OracleSigner::new(address.clone(), 0, &[], &[], &config);
+        assert_eq!(signer.derivation_path, vec![vec![1u8; 20]]);
+
+        let rotated = OracleSigner::new(address.clone(), 1, &[], &[], &config);
+        assert_eq!(
+            rotated.derivation_path,
+            vec![vec![1u8; 20], 1u32.to_be_bytes().to_vec()]
+        );
+        assert_ne!(
+            rotated.derivation_path,
+            OracleSigner::new(address, 2, &[], &[], &config).derivation_path
+        );
+    }
+
+    #[test]
+    fn test_signer_config() {
+        let address = H160::from_slice(&[1; 20]);
+
+        let signer =
+            OracleSigner::new(address.clone(), 0, &[], &[], &OracleSignerConfig::default());
+        assert!(matches!(signer.key_id, SigningKeyId::Dfx));
+
+        let config = OracleSignerConfig {
+            key_id: SigningKeyId::Production,
+            derivation_suffix: vec![b"oracular".to_vec(), vec![7]],
+        };
+
+        let signer = OracleSigner::new(address.clone(), 0, &[], &[], &config);
+        assert!(matches!(signer.key_id, SigningKeyId::Production));
+        assert_eq!(
+            signer.derivation_path,
+            vec![vec![1u8; 20], b"oracular".to_vec(), vec![7]]
+        );
+
+        let rotated = OracleSigner::new(address, 3, &[], &[], &config);
+        assert_eq!(
+            rotated.derivation_path,
+            vec![
+                vec![1u8; 20],
+                3u32.to_be_bytes().to_vec(),
+                b"oracular".to_vec(),
+                vec![7]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_oracle_derivation_suffix_and_prefix() {
+        let address = H160::from_slice(&[1; 20]);
+        let config = OracleSignerConfig {
+            key_id: SigningKeyId::Production,
+            derivation_suffix: vec![b"oracular".to_vec()],
+        };
+
+        let signer = OracleSigner::new(address.clone(), 0, &[], &[], &config);
+        let isolated = OracleSigner::new(address.clone(), 0, &[b"eth-usd".to_vec()], &[], &config);
+        assert_eq!(
+            isolated.derivation_path,
+            vec![vec![1u8; 20], b"oracular".to_vec(), b"eth-usd".to_vec()]
+        );
+
+        // Each suffix derives another key, hence another address
+        assert_ne!(isolated.derivation_path, signer.derivation_path);
+        assert_ne!(
+            isolated.derivation_path,
+            OracleSigner::new(address.clone(), 0, &[b"btc-usd".to_vec()], &[], &config)
+                .derivation_path
+        );
+
+        let namespaced = OracleSigner::new(
+            address,
+            2,
+            &[b"eth-usd".to_vec()],
+            &[b"staging".to_vec()],
+            &config,
+        );
+        assert_eq!(
+            namespaced.derivation_path,
+            vec![
+                b"staging".to_vec(),
+                vec![1u8; 20],
+                2u32.to_be_bytes().to_vec(),
+                b"oracular".to_vec(),
+                b"eth-usd".to_vec()
+            ]
+        );
+    }
+}
@@ -2,14 +2,19 @@ use candid::Principal;
 use did::H160;
 use ic_canister_client::CanisterClient;
 use ic_exports::ic_kit::mock_principals::alice;
-use oracular::canister::{EvmDestination, HttpOrigin, Origin};
-use oracular::error::Result;
+use oracular::canister::{
+    BatchOracleRequest, CanisterHealth, CanisterStats, CostStats, DashboardStats, DependencyEdge,
+    EvmDestination, HttpOrigin, ImportSummary, MemoryUsageReport, OracleCycleStats, OracleExport,
+    OracleHealthSummary, OracleStateSnapshot, Origin, RestoreSummary,
+};
+use oracular::error::{OracleStage, Result};
 use oracular::provider::Provider;
-use oracular::state::oracle_storage::OracleMetadata;
-use oracular::state::UpdateOracleMetadata;
+use oracular::state::oracle_storage::{OracleMetadata, OracleStatus};
+use oracular::state::{RateLimit, UpdateOracleMetadata};
 
 use crate::context::state_machine::StateMachineTestContext;
 use crate::context::TestContext;
+use crate::utils::wasm::get_oracular_canister_bytecode;
 
 #[tokio::test]
 async fn set_owner_access() {
@@ -40,14 +45,31 @@ async fn test_create_oracle_http_origin() {
     let origin = Origin::Http(HttpOrigin {
         url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
         json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
     });
 
     let destination = EvmDestination {
         contract: H160::from_hex_str("0x637F877db257ccba80B1fe06b0bEA039cd92C736").unwrap(),
         provider: Provider {
             chain_id: 355113,
-            hostname: "https://127.0.0.1:8545".to_string(),
+            endpoints: vec!["https://127.0.0.1:8545".to_string()],
         },
+        chain_id: None,
+        extra_destinations: vec![],
+        value_wei: None,
     };
 
     let res = client
@@ -81,6 +103,94 @@ async fn test_create_oracle_http_origin() {
     assert_eq!(oracle.1.timer_interval, 1);
 }
 
+#[tokio::test]
+async fn test_create_oracle_with_initial_delay() {
+    let ctx = StateMachineTestContext::reset_and_lock().await;
+
+    let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
+
+    let user_address = H160::from_slice(&[5; 20]);
+
+    let origin = Origin::Http(HttpOrigin {
+        url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
+        json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
+    });
+
+    let destination = EvmDestination {
+        contract: H160::from_hex_str("0x637F877db257ccba80B1fe06b0bEA039cd92C736").unwrap(),
+        provider: Provider {
+            chain_id: 355113,
+            endpoints: vec!["https://127.0.0.1:8545".to_string()],
+        },
+        chain_id: None,
+        extra_destinations: vec![],
+        value_wei: None,
+    };
+
+    client
+        .update::<(H160, Origin, u64, EvmDestination, Option<u64>), Result<()>>(
+            "create_oracle",
+            (user_address.clone(), origin, 1, destination, Some(60)),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let oracles = client
+        .query::<(H160,), Result<Vec<(H160, OracleMetadata)>>>(
+            "get_user_oracles",
+            (user_address.clone(),),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    let oracle = &oracles[0].1;
+    assert_eq!(oracle.status, OracleStatus::Pending);
+    assert!(oracle.first_tick_at.is_some());
+
+    // The timer does not fire before the initial delay
+    ctx.advance_time(std::time::Duration::from_secs(30)).await;
+
+    let oracles = client
+        .query::<(H160,), Result<Vec<(H160, OracleMetadata)>>>(
+            "get_user_oracles",
+            (user_address.clone(),),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    let oracle = &oracles[0].1;
+    assert_eq!(oracle.status, OracleStatus::Pending);
+    assert_eq!(oracle.last_attempt_at, 0);
+
+    ctx.advance_time(std::time::Duration::from_secs(31)).await;
+
+    let oracles = client
+        .query::<(H160,), Result<Vec<(H160, OracleMetadata)>>>(
+            "get_user_oracles",
+            (user_address.clone(),),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    let oracle = &oracles[0].1;
+    assert_eq!(oracle.status, OracleStatus::Active);
+}
+
 #[tokio::test]
 async fn test_update_oracle() {
     let ctx = StateMachineTestContext::reset_and_lock().await;
@@ -92,14 +202,31 @@ async fn test_update_oracle() {
     let origin = Origin::Http(HttpOrigin {
         url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
         json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
     });
 
     let destination = EvmDestination {
         contract: H160::from_hex_str("0x637F877db257ccba80B1fe06b0bEA039cd92C736").unwrap(),
         provider: Provider {
             chain_id: 355113,
-            hostname: "https://127.0.0.1:8545".to_string(),
+            endpoints: vec!["https://127.0.0.1:8545".to_string()],
         },
+        chain_id: None,
+        extra_destinations: vec![],
+        value_wei: None,
     };
     client
         .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
@@ -115,12 +242,25 @@ async fn test_update_oracle() {
     let new_origin = Origin::Http(HttpOrigin {
         url: String::from("https://example.com"),
         json_path: String::from("data"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
     });
 
     let update_metadata = UpdateOracleMetadata {
         origin: Some(new_origin.clone()),
-        evm: None,
-        timestamp: None,
+        ..Default::default()
     };
 
     client
@@ -156,19 +296,47 @@ async fn delete_oracle() {
 
     let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
 
+    // The destination provider is not reachable from the test environment
+    client
+        .update::<(bool,), Result<()>>("set_skip_chain_id_validation", (true,))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(client
+        .query::<(), bool>("get_skip_chain_id_validation", ())
+        .await
+        .unwrap());
+
     let user_address = H160::from_slice(&[5; 20]);
 
     let origin = Origin::Http(HttpOrigin {
         url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
         json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
     });
 
     let destination = EvmDestination {
         contract: H160::from_hex_str("0x637F877db257ccba80B1fe06b0bEA039cd92C736").unwrap(),
         provider: Provider {
             chain_id: 355113,
-            hostname: "https://example.com".to_string(),
+            endpoints: vec!["https://example.com".to_string()],
         },
+        chain_id: None,
+        extra_destinations: vec![],
+        value_wei: None,
     };
 
     client
@@ -213,3 +381,1733 @@ async fn delete_oracle() {
 
     assert_eq!(res, oracular::error::Error::UserNotFound); // If user not found, it means the oracle was deleted
 }
+
+#[tokio::test]
+async fn test_create_oracles_batch() {
+    let ctx = StateMachineTestContext::reset_and_lock().await;
+
+    let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
+
+    let user_address = H160::from_slice(&[5; 20]);
+
+    let origin = Origin::Http(HttpOrigin {
+        url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
+        json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
+    });
+
+    let requests = (1..=3u8)
+        .map(|i| BatchOracleRequest {
+            user_address: user_address.clone(),
+            origin: origin.clone(),
+            interval: 1,
+            destination: EvmDestination {
+                contract: H160::from_slice(&[i; 20]),
+                provider: Provider {
+                    chain_id: 355113,
+                    endpoints: vec!["https://127.0.0.1:8545".to_string()],
+                },
+                chain_id: None,
+                extra_destinations: vec![],
+                value_wei: None,
+            },
+            update_immediately: false,
+        })
+        .collect::<Vec<_>>();
+
+    let res = client
+        .update::<(Vec<BatchOracleRequest>,), Result<Vec<(H160, Result<()>)>>>(
+            "create_oracles_batch",
+            (requests.clone(),),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(res.len(), 3);
+    for ((contract, result), request) in res.iter().zip(&requests) {
+        assert_eq!(contract, &request.destination.contract);
+        assert!(result.is_ok());
+    }
+
+    let res = client
+        .query::<(H160,), Result<Vec<(H160, OracleMetadata)>>>(
+            "get_user_oracles",
+            (user_address.clone(),),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(res.len(), 3);
+
+    // A repeated request in the batch is rejected without creating the oracle twice
+    let mut request = requests[0].clone();
+    request.destination.contract = H160::from_slice(&[4; 20]);
+    let res = client
+        .update::<(Vec<BatchOracleRequest>,), Result<Vec<(H160, Result<()>)>>>(
+            "create_oracles_batch",
+            (vec![request.clone(), request],),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(res.len(), 2);
+    assert!(res[0].1.is_ok());
+    assert_eq!(res[1].1, Err(oracular::error::Error::OracleAlreadyExists));
+}
+
+#[tokio::test]
+async fn test_oracle_timers_restored_after_upgrade() {
+    let ctx = StateMachineTestContext::reset_and_lock().await;
+
+    let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
+
+    let user_address = H160::from_slice(&[5; 20]);
+
+    let origin = Origin::Http(HttpOrigin {
+        url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
+        json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
+    });
+
+    let destination = EvmDestination {
+        contract: H160::from_hex_str("0x637F877db257ccba80B1fe06b0bEA039cd92C736").unwrap(),
+        provider: Provider {
+            chain_id: 355113,
+            endpoints: vec!["https://127.0.0.1:8545".to_string()],
+        },
+        chain_id: None,
+        extra_destinations: vec![],
+        value_wei: None,
+    };
+
+    client
+        .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
+            "create_oracle",
+            (user_address.clone(), origin.clone(), 1, destination.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let wasm = get_oracular_canister_bytecode().await;
+    ctx.upgrade_canister(ctx.canisters.oracular, wasm, ())
+        .await
+        .unwrap();
+
+    ctx.advance_time(std::time::Duration::from_secs(10)).await;
+
+    let metadata = client
+        .query::<(H160, H160), Result<OracleMetadata>>(
+            "get_oracle_metadata",
+            (user_address.clone(), destination.contract.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(metadata.status, OracleStatus::Active);
+    assert_eq!(metadata.origin, origin);
+}
+
+#[tokio::test]
+async fn test_create_oracle_with_fallback_endpoints() {
+    let ctx = StateMachineTestContext::reset_and_lock().await;
+
+    let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
+
+    let user_address = H160::from_slice(&[5; 20]);
+
+    let origin = Origin::Http(HttpOrigin {
+        url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
+        json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
+    });
+
+    let mut destination = EvmDestination {
+        contract: H160::from_hex_str("0x637F877db257ccba80B1fe06b0bEA039cd92C736").unwrap(),
+        provider: Provider {
+            chain_id: 355113,
+            endpoints: vec![],
+        },
+        chain_id: None,
+        extra_destinations: vec![],
+        value_wei: None,
+    };
+
+    let res = client
+        .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
+            "create_oracle",
+            (user_address.clone(), origin.clone(), 1, destination.clone()),
+        )
+        .await
+        .unwrap();
+
+    assert!(res.is_err());
+
+    // The primary endpoint is unreachable
+    destination.provider.endpoints = vec![
+        "https://127.0.0.1:1".to_string(),
+        "https://127.0.0.1:8545".to_string(),
+    ];
+
+    client
+        .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
+            "create_oracle",
+            (user_address.clone(), origin.clone(), 1, destination.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    ctx.advance_time(std::time::Duration::from_secs(10)).await;
+
+    let metadata = client
+        .query::<(H160, H160), Result<OracleMetadata>>(
+            "get_oracle_metadata",
+            (user_address.clone(), destination.contract.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(metadata.evm, destination);
+}
+
+#[tokio::test]
+async fn test_delete_oracles_batch() {
+    let ctx = StateMachineTestContext::reset_and_lock().await;
+
+    let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
+
+    let user_address = H160::from_slice(&[5; 20]);
+
+    let origin = Origin::Http(HttpOrigin {
+        url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
+        json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
+    });
+
+    let contracts = (1..=4u8)
+        .map(|i| H160::from_slice(&[i; 20]))
+        .collect::<Vec<_>>();
+
+    for contract in &contracts {
+        let destination = EvmDestination {
+            contract: contract.clone(),
+            provider: Provider {
+                chain_id: 355113,
+                endpoints: vec!["https://127.0.0.1:8545".to_string()],
+            },
+            chain_id: None,
+            extra_destinations: vec![],
+            value_wei: None,
+        };
+
+        client
+            .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
+                "create_oracle",
+                (user_address.clone(), origin.clone(), 1, destination),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    let missing_contract = H160::from_slice(&[9; 20]);
+
+    let res = client
+        .update::<(H160, Vec<H160>), Result<Vec<(H160, Result<()>)>>>(
+            "delete_oracles_batch",
+            (
+                user_address.clone(),
+                vec![
+                    contracts[0].clone(),
+                    missing_contract.clone(),
+                    contracts[1].clone(),
+                ],
+            ),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(res.len(), 3);
+    assert!(res[0].1.is_ok());
+    assert_eq!(res[1].0, missing_contract);
+    assert!(res[1].1.is_err());
+    assert!(res[2].1.is_ok());
+
+    let deleted = client
+        .update::<(H160,), Result<u32>>("delete_all_user_oracles", (user_address.clone(),))
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(deleted, 2);
+
+    let res = client
+        .query::<(H160,), Result<Vec<(H160, OracleMetadata)>>>(
+            "get_user_oracles",
+            (user_address.clone(),),
+        )
+        .await
+        .unwrap()
+        .unwrap_err();
+
+    assert_eq!(res, oracular::error::Error::UserNotFound);
+}
+
+#[tokio::test]
+async fn test_health_summary() {
+    let ctx = StateMachineTestContext::reset_and_lock().await;
+
+    let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
+
+    let user_address = H160::from_slice(&[5; 20]);
+
+    let origin = Origin::Http(HttpOrigin {
+        url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
+        json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
+    });
+
+    let destination = EvmDestination {
+        contract: H160::from_hex_str("0x637F877db257ccba80B1fe06b0bEA039cd92C736").unwrap(),
+        provider: Provider {
+            chain_id: 355113,
+            endpoints: vec!["https://127.0.0.1:8545".to_string()],
+        },
+        chain_id: None,
+        extra_destinations: vec![],
+        value_wei: None,
+    };
+
+    client
+        .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
+            "create_oracle",
+            (user_address.clone(), origin.clone(), 1, destination.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let summary = client
+        .query::<(), OracleHealthSummary>("get_health_summary", ())
+        .await
+        .unwrap();
+
+    assert_eq!(summary.total_users, 1);
+    assert_eq!(summary.total_oracles, 1);
+    assert_eq!(
+        summary.active_oracles
+            + summary.paused_oracles
+            + summary.errored_oracles
+            + summary.circuit_open_oracles
+            + summary.pending_oracles,
+        1
+    );
+    assert!(summary.cycles_balance > 0);
+    assert!(summary.stable_memory_bytes > 0);
+
+    let dashboard = client
+        .query::<(), DashboardStats>("get_dashboard_stats", ())
+        .await
+        .unwrap();
+    assert_eq!(dashboard.total_oracles, 1);
+    assert_eq!(
+        dashboard.active_oracles + dashboard.paused_oracles + dashboard.errored_oracles,
+        summary.total_oracles - summary.circuit_open_oracles
+    );
+
+    let quick_stats = client
+        .query::<(), (u64, u64, u64)>("get_quick_stats", ())
+        .await
+        .unwrap();
+    assert_eq!(
+        quick_stats,
+        (1, dashboard.active_oracles, dashboard.errored_oracles)
+    );
+
+    let stats = client
+        .query::<(), Result<CanisterStats>>("get_canister_stats", ())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(stats.oracle_count, 1);
+    assert_eq!(stats.user_count, 1);
+    assert!(stats.cycle_balance > 0);
+    assert!(stats.heap_memory_bytes > 0);
+
+    // The health is public
+    let health = ctx
+        .client(ctx.canisters.oracular, "alice")
+        .query::<(), CanisterHealth>("get_canister_health", ())
+        .await
+        .unwrap();
+    assert_eq!(health.oracle_count, 1);
+    assert_eq!(health.user_count, 1);
+    assert_eq!(
+        health.active_timer_count,
+        summary.active_oracles + summary.circuit_open_oracles
+    );
+    assert!(health.cycle_balance > 0);
+    assert!(health.stable_memory_pages > 0);
+    assert!(health.ic_time_ns > 0);
+
+    let usage = client
+        .query::<(), Result<MemoryUsageReport>>("get_memory_usage", ())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert!(usage.oracle_storage_pages > 0);
+    assert!(usage.settings_pages > 0);
+    assert!(usage.total_pages >= usage.oracle_storage_pages + usage.settings_pages);
+    assert!(usage.max_pages > usage.total_pages);
+
+    let entry_count = client
+        .query::<(), u64>("get_oracle_storage_entry_count", ())
+        .await
+        .unwrap();
+    assert_eq!(entry_count, 1);
+}
+
+#[tokio::test]
+async fn test_clone_oracle() {
+    let ctx = StateMachineTestContext::reset_and_lock().await;
+
+    let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
+
+    let user_address = H160::from_slice(&[5; 20]);
+
+    let origin = Origin::Http(HttpOrigin {
+        url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
+        json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
+    });
+
+    let destination = EvmDestination {
+        contract: H160::from_slice(&[1; 20]),
+        provider: Provider {
+            chain_id: 355113,
+            endpoints: vec!["https://127.0.0.1:8545".to_string()],
+        },
+        chain_id: None,
+        extra_destinations: vec![],
+        value_wei: None,
+    };
+
+    client
+        .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
+            "create_oracle",
+            (
+                user_address.clone(),
+                origin.clone(),
+                60,
+                destination.clone(),
+            ),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let new_destination = EvmDestination {
+        contract: H160::from_slice(&[2; 20]),
+        provider: Provider {
+            chain_id: 1,
+            endpoints: vec!["https://127.0.0.1:8546".to_string()],
+        },
+        chain_id: None,
+        extra_destinations: vec![],
+        value_wei: None,
+    };
+
+    client
+        .update::<(H160, H160, EvmDestination), Result<()>>(
+            "clone_oracle",
+            (
+                user_address.clone(),
+                destination.contract.clone(),
+                new_destination.clone(),
+            ),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Cloning to an existing destination fails
+    let res = client
+        .update::<(H160, H160, EvmDestination), Result<()>>(
+            "clone_oracle",
+            (
+                user_address.clone(),
+                destination.contract.clone(),
+                new_destination.clone(),
+            ),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res, Err(oracular::error::Error::OracleAlreadyExists));
+
+    // Updating the source oracle does not affect the clone
+    let update_metadata = UpdateOracleMetadata {
+        timestamp: Some(120),
+        ..Default::default()
+    };
+    client
+        .update::<(H160, H160, UpdateOracleMetadata), Result<()>>(
+            "update_oracle_metadata",
+            (
+                user_address.clone(),
+                destination.contract.clone(),
+                update_metadata,
+            ),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let clone = client
+        .query::<(H160, H160), Result<OracleMetadata>>(
+            "get_oracle_metadata",
+            (user_address.clone(), new_destination.contract.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(clone.origin, origin);
+    assert_eq!(clone.evm, new_destination);
+    assert_eq!(clone.timer_interval, 60);
+}
+
+#[tokio::test]
+async fn test_registered_provider() {
+    let ctx = StateMachineTestContext::reset_and_lock().await;
+
+    let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
+
+    let user_address = H160::from_slice(&[5; 20]);
+
+    let res = client
+        .query::<(u64,), Result<Provider>>("get_provider", (355113,))
+        .await
+        .unwrap();
+    assert_eq!(res, Err(oracular::error::Error::ProviderNotFound(355113)));
+
+    client
+        .update::<(u64, Vec<String>), Result<()>>(
+            "register_provider",
+            (355113, vec!["https://127.0.0.1:8545".to_string()]),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let origin = Origin::Http(HttpOrigin {
+        url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
+        json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
+    });
+
+    // The provider of the destination is replaced by the registered one
+    let destination = EvmDestination {
+        contract: H160::from_slice(&[1; 20]),
+        provider: Provider {
+            chain_id: 355113,
+            endpoints: vec![],
+        },
+        chain_id: Some(355113),
+        extra_destinations: vec![],
+        value_wei: None,
+    };
+
+    client
+        .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
+            "create_oracle",
+            (user_address.clone(), origin, 60, destination.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let new_provider = Provider {
+        chain_id: 355113,
+        endpoints: vec!["https://127.0.0.1:8546".to_string()],
+    };
+
+    client
+        .update::<(u64, Vec<String>), Result<()>>(
+            "update_provider",
+            (355113, new_provider.endpoints.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let provider = client
+        .query::<(u64,), Result<Provider>>("get_provider", (355113,))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(provider, new_provider);
+
+    let metadata = client
+        .query::<(H160, H160), Result<OracleMetadata>>(
+            "get_oracle_metadata",
+            (user_address.clone(), destination.contract.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(metadata.evm.provider, new_provider);
+
+    // Replacing the providers of the chain replaces the registered one as well
+    let chain_provider = Provider {
+        chain_id: 355113,
+        endpoints: vec!["https://127.0.0.1:8547".to_string()],
+    };
+    let updated = client
+        .update::<(u64, Vec<String>, bool, bool, bool), Result<u64>>(
+            "update_provider_for_chain",
+            (355113, chain_provider.endpoints.clone(), false, true, true),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(updated, 1);
+
+    let provider = client
+        .query::<(u64,), Result<Provider>>("get_provider", (355113,))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(provider, chain_provider);
+
+    let metadata = client
+        .query::<(H160, H160), Result<OracleMetadata>>(
+            "get_oracle_metadata",
+            (user_address, destination.contract),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(metadata.evm.provider, chain_provider);
+}
+
+#[tokio::test]
+async fn test_export_import_oracle_configs() {
+    let ctx = StateMachineTestContext::reset_and_lock().await;
+
+    let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
+
+    let user_address = H160::from_slice(&[5; 20]);
+
+    let origin = Origin::Http(HttpOrigin {
+        url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
+        json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
+    });
+
+    let destination = EvmDestination {
+        contract: H160::from_slice(&[1; 20]),
+        provider: Provider {
+            chain_id: 355113,
+            endpoints: vec!["https://127.0.0.1:8545".to_string()],
+        },
+        chain_id: None,
+        extra_destinations: vec![],
+        value_wei: None,
+    };
+
+    client
+        .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
+            "create_oracle",
+            (
+                user_address.clone(),
+                origin.clone(),
+                60,
+                destination.clone(),
+            ),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let export = client
+        .query::<(), Result<OracleExport>>("export_all_oracle_configs", ())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(export.oracles.len(), 1);
+
+    client
+        .update::<(H160, H160), Result<()>>(
+            "delete_oracle",
+            (user_address.clone(), destination.contract.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let summary = client
+        .update::<(OracleExport,), Result<ImportSummary>>(
+            "import_oracle_configs",
+            (export.clone(),),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(summary.created, 1);
+    assert_eq!(summary.skipped_existing, 0);
+    assert!(summary.failed.is_empty());
+
+    let metadata = client
+        .query::<(H160, H160), Result<OracleMetadata>>(
+            "get_oracle_metadata",
+            (user_address, destination.contract.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(metadata.origin, origin);
+    assert_eq!(metadata.evm, destination);
+    assert_eq!(metadata.timer_interval, 60);
+
+    // Importing again skips the existing oracles
+    let summary = client
+        .update::<(OracleExport,), Result<ImportSummary>>("import_oracle_configs", (export,))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(summary.created, 0);
+    assert_eq!(summary.skipped_existing, 1);
+}
+
+#[tokio::test]
+async fn test_snapshot_and_restore_oracle_state() {
+    let ctx = StateMachineTestContext::reset_and_lock().await;
+
+    let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
+
+    let user_address = H160::from_slice(&[5; 20]);
+
+    let origin = Origin::Http(HttpOrigin {
+        url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
+        json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
+    });
+
+    let destination = |contract: u8| EvmDestination {
+        contract: H160::from_slice(&[contract; 20]),
+        provider: Provider {
+            chain_id: 355113,
+            endpoints: vec!["https://127.0.0.1:8545".to_string()],
+        },
+        chain_id: None,
+        extra_destinations: vec![],
+        value_wei: None,
+    };
+
+    client
+        .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
+            "create_oracle",
+            (user_address.clone(), origin.clone(), 60, destination(1)),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let snapshot = client
+        .query::<(), Result<OracleStateSnapshot>>("snapshot_oracle_state", ())
+        .await
+        .unwrap()
+        .unwrap();
+    let metadata = client
+        .query::<(H160, H160), Result<OracleMetadata>>(
+            "get_oracle_metadata",
+            (user_address.clone(), destination(1).contract),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Bulk changes after the snapshot
+    client
+        .update::<(H160, H160), Result<()>>(
+            "delete_oracle",
+            (user_address.clone(), destination(1).contract),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    client
+        .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
+            "create_oracle",
+            (user_address.clone(), origin, 120, destination(2)),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Snapshots of another layout are rejected without changing the state
+    let incompatible = OracleStateSnapshot {
+        schema_version: snapshot.schema_version + 1,
+        data: snapshot.data.clone(),
+    };
+    let res = client
+        .update::<(OracleStateSnapshot,), Result<RestoreSummary>>(
+            "restore_oracle_state",
+            (incompatible,),
+        )
+        .await
+        .unwrap();
+    assert!(res.is_err());
+    assert_eq!(
+        client
+            .query::<(H160,), u64>("get_user_oracle_count", (user_address.clone(),))
+            .await
+            .unwrap(),
+        1
+    );
+
+    let summary = client
+        .update::<(OracleStateSnapshot,), Result<RestoreSummary>>(
+            "restore_oracle_state",
+            (snapshot,),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        summary,
+        RestoreSummary {
+            oracles_restored: 1,
+            timers_started: 1,
+            errors: vec![],
+        }
+    );
+
+    let oracles = client
+        .query::<(H160,), Result<Vec<(H160, OracleMetadata)>>>("get_user_oracles", (user_address,))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(oracles.len(), 1);
+    let (contract, restored) = &oracles[0];
+    assert_eq!(contract, &destination(1).contract);
+    assert_eq!(restored.origin, metadata.origin);
+    assert_eq!(restored.evm, metadata.evm);
+    assert_eq!(restored.timer_interval, 60);
+}
+
+#[tokio::test]
+async fn test_pause_and_resume_all_oracles() {
+    let ctx = StateMachineTestContext::reset_and_lock().await;
+
+    let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
+
+    let user_address = H160::from_slice(&[5; 20]);
+
+    let origin = Origin::Http(HttpOrigin {
+        url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
+        json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
+    });
+
+    let destination = EvmDestination {
+        contract: H160::from_hex_str("0x637F877db257ccba80B1fe06b0bEA039cd92C736").unwrap(),
+        provider: Provider {
+            chain_id: 355113,
+            endpoints: vec!["https://127.0.0.1:8545".to_string()],
+        },
+        chain_id: None,
+        extra_destinations: vec![],
+        value_wei: None,
+    };
+
+    client
+        .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
+            "create_oracle",
+            (user_address.clone(), origin, 1, destination.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let paused = client
+        .update::<(), Result<u64>>("pause_all_oracles", ())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(paused, 1);
+
+    let get_metadata = || {
+        client.query::<(H160, H160), Result<OracleMetadata>>(
+            "get_oracle_metadata",
+            (user_address.clone(), destination.contract.clone()),
+        )
+    };
+
+    let metadata = get_metadata().await.unwrap().unwrap();
+    assert_eq!(metadata.status, OracleStatus::Paused);
+
+    // No update is attempted while the oracles are paused
+    ctx.advance_time(std::time::Duration::from_secs(10)).await;
+
+    let paused_metadata = get_metadata().await.unwrap().unwrap();
+    assert_eq!(paused_metadata.total_updates, metadata.total_updates);
+
+    let resumed = client
+        .update::<(), Result<u64>>("resume_all_oracles", ())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(resumed, 1);
+
+    let metadata = get_metadata().await.unwrap().unwrap();
+    assert_eq!(metadata.status, OracleStatus::Active);
+    assert_eq!(metadata.status_before_emergency, None);
+}
+
+#[tokio::test]
+async fn test_update_emergency_paused_oracle() {
+    let ctx = StateMachineTestContext::reset_and_lock().await;
+
+    let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
+
+    let user_address = H160::from_slice(&[5; 20]);
+
+    let origin = Origin::Http(HttpOrigin {
+        url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
+        json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
+    });
+
+    let destination = EvmDestination {
+        contract: H160::from_hex_str("0x637F877db257ccba80B1fe06b0bEA039cd92C736").unwrap(),
+        provider: Provider {
+            chain_id: 355113,
+            endpoints: vec!["https://127.0.0.1:8545".to_string()],
+        },
+        chain_id: None,
+        extra_destinations: vec![],
+        value_wei: None,
+    };
+
+    client
+        .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
+            "create_oracle",
+            (user_address.clone(), origin, 1, destination.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    client
+        .update::<(), Result<u64>>("pause_all_oracles", ())
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Editing the oracle does not end the emergency pause
+    client
+        .update::<(H160, H160, UpdateOracleMetadata), Result<()>>(
+            "update_oracle_metadata",
+            (
+                user_address.clone(),
+                destination.contract.clone(),
+                UpdateOracleMetadata {
+                    label: Some(String::from("BTC/ETH")),
+                    ..Default::default()
+                },
+            ),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let get_metadata = || {
+        client.query::<(H160, H160), Result<OracleMetadata>>(
+            "get_oracle_metadata",
+            (user_address.clone(), destination.contract.clone()),
+        )
+    };
+
+    let metadata = get_metadata().await.unwrap().unwrap();
+    assert_eq!(metadata.status, OracleStatus::Paused);
+    assert_eq!(metadata.status_before_emergency, Some(OracleStatus::Active));
+
+    ctx.advance_time(std::time::Duration::from_secs(10)).await;
+    assert_eq!(
+        get_metadata().await.unwrap().unwrap().total_updates,
+        metadata.total_updates
+    );
+
+    let resumed = client
+        .update::<(), Result<u64>>("resume_all_oracles", ())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(resumed, 1);
+    assert_eq!(
+        get_metadata().await.unwrap().unwrap().status,
+        OracleStatus::Active
+    );
+
+    // A single timer was running: pausing again stops all the updates
+    client
+        .update::<(), Result<u64>>("pause_all_oracles", ())
+        .await
+        .unwrap()
+        .unwrap();
+    let metadata = get_metadata().await.unwrap().unwrap();
+
+    ctx.advance_time(std::time::Duration::from_secs(10)).await;
+    assert_eq!(
+        get_metadata().await.unwrap().unwrap().total_updates,
+        metadata.total_updates
+    );
+}
+
+#[tokio::test]
+async fn test_oracle_max_executions() {
+    let ctx = StateMachineTestContext::reset_and_lock().await;
+
+    let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
+
+    let user_address = H160::from_slice(&[5; 20]);
+
+    let origin = Origin::Http(HttpOrigin {
+        url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
+        json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
+    });
+
+    let destination = EvmDestination {
+        contract: H160::from_hex_str("0x637F877db257ccba80B1fe06b0bEA039cd92C736").unwrap(),
+        provider: Provider {
+            chain_id: 355113,
+            endpoints: vec!["https://127.0.0.1:8545".to_string()],
+        },
+        chain_id: None,
+        extra_destinations: vec![],
+        value_wei: None,
+    };
+
+    client
+        .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
+            "create_oracle",
+            (user_address.clone(), origin, 1, destination.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let update_metadata = UpdateOracleMetadata {
+        max_executions: Some(3),
+        ..Default::default()
+    };
+    client
+        .update::<(H160, H160, UpdateOracleMetadata), Result<()>>(
+            "update_oracle_metadata",
+            (
+                user_address.clone(),
+                destination.contract.clone(),
+                update_metadata,
+            ),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let get_metadata = || {
+        client.query::<(H160, H160), Result<OracleMetadata>>(
+            "get_oracle_metadata",
+            (user_address.clone(), destination.contract.clone()),
+        )
+    };
+
+    for tick in 1..=3 {
+        ctx.advance_time(std::time::Duration::from_secs(2)).await;
+
+        let metadata = get_metadata().await.unwrap().unwrap();
+        assert_eq!(metadata.execution_count, tick);
+    }
+
+    let metadata = get_metadata().await.unwrap().unwrap();
+    assert_eq!(metadata.status, OracleStatus::Paused);
+
+    // The timer is stopped after the last execution
+    ctx.advance_time(std::time::Duration::from_secs(2)).await;
+
+    let metadata = get_metadata().await.unwrap().unwrap();
+    assert_eq!(metadata.execution_count, 3);
+    assert_eq!(metadata.status, OracleStatus::Paused);
+}
+
+#[tokio::test]
+async fn test_oracle_creation_rate_limit() {
+    let ctx = StateMachineTestContext::reset_and_lock().await;
+
+    let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
+
+    let user_address = H160::from_slice(&[5; 20]);
+
+    let origin = Origin::Http(HttpOrigin {
+        url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
+        json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
+    });
+
+    let destination = |i: u8| EvmDestination {
+        contract: H160::from_slice(&[i; 20]),
+        provider: Provider {
+            chain_id: 355113,
+            endpoints: vec!["https://127.0.0.1:8545".to_string()],
+        },
+        chain_id: None,
+        extra_destinations: vec![],
+        value_wei: None,
+    };
+
+    client
+        .update::<(Option<RateLimit>,), Result<()>>(
+            "set_oracle_creation_rate_limit",
+            (Some(RateLimit { max_per_hour: 2 }),),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    for i in 1..=2u8 {
+        client
+            .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
+                "create_oracle",
+                (user_address.clone(), origin.clone(), 1, destination(i)),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    let err = client
+        .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
+            "create_oracle",
+            (user_address.clone(), origin.clone(), 1, destination(3)),
+        )
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(err, oracular::error::Error::RateLimitExceeded(2));
+
+    let requests = vec![BatchOracleRequest {
+        user_address: user_address.clone(),
+        origin: origin.clone(),
+        interval: 1,
+        destination: destination(4),
+        update_immediately: false,
+    }];
+    let err = client
+        .update::<(Vec<BatchOracleRequest>,), Result<Vec<(H160, Result<()>)>>>(
+            "create_oracles_batch",
+            (requests,),
+        )
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(err, oracular::error::Error::RateLimitExceeded(2));
+
+    // Other users are not affected
+    client
+        .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
+            "create_oracle",
+            (H160::from_slice(&[6; 20]), origin, 1, destination(3)),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_trigger_oracle_update() {
+    let ctx = StateMachineTestContext::reset_and_lock().await;
+
+    let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
+
+    let user_address = H160::from_slice(&[5; 20]);
+
+    let origin = Origin::Http(HttpOrigin {
+        url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
+        json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
+    });
+
+    let destination = EvmDestination {
+        contract: H160::from_hex_str("0x637F877db257ccba80B1fe06b0bEA039cd92C736").unwrap(),
+        provider: Provider {
+            chain_id: 355113,
+            endpoints: vec!["https://127.0.0.1:8545".to_string()],
+        },
+        chain_id: None,
+        extra_destinations: vec![],
+        value_wei: None,
+    };
+
+    // A long interval keeps the timer from updating the oracle during the test
+    client
+        .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
+            "create_oracle",
+            (user_address.clone(), origin, 3_600, destination.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Only the oracles of the user can be updated
+    let err = client
+        .update::<(H160, H160), Result<()>>(
+            "trigger_oracle_update",
+            (H160::from_slice(&[6; 20]), destination.contract.clone()),
+        )
+        .await
+        .unwrap();
+    assert!(err.is_err());
+
+    // Only the owner or an admin of the canister can trigger an update
+    let err = ctx
+        .client(ctx.canisters.oracular, "alice")
+        .update::<(H160, H160), Result<()>>(
+            "trigger_oracle_update",
+            (user_address.clone(), destination.contract.clone()),
+        )
+        .await
+        .unwrap();
+    assert!(err.is_err());
+
+    let get_metadata = || {
+        client.query::<(H160, H160), Result<OracleMetadata>>(
+            "get_oracle_metadata",
+            (user_address.clone(), destination.contract.clone()),
+        )
+    };
+
+    let metadata = get_metadata().await.unwrap().unwrap();
+    assert_eq!(metadata.total_updates, 0);
+    assert_eq!(metadata.last_attempt_at, 0);
+
+    // The origin is not reachable from the state machine, so the update is
+    // recorded as an attempt
+    client
+        .update::<(H160, H160), Result<()>>(
+            "trigger_oracle_update",
+            (user_address.clone(), destination.contract.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap_err();
+
+    let metadata = get_metadata().await.unwrap().unwrap();
+    assert_eq!(metadata.total_updates, 1);
+    assert!(metadata.last_attempt_at > 0);
+    assert_eq!(metadata.consecutive_errors, 1);
+    assert_eq!(
+        metadata.last_error.map(|error| error.stage),
+        Some(OracleStage::DataFetch)
+    );
+    assert_eq!(metadata.status, OracleStatus::Active);
+}
+
+#[tokio::test]
+async fn test_deposit_cycles_for_oracle() {
+    let ctx = StateMachineTestContext::reset_and_lock().await;
+
+    let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
+
+    let user_address = H160::from_slice(&[5; 20]);
+
+    let origin = Origin::Http(HttpOrigin {
+        url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
+        json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
+    });
+
+    let destination = EvmDestination {
+        contract: H160::from_hex_str("0x637F877db257ccba80B1fe06b0bEA039cd92C736").unwrap(),
+        provider: Provider {
+            chain_id: 355113,
+            endpoints: vec!["https://127.0.0.1:8545".to_string()],
+        },
+        chain_id: None,
+        extra_destinations: vec![],
+        value_wei: None,
+    };
+
+    client
+        .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
+            "create_oracle",
+            (user_address.clone(), origin, 3_600, destination.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let deposit = || {
+        client.update::<(H160, H160), Result<u128>>(
+            "deposit_cycles_for_oracle",
+            (user_address.clone(), destination.contract.clone()),
+        )
+    };
+
+    // Cycles cannot be deposited for an oracle without a budget
+    deposit().await.unwrap().unwrap_err();
+
+    let update_metadata = UpdateOracleMetadata {
+        cycle_budget: Some(1_000_000),
+        ..Default::default()
+    };
+    client
+        .update::<(H160, H160, UpdateOracleMetadata), Result<()>>(
+            "update_oracle_metadata",
+            (
+                user_address.clone(),
+                destination.contract.clone(),
+                update_metadata,
+            ),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Ingress messages carry no cycles, so nothing is deposited
+    deposit().await.unwrap().unwrap_err();
+
+    let stats = client
+        .query::<(H160, H160), Result<OracleCycleStats>>(
+            "get_oracle_cycle_stats",
+            (user_address.clone(), destination.contract.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(stats.budget, Some(1_000_000));
+
+    // No update ran yet
+    let cost_stats = client
+        .query::<(H160, H160), Result<CostStats>>(
+            "get_oracle_cost_stats",
+            (user_address.clone(), destination.contract.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(cost_stats.avg_cycles_per_call, 0);
+    assert_eq!(cost_stats.total_cycles_consumed, 0);
+
+    // Only the owner can read the costs of all the oracles
+    let all_cost_stats = ctx
+        .client(ctx.canisters.oracular, "alice")
+        .query::<(), Result<Vec<(H160, H160, CostStats)>>>("get_all_oracle_cost_stats", ())
+        .await
+        .unwrap();
+    assert!(all_cost_stats.is_err());
+
+    let all_cost_stats = client
+        .query::<(), Result<Vec<(H160, H160, CostStats)>>>("get_all_oracle_cost_stats", ())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        all_cost_stats,
+        vec![(
+            user_address.clone(),
+            destination.contract.clone(),
+            cost_stats
+        )]
+    );
+}
+
+#[tokio::test]
+async fn test_provider_api_key() {
+    let ctx = StateMachineTestContext::reset_and_lock().await;
+
+    let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
+
+    client
+        .update::<(u64, String), Result<()>>("set_provider_api_key", (1, String::from("secret")))
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Only the owner can manage the keys
+    let alice_client = ctx.client(ctx.canisters.oracular, "alice");
+    alice_client
+        .update::<(u64, String), Result<()>>("set_provider_api_key", (1, String::from("other")))
+        .await
+        .unwrap()
+        .unwrap_err();
+    alice_client
+        .update::<(u64,), Result<()>>("delete_provider_api_key", (1,))
+        .await
+        .unwrap()
+        .unwrap_err();
+
+    client
+        .update::<(u64,), Result<()>>("delete_provider_api_key", (1,))
+        .await
+        .unwrap()
+        .unwrap();
+    client
+        .update::<(u64,), Result<()>>("delete_provider_api_key", (1,))
+        .await
+        .unwrap()
+        .unwrap_err();
+}
+
+#[tokio::test]
+async fn test_function_registry() {
+    let ctx = StateMachineTestContext::reset_and_lock().await;
+
+    let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
+
+    let abi_json = String::from(
+        r#"{"type":"function","name":"setRate","inputs":[{"name":"rate","type":"uint256"},{"name":"pair","type":"bytes32"}],"outputs":[],"stateMutability":"nonpayable"}"#,
+    );
+    client
+        .update::<(String, String), Result<()>>(
+            "register_function",
+            (String::from("setRate"), abi_json.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let err = client
+        .update::<(String, String), Result<()>>(
+            "register_function",
+            (String::from("broken"), String::from("{\"name\":\"broken\"")),
+        )
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert!(matches!(err, oracular::error::Error::InvalidAbi(_)));
+
+    // Only the owner can register functions
+    let alice_client = ctx.client(ctx.canisters.oracular, "alice");
+    alice_client
+        .update::<(String, String), Result<()>>(
+            "register_function",
+            (String::from("other"), abi_json.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap_err();
+
+    let functions = client
+        .query::<(), Vec<String>>("list_registered_functions", ())
+        .await
+        .unwrap();
+    assert_eq!(functions, vec![String::from("setRate")]);
+
+    let registered = client
+        .query::<(String,), Result<String>>("get_registered_function", (String::from("setRate"),))
+        .await
+        .unwrap();
+    assert_eq!(registered, Ok(abi_json));
+
+    client
+        .query::<(String,), Result<String>>("get_registered_function", (String::from("other"),))
+        .await
+        .unwrap()
+        .unwrap_err();
+}
+
+#[tokio::test]
+async fn test_oracle_dependency_graph() {
+    let ctx = StateMachineTestContext::reset_and_lock().await;
+
+    let client = ctx.client(ctx.canisters.oracular, ctx.admin_name());
+
+    let user_address = H160::from_slice(&[5; 20]);
+
+    let origin = Origin::Http(HttpOrigin {
+        url: String::from("https://api.coinbase.com/v2/prices/BTC-ETH/spot"),
+        json_path: String::from("data.amount"),
+        json_paths: vec![],
+        forbidden_paths: vec![],
+        required_paths: vec![],
+        decimals: None,
+        max_response_bytes: None,
+        parse_mode: None,
+        auth: None,
+        enable_filter_expressions: false,
+        skip_cache: false,
+        json_aggregate: None,
+        session_cookie: None,
+        valid_status_codes: vec![200],
+        signing_config: None,
+        timeout_ms: None,
+    });
+
+    let destination = |contract: H160| EvmDestination {
+        contract,
+        provider: Provider {
+            chain_id: 355113,
+            endpoints: vec!["https://127.0.0.1:8545".to_string()],
+        },
+        chain_id: None,
+        extra_destinations: vec![],
+        value_wei: None,
+    };
+    let triggered = H160::from_slice(&[6; 20]);
+    let source = H160::from_slice(&[7; 20]);
+
+    for contract in [triggered.clone(), source.clone()] {
+        client
+            .update::<(H160, Origin, u64, EvmDestination), Result<()>>(
+                "create_oracle",
+                (
+                    user_address.clone(),
+                    origin.clone(),
+                    3_600,
+                    destination(contract),
+                ),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    let update_metadata = UpdateOracleMetadata {
+        triggers: Some(vec![(user_address.clone(), triggered.clone())]),
+        ..Default::default()
+    };
+    client
+        .update::<(H160, H160, UpdateOracleMetadata), Result<()>>(
+            "update_oracle_metadata",
+            (user_address.clone(), source.clone(), update_metadata),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let edges = client
+        .query::<(), Vec<DependencyEdge>>("get_oracle_dependency_graph", ())
+        .await
+        .unwrap();
+    assert_eq!(
+        edges,
+        vec![DependencyEdge {
+            from_user: user_address.clone(),
+            from_contract: source.clone(),
+            to_user: user_address,
+            to_contract: triggered.clone(),
+        }]
+    );
+
+    let order = client
+        .query::<(), Result<Vec<H160>>>("validate_dependency_graph", ())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(order, vec![source, triggered]);
+}
@@ -1,3 +1,4 @@
 use ethers::contract::abigen;
 
 abigen!(PriceFeedApi, "$OUT_DIR/PriceFeed.sol/PriceFeed.json");
+abigen!(BatchPriceFeedApi, "$OUT_DIR/IBatchPriceFeed.sol/IBatchPriceFeed.json");